@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::time::Instant;
+
+use actix::prelude::*;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+
+use crate::daemon::Request;
+
+use super::command::format_elapsed;
+use super::console::ConsoleActor;
+use super::state::{describe_exit, TaskState};
+
+/// Writes what happens as plain sentences, one per line, for screen
+/// readers and braille displays (`--a11y`): the state changes of the
+/// tasks, the answers to the [`Instruction`]s and the output of the
+/// focused task, or of every task until one is focused.
+pub struct Announcer {
+    writer: Box<dyn Write + Send>,
+    /// Task whose output is written, every task when `None`.
+    focus: Option<String>,
+    /// When each running task started.
+    started: HashMap<String, Instant>,
+}
+
+impl Announcer {
+    pub fn new(writer: Box<dyn Write + Send>) -> Self {
+        Self {
+            writer,
+            focus: None,
+            started: HashMap::default(),
+        }
+    }
+
+    pub fn say(&mut self, line: &str) {
+        let _ = writeln!(self.writer, "{line}");
+        let _ = self.writer.flush();
+    }
+
+    /// Writes a line of `panel` if it is focused.
+    pub fn output(&mut self, panel: &str, message: &str) {
+        if self.focus.as_ref().is_none_or(|focus| focus == panel) {
+            self.say(&format!("{panel} | {message}"));
+        }
+    }
+
+    /// Tells the new state of `task`, skipping the transient ones.
+    pub fn state_changed(&mut self, task: &str, state: TaskState) {
+        let event = match state {
+            TaskState::Pending | TaskState::Starting | TaskState::Stopping => return,
+            TaskState::WaitingOnUpstream => "waiting on upstream".to_string(),
+            TaskState::Queued => "queued".to_string(),
+            TaskState::Running => {
+                self.started.insert(task.to_string(), Instant::now());
+                "started".to_string()
+            }
+            TaskState::Blocked => "blocked, could not start".to_string(),
+            TaskState::Exited(status) => {
+                let exit = describe_exit(status);
+                match self.started.remove(task) {
+                    Some(started) => format!("{exit} after {}", format_elapsed(started.elapsed())),
+                    None => exit,
+                }
+            }
+        };
+        self.say(&format!("task {task}: {event}"));
+    }
+
+    /// Writes the output of `task` only, or of every task when `None`.
+    pub fn focus(&mut self, task: Option<String>) {
+        let line = match &task {
+            Some(task) => format!("now showing output of task {task}"),
+            None => "now showing output of every task".to_string(),
+        };
+        self.focus = task;
+        self.say(&line);
+    }
+}
+
+/// Line read from stdin with `--a11y`, instead of the keys of the
+/// interactive view. `status` and `stop` are the requests of the control
+/// socket, see [`Request`].
+#[derive(Message, Debug, Clone, PartialEq)]
+#[rtype(result = "()")]
+pub enum Instruction {
+    /// Writes the output of this task only, or of every task when `None`
+    /// (`focus all`).
+    Focus(Option<String>),
+    /// Reloads this task, as `r` does.
+    Reload(String),
+    /// Tells the state of each task.
+    Status,
+    /// Stops every task and exits, as `q` does.
+    Stop,
+    Help,
+    Unknown(String),
+}
+
+pub const HELP: &str = "commands: focus TASK, focus all, reload TASK, status, stop (or quit), help";
+
+impl Instruction {
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        if line.is_empty() {
+            return None;
+        }
+        let (verb, arg) = match line.split_once(char::is_whitespace) {
+            Some((verb, arg)) => (verb, Some(arg.trim().to_string())),
+            None => (line, None),
+        };
+        let instruction = match (verb, arg) {
+            ("focus", Some(task)) if task == "all" => Instruction::Focus(None),
+            ("focus", Some(task)) => Instruction::Focus(Some(task)),
+            ("reload", Some(task)) => Instruction::Reload(task),
+            ("quit", None) => Instruction::Stop,
+            ("help", None) => Instruction::Help,
+            (_, None) => match Request::parse(line) {
+                Some(Request::Status) => Instruction::Status,
+                Some(Request::Stop) => Instruction::Stop,
+                _ => Instruction::Unknown(line.to_string()),
+            },
+            _ => Instruction::Unknown(line.to_string()),
+        };
+        Some(instruction)
+    }
+}
+
+/// Sends the instructions read from `reader` line by line to `console`,
+/// until it closes.
+pub async fn read_instructions<R: AsyncBufRead + Unpin>(reader: R, console: Addr<ConsoleActor>) {
+    let mut lines = reader.lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if let Some(instruction) = Instruction::parse(&line) {
+            if console.send(instruction).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_instructions() {
+        let parse = |line| Instruction::parse(line).unwrap();
+        assert_eq!(Instruction::parse("   "), None);
+
+        assert_eq!(parse("focus web"), Instruction::Focus(Some("web".into())));
+        assert_eq!(parse("  focus all "), Instruction::Focus(None));
+        assert_eq!(parse("reload api"), Instruction::Reload("api".into()));
+        assert_eq!(parse("status"), Instruction::Status);
+        assert_eq!(parse("stop"), Instruction::Stop);
+        assert_eq!(parse("quit"), Instruction::Stop);
+        assert_eq!(parse("attach"), Instruction::Unknown("attach".into()));
+        assert_eq!(parse("reload"), Instruction::Unknown("reload".into()));
+    }
+}
@@ -1,35 +1,48 @@
 use actix::clock::sleep;
 use actix::prelude::*;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use chrono::{DateTime, Local};
-use subprocess::{ExitStatus, Popen, Redirection};
+use subprocess::{ExitStatus, Popen};
 
-use globset::{Glob, GlobSetBuilder};
+use globset::{Glob, GlobSet, GlobSetBuilder};
 use path_absolutize::*;
-use std::collections::BTreeMap;
+use regex::Regex;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
-use std::io::Write;
+use std::io::{Read, Write};
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 use std::{collections::HashMap, time::Duration};
-use std::{
-    io::{BufRead, BufReader},
-    path::PathBuf,
-};
+use std::{io::BufReader, path::PathBuf};
 
-use crate::actors::grim_reaper::PermaDeathInvite;
+use crate::actors::grim_reaper::{self, PermaDeathInvite};
+use crate::actors::slots::{Acquire, Release, Slot, SlotsActor};
+use crate::actors::state::{describe_exit, Finished, TaskState};
 use crate::config::color::ColorOption;
 use crate::config::{
+    level::LevelFilter,
+    ops,
+    pin::Pins,
     pipe::{OutputRedirection, Pipe},
-    Config, Task,
+    requires::describe_missing,
+    schedule,
+    stream::{Encoding, RawLines, Stream, PARTIAL_FLUSH},
+    Config, StopSignal, Task,
 };
-use crate::exec::ExecBuilder;
+use crate::exec::{upstream_env, ExecBuilder};
+use crate::file_mode;
+use crate::utils::glob_matches_any;
 
-use super::console::{Output, OutputKind, PanelStatus, RegisterPanel};
-use super::watcher::{IgnorePath, WatchGlob};
+use super::console::{DynamicPanel, Output, OutputKind, RegisterPanel, StateChanged};
+use super::watcher::{IgnorePath, ReloadAllOn, WatchGlob};
 
 #[cfg(not(test))]
-mod prelude {
+pub(crate) mod prelude {
     use crate::actors::{console::ConsoleActor, watcher::WatcherActor};
 
     pub type WatcherAct = WatcherActor;
@@ -37,7 +50,7 @@ mod prelude {
 }
 
 #[cfg(test)]
-mod prelude {
+pub(crate) mod prelude {
     use crate::actors::{console::ConsoleActor, watcher::WatcherActor};
     use actix::actors::mocker::Mocker;
 
@@ -53,13 +66,40 @@ pub struct ExtendedTask {
     pipes: Vec<Pipe>,
     colors: Vec<ColorOption>,
     cwd: PathBuf,
+    /// See [`schedule::starts_on_schedule`].
+    starts_on_schedule: bool,
+    pins: Pins,
+    levels: Option<LevelFilter>,
+    /// Tasks depending on this one, see [`SlotsActor`].
+    dependents: usize,
+    /// See [`Task::ready_when`].
+    ready_when: Option<Regex>,
+    /// Time the child has to exit once asked to stop, see
+    /// [`Task::kill_timeout`].
+    kill_timeout: Duration,
 }
 
 impl Task {
     pub fn extend(&self, name: String, config: &Config) -> ExtendedTask {
         let cwd = self.get_absolute_workdir(&config.base_dir);
-        let pipes = config.pipes_map.get(&name).unwrap_or(&Vec::new()).clone();
+        let mut pipes = config.pipes_map.get(&name).unwrap_or(&Vec::new()).clone();
+        for pipe in &mut pipes {
+            if let OutputRedirection::File(_, mode @ None) = &mut pipe.redirection {
+                *mode = config.file_mode;
+            }
+        }
         let colors = config.colors_map.get(&name).unwrap_or(&Vec::new()).clone();
+        let pins = config.pins_map.get(&name).cloned().unwrap_or_default();
+        let levels = config.level_filters.get(&name).cloned();
+        let ready_when = config.ready_map.get(&name).cloned();
+        let active_ops = config.active_ops();
+        let starts_on_schedule = schedule::starts_on_schedule(&active_ops, &name);
+        let dependents = ops::count_dependents(&active_ops, &name);
+        let kill_timeout = self
+            .kill_timeout
+            .map(|schedule::Every(timeout)| timeout)
+            .or(config.kill_timeout)
+            .unwrap_or(KILL_TIMEOUT);
 
         ExtendedTask {
             name,
@@ -67,6 +107,32 @@ impl Task {
             pipes,
             colors,
             cwd,
+            starts_on_schedule,
+            pins,
+            levels,
+            dependents,
+            ready_when,
+            kill_timeout,
+        }
+    }
+}
+
+impl ExtendedTask {
+    /// Time the child has to exit by itself once its output closed before
+    /// it is asked to stop, twice its `kill_timeout` so that the tasks
+    /// shutting down slowly get longer too.
+    fn exit_wait(&self) -> Duration {
+        self.kill_timeout * 2
+    }
+
+    /// Command the task runs as shown to the user, its entrypoint when it
+    /// has no command.
+    fn command(&self) -> Cow<'_, str> {
+        let task = &self.task;
+        match (&task.command, &task.entrypoint) {
+            (Some(command), _) => Cow::Borrowed(command),
+            (None, Some(entrypoint)) => Cow::Owned(entrypoint.to_string()),
+            (None, None) => Cow::Borrowed(""),
         }
     }
 }
@@ -74,70 +140,133 @@ impl Task {
 #[derive(Debug)]
 pub enum Child {
     NotStarted,
-    Killed,
+    /// Stopped, `forced` when it had to be killed once its grace period
+    /// was over.
+    Killed {
+        forced: bool,
+    },
     Process(Popen),
+    /// Handed to a thread of its own which stops it, see
+    /// [`CommandActor::kill_child`].
+    Stopping,
     Exited(ExitStatus),
 }
 
-impl Child {
-    fn poll(&mut self, kill: bool) -> Result<bool> {
-        if let Child::Process(p) = self {
-            match p.poll() {
-                Some(exit) => {
-                    *self = Self::Exited(exit);
-                    Ok(true)
-                }
-                None if kill => {
-                    p.terminate()?;
-                    match p.wait_timeout(Duration::from_millis(500))? {
-                        Some(_status) => {
-                            //println!("terminated with {:?}", status);
-                        }
-                        None => {
-                            p.kill()?;
-                            let _status = p.wait()?;
-                            //println!("killed with {:?} ", _status);
-                        }
-                    }
+/// Sends `signal` to the process group the child leads (see
+/// [`ExecBuilder::popen`]), reaching the processes it spawned as well.
+#[cfg(unix)]
+fn signal_group(pid: Option<u32>, signal: libc::c_int) -> std::io::Result<()> {
+    let Some(pid) = pid else {
+        return Ok(());
+    };
+    // SAFETY: killpg has no memory effects
+    if unsafe { libc::killpg(pid as libc::pid_t, signal) } == 0 {
+        return Ok(());
+    }
+    match std::io::Error::last_os_error() {
+        // every process of the group already exited
+        err if err.raw_os_error() == Some(libc::ESRCH) => Ok(()),
+        err => Err(err),
+    }
+}
 
-                    *self = Self::Killed;
-                    Ok(true)
-                }
-                None => Ok(false),
-            }
-        } else {
-            Ok(false)
-        }
+#[cfg(unix)]
+fn set_nonblocking(file: &fs::File) -> std::io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let fd = file.as_raw_fd();
+    // SAFETY: fcntl only changes the flags of the descriptor
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if flags == -1 || unsafe { libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) } == -1 {
+        return Err(std::io::Error::last_os_error());
     }
+    Ok(())
+}
 
-    fn wait_or_kill(&mut self, dur: Duration) -> Result<bool> {
-        if let Child::Process(p) = self {
-            match p.wait_timeout(dur)? {
-                Some(status) => {
-                    *self = Child::Exited(status);
-                    Ok(true)
-                }
-                None => {
-                    p.terminate()?;
-                    p.kill()?;
-                    let _status = p.wait()?;
-                    if p.wait_timeout(Duration::from_millis(500))?.is_none() {
-                        p.kill()?;
-                        p.wait()?;
-                    }
-                    *self = Self::Killed;
-                    Ok(true)
-                }
-            }
-        } else {
-            Ok(false)
+#[cfg(unix)]
+fn send_terminate(p: &mut Popen, signal: StopSignal) -> std::io::Result<()> {
+    signal_group(p.pid(), signal.number())
+}
+
+#[cfg(not(unix))]
+fn send_terminate(p: &mut Popen, _: StopSignal) -> std::io::Result<()> {
+    p.terminate()
+}
+
+#[cfg(unix)]
+fn send_kill(p: &mut Popen) -> std::io::Result<()> {
+    signal_group(p.pid(), libc::SIGKILL)
+}
+
+#[cfg(not(unix))]
+fn send_kill(p: &mut Popen) -> std::io::Result<()> {
+    p.kill()
+}
+
+/// Time a child has to exit once asked to stop, unless its task or
+/// `--kill-timeout` sets another one.
+pub const KILL_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Threads stopping the children of the tasks, which whiz waits for before
+/// exiting, see [`wait_for_stopping_children`].
+static STOPPING: Mutex<Vec<std::thread::JoinHandle<()>>> = Mutex::new(Vec::new());
+
+/// Waits until the children being stopped are gone, killed once their
+/// grace period is over.
+pub fn wait_for_stopping_children() {
+    let stopping = std::mem::take(&mut *STOPPING.lock().unwrap());
+    for thread in stopping {
+        let _ = thread.join();
+    }
+}
+
+/// Runs `stop` on a thread of its own, which whiz waits for before exiting.
+fn spawn_stopping(stop: impl FnOnce() + Send + 'static) {
+    let mut stopping = STOPPING.lock().unwrap();
+    stopping.retain(|thread| !thread.is_finished());
+    stopping.push(std::thread::spawn(stop));
+}
+
+/// Waits `grace` for the process which was sent the stop signal to exit,
+/// then kills it along with what is left of its group, and runs `removal`
+/// for its container. Returns whether it had to be killed. Blocks, so it
+/// runs on a thread of its own.
+fn kill_after(
+    mut p: Popen,
+    grace: Duration,
+    removal: Option<std::process::Command>,
+) -> Result<bool> {
+    #[cfg(unix)]
+    let pid = p.pid();
+    let forced = p.wait_timeout(grace)?.is_none();
+    if forced {
+        send_kill(&mut p)?;
+        p.wait()?;
+    }
+    // the processes of the group which outlived the child
+    #[cfg(unix)]
+    signal_group(pid, libc::SIGKILL)?;
+    if let Some(mut removal) = removal {
+        removal.status()?;
+    }
+    Ok(forced)
+}
+
+impl Child {
+    /// Returns the exit status without finalizing the child, which is left
+    /// to `StdoutTerminated` so that dependents are reloaded exactly once.
+    fn peek_status(&mut self) -> Option<ExitStatus> {
+        match self {
+            Child::NotStarted | Child::Stopping => None,
+            Child::Process(p) => p.poll(),
+            _ => self.exit_status(),
         }
     }
 
     fn exit_status(&mut self) -> Option<ExitStatus> {
         match &self {
-            Child::Process(_) => None,
-            Child::Killed => Some(ExitStatus::Undetermined),
+            Child::Process(_) | Child::Stopping => None,
+            Child::Killed { .. } => Some(ExitStatus::Undetermined),
             Child::Exited(exit) => Some(*exit),
             Child::NotStarted => panic!("should not happen"),
         }
@@ -150,6 +279,8 @@ pub struct CommandActorsBuilder {
     watcher: Addr<WatcherAct>,
     verbose: bool,
     watch_enabled_globally: bool,
+    max_parallel: Option<usize>,
+    dry_watch: bool,
 }
 
 impl CommandActorsBuilder {
@@ -160,6 +291,8 @@ impl CommandActorsBuilder {
             watcher,
             verbose: false,
             watch_enabled_globally: true,
+            max_parallel: None,
+            dry_watch: false,
         }
     }
 
@@ -177,6 +310,24 @@ impl CommandActorsBuilder {
         }
     }
 
+    /// Runs at most `max_parallel` tasks at once, see [`SlotsActor`].
+    pub fn max_parallel(self, max_parallel: Option<usize>) -> Self {
+        Self {
+            max_parallel,
+            ..self
+        }
+    }
+
+    /// Starts nothing, the watcher telling instead which tasks the
+    /// changed files would reload, see
+    /// [`crate::actors::watcher::WatcherActor::dry_watch`].
+    pub fn dry_watch(self, toggle: bool) -> Self {
+        Self {
+            dry_watch: toggle,
+            ..self
+        }
+    }
+
     pub async fn build(self) -> Result<HashMap<String, Addr<CommandActor>>> {
         let Self {
             config,
@@ -184,14 +335,23 @@ impl CommandActorsBuilder {
             watcher,
             verbose,
             watch_enabled_globally,
+            max_parallel,
+            dry_watch,
         } = self;
 
+        let slots = max_parallel.map(|max| SlotsActor::new(max).start());
+
         let mut commands: HashMap<String, Addr<CommandActor>> = HashMap::new();
+        let mut roots = Vec::new();
+        let mut primaries = Vec::new();
 
+        let active_ops = config.active_ops();
         for (op_name, nexts) in config.build_dag().unwrap().into_iter() {
-            let task = config.ops.get(&op_name).unwrap();
+            let task = active_ops.get(&op_name).unwrap();
 
-            let exec_builder = ExecBuilder::new(task, &config).await?;
+            let exec_builder = ExecBuilder::new(&op_name, task, &config)
+                .await
+                .map_err(|err| anyhow!("task {op_name}: {err}"))?;
             let op = task.extend(op_name.clone(), &config);
 
             let actor = CommandActor::new(
@@ -206,32 +366,130 @@ impl CommandActorsBuilder {
                 watch_enabled_globally,
                 exec_builder,
             )
+            .slots(slots.clone())
+            .dry_watch(dry_watch)
             .start();
 
             if task.depends_on.resolve().is_empty() {
-                actor.do_send(Reload::Start)
+                roots.push(actor.clone());
+            }
+            if task.primary {
+                primaries.push(op_name.clone());
             }
             commands.insert(op_name, actor);
         }
 
+        // peers must be known before anything starts, as a primary task
+        // can exit right away
+        for primary in primaries {
+            let peers = commands
+                .iter()
+                .filter(|(op_name, _)| **op_name != primary)
+                .map(|(_, addr)| addr.clone())
+                .collect();
+            commands.get(&primary).unwrap().do_send(Peers(peers));
+        }
+
+        if watch_enabled_globally {
+            watcher.do_send(ReloadAllOn::new(&config, roots.clone(), commands.clone())?);
+        }
+
+        if !dry_watch {
+            for root in roots {
+                root.do_send(Reload::Start);
+            }
+        }
+
         Ok(commands)
     }
 }
 
+/// Code a task exits with once killed by its `timeout`, as `timeout(1)`.
+pub const TIMED_OUT: u32 = 124;
+
+/// Time a task exiting waits before its `restart` policy starts it again.
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+
+/// Size of the pseudo-terminal of a task until the console tells the one
+/// of its panel, as `(columns, rows)`.
+const DEFAULT_TTY_SIZE: (u16, u16) = (80, 24);
+
+/// Longest a scheduled task sleeps before checking the time again.
+const MAX_SCHEDULE_WAIT: Duration = Duration::from_secs(24 * 60 * 60);
+
 pub struct CommandActor {
     operator: ExtendedTask,
     console: Addr<ConsoleAct>,
     watcher: Addr<WatcherAct>,
+    /// Thread reading the output of the child.
     arbiter: Arbiter,
+    /// Tells the reader of the current child to stop, see
+    /// [`Self::teardown`].
+    reader_cancelled: Arc<AtomicBool>,
     child: Child,
     nexts: Vec<Addr<CommandActor>>,
     self_addr: Option<Addr<CommandActor>>,
     pending_upstream: BTreeMap<String, usize>,
+    /// Upstream tasks which must succeed and failed last, see
+    /// [`crate::config::Condition::Success`].
+    failed_upstream: BTreeSet<String>,
+    /// How the last run of each upstream task ended, given to the next
+    /// runs in their environment.
+    upstream_runs: BTreeMap<String, Finished>,
     verbose: bool,
     started_at: DateTime<Local>,
     watch: bool,
     death_invite: Option<PermaDeathInvite>,
+    /// Invite answered while the reaper waits for the tasks to settle,
+    /// revoked if the task starts again, see [`PermaDeathInvite::revoke`].
+    answered_invite: Option<PermaDeathInvite>,
     exec_builder: ExecBuilder,
+    peers: Vec<Addr<CommandActor>>,
+    state: TaskState,
+    /// Next run of a scheduled task, see [`Self::schedule_next`].
+    schedule_handle: Option<SpawnHandle>,
+    /// Whether the run started with whiz or from its upstream, which is
+    /// retried when it fails, see [`Task::retries`].
+    retryable: bool,
+    /// Retries of the run so far.
+    retried: u32,
+    /// Next retry of a failed run, see [`Self::retry_after`].
+    retry_handle: Option<SpawnHandle>,
+    /// Restarts by the `restart` policy since the task was last started
+    /// by something else.
+    restarts: u32,
+    /// Next restart, see [`Self::restart_after`].
+    restart_handle: Option<SpawnHandle>,
+    /// Files changed since the first change the task waits to settle, see
+    /// [`Task::debounce`].
+    debounced: Vec<String>,
+    /// Reload once the changes settled, see [`Self::debounce`].
+    debounce_handle: Option<SpawnHandle>,
+    /// Whether the changes settled, so that their reload goes through.
+    debounce_due: bool,
+    /// End of the time the run has, see [`Task::timeout`].
+    timeout_handle: Option<SpawnHandle>,
+    /// Whether the run was stopped by its timeout.
+    timed_out: bool,
+    /// Whether the dependents were reloaded as soon as the run was ready
+    /// (see [`Task::ready_when`]), so that its end does not reload them twice.
+    released_early: bool,
+    /// Whether a task set to run `once` succeeded, see [`Task::once`].
+    once_done: bool,
+    /// Size of the panel of the task, given to its pseudo-terminal.
+    tty_size: (u16, u16),
+    /// Master side of the pseudo-terminal of the run, see [`Task::tty`].
+    #[cfg(unix)]
+    tty: Option<fs::File>,
+    /// Stdin of the run when it has no pseudo-terminal, see [`Input`].
+    stdin: Option<fs::File>,
+    slots: Option<Addr<SlotsActor>>,
+    /// Waits for a slot to spawn.
+    queued: bool,
+    /// Holds a slot until the task is finished.
+    holds_slot: bool,
+    /// Never runs, see [`CommandActorsBuilder::dry_watch`].
+    dry_watch: bool,
 }
 
 impl CommandActor {
@@ -250,18 +508,55 @@ impl CommandActor {
             console,
             watcher,
             arbiter: Arbiter::new(),
+            reader_cancelled: Arc::default(),
             child: Child::NotStarted,
             nexts,
             self_addr: None,
             pending_upstream: BTreeMap::default(),
+            failed_upstream: BTreeSet::default(),
+            upstream_runs: BTreeMap::default(),
             verbose,
             started_at: Local::now(),
             watch,
             death_invite: None,
+            answered_invite: None,
             exec_builder,
+            peers: Vec::new(),
+            state: TaskState::Pending,
+            schedule_handle: None,
+            retryable: false,
+            retried: 0,
+            retry_handle: None,
+            restarts: 0,
+            restart_handle: None,
+            debounced: Vec::new(),
+            debounce_handle: None,
+            debounce_due: false,
+            tty_size: DEFAULT_TTY_SIZE,
+            #[cfg(unix)]
+            tty: None,
+            stdin: None,
+            timeout_handle: None,
+            timed_out: false,
+            released_early: false,
+            once_done: false,
+            slots: None,
+            queued: false,
+            holds_slot: false,
+            dry_watch: false,
         }
     }
 
+    pub fn slots(mut self, slots: Option<Addr<SlotsActor>>) -> Self {
+        self.slots = slots;
+        self
+    }
+
+    pub fn dry_watch(mut self, toggle: bool) -> Self {
+        self.dry_watch = toggle;
+        self
+    }
+
     fn log_info(&self, log: String) {
         let job_name = self.operator.name.clone();
 
@@ -275,12 +570,225 @@ impl CommandActor {
         }
     }
 
-    fn ensure_stopped(&mut self) {
-        if self.child.poll(true).unwrap() {
-            self.send_reload();
+    /// Moves to `state` and broadcasts it, ignoring illegal transitions
+    /// (see [`TaskState::can_become`]).
+    fn set_state(&mut self, state: TaskState) {
+        if state == self.state {
+            return;
+        }
+        let legal = self.state.can_become(&state);
+        debug_assert!(
+            legal,
+            "{}: illegal transition from {} to {}",
+            self.operator.name, self.state, state
+        );
+        if !legal {
+            return;
+        }
+
+        self.log_debug(format!("STATE: {} -> {}", self.state, state));
+        self.state = state;
+        let changed = StateChanged {
+            panel_name: self.operator.name.clone(),
+            state,
+        };
+        if let Some(invite) = self.death_invite.as_ref().or(self.answered_invite.as_ref()) {
+            invite.state_changed(changed.clone());
+        }
+        self.console.do_send(changed);
+        if state.is_finished() {
+            self.release_slot();
+        } else if let Some(invite) = self.answered_invite.take() {
+            // the reaper waits for the next answer
+            self.death_invite = Some(invite);
         }
     }
 
+    /// Waits for a slot before spawning the task, see [`SlotsActor`].
+    fn acquire_slot(&mut self, ctx: &mut Context<Self>) {
+        let Some(slots) = &self.slots else {
+            self.reload(ctx);
+            return;
+        };
+        self.queued = true;
+        slots.do_send(Acquire {
+            name: self.operator.name.clone(),
+            priority: self.operator.dependents,
+            addr: ctx.address().recipient(),
+        });
+    }
+
+    fn release_slot(&mut self) {
+        if let (true, Some(slots)) = (self.holds_slot, &self.slots) {
+            slots.do_send(Release {
+                name: self.operator.name.clone(),
+            });
+        }
+        self.holds_slot = false;
+    }
+
+    /// Runs the blocking `work` on a thread of its own, holding the
+    /// messages of the actor meanwhile so that the other tasks and the
+    /// console carry on, then calls `then` with its result.
+    fn off_thread<T: Send + 'static>(
+        &mut self,
+        ctx: &mut Context<Self>,
+        work: impl FnOnce() -> Result<T> + Send + 'static,
+        then: impl FnOnce(&mut Self, &mut Context<Self>, Result<T>) + 'static,
+    ) {
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+        spawn_stopping(move || {
+            let _ = sender.send(work());
+        });
+        ctx.wait(
+            actix::fut::wrap_future::<_, Self>(receiver).map(move |res, act, ctx| {
+                then(act, ctx, res.unwrap_or_else(|err| Err(err.into())))
+            }),
+        );
+    }
+
+    /// Stops the child with the stop signal and kill timeout of the task,
+    /// after waiting `wait` for it to exit by itself, then calls `then` with
+    /// whether it got finalized. Tells in verbose mode how it was stopped.
+    fn kill_child(
+        &mut self,
+        wait: Option<Duration>,
+        ctx: &mut Context<Self>,
+        then: impl FnOnce(&mut Self, &mut Context<Self>, bool) + 'static,
+    ) {
+        let mut p = match std::mem::replace(&mut self.child, Child::Stopping) {
+            Child::Process(p) => p,
+            child => {
+                self.child = child;
+                return then(self, ctx, false);
+            }
+        };
+        if let Some(status) = p.poll() {
+            self.child = Child::Exited(status);
+            return then(self, ctx, true);
+        }
+        let Some(wait) = wait else {
+            return self.terminate_child(p, ctx, then);
+        };
+        self.off_thread(
+            ctx,
+            move || Ok((p.wait_timeout(wait), p)),
+            move |act, ctx, res| match res {
+                Ok((Ok(Some(status)), _)) => {
+                    act.child = Child::Exited(status);
+                    then(act, ctx, true)
+                }
+                Ok((_, p)) => act.terminate_child(p, ctx, then),
+                Err(_) => {
+                    act.child = Child::Killed { forced: false };
+                    then(act, ctx, true)
+                }
+            },
+        );
+    }
+
+    /// Sends the stop signal to the child, then kills it if it is still
+    /// there after the kill timeout, see [`kill_after`].
+    fn terminate_child(
+        &mut self,
+        mut p: Popen,
+        ctx: &mut Context<Self>,
+        then: impl FnOnce(&mut Self, &mut Context<Self>, bool) + 'static,
+    ) {
+        let signal = self.operator.task.stop_signal;
+        let grace = self.operator.kill_timeout;
+        if let Err(err) = send_terminate(&mut p, signal) {
+            self.log_info(format!("Cannot stop: {err}"));
+        }
+        self.log_debug(format!("STOP: sent {}", signal.name()));
+        let removal = self.container_removal();
+        self.off_thread(
+            ctx,
+            move || kill_after(p, grace, removal),
+            move |act, ctx, forced| {
+                let forced = forced.unwrap_or_else(|err| {
+                    act.log_info(format!("Cannot stop: {err}"));
+                    false
+                });
+                if forced {
+                    act.log_debug(format!(
+                        "STOP: still running after {}ms, sent SIGKILL",
+                        grace.as_millis()
+                    ));
+                }
+                act.child = Child::Killed { forced };
+                then(act, ctx, true)
+            },
+        );
+    }
+
+    /// Returns the command removing the container of the task, which the
+    /// engine leaves behind when it gets killed.
+    fn container_removal(&self) -> Option<std::process::Command> {
+        let container = self.operator.task.container.as_ref()?;
+        Some(container.remove(&self.operator.name))
+    }
+
+    /// Kills the child if it still runs, then calls `then` with whether it
+    /// got finalized.
+    fn stop(
+        &mut self,
+        ctx: &mut Context<Self>,
+        then: impl FnOnce(&mut Self, &mut Context<Self>, bool) + 'static,
+    ) {
+        if let Child::Process(_) = self.child {
+            self.set_state(TaskState::Stopping);
+        }
+        self.kill_child(None, ctx, move |act, ctx, finalized| {
+            if finalized {
+                if let Some(exit) = act.child.exit_status() {
+                    act.set_state(TaskState::Exited(exit));
+                }
+            }
+            then(act, ctx, finalized)
+        });
+    }
+
+    /// Sends the stop signal to the child, leaving killing it after the
+    /// kill timeout to a thread of its own. Then stops the reader of its
+    /// output and the arbiter it runs on. Runs once the actor is stopped or
+    /// dropped, so failures are only reported.
+    fn teardown(&mut self) {
+        if let Child::Process(mut p) = std::mem::replace(&mut self.child, Child::Stopping) {
+            match p.poll() {
+                Some(status) => self.child = Child::Exited(status),
+                None => {
+                    if let Err(err) = send_terminate(&mut p, self.operator.task.stop_signal) {
+                        self.log_info(format!("Cannot stop: {err}"));
+                    }
+                    let grace = self.operator.kill_timeout;
+                    let removal = self.container_removal();
+                    spawn_stopping(move || {
+                        let _ = kill_after(p, grace, removal);
+                    });
+                }
+            }
+        }
+        self.reader_cancelled.store(true, Ordering::Relaxed);
+        self.arbiter.stop();
+        self.release_slot();
+    }
+
+    /// Stops the child as [`Self::stop`] does, releasing the dependents
+    /// when it got finalized, then calls `then`.
+    fn ensure_stopped(
+        &mut self,
+        ctx: &mut Context<Self>,
+        then: impl FnOnce(&mut Self, &mut Context<Self>) + 'static,
+    ) {
+        self.stop(ctx, move |act, ctx, finalized| {
+            if finalized {
+                act.send_reload(false);
+            }
+            then(act, ctx)
+        });
+    }
+
     fn upstream(&self) -> String {
         Vec::from_iter(
             self.pending_upstream
@@ -290,13 +798,39 @@ impl CommandActor {
         .join(", ")
     }
 
-    fn send_reload(&self) {
+    /// Releases the dependents, telling them whether this run `failed` for
+    /// the ones which need it to succeed.
+    fn send_reload(&mut self, failed: bool) {
+        if std::mem::take(&mut self.released_early) || self.once_done {
+            return;
+        }
+        let op_name = self.operator.name.clone();
+        let finished = self.finished();
         for next in (self.nexts).iter() {
-            next.do_send(Reload::Op(self.operator.name.clone()));
+            next.do_send(match failed {
+                true => Reload::Failed(op_name.clone(), finished),
+                false => Reload::Op(op_name.clone(), finished),
+            });
         }
     }
 
-    fn send_will_reload(&self) {
+    /// Returns how the current run ended, once it exited.
+    fn finished(&self) -> Option<Finished> {
+        match (&self.child, self.state) {
+            (Child::Exited(status), TaskState::Stopping | TaskState::Exited(_)) => Some(Finished {
+                status: *status,
+                duration: (Local::now() - self.started_at)
+                    .to_std()
+                    .unwrap_or_default(),
+            }),
+            _ => None,
+        }
+    }
+
+    fn send_will_reload(&mut self) {
+        if self.once_done {
+            return;
+        }
         for next in (self.nexts).iter() {
             next.do_send(WillReload {
                 op_name: self.operator.name.clone(),
@@ -304,40 +838,145 @@ impl CommandActor {
         }
     }
 
-    fn reload(&mut self) -> Result<()> {
-        self.log_debug(self.exec_builder.as_string());
-        self.console.do_send(PanelStatus {
-            panel_name: self.operator.name.clone(),
-            status: None,
-        });
-
-        let mut p = self
+    /// Runs a hook of the task (see [`Task::before`]) to completion, its
+    /// lines showing as the ones of whiz, and returns how it exited.
+    fn run_hook(&self, hook: &str, command: &str) -> ExitStatus {
+        self.log_info(format!("{hook} `{command}`"));
+        match self
             .exec_builder
-            .build()
-            .unwrap()
-            .stdout(Redirection::Pipe)
-            .stderr(Redirection::Merge)
-            .popen()
-            .unwrap();
+            .hook(command, &upstream_env(&self.upstream_runs))
+        {
+            Ok(capture) => {
+                for line in capture.stdout_str().lines() {
+                    self.log_info(line.to_string());
+                }
+                capture.exit_status
+            }
+            Err(err) => {
+                self.log_info(format!("Cannot run {hook}: {err}"));
+                ExitStatus::Undetermined
+            }
+        }
+    }
 
+    /// Spawns the command, returning the output to read, on a
+    /// pseudo-terminal when the task sets `tty` (only on Unix).
+    fn spawn(&mut self) -> Result<(Popen, Box<dyn Read + Send>)> {
+        let extra_env = upstream_env(&self.upstream_runs);
+        #[cfg(unix)]
+        if self.operator.task.tty {
+            let pty = crate::pty::Pty::open(self.tty_size)?;
+            let p = self.exec_builder.popen_tty(&extra_env, pty.slave)?;
+            self.tty = Some(pty.master.try_clone()?);
+            return Ok((p, Box::new(crate::pty::Reader(pty.master))));
+        }
+        let mut p = self.exec_builder.popen(&extra_env)?;
         let stdout = p.stdout.take().unwrap();
-        let reader = BufReader::new(stdout);
+        self.stdin = p.stdin.take();
+        #[cfg(unix)]
+        if let Some(stdin) = &self.stdin {
+            // a task which does not read its input must not block whiz
+            set_nonblocking(stdin)?;
+        }
+        Ok((p, Box::new(stdout)))
+    }
+
+    fn reload(&mut self, cx: &mut Context<Self>) {
+        self.log_debug(self.exec_builder.as_string());
+        self.set_state(TaskState::Starting);
+
+        let missing = self.exec_builder.missing(&self.operator.task.requires);
+        if let (true, Some(before)) = (missing.is_empty(), self.operator.task.before.clone()) {
+            let status = self.run_hook("before", &before);
+            if !status.success() {
+                self.log_info(format!("before {}, not started", describe_exit(status)));
+                self.child = Child::Exited(status);
+                self.set_state(TaskState::Exited(status));
+                self.send_reload(true);
+                if self.operator.task.primary {
+                    self.end_session(status);
+                }
+                self.accept_death_invite(cx);
+                return;
+            }
+        }
+        let started = match missing.is_empty() {
+            true => self.spawn().map_err(|err| err.to_string()),
+            false => Err(describe_missing(&missing)),
+        };
+        let (mut p, stdout) = match started {
+            Ok(started) => started,
+            Err(err) => {
+                self.log_info(format!("Cannot start: {err}"));
+                self.set_state(TaskState::Blocked);
+                // dependents are released as for any failure
+                self.send_reload(true);
+                self.accept_death_invite(cx);
+                return;
+            }
+        };
+
+        // even the tasks which print nothing tell they ran
+        self.log_info(started_message(&self.operator.command(), p.pid()));
+
+        let started_at = Local::now();
+        if let Some(stderr) = p.stderr.take() {
+            // read even when quiet, so that the child never blocks on it
+            let console = (!self.operator.task.quiet).then(|| self.console.clone());
+            let self_addr = self.self_addr.clone();
+            read_stderr(
+                stderr,
+                self.operator.task.encoding,
+                console,
+                self.operator.name.clone(),
+                self.reader_cancelled.clone(),
+                self.operator.ready_when.clone(),
+                move || {
+                    if let Some(addr) = self_addr {
+                        addr.do_send(Ready { started_at });
+                    }
+                },
+            );
+        }
+        let stream = self.operator.task.stream;
+        let encoding = self.operator.task.encoding;
 
         let console = self.console.clone();
         let op_name = self.operator.name.clone();
         let self_addr = self.self_addr.clone();
-        let started_at = Local::now();
         let cwd = self.operator.cwd.clone();
         let watcher = self.watcher.clone();
         let task_pipes = self.operator.pipes.clone();
         let task_colors = self.operator.colors.clone();
+        let max_dynamic_tabs = self.operator.task.max_dynamic_tabs;
+        let quiet = self.operator.task.quiet;
+        let pins = self.operator.pins.clone();
+        let mut levels = self.operator.levels.clone();
+        let cancelled = self.reader_cancelled.clone();
+        let mut ready_when = self.operator.ready_when.clone();
 
         let fut = async move {
-            for line in reader.lines() {
-                let mut line = line.unwrap();
+            let panel_addr = self_addr.clone();
+            let mut show_line = |mut line: String| {
+                // only the first match of a run tells it is ready
+                if ready_when
+                    .as_ref()
+                    .is_some_and(|regex| regex.is_match(&line))
+                {
+                    ready_when = None;
+                    if let Some(addr) = &panel_addr {
+                        addr.do_send(Ready { started_at });
+                    }
+                }
 
                 let task_pipe = task_pipes.iter().find(|pipe| pipe.regex.is_match(&line));
 
+                if let Some(levels) = &mut levels {
+                    if !levels.keeps(&line) {
+                        return;
+                    }
+                }
+
                 if let Some(task_pipe) = task_pipe {
                     match &task_pipe.redirection {
                         OutputRedirection::Tab(name) => {
@@ -345,13 +984,17 @@ impl CommandActor {
                             if let Some(capture) = task_pipe.regex.captures(&line) {
                                 capture.expand(&name.clone(), &mut tab_name);
                             }
-                            if let Some(addr) = &self_addr {
+                            if let Some(addr) = &panel_addr {
                                 // tabs must be created on each loop,
                                 // as their name can be dynamic
                                 console.do_send(RegisterPanel {
                                     name: tab_name.to_owned(),
-                                    addr: addr.clone(),
+                                    addr: Some(addr.clone()),
                                     colors: task_colors.clone(),
+                                    dynamic: name.contains('$').then(|| DynamicPanel {
+                                        task: op_name.clone(),
+                                        max_tabs: max_dynamic_tabs,
+                                    }),
                                 });
                             }
                             console.do_send(Output::now(
@@ -360,7 +1003,7 @@ impl CommandActor {
                                 OutputKind::Command,
                             ));
                         }
-                        OutputRedirection::File(path) => {
+                        OutputRedirection::File(path, mode) => {
                             let path = task_pipe.regex.replace(&line, path);
                             let mut path = Path::new(path.as_ref()).to_path_buf();
 
@@ -376,11 +1019,12 @@ impl CommandActor {
                             // as the path is dynamic, therefore there
                             // is no a way to optimize it to create it
                             // only once
-                            let mut file = fs::OpenOptions::new()
-                                .create(true)
-                                .append(true)
-                                .open(&path)
-                                .unwrap();
+                            let mut file = file_mode::open(
+                                fs::OpenOptions::new().create(true).append(true),
+                                &path,
+                                *mode,
+                            )
+                            .unwrap();
 
                             // exlude file path from watcher before writing to it
                             // to avoid infinite loops
@@ -391,8 +1035,58 @@ impl CommandActor {
                             file.write_all(line.as_bytes()).unwrap();
                         }
                     }
-                } else {
-                    console.do_send(Output::now(op_name.clone(), line, OutputKind::Command));
+                } else if !quiet {
+                    let pin = pins.slot(&line);
+                    console
+                        .do_send(Output::now(op_name.clone(), line, OutputKind::Command).pin(pin));
+                }
+            };
+
+            match stream {
+                Stream::Line => {
+                    for line in encoding.lines(BufReader::new(stdout)) {
+                        if cancelled.load(Ordering::Relaxed) {
+                            return;
+                        }
+                        show_line(line.unwrap());
+                    }
+                }
+                Stream::Raw => {
+                    let chunks = read_chunks(stdout);
+                    let mut lines = RawLines::new(encoding);
+                    // whether the partial line shows in the panel
+                    let mut partial = false;
+                    let mut flushed_at = Instant::now();
+                    loop {
+                        let chunk = chunks.recv_timeout(PARTIAL_FLUSH);
+                        if cancelled.load(Ordering::Relaxed) {
+                            return;
+                        }
+                        let ended = match chunk {
+                            Ok(bytes) => lines.push(&bytes),
+                            Err(RecvTimeoutError::Timeout) => Vec::new(),
+                            Err(RecvTimeoutError::Disconnected) => break,
+                        };
+                        for line in ended {
+                            if std::mem::take(&mut partial) {
+                                console.do_send(partial_output(&op_name, String::new()));
+                            }
+                            show_line(line);
+                        }
+                        if flushed_at.elapsed() >= PARTIAL_FLUSH {
+                            flushed_at = Instant::now();
+                            if let Some(line) = lines.flush().filter(|_| !quiet) {
+                                console.do_send(partial_output(&op_name, line));
+                                partial = true;
+                            }
+                        }
+                    }
+                    if partial {
+                        console.do_send(partial_output(&op_name, String::new()));
+                    }
+                    if let Some(line) = lines.finish() {
+                        show_line(line);
+                    }
                 }
             }
 
@@ -403,18 +1097,262 @@ impl CommandActor {
 
         self.child = Child::Process(p);
         self.started_at = started_at;
+        self.set_state(TaskState::Running);
         self.arbiter.spawn(fut);
+        self.start_timeout(cx);
+    }
+
+    /// Gives the new run the whole `timeout` of the task, replacing the
+    /// time left to the previous one.
+    fn start_timeout(&mut self, ctx: &mut Context<Self>) {
+        if let Some(handle) = self.timeout_handle.take() {
+            ctx.cancel_future(handle);
+        }
+        self.timed_out = false;
+        let Some(schedule::Every(timeout)) = self.operator.task.timeout else {
+            return;
+        };
+        let handle = ctx.run_later(timeout, move |act, ctx| {
+            act.timeout_handle = None;
+            act.time_out(timeout, ctx);
+        });
+        self.timeout_handle = Some(handle);
+    }
+
+    /// Terminates the run which lasts too long, then kills it if it is
+    /// still there after its `kill_timeout`. Its end is then reported by
+    /// `StdoutTerminated` as for any other run.
+    fn time_out(&mut self, timeout: Duration, ctx: &mut Context<Self>) {
+        let Child::Process(p) = &mut self.child else {
+            return;
+        };
+        if p.poll().is_some() {
+            return;
+        }
+        let signal = self.operator.task.stop_signal;
+        if let Err(err) = send_terminate(p, signal) {
+            self.log_info(format!("Cannot stop: {err}"));
+        }
+        self.timed_out = true;
+        self.log_info(format!("timed out after {}s", timeout.as_secs()));
+        self.log_debug(format!("STOP: sent {}", signal.name()));
+
+        let started_at = self.started_at;
+        ctx.run_later(self.operator.kill_timeout, move |act, _| {
+            if act.started_at != started_at {
+                return;
+            }
+            if let Child::Process(p) = &mut act.child {
+                if p.poll().is_none() {
+                    if let Err(err) = send_kill(p) {
+                        act.log_info(format!("Cannot stop: {err}"));
+                    }
+                    act.log_debug("STOP: still running, sent SIGKILL".to_string());
+                }
+            }
+        });
+    }
+
+    /// Stands in for [`Self::reload`] on tasks that only forward watch
+    /// events: nothing is spawned and dependents are reloaded right away.
+    fn trigger(&mut self, cx: &mut Context<Self>) {
+        self.log_debug("TRIGGER: forwarding to dependents".to_string());
+        self.set_state(TaskState::Starting);
+        self.started_at = Local::now();
+        self.child = Child::Exited(ExitStatus::Exited(0));
+        self.set_state(TaskState::Exited(ExitStatus::Exited(0)));
+        self.send_reload(false);
+        self.accept_death_invite(cx);
+    }
 
-        Ok(())
+    /// Stops every other task and exits whiz with the exit code of
+    /// this (primary) task.
+    fn end_session(&mut self, status: ExitStatus) {
+        let code = grim_reaper::exit_code(&self.operator.name, &status);
+        self.log_info(format!("Primary task exited, shutting down (code {code})"));
+
+        let peers = std::mem::take(&mut self.peers);
+        actix::spawn(async move {
+            for peer in peers {
+                // peers that are already stopped have nothing left to kill
+                let _ = peer.send(PoisonPill).await;
+            }
+            System::current().stop_with_code(code);
+        });
+    }
+
+    /// Plans the next run of a scheduled task, replacing the planned one.
+    fn schedule_next(&mut self, ctx: &mut Context<Self>) {
+        if let Some(handle) = self.schedule_handle.take() {
+            ctx.cancel_future(handle);
+        }
+        let Some(schedule) = self.operator.task.schedule() else {
+            return;
+        };
+        match schedule.next_after(&Local::now()) {
+            Some(next) => {
+                self.log_info(format!(
+                    "SCHEDULE: next run at {}",
+                    next.format("%Y-%m-%d %H:%M:%S")
+                ));
+                self.run_at(next, ctx);
+            }
+            None => self.log_info("SCHEDULE: never runs".to_string()),
+        }
+    }
+
+    fn run_at(&mut self, next: DateTime<Local>, ctx: &mut Context<Self>) {
+        // waking up daily keeps the timer within what the runtime supports
+        // and notices when the clock is changed
+        let delay = (next - Local::now())
+            .to_std()
+            .unwrap_or_default()
+            .min(MAX_SCHEDULE_WAIT);
+        let handle = ctx.run_later(delay, move |act, ctx| {
+            act.schedule_handle = None;
+            if Local::now() < next {
+                act.run_at(next, ctx);
+                return;
+            }
+            act.handle(Reload::Schedule, ctx);
+            act.schedule_next(ctx);
+        });
+        self.schedule_handle = Some(handle);
+    }
+
+    /// Whether the current run is retried if it fails.
+    fn may_retry(&self) -> bool {
+        self.retryable && self.retried < self.operator.task.retries.count
+    }
+
+    /// Runs the failed task again after `delay`, its dependents and the
+    /// reaper waiting until it succeeds or runs out of retries.
+    fn retry_after(&mut self, delay: Duration, ctx: &mut Context<Self>) {
+        self.retried += 1;
+        self.log_info(format!(
+            "RETRY {}/{} in {}",
+            self.retried,
+            self.operator.task.retries.count,
+            format_delay(delay)
+        ));
+        let handle = ctx.run_later(delay, |act, ctx| {
+            act.retry_handle = None;
+            act.acquire_slot(ctx);
+        });
+        self.retry_handle = Some(handle);
+    }
+
+    /// Starts the task again once its run exited, as its `restart` policy
+    /// asks, its dependents waiting as after a manual reload.
+    fn restart_after(&mut self, delay: Duration, ctx: &mut Context<Self>) {
+        self.restarts += 1;
+        self.log_info(format!("restarting (attempt {})", self.restarts));
+        let handle = ctx.run_later(delay, |act, ctx| {
+            act.restart_handle = None;
+            act.send_will_reload();
+            act.retryable = false;
+            act.retried = 0;
+            act.acquire_slot(ctx);
+        });
+        self.restart_handle = Some(handle);
+    }
+
+    /// Gives up on the planned restart, e.g. when something else starts
+    /// the task, which counts the restarts from zero again.
+    fn cancel_restart(&mut self, ctx: &mut Context<Self>) {
+        if let Some(handle) = self.restart_handle.take() {
+            ctx.cancel_future(handle);
+        }
+        self.restarts = 0;
+    }
+
+    /// Delays the reload after a change of `files` until no other change
+    /// came for `window`, reloading once for all the files changed.
+    fn debounce(&mut self, files: &str, window: Duration, ctx: &mut Context<Self>) {
+        for file in files.split(", ") {
+            if !self.debounced.iter().any(|other| other == file) {
+                self.debounced.push(file.to_string());
+            }
+        }
+        if let Some(handle) = self.debounce_handle.take() {
+            ctx.cancel_future(handle);
+        }
+        self.debounce_handle = Some(ctx.run_later(window, |act, ctx| {
+            act.debounce_handle = None;
+            act.debounce_due = true;
+            let files = std::mem::take(&mut act.debounced).join(", ");
+            Handler::handle(act, Reload::Watch(files), ctx);
+        }));
+    }
+
+    /// Drops the changes waiting to settle, as the task reloads anyway.
+    fn cancel_debounce(&mut self, ctx: &mut Context<Self>) {
+        if let Some(handle) = self.debounce_handle.take() {
+            ctx.cancel_future(handle);
+        }
+        self.debounced.clear();
+    }
+
+    /// Gives up on the planned retry, releasing the dependents which
+    /// waited for it.
+    fn cancel_retry(&mut self, ctx: &mut Context<Self>) {
+        if let Some(handle) = self.retry_handle.take() {
+            ctx.cancel_future(handle);
+            self.send_reload(false);
+        }
+    }
+
+    /// Warns about the watch globs matching no file, most likely a typo
+    /// that would otherwise never reload the task.
+    fn warn_unmatched_globs(&self, patterns: Vec<String>, off: GlobSet) {
+        let console = self.console.clone();
+        let op_name = self.operator.name.clone();
+        actix::spawn(async move {
+            // walking the watched directories can take a while
+            let unmatched = tokio::task::spawn_blocking(move || {
+                patterns
+                    .into_iter()
+                    .filter(|pattern| !glob_matches_any(pattern, &off))
+                    .collect::<Vec<_>>()
+            })
+            .await
+            .unwrap_or_default();
+
+            for pattern in unmatched {
+                console.do_send(Output::now(
+                    op_name.clone(),
+                    format!("WATCH: {pattern} does not match any file"),
+                    OutputKind::Service,
+                ));
+            }
+        });
     }
 
     fn accept_death_invite(&mut self, cx: &mut Context<Self>) {
+        // the task is not done until it ran out of retries
+        if self.retry_handle.is_some() {
+            return;
+        }
         if let Some(invite) = self.death_invite.take() {
-            let status = match &self.child {
-                Child::Killed => ExitStatus::Other(1),
-                Child::Exited(val) => *val,
-                child => panic!("invalid death invite acceptance: {child:?}"),
+            let status = match self.state {
+                // a task stopped before it could ever run did not succeed,
+                // neither did a killed one
+                TaskState::Pending
+                | TaskState::WaitingOnUpstream
+                | TaskState::Queued
+                | TaskState::Blocked
+                | TaskState::Exited(ExitStatus::Undetermined) => ExitStatus::Other(1),
+                TaskState::Exited(status) => status,
+                TaskState::Starting | TaskState::Running | TaskState::Stopping => {
+                    // a reload spawned a new child since the invite was
+                    // stashed: re-arm it so that the new child answers
+                    self.death_invite = Some(invite);
+                    return;
+                }
             };
+            if invite.settles() {
+                self.answered_invite = Some(invite.clone());
+            }
             invite.rsvp::<Self, Context<Self>>(self.operator.name.clone(), status, cx);
         }
     }
@@ -429,27 +1367,66 @@ impl Actor for CommandActor {
 
         self.console.do_send(RegisterPanel {
             name: self.operator.name.clone(),
-            addr,
+            addr: Some(addr),
             colors: self.operator.colors.clone(),
+            dynamic: None,
         });
 
-        let watches = self.operator.task.watch.resolve();
+        #[cfg(not(unix))]
+        if !self.operator.task.limits.is_empty() {
+            self.log_info("LIMITS: only applied on Unix, ignoring them".to_string());
+        }
+        if let Some(warning) = self.operator.task.entrypoint_warning() {
+            self.log_debug(format!("EXEC: {warning}"));
+        }
+
+        self.register_watch(ctx, false);
+        self.schedule_next(ctx);
+    }
+
+    fn stopped(&mut self, _: &mut Self::Context) {
+        self.self_addr = None;
+        self.teardown();
+    }
+}
+
+impl Drop for CommandActor {
+    /// Actors are dropped without being stopped when the system shuts down.
+    fn drop(&mut self) {
+        self.teardown();
+    }
+}
+
+impl CommandActor {
+    /// Registers the watch globs of the task, `replace`-ing the ones it
+    /// registered before even when it has none left.
+    fn register_watch(&mut self, ctx: &mut Context<Self>, replace: bool) {
+        // the files of a task which runs once are not watched at all
+        let (watches, runs) = match self.operator.task.once {
+            true => (Vec::new(), Vec::new()),
+            false => (
+                self.operator.task.watch.resolve(),
+                self.operator.task.on_change_run.resolve(),
+            ),
+        };
+
+        if self.watch && (replace || !watches.is_empty() || !runs.is_empty()) {
+            let patterns: Vec<String> = watches
+                .iter()
+                .map(|pattern| {
+                    self.operator
+                        .cwd
+                        .join(pattern)
+                        .absolutize()
+                        .unwrap()
+                        .to_string_lossy()
+                        .to_string()
+                })
+                .collect();
 
-        if self.watch && !watches.is_empty() {
             let mut on = GlobSetBuilder::new();
-            for pattern in watches {
-                on.add(
-                    Glob::new(
-                        &self
-                            .operator
-                            .cwd
-                            .join(pattern)
-                            .absolutize()
-                            .unwrap()
-                            .to_string_lossy(),
-                    )
-                    .unwrap(),
-                );
+            for pattern in &patterns {
+                on.add(Glob::new(pattern).unwrap());
             }
 
             let mut off = GlobSetBuilder::new();
@@ -472,16 +1449,16 @@ impl Actor for CommandActor {
                 command: ctx.address(),
                 on: on.build().unwrap(),
                 off: off.build().unwrap(),
+                runs,
+                events: self.operator.task.watch_events.resolve(),
             };
 
+            if !patterns.is_empty() {
+                self.warn_unmatched_globs(patterns, glob.off.clone());
+            }
             self.watcher.do_send(glob);
         }
     }
-
-    fn stopped(&mut self, _: &mut Self::Context) {
-        self.self_addr = None;
-        self.child.poll(true).unwrap();
-    }
 }
 
 #[derive(Message)]
@@ -493,7 +1470,13 @@ pub struct WillReload {
 impl Handler<WillReload> for CommandActor {
     type Result = ();
 
-    fn handle(&mut self, msg: WillReload, _: &mut Context<Self>) -> Self::Result {
+    fn handle(&mut self, msg: WillReload, ctx: &mut Context<Self>) -> Self::Result {
+        if self.once_done {
+            return;
+        }
+        self.cancel_retry(ctx);
+        self.cancel_restart(ctx);
+        self.cancel_debounce(ctx);
         let counter = self.pending_upstream.remove(&msg.op_name).unwrap_or(0);
         self.pending_upstream
             .insert(msg.op_name.clone(), counter + 1);
@@ -501,9 +1484,10 @@ impl Handler<WillReload> for CommandActor {
         self.log_info(format!("Waiting on {}", msg.op_name));
         self.log_debug(format!("WAIT: +{} [{}]", msg.op_name, self.upstream()));
 
-        self.ensure_stopped();
-
-        self.send_will_reload();
+        self.ensure_stopped(ctx, |act, _| {
+            act.set_state(TaskState::WaitingOnUpstream);
+            act.send_will_reload();
+        });
     }
 }
 
@@ -513,15 +1497,58 @@ pub enum Reload {
     Start,
     Manual,
     Watch(String),
-    Op(String),
+    /// The upstream task is done, with how its run ended when it exited.
+    Op(String, Option<Finished>),
+    /// The upstream task failed, see [`crate::config::Condition::Success`].
+    Failed(String, Option<Finished>),
+    /// The task was added or changed in the config file.
+    Config,
+    /// The schedule of the task is due.
+    Schedule,
 }
 
 impl Handler<Reload> for CommandActor {
     type Result = ();
 
-    fn handle(&mut self, msg: Reload, _: &mut Context<Self>) -> Self::Result {
-        self.ensure_stopped();
+    fn handle(&mut self, msg: Reload, ctx: &mut Context<Self>) -> Self::Result {
+        if self.dry_watch {
+            return;
+        }
+        match &msg {
+            // its schedule starts the task instead
+            Reload::Start if self.operator.task.schedule().is_some() => return,
+            // only the command menu or a manual reload starts it, not
+            // even `reload_all_on`
+            Reload::Start | Reload::Config | Reload::Watch(_) if self.operator.task.on_demand => {
+                return
+            }
+            Reload::Watch(_) if self.operator.task.once => return,
+            Reload::Watch(files) if !std::mem::take(&mut self.debounce_due) => {
+                if let Some(schedule::Every(window)) = self.operator.task.debounce {
+                    self.debounce(files, window, ctx);
+                    return;
+                }
+            }
+            // as the `WillReload` of its upstream was ignored
+            Reload::Op(..) | Reload::Failed(..) if self.once_done => return,
+            Reload::Schedule if !self.operator.task.overlap.runs(self.state.is_active()) => {
+                self.log_info("SCHEDULE: still running, skipping this run".to_string());
+                return;
+            }
+            _ => {}
+        }
+
+        self.cancel_retry(ctx);
+        self.cancel_restart(ctx);
+        self.cancel_debounce(ctx);
+        self.ensure_stopped(ctx, move |act, ctx| act.continue_reload(msg, ctx));
+    }
+}
 
+impl CommandActor {
+    /// Starts the run due to `msg` once the previous one is stopped, or
+    /// waits on the upstream tasks still pending.
+    fn continue_reload(&mut self, msg: Reload, ctx: &mut Context<Self>) {
         match &msg {
             Reload::Start => {
                 self.send_will_reload();
@@ -541,7 +1568,20 @@ impl Handler<Reload> for CommandActor {
                 self.log_info(format!("RELOAD: file changed: {files} "));
                 self.send_will_reload();
             }
-            Reload::Op(op_name) => {
+            Reload::Config => {
+                self.log_info("RELOAD: config changed".to_string());
+                self.send_will_reload();
+            }
+            Reload::Schedule => {
+                self.log_info("RELOAD: scheduled".to_string());
+                self.send_will_reload();
+            }
+            Reload::Op(op_name, finished) | Reload::Failed(op_name, finished) => {
+                match finished {
+                    Some(finished) => self.upstream_runs.insert(op_name.clone(), *finished),
+                    None => self.upstream_runs.remove(op_name),
+                };
+
                 let counter = self.pending_upstream.remove(op_name).unwrap();
 
                 if counter > 1 {
@@ -550,7 +1590,23 @@ impl Handler<Reload> for CommandActor {
 
                 self.log_debug(format!("WAIT: -{} [{}]", op_name.clone(), self.upstream()));
 
+                if self.operator.task.success_depends_on.contains(op_name) {
+                    match &msg {
+                        Reload::Failed(..) => self.failed_upstream.insert(op_name.clone()),
+                        _ => self.failed_upstream.remove(op_name),
+                    };
+                }
+
                 if !self.pending_upstream.is_empty() {
+                    // a manual reload may have run it in the meantime
+                    self.set_state(TaskState::WaitingOnUpstream);
+                    return;
+                } else if !self.failed_upstream.is_empty() {
+                    let failed = Vec::from_iter(self.failed_upstream.iter().cloned());
+                    self.log_info(format!("blocked: {} failed", failed.join(", ")));
+                    self.set_state(TaskState::Blocked);
+                    self.send_reload(true);
+                    self.accept_death_invite(ctx);
                     return;
                 } else {
                     self.log_info("Upstream(s) finished".to_string());
@@ -558,7 +1614,38 @@ impl Handler<Reload> for CommandActor {
             }
         }
 
-        self.reload().unwrap();
+        self.retryable = matches!(msg, Reload::Start | Reload::Op(..) | Reload::Failed(..));
+        self.retried = 0;
+        if self.operator.task.is_trigger_only() {
+            self.trigger(ctx);
+        } else {
+            self.acquire_slot(ctx);
+        }
+    }
+}
+
+impl Handler<Slot> for CommandActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: Slot, ctx: &mut Context<Self>) -> Self::Result {
+        match msg {
+            Slot::Queued => {
+                self.log_info("queued for execution slot".to_string());
+                // unless it got a slot in the meantime
+                if self.queued && self.state.can_become(&TaskState::Queued) {
+                    self.set_state(TaskState::Queued);
+                }
+            }
+            Slot::Granted if self.queued => {
+                self.queued = false;
+                self.holds_slot = true;
+                self.reload(ctx);
+            }
+            Slot::Granted => {
+                self.holds_slot = true;
+                self.release_slot();
+            }
+        }
     }
 }
 
@@ -570,8 +1657,26 @@ impl Handler<GetStatus> for CommandActor {
     type Result = Result<Option<ExitStatus>, std::io::Error>;
 
     fn handle(&mut self, _: GetStatus, _: &mut Self::Context) -> Self::Result {
-        self.child.poll(false).unwrap();
-        Ok(self.child.exit_status())
+        match self.state {
+            // nothing was spawned to ask
+            TaskState::Blocked => Ok(Some(ExitStatus::Other(1))),
+            // a failed run is only final once it is not retried
+            _ if self.retry_handle.is_some() => Ok(None),
+            state if state.is_active() && self.may_retry() => Ok(None),
+            _ => Ok(self.child.peek_status()),
+        }
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "TaskState")]
+pub struct GetState;
+
+impl Handler<GetState> for CommandActor {
+    type Result = MessageResult<GetState>;
+
+    fn handle(&mut self, _: GetState, _: &mut Self::Context) -> Self::Result {
+        MessageResult(self.state)
     }
 }
 
@@ -598,6 +1703,100 @@ impl Handler<WaitStatus> for CommandActor {
     }
 }
 
+/// Reads the output of a child as it comes on a thread of its own, so
+/// that its partial last line can be flushed while it waits for more.
+fn read_chunks(mut stdout: impl Read + Send + 'static) -> Receiver<Vec<u8>> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut buffer = [0; 4096];
+        while let Ok(read @ 1..) = stdout.read(&mut buffer) {
+            if sender.send(buffer[..read].to_vec()).is_err() {
+                return;
+            }
+        }
+    });
+    receiver
+}
+
+/// Shows the lines `op_name` prints on `stderr` when it is not merged into
+/// stdout, on a thread of their own as the reader of stdout blocks. They
+/// are dropped without a `console`. The first line matching `ready_when`
+/// calls `on_ready`, as the readiness of many servers is logged there.
+fn read_stderr(
+    stderr: fs::File,
+    encoding: Encoding,
+    console: Option<Addr<ConsoleAct>>,
+    op_name: String,
+    cancelled: Arc<AtomicBool>,
+    ready_when: Option<Regex>,
+    on_ready: impl FnOnce() + Send + 'static,
+) {
+    std::thread::spawn(move || {
+        let mut on_ready = Some(on_ready);
+        for line in encoding.lines(BufReader::new(stderr)) {
+            let Ok(line) = line else {
+                return;
+            };
+            if cancelled.load(Ordering::Relaxed) {
+                return;
+            }
+            if ready_when
+                .as_ref()
+                .is_some_and(|regex| regex.is_match(&line))
+            {
+                if let Some(on_ready) = on_ready.take() {
+                    on_ready();
+                }
+            }
+            if let Some(console) = &console {
+                console.do_send(Output::now(op_name.clone(), line, OutputKind::Stderr));
+            }
+        }
+    });
+}
+
+/// Start of a line of `op_name` still being printed, see [`Output::partial`].
+fn partial_output(op_name: &str, line: String) -> Output {
+    Output::now(op_name.to_string(), line, OutputKind::Command).partial(true)
+}
+
+/// Line telling a child was spawned, e.g. ``started `cargo fmt` (pid 12345)``.
+fn started_message(command: &str, pid: Option<u32>) -> String {
+    match pid {
+        Some(pid) => format!("started `{command}` (pid {pid})"),
+        None => format!("started `{command}`"),
+    }
+}
+
+/// Line telling how a child exited once reaped, e.g. `exited 0 in 180ms`.
+fn exited_message(status: ExitStatus, elapsed: Duration) -> String {
+    format!("{} in {}", describe_exit(status), format_elapsed(elapsed))
+}
+
+/// Formats a duration to the millisecond below a second (`180ms`), to the
+/// tenth of a second below a minute (`2.4s`) and to the second above
+/// (`3m07s`).
+pub(crate) fn format_elapsed(elapsed: Duration) -> String {
+    let millis = elapsed.as_millis();
+    match millis {
+        0..=999 => format!("{millis}ms"),
+        1_000..=59_999 => format!("{}.{}s", millis / 1_000, millis % 1_000 / 100),
+        _ => {
+            let secs = elapsed.as_secs();
+            format!("{}m{:02}s", secs / 60, secs % 60)
+        }
+    }
+}
+
+/// Formats a delay the way it is written in the config, e.g. `1s` or
+/// `500ms`, falling back to [`format_elapsed`] for the others.
+fn format_delay(delay: Duration) -> String {
+    match delay.as_millis() {
+        millis if millis >= 1_000 && millis % 1_000 == 0 => format!("{}s", millis / 1_000),
+        _ => format_elapsed(delay),
+    }
+}
+
 #[derive(Message)]
 #[rtype(result = "()")]
 struct StdoutTerminated {
@@ -608,23 +1807,205 @@ impl Handler<StdoutTerminated> for CommandActor {
     type Result = ();
 
     fn handle(&mut self, msg: StdoutTerminated, cx: &mut Self::Context) -> Self::Result {
-        if msg.started_at == self.started_at {
-            // since there's a chance that child might not be done by this point
-            // wait for it die for a maximum of 1 seconds
-            // before pulling the plug
-            if self
-                .child
-                .wait_or_kill(Duration::from_millis(1000))
-                .unwrap()
+        // a stopped child was already finalized by whoever stopped it
+        if msg.started_at == self.started_at && self.state.is_active() {
+            if self.operator.task.long_running {
+                if let Child::Process(p) = &mut self.child {
+                    if p.poll().is_none() {
+                        // the output is closed but the process is still alive,
+                        // only finalize once it truly exits
+                        cx.notify_later(msg, Duration::from_millis(100));
+                        return;
+                    }
+                }
+            }
+
+            #[cfg(unix)]
             {
-                self.send_reload();
+                self.tty = None;
             }
-            let exit = self.child.exit_status();
-            self.console.do_send(PanelStatus {
-                panel_name: self.operator.name.clone(),
-                status: exit,
+            self.stdin = None;
+
+            // since there's a chance that child might not be done by this point
+            // wait for it die for a while before pulling the plug
+            self.set_state(TaskState::Stopping);
+            let wait = self.operator.exit_wait();
+            self.kill_child(Some(wait), cx, |act, cx, finalized| {
+                act.finish_run(finalized, cx)
             });
-            self.accept_death_invite(cx);
+        }
+    }
+}
+
+impl CommandActor {
+    /// Runs the after hook of the run which ended, then releases its
+    /// dependents, or retries or restarts it.
+    fn finish_run(&mut self, finalized: bool, cx: &mut Context<Self>) {
+        if self.timed_out {
+            self.child = Child::Exited(ExitStatus::Exited(TIMED_OUT));
+        }
+        let exit = self.child.exit_status();
+        if let (Some(_), Some(after)) = (exit, self.operator.task.after.clone()) {
+            let status = self.run_hook("after", &after);
+            if !status.success() {
+                self.log_info(format!("after {}", describe_exit(status)));
+            }
+        }
+        let retry = exit.is_some_and(|exit| !exit.success()) && self.may_retry();
+        if finalized && !retry {
+            self.send_reload(exit.is_some_and(|exit| !exit.success()));
+            self.once_done |= self.operator.task.once && exit.is_some_and(|exit| exit.success());
+        }
+        if let Some(exit) = exit {
+            let elapsed = (Local::now() - self.started_at)
+                .to_std()
+                .unwrap_or_default();
+            self.log_info(exited_message(exit, elapsed));
+            self.set_state(TaskState::Exited(exit));
+        }
+        if retry {
+            let delay = self.operator.task.retry_delay(self.retried + 1);
+            self.retry_after(delay, cx);
+            return;
+        }
+        if let (true, Child::Exited(status)) = (self.operator.task.primary, &self.child) {
+            self.end_session(*status);
+        }
+        // once whiz waits for the tasks to exit, they stay down
+        let restarts = exit.is_some_and(|exit| self.operator.task.restart.restarts(&exit));
+        if restarts && self.death_invite.is_none() {
+            self.restart_after(RESTART_DELAY, cx);
+            return;
+        }
+        self.accept_death_invite(cx);
+    }
+}
+
+/// Size of the logs area of the panels, which the console sends as it
+/// changes for the tasks running on a pseudo-terminal.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct TtySize {
+    pub columns: u16,
+    pub rows: u16,
+}
+
+impl Handler<TtySize> for CommandActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: TtySize, _: &mut Self::Context) -> Self::Result {
+        self.tty_size = (msg.columns, msg.rows);
+        #[cfg(unix)]
+        if let Some(master) = &self.tty {
+            let _ = crate::pty::resize(master, self.tty_size);
+        }
+    }
+}
+
+/// Keys typed in the panel of the task, written to the stdin of its
+/// run, or to its pseudo-terminal which echoes them, see [`Task::tty`].
+/// They are dropped when the task does not read them.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Input(pub Vec<u8>);
+
+impl Handler<Input> for CommandActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: Input, _: &mut Self::Context) -> Self::Result {
+        #[cfg(unix)]
+        let stdin = self.tty.as_mut().or(self.stdin.as_mut());
+        #[cfg(not(unix))]
+        let stdin = self.stdin.as_mut();
+        let Some(stdin) = stdin else {
+            return;
+        };
+        if let Err(err) = stdin.write_all(&msg.0) {
+            self.log_debug(format!("INPUT: cannot write: {err}"));
+        }
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct Ready {
+    pub started_at: DateTime<Local>,
+}
+
+impl Handler<Ready> for CommandActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: Ready, _: &mut Self::Context) -> Self::Result {
+        if msg.started_at != self.started_at || !self.state.is_active() || self.released_early {
+            return;
+        }
+        if let Some(regex) = &self.operator.ready_when {
+            self.log_info(format!("READY (matched /{regex}/)"));
+        }
+        self.send_reload(false);
+        self.released_early = true;
+        self.once_done |= self.operator.task.once;
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Peers(pub Vec<Addr<CommandActor>>);
+
+impl Handler<Peers> for CommandActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: Peers, _: &mut Context<Self>) -> Self::Result {
+        let Peers(peers) = msg;
+        self.peers = peers;
+    }
+}
+
+/// Replaces the dependents of the task.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Nexts(pub Vec<Addr<CommandActor>>);
+
+impl Handler<Nexts> for CommandActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: Nexts, _: &mut Context<Self>) -> Self::Result {
+        let Nexts(nexts) = msg;
+        self.nexts = nexts;
+    }
+}
+
+/// Replaces the definition of the task, which keeps running until
+/// it is reloaded.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct UpdateTask {
+    pub operator: ExtendedTask,
+    pub exec_builder: ExecBuilder,
+}
+
+impl Handler<UpdateTask> for CommandActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: UpdateTask, ctx: &mut Context<Self>) -> Self::Result {
+        // an upstream which is gone will never finish
+        let depends_on = msg.operator.task.depends_on.resolve();
+        self.pending_upstream
+            .retain(|op_name, _| depends_on.contains(op_name));
+        let rescheduled = msg.operator.task.schedule() != self.operator.task.schedule();
+
+        self.operator = msg.operator;
+        self.exec_builder = msg.exec_builder;
+
+        self.console.do_send(RegisterPanel {
+            name: self.operator.name.clone(),
+            addr: Some(ctx.address()),
+            colors: self.operator.colors.clone(),
+            dynamic: None,
+        });
+        self.register_watch(ctx, true);
+        if rescheduled {
+            self.schedule_next(ctx);
         }
     }
 }
@@ -637,8 +2018,14 @@ impl Handler<PoisonPill> for CommandActor {
     type Result = ();
 
     fn handle(&mut self, _: PoisonPill, ctx: &mut Context<Self>) -> Self::Result {
-        self.accept_death_invite(ctx);
-        ctx.stop();
+        if let Some(handle) = self.retry_handle.take() {
+            ctx.cancel_future(handle);
+        }
+        self.cancel_restart(ctx);
+        self.stop(ctx, |act, ctx, _| {
+            act.accept_death_invite(ctx);
+            ctx.stop();
+        });
     }
 }
 
@@ -646,16 +2033,100 @@ impl Handler<PermaDeathInvite> for CommandActor {
     type Result = ();
 
     fn handle(&mut self, evt: PermaDeathInvite, cx: &mut Context<Self>) -> Self::Result {
-        self.child.poll(false).unwrap();
-        let status = match &self.child {
-            Child::Killed => Some(ExitStatus::Other(1)),
-            Child::Exited(val) => Some(*val),
-            _ => None,
-        };
-        if let Some(status) = status {
-            evt.rsvp::<Self, Self::Context>(self.operator.name.clone(), status, cx);
-        } else {
-            self.death_invite = Some(evt);
+        // a task only started by a schedule or on demand may never run, it
+        // is only waited for once it did
+        if (self.operator.starts_on_schedule || self.operator.task.on_demand)
+            && self.state == TaskState::Pending
+        {
+            if evt.settles() {
+                self.answered_invite = Some(evt.clone());
+            }
+            evt.rsvp::<Self, Context<Self>>(self.operator.name.clone(), ExitStatus::Exited(0), cx);
+            return;
         }
+
+        // stays down instead of restarting
+        self.cancel_restart(cx);
+
+        // a process that exited may not have been finalized yet (its
+        // dependents not reloaded), so only a finished task can answer now
+        self.death_invite = Some(evt);
+        if self.state.is_finished() {
+            self.accept_death_invite(cx);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ConfigInner;
+
+    #[test]
+    fn resolves_kill_timeout() {
+        let raw = r#"
+            db:
+                command: postgres
+                kill_timeout: 10s
+            api:
+                command: cargo run
+        "#;
+        let mut config = ConfigInner::from_raw(raw.parse().unwrap(), PathBuf::from(".")).unwrap();
+        let kill_timeout = |config: &ConfigInner, name: &str| {
+            let config = Arc::new(config.clone());
+            config.ops[name]
+                .extend(name.to_string(), &config)
+                .kill_timeout
+        };
+
+        assert_eq!(kill_timeout(&config, "db"), Duration::from_secs(10));
+        assert_eq!(kill_timeout(&config, "api"), KILL_TIMEOUT);
+        let api = config.ops["api"].extend("api".to_string(), &Arc::new(config.clone()));
+        assert_eq!(api.exit_wait(), Duration::from_millis(1000));
+
+        // --kill-timeout only applies to the tasks without their own
+        config.kill_timeout = Some(Duration::from_secs(2));
+        assert_eq!(kill_timeout(&config, "db"), Duration::from_secs(10));
+        assert_eq!(kill_timeout(&config, "api"), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn formats_started_child() {
+        assert_eq!(
+            started_message("cargo fmt", Some(12345)),
+            "started `cargo fmt` (pid 12345)"
+        );
+        assert_eq!(started_message("cargo fmt", None), "started `cargo fmt`");
+    }
+
+    #[test]
+    fn formats_elapsed_time() {
+        assert_eq!(format_elapsed(Duration::ZERO), "0ms");
+        assert_eq!(format_elapsed(Duration::from_millis(180)), "180ms");
+        assert_eq!(format_elapsed(Duration::from_millis(999)), "999ms");
+        assert_eq!(format_elapsed(Duration::from_millis(1_000)), "1.0s");
+        assert_eq!(format_elapsed(Duration::from_millis(2_480)), "2.4s");
+        assert_eq!(format_elapsed(Duration::from_secs(187)), "3m07s");
+    }
+
+    #[test]
+    fn formats_retry_delays() {
+        assert_eq!(format_delay(Duration::from_millis(500)), "500ms");
+        assert_eq!(format_delay(Duration::from_secs(1)), "1s");
+        assert_eq!(format_delay(Duration::from_millis(1_500)), "1.5s");
+    }
+
+    #[test]
+    fn formats_exit_status() {
+        let elapsed = Duration::from_millis(180);
+        assert_eq!(
+            exited_message(ExitStatus::Exited(0), elapsed),
+            "exited 0 in 180ms"
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            exited_message(ExitStatus::Signaled(9), elapsed),
+            "killed by signal 9 (SIGKILL) in 180ms"
+        );
     }
 }
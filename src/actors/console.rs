@@ -1,26 +1,49 @@
 use actix::prelude::*;
+#[cfg(feature = "tui")]
+use base64::prelude::{Engine, BASE64_STANDARD};
 use chrono::prelude::*;
+#[cfg(feature = "tui")]
 use crossterm::event::KeyEvent;
+#[cfg(feature = "tui")]
+use ratatui::buffer::Buffer;
+#[cfg(feature = "tui")]
 use ratatui::layout::Rect;
+#[cfg(feature = "tui")]
 use ratatui::prelude::Alignment;
+#[cfg(feature = "tui")]
 use ratatui::text::Line;
+#[cfg(feature = "tui")]
 use ratatui::widgets::{List, ListItem, ListState};
+#[cfg(feature = "tui")]
 use ratatui::Frame;
+#[cfg(feature = "tui")]
+use ratatui::{TerminalOptions, Viewport};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "tui")]
 use std::borrow::Cow;
+#[cfg(feature = "tui")]
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+#[cfg(feature = "tui")]
 use std::rc::Rc;
-use std::{cmp::min, collections::HashMap, io};
-use std::{str, usize};
+#[cfg(feature = "tui")]
+use std::str;
+use std::time::Duration;
+#[cfg(feature = "tui")]
+use std::{cmp::min, io};
 use subprocess::ExitStatus;
 
+#[cfg(feature = "tui")]
 use ratatui::{
     backend::CrosstermBackend,
     layout::{Constraint, Direction, Layout},
     style::{Color, Modifier, Style},
     text::Span,
-    widgets::{Block, Borders, Paragraph, Tabs, Wrap},
+    widgets::{Block, Borders, Clear, Paragraph, Tabs, Wrap},
     Terminal,
 };
 
+#[cfg(feature = "tui")]
 use crossterm::{
     cursor,
     event::{self, Event, KeyCode, KeyModifiers, MouseEventKind},
@@ -28,18 +51,67 @@ use crossterm::{
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 
-use crate::config::color::{ColorOption, Colorizer};
+use crate::config::color::ColorOption;
+#[cfg(feature = "tui")]
+use crate::config::color::Colorizer;
+#[cfg(feature = "tui")]
+use crate::config::status::{StatusTheme, TaskStatus};
+use crate::exit::ExitCode;
+use crate::replay::{RecordedEvent, Recorder, SessionHeader};
+#[cfg(feature = "testing")]
+use crate::testing::{ConsoleEvent, ConsoleSink};
+
+use super::a11y::{Announcer, Instruction, HELP};
+#[cfg(feature = "tui")]
+use super::command::{format_elapsed, Input};
+use super::command::{CommandActor, PoisonPill, Reload, TtySize};
+use super::reloader::Restart;
+use super::state::{describe_exit, killed_by, TaskState};
+
+/// Bounds of the width of the menu listing the tasks, see [`menu_width`].
+#[cfg(feature = "tui")]
+const MIN_MENU_WIDTH: u16 = 20;
+#[cfg(feature = "tui")]
+const MAX_MENU_WIDTH: u16 = 40;
+/// How often the size of the terminal is compared with the drawn one.
+#[cfg(feature = "tui")]
+const SIZE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Name of the panel listing the state changes and reloads of every task,
+/// see [`ConsoleActor::timeline`].
+pub const TIMELINE: &str = "(timeline)";
+/// ANSI colors of the task names in the timeline and the line prefixes,
+/// red being kept for errors.
+#[cfg(feature = "tui")]
+const TASK_COLORS: [u8; 10] = [36, 33, 35, 32, 34, 96, 93, 95, 92, 94];
+
+/// Returns `name` in the ANSI color of the task, which it keeps across
+/// sessions.
+#[cfg(feature = "tui")]
+fn colored_task(name: &str) -> String {
+    let hash = name.bytes().fold(0usize, |hash, byte| {
+        hash.wrapping_mul(31).wrapping_add(byte as usize)
+    });
+    let color = TASK_COLORS[hash % TASK_COLORS.len()];
+    format!("\x1b[{color}m{name}\x1b[39m")
+}
 
-use super::command::{CommandActor, PoisonPill, Reload};
+/// Lowest log height showing the rows hidden above and below, see
+/// [`LogWindow`].
+#[cfg(feature = "tui")]
+const MIN_INDICATED_HEIGHT: usize = 3;
 
-const MENU_WIDTH: u16 = 30;
-const MAX_CHARS: usize = (MENU_WIDTH - 6) as usize;
+/// Size of the terminal a headless console lays its panels out for.
+#[cfg(not(feature = "tui"))]
+const HEADLESS_SIZE: (u16, u16) = (80, 24);
 
+#[cfg(feature = "tui")]
 enum LayoutDirection {
     Horizontal,
     Vertical,
 }
 
+#[cfg(feature = "tui")]
 impl LayoutDirection {
     fn get_opposite_orientation(&self) -> Self {
         match self {
@@ -49,11 +121,13 @@ impl LayoutDirection {
     }
 }
 
+#[cfg(feature = "tui")]
 enum AppMode {
     Menu,
     View,
 }
 
+#[cfg(feature = "tui")]
 impl AppMode {
     fn get_opposite_mode(&self) -> Self {
         match self {
@@ -63,54 +137,563 @@ impl AppMode {
     }
 }
 
+/// How many panels the log area shows side by side, cycled with `s`. The
+/// cells show the page of panels of the focused one, which scrolls.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Split {
+    #[default]
+    Single,
+    /// Two panels side by side.
+    Columns,
+    /// Four panels, two by two.
+    Grid,
+}
+
+#[cfg(feature = "tui")]
+impl Split {
+    fn next(self) -> Self {
+        match self {
+            Self::Single => Self::Columns,
+            Self::Columns => Self::Grid,
+            Self::Grid => Self::Single,
+        }
+    }
+
+    /// Returns the columns and rows of cells.
+    fn grid(self) -> (u16, u16) {
+        match self {
+            Self::Single => (1, 1),
+            Self::Columns => (2, 1),
+            Self::Grid => (2, 2),
+        }
+    }
+}
+
+/// Kinds of output lines a panel shows, cycled with `v`.
+#[cfg(feature = "tui")]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+enum Visibility {
+    #[default]
+    All,
+    /// Only the lines the task printed.
+    Command,
+    /// Only the lines whiz printed about the task.
+    Service,
+}
+
+#[cfg(feature = "tui")]
+impl Visibility {
+    fn next(self) -> Self {
+        match self {
+            Self::All => Self::Command,
+            Self::Command => Self::Service,
+            Self::Service => Self::All,
+        }
+    }
+
+    fn shows(self, kind: OutputKind) -> bool {
+        match self {
+            Self::All => true,
+            Self::Command => kind != OutputKind::Service,
+            Self::Service => kind == OutputKind::Service,
+        }
+    }
+
+    /// Tells in the title of the panel which lines are hidden.
+    fn label(self) -> Option<&'static str> {
+        match self {
+            Self::All => None,
+            Self::Command => Some(" [command]"),
+            Self::Service => Some(" [service]"),
+        }
+    }
+}
+
+/// Lines of a panel selected with `V` to copy them with `y`, as indices in
+/// its logs, which only grow: the selection stays on the same lines while
+/// output arrives.
+#[cfg(feature = "tui")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Selection {
+    /// Line the selection started on.
+    anchor: usize,
+    /// Line moved with `j` and `k`.
+    cursor: usize,
+}
+
+#[cfg(feature = "tui")]
+impl Selection {
+    fn contains(self, line: usize) -> bool {
+        min(self.anchor, self.cursor) <= line && line <= self.anchor.max(self.cursor)
+    }
+}
+
+/// Rows of a panel shown in a log area, the first edge row telling how many
+/// rows are hidden above and the last one how many are hidden below, only
+/// when some are.
+#[cfg(feature = "tui")]
+#[derive(Debug, PartialEq)]
+struct LogWindow {
+    /// Rows hidden above, i.e. the first row shown.
+    above: usize,
+    /// Rows hidden below.
+    below: usize,
+    /// Rows shown.
+    rows: usize,
+    indicated: bool,
+}
+
+#[cfg(feature = "tui")]
+impl LogWindow {
+    /// Rows of `lines` a log area of `height` shows once scrolled `shift`
+    /// rows up from the bottom.
+    fn new(lines: usize, height: usize, shift: usize) -> Self {
+        let below = min(shift, maximum_scroll(lines, height));
+        let indicated = height >= MIN_INDICATED_HEIGHT;
+        let mut rows = height - usize::from(indicated && below > 0);
+        if indicated && lines - below > rows {
+            rows -= 1;
+        }
+        let rows = min(rows, lines - below);
+        Self {
+            above: lines - below - rows,
+            below,
+            rows,
+            indicated,
+        }
+    }
+
+    fn top_indicator(&self) -> Option<String> {
+        (self.indicated && self.above > 0).then(|| format!("▲ {} more", self.above))
+    }
+
+    fn bottom_indicator(&self) -> Option<String> {
+        (self.indicated && self.below > 0).then(|| format!("▼ {} more", self.below))
+    }
+}
+
+/// Rows a panel of `lines` rows scrolls up at most in a log area of
+/// `height`, until its first row shows above the bottom indicator.
+#[cfg(feature = "tui")]
+fn maximum_scroll(lines: usize, height: usize) -> usize {
+    match lines.checked_sub(height) {
+        None | Some(0) => 0,
+        Some(overflow) if height >= MIN_INDICATED_HEIGHT => overflow + 1,
+        Some(overflow) => overflow,
+    }
+}
+
+/// Line repeated at the end of a panel, see [`ConsoleActor::dedup`].
+#[cfg(feature = "tui")]
+struct Repeats {
+    /// The line as the task printed it.
+    raw: String,
+    kind: OutputKind,
+    /// The line as shown the first time, e.g. with its timestamp.
+    shown: String,
+    count: u32,
+}
+
 pub struct Panel {
-    logs: Vec<(String, OutputKind)>,
+    /// Lines of the panel with the time they were printed at, in order.
+    #[cfg(feature = "tui")]
+    logs: Vec<(String, OutputKind, DateTime<Local>)>,
+    /// Index in `logs` of each row shown, given the visibility.
+    #[cfg(feature = "tui")]
     line_offsets: Vec<usize>,
+    #[cfg(feature = "tui")]
     shift: u16,
-    command: Addr<CommandActor>,
-    status: Option<ExitStatus>,
+    #[cfg(feature = "tui")]
+    visibility: Visibility,
+    /// Latest line of each pin slot, see [`crate::config::pin::Pins`].
+    #[cfg(feature = "tui")]
+    pinned: BTreeMap<usize, (String, OutputKind)>,
+    #[cfg(feature = "tui")]
+    selection: Option<Selection>,
+    /// The last line is still being printed, see [`Output::partial`].
+    #[cfg(feature = "tui")]
+    partial: bool,
+    /// The lines printed before are dropped when they arrive late, see
+    /// [`Panel::clear`].
+    #[cfg(feature = "tui")]
+    cleared_at: Option<DateTime<Local>>,
+    /// Last line while it repeats, see [`ConsoleActor::dedup`].
+    #[cfg(feature = "tui")]
+    repeats: Option<Repeats>,
+    /// Missing for panels replayed from a recording.
+    command: Option<Addr<CommandActor>>,
+    state: TaskState,
+    /// Created by a task, see [`DynamicPanel`], instead of being one.
+    dynamic: bool,
+    #[cfg_attr(not(feature = "tui"), allow(dead_code))]
     colors: Vec<ColorOption>,
 }
 
 impl Panel {
-    pub fn new(command: Addr<CommandActor>, colors: Vec<ColorOption>) -> Self {
+    pub fn new(command: Option<Addr<CommandActor>>, colors: Vec<ColorOption>) -> Self {
         Self {
+            #[cfg(feature = "tui")]
             logs: Vec::default(),
+            #[cfg(feature = "tui")]
             line_offsets: Vec::default(),
+            #[cfg(feature = "tui")]
             shift: 0,
+            #[cfg(feature = "tui")]
+            visibility: Visibility::default(),
+            #[cfg(feature = "tui")]
+            pinned: BTreeMap::default(),
+            #[cfg(feature = "tui")]
+            selection: None,
+            #[cfg(feature = "tui")]
+            partial: false,
+            #[cfg(feature = "tui")]
+            cleared_at: None,
+            #[cfg(feature = "tui")]
+            repeats: None,
             command,
-            status: None,
+            state: TaskState::Pending,
+            dynamic: false,
             colors,
         }
     }
 
+    #[cfg(feature = "tui")]
+    pub fn task_status(&self) -> TaskStatus {
+        TaskStatus::from(self.state)
+    }
+
+    #[cfg(feature = "tui")]
     pub fn sync_lines(&mut self, width: u16) {
         self.line_offsets = self
             .logs
             .iter()
             .enumerate()
+            .filter(|(_, l)| self.visibility.shows(l.1))
             .flat_map(|(i, l)| vec![i; wrapped_lines(&l.0, width)])
             .collect();
     }
+
+    /// Shows the next kinds of lines, following the output again.
+    #[cfg(feature = "tui")]
+    pub fn cycle_visibility(&mut self, width: u16) {
+        self.visibility = self.visibility.next();
+        self.shift = 0;
+        self.sync_lines(width);
+    }
+
+    /// Removes every line, pinned ones included, so that the next run of
+    /// the task starts on an empty panel.
+    #[cfg(feature = "tui")]
+    fn clear(&mut self) {
+        self.logs.clear();
+        self.line_offsets.clear();
+        self.pinned.clear();
+        self.shift = 0;
+        self.selection = None;
+        self.partial = false;
+        self.repeats = None;
+        self.cleared_at = Some(Local::now());
+    }
+
+    /// Counts `raw` once more when it repeats the last line, rewritten with
+    /// its count as in `ready (x3)`, and returns whether it did.
+    #[cfg(feature = "tui")]
+    fn count_repeat(&mut self, raw: &str, kind: OutputKind, width: u16) -> bool {
+        let Some(repeats) = &mut self.repeats else {
+            return false;
+        };
+        let Some(last) = self.logs.len().checked_sub(1) else {
+            return false;
+        };
+        if repeats.raw != raw || repeats.kind != kind || self.partial {
+            return false;
+        }
+        repeats.count += 1;
+        let line = format!("{} (x{})", repeats.shown, repeats.count);
+        while self.line_offsets.last() == Some(&last) {
+            self.line_offsets.pop();
+        }
+        if self.visibility.shows(kind) {
+            let line_count = wrapped_lines(&line, width);
+            self.line_offsets.extend(vec![last; line_count]);
+        }
+        self.logs[last].0 = line;
+        true
+    }
+
+    /// Removes the partial last line, replaced by the next one.
+    #[cfg(feature = "tui")]
+    fn drop_partial(&mut self) {
+        if !std::mem::take(&mut self.partial) {
+            return;
+        }
+        let Some(line) = self.logs.len().checked_sub(1) else {
+            return;
+        };
+        self.logs.pop();
+        while self.line_offsets.last() == Some(&line) {
+            self.line_offsets.pop();
+        }
+    }
+
+    /// Starts a selection on the line shown at the bottom.
+    #[cfg(feature = "tui")]
+    fn start_selection(&mut self) {
+        let rows = self.line_offsets.len();
+        if let Some(&line) = self
+            .line_offsets
+            .get(rows.saturating_sub(1 + self.shift as usize))
+        {
+            self.selection = Some(Selection {
+                anchor: line,
+                cursor: line,
+            });
+        }
+    }
+
+    /// Moves the end of the selection to the previous line shown, or to
+    /// the next one.
+    #[cfg(feature = "tui")]
+    fn move_selection(&mut self, up: bool) {
+        let Some(mut selection) = self.selection else {
+            return;
+        };
+        let shown = |line: &usize| self.visibility.shows(self.logs[*line].1);
+        let next = match up {
+            true => (0..selection.cursor).rev().find(shown),
+            false => (selection.cursor + 1..self.logs.len()).find(shown),
+        };
+        if let Some(line) = next {
+            selection.cursor = line;
+            self.selection = Some(selection);
+        }
+    }
+
+    /// Returns the selected lines shown, without their ANSI escapes.
+    #[cfg(feature = "tui")]
+    fn selected_text(&self) -> Option<String> {
+        let selection = self.selection?;
+        let lines = self
+            .logs
+            .iter()
+            .enumerate()
+            .filter(|(line, (_, kind, _))| {
+                selection.contains(*line) && self.visibility.shows(*kind)
+            })
+            .map(|(_, (message, _, _))| {
+                String::from_utf8_lossy(&strip_ansi_escapes::strip(message)).into_owned()
+            })
+            .collect::<Vec<_>>();
+        Some(lines.join("\n"))
+    }
+
+    /// Returns the index in `logs` of the last line shown which was
+    /// printed at or before `timestamp`, the lines coming in order.
+    #[cfg(feature = "tui")]
+    fn line_at(&self, timestamp: &DateTime<Local>) -> Option<usize> {
+        let end = self.logs.partition_point(|(_, _, at)| at <= timestamp);
+        (0..end)
+            .rev()
+            .find(|line| self.visibility.shows(self.logs[*line].1))
+    }
+
+    /// Returns when the line shown at the bottom was printed.
+    #[cfg(feature = "tui")]
+    fn bottom_timestamp(&self) -> Option<DateTime<Local>> {
+        let rows = self.line_offsets.len();
+        let line = self
+            .line_offsets
+            .get(rows.saturating_sub(1 + self.shift as usize))?;
+        Some(self.logs[*line].2)
+    }
+
+    /// Scrolls until the last line printed at or before `timestamp` shows
+    /// at the bottom, or to the top when none was.
+    #[cfg(feature = "tui")]
+    fn lock_to(&mut self, timestamp: &DateTime<Local>) {
+        let rows = self.line_offsets.len();
+        let below = match self.line_at(timestamp) {
+            Some(line) => rows - self.line_offsets.partition_point(|offset| *offset <= line),
+            None => rows,
+        };
+        self.shift = min(below, u16::MAX as usize) as u16;
+    }
+
+    /// Returns when the line shown before the one of `timestamp` was
+    /// printed, or the one after it.
+    #[cfg(feature = "tui")]
+    fn step_from(&self, timestamp: &DateTime<Local>, back: bool) -> Option<DateTime<Local>> {
+        let shown = |line: &usize| self.visibility.shows(self.logs[*line].1);
+        let line = match (self.line_at(timestamp), back) {
+            (Some(line), true) => {
+                let at = self.logs[line].2;
+                (0..line)
+                    .rev()
+                    .find(|other| shown(other) && self.logs[*other].2 < at)?
+            }
+            (Some(line), false) => (line + 1..self.logs.len()).find(shown)?,
+            (None, true) => return None,
+            (None, false) => (0..self.logs.len()).find(shown)?,
+        };
+        Some(self.logs[line].2)
+    }
+
+    /// Returns how many rows of a log area of `height` the pinned lines
+    /// take, separator included. They never take more than half of it.
+    #[cfg(feature = "tui")]
+    pub fn pinned_height(&self, height: u16) -> u16 {
+        let rows = min(self.pinned.len(), (height / 2) as usize) as u16;
+        match rows {
+            0 => 0,
+            rows => min(rows + 1, height),
+        }
+    }
 }
 
+#[cfg(feature = "tui")]
+type ConsoleTerminal = Terminal<CrosstermBackend<Box<dyn io::Write + Send>>>;
+
 pub struct ConsoleActor {
-    terminal: Terminal<CrosstermBackend<io::Stdout>>,
+    #[cfg(feature = "tui")]
+    terminal: ConsoleTerminal,
     index: String,
     order: Vec<String>,
     arbiter: Arbiter,
     panels: HashMap<String, Panel>,
     timestamp: bool,
+    #[cfg(feature = "tui")]
     layout_direction: LayoutDirection,
+    #[cfg(feature = "tui")]
     mode: AppMode,
+    #[cfg(feature = "tui")]
+    split: Split,
+    #[cfg(feature = "tui")]
     list_state: ListState,
+    #[cfg(feature = "tui")]
+    status_theme: StatusTheme,
+    /// Style of the lines tasks print on stderr, see [`OutputKind::Stderr`].
+    #[cfg(feature = "tui")]
+    stderr_style: Style,
+    /// Prints the output line by line instead of drawing the panels.
+    #[cfg(feature = "tui")]
+    headless: bool,
+    /// Draws the panels into a buffer instead of the terminal, sized as
+    /// a terminal of this size.
+    #[cfg(feature = "tui")]
+    offscreen: Option<Rect>,
+    #[cfg(feature = "tui")]
+    last_frame: Buffer,
+    #[cfg(feature = "tui")]
+    timeline: bool,
+    /// Prefixes the lines of the tasks with their name, see
+    /// [`ConsoleActor::prefix_lines`].
+    #[cfg(feature = "tui")]
+    prefix_lines: bool,
+    /// Lines scrolled by the arrows and the mouse wheel, see
+    /// [`ConsoleActor::scroll_step`].
+    #[cfg(feature = "tui")]
+    scroll_step: u16,
+    /// Clears the focused panel and reruns its task, see
+    /// [`ConsoleActor::restart_key`].
+    #[cfg(feature = "tui")]
+    restart_key: char,
+    /// Collapses the repeated lines, see [`ConsoleActor::dedup`].
+    #[cfg(feature = "tui")]
+    dedup: bool,
+    /// Tasks listed by the command menu, see [`ConsoleActor::on_demand`].
+    #[cfg(feature = "tui")]
+    on_demand: Vec<String>,
+    /// Task selected in the command menu, while it is open.
+    #[cfg(feature = "tui")]
+    command_menu: Option<usize>,
+    /// Dynamic panels created by each task, see [`DynamicPanel`].
+    dynamic_panels: HashMap<String, usize>,
+    /// Dynamic panels over the cap of their task, to the panel their
+    /// lines are folded into.
+    folded_panels: HashMap<String, String>,
+    recorder: Option<Recorder>,
+    /// Told about the states of the tasks, see [`WatchStates`].
+    state_watchers: Vec<WatchStates>,
+    /// Events come from a recording, which already holds the output
+    /// derived from them.
+    replay: bool,
+    /// Writes plain sentences instead of the lines, see [`Announcer`].
+    announcer: Option<Announcer>,
+    /// Restarts every task on Ctrl-r, see [`SetRestart`].
+    #[cfg_attr(not(feature = "tui"), allow(dead_code))]
+    restart: Option<Recipient<Restart>>,
+    /// Exits this long after every task is done, see
+    /// [`ConsoleActor::exit_when_done`].
+    exit_when_done: Option<Duration>,
+    /// Exit scheduled since every task is done.
+    done_exit: Option<SpawnHandle>,
+    /// Exiting since every task is done, which prints the summary.
+    done: bool,
+    /// Text last copied to the clipboard, see [`Copied`].
+    #[cfg(feature = "tui")]
+    copied: Option<String>,
+    /// Moment every panel is scrolled back to, see [`Panel::lock_to`].
+    #[cfg(feature = "tui")]
+    time_lock: Option<DateTime<Local>>,
+    /// Keys go to the focused task instead, see [`ConsoleActor::insert`].
+    #[cfg(feature = "tui")]
+    insert: bool,
+    #[cfg(feature = "testing")]
+    sink: Option<ConsoleSink>,
+}
+
+#[cfg(feature = "tui")]
+fn new_terminal(fixed: Option<Rect>, writer: Box<dyn io::Write + Send>) -> ConsoleTerminal {
+    let backend = CrosstermBackend::new(writer);
+    match fixed {
+        // do not query a terminal that may not exist
+        Some(area) => Terminal::with_options(
+            backend,
+            TerminalOptions {
+                viewport: Viewport::Fixed(area),
+            },
+        )
+        .unwrap(),
+        None => Terminal::new(backend).unwrap(),
+    }
 }
 
-fn chunks(mode: &AppMode, direction: &LayoutDirection, f: &Frame) -> Rc<[Rect]> {
+/// Returns what a terminal sends for `key`, typed in insert mode.
+#[cfg(feature = "tui")]
+fn key_bytes(key: &KeyEvent) -> Option<Vec<u8>> {
+    let bytes: &[u8] = match key.code {
+        // Ctrl-a to Ctrl-z are the bytes 1 to 26
+        KeyCode::Char(ch) if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            return ch
+                .is_ascii_alphabetic()
+                .then(|| vec![ch.to_ascii_lowercase() as u8 & 0x1f]);
+        }
+        KeyCode::Char(ch) => return Some(ch.to_string().into_bytes()),
+        KeyCode::Enter => b"\n",
+        KeyCode::Tab => b"\t",
+        KeyCode::Backspace => b"\x7f",
+        KeyCode::Up => b"\x1b[A",
+        KeyCode::Down => b"\x1b[B",
+        KeyCode::Right => b"\x1b[C",
+        KeyCode::Left => b"\x1b[D",
+        KeyCode::Home => b"\x1b[H",
+        KeyCode::End => b"\x1b[F",
+        KeyCode::Delete => b"\x1b[3~",
+        _ => return None,
+    };
+    Some(bytes.to_vec())
+}
+
+#[cfg(feature = "tui")]
+fn chunks(mode: &AppMode, direction: &LayoutDirection, f: &Frame, longest: usize) -> Rc<[Rect]> {
     let chunks_constraints = match mode {
         AppMode::Menu => match direction {
             LayoutDirection::Horizontal => vec![Constraint::Min(0), Constraint::Length(3)],
-            LayoutDirection::Vertical => vec![Constraint::Min(0), Constraint::Length(MENU_WIDTH)],
+            LayoutDirection::Vertical => {
+                let width = menu_width(f.size().width, longest);
+                vec![Constraint::Min(0), Constraint::Length(width)]
+            }
         },
         AppMode::View => vec![Constraint::Min(0)],
     };
@@ -124,37 +707,467 @@ fn chunks(mode: &AppMode, direction: &LayoutDirection, f: &Frame) -> Rc<[Rect]>
         .split(f.size())
 }
 
+/// Draws the logs of `panel` in `area`, under its pinned lines, tinted
+/// after the status of its task when a `status_theme` is given.
+#[cfg(feature = "tui")]
+fn draw_logs(
+    f: &mut Frame,
+    panel: &Panel,
+    area: Rect,
+    status_theme: Option<&StatusTheme>,
+    stderr_style: Style,
+) {
+    let logs = &panel.logs;
+    let shift = panel.shift as usize;
+    let line_offsets = &panel.line_offsets;
+    let lines = line_offsets.len();
+    let mut log_area = area;
+
+    if let Some(status_theme) = status_theme {
+        let tint = status_theme.panel_style(panel.task_status());
+        f.render_widget(Block::default().style(tint), log_area);
+    }
+
+    // the pinned lines take the top rows, above a separator
+    let dim = Style::default().add_modifier(Modifier::DIM);
+    let pinned_height = panel.pinned_height(log_area.height);
+    if pinned_height > 0 {
+        let pinned = panel
+            .pinned
+            .values()
+            .take(pinned_height as usize - 1)
+            .flat_map(|(s, kind)| {
+                Colorizer::new(
+                    &panel.colors,
+                    kind.style(stderr_style).add_modifier(Modifier::BOLD),
+                )
+                .patch_text(s)
+            })
+            .collect::<Vec<_>>();
+        let rows = Rect {
+            height: pinned_height - 1,
+            ..log_area
+        };
+        f.render_widget(Paragraph::new(pinned), rows);
+        let separator = Rect {
+            y: rows.bottom(),
+            height: 1,
+            ..log_area
+        };
+        let line = "─".repeat(log_area.width as usize);
+        f.render_widget(Paragraph::new(Span::styled(line, dim)), separator);
+        log_area.y += pinned_height;
+        log_area.height -= pinned_height;
+    }
+    let window = LogWindow::new(lines, log_area.height as usize, shift);
+
+    // the indicators take the edge rows of the log area
+    let mut logs_area = log_area;
+    if let Some(indicator) = window.top_indicator() {
+        let row = Rect {
+            height: 1,
+            ..log_area
+        };
+        f.render_widget(Paragraph::new(Span::styled(indicator, dim)), row);
+        logs_area.y += 1;
+        logs_area.height -= 1;
+    }
+    if let Some(indicator) = window.bottom_indicator() {
+        let row = Rect {
+            y: log_area.bottom() - 1,
+            height: 1,
+            ..log_area
+        };
+        f.render_widget(Paragraph::new(Span::styled(indicator, dim)), row);
+        logs_area.height -= 1;
+    }
+
+    let scroll_offset = window.above;
+    let offset_end = (scroll_offset + window.rows).wrapping_sub(1);
+
+    let line_start = line_offsets.get(scroll_offset).cloned().unwrap_or(0);
+    let line_end = line_offsets.get(offset_end).cloned().unwrap_or(0);
+
+    let wrap_offset = line_offsets
+        .get(..scroll_offset)
+        .map(|offsets| {
+            offsets
+                .iter()
+                .rev()
+                .take_while(|&line| *line == line_start)
+                .count()
+        })
+        .unwrap_or(0);
+
+    let selected = Style::default().add_modifier(Modifier::REVERSED);
+    let lines = logs
+        .get(line_start..=line_end)
+        .map(|logs| {
+            logs.iter()
+                .zip(line_start..)
+                .filter(|((_, kind, _), _)| panel.visibility.shows(*kind))
+                .flat_map(|((s, kind, _), line)| {
+                    let text =
+                        Colorizer::new(&panel.colors, kind.style(stderr_style)).patch_text(s);
+                    match panel
+                        .selection
+                        .is_some_and(|selection| selection.contains(line))
+                    {
+                        true => text.into_iter().map(|l| l.patch_style(selected)).collect(),
+                        false => text,
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    let paragraph = Paragraph::new(lines)
+        .wrap(Wrap { trim: false })
+        .scroll((wrap_offset as u16, 0));
+
+    f.render_widget(paragraph, logs_area);
+}
+
+/// Splits the log area into the cells of `split`, all of the same size.
+#[cfg(feature = "tui")]
+fn split_cells(area: Rect, split: Split) -> Vec<Rect> {
+    let (columns, rows) = split.grid();
+    let (width, height) = (area.width / columns, area.height / rows);
+    (0..rows)
+        .flat_map(|row| {
+            (0..columns).map(move |column| Rect {
+                x: area.x + column * width,
+                y: area.y + row * height,
+                width,
+                height,
+            })
+        })
+        .collect()
+}
+
 impl ConsoleActor {
     pub fn new(order: Vec<String>, timestamp: bool) -> Self {
-        let stdout = io::stdout();
-        let backend = CrosstermBackend::new(stdout);
-        let terminal = Terminal::new(backend).unwrap();
         Self {
-            terminal,
+            #[cfg(feature = "tui")]
+            terminal: new_terminal(None, Box::new(io::stdout())),
             index: order[0].clone(),
             order,
             arbiter: Arbiter::new(),
             panels: HashMap::default(),
             timestamp,
+            #[cfg(feature = "tui")]
             mode: AppMode::Menu,
+            #[cfg(feature = "tui")]
+            split: Split::default(),
+            #[cfg(feature = "tui")]
             layout_direction: LayoutDirection::Horizontal,
+            #[cfg(feature = "tui")]
             list_state: ListState::default().with_selected(Some(0)),
+            #[cfg(feature = "tui")]
+            status_theme: StatusTheme::default(),
+            #[cfg(feature = "tui")]
+            stderr_style: Style::default().fg(Color::Red).add_modifier(Modifier::DIM),
+            #[cfg(feature = "tui")]
+            headless: false,
+            #[cfg(feature = "tui")]
+            offscreen: None,
+            #[cfg(feature = "tui")]
+            last_frame: Buffer::default(),
+            #[cfg(feature = "tui")]
+            timeline: false,
+            #[cfg(feature = "tui")]
+            prefix_lines: false,
+            #[cfg(feature = "tui")]
+            scroll_step: 1,
+            #[cfg(feature = "tui")]
+            restart_key: 'R',
+            #[cfg(feature = "tui")]
+            dedup: false,
+            #[cfg(feature = "tui")]
+            on_demand: Vec::new(),
+            #[cfg(feature = "tui")]
+            command_menu: None,
+            dynamic_panels: HashMap::default(),
+            folded_panels: HashMap::default(),
+            recorder: None,
+            state_watchers: Vec::new(),
+            replay: false,
+            announcer: None,
+            restart: None,
+            exit_when_done: None,
+            done_exit: None,
+            done: false,
+            #[cfg(feature = "tui")]
+            copied: None,
+            #[cfg(feature = "tui")]
+            time_lock: None,
+            #[cfg(feature = "tui")]
+            insert: false,
+            #[cfg(feature = "testing")]
+            sink: None,
+        }
+    }
+
+    /// Focuses the panel of `task` instead of the first one, unless it is
+    /// not shown, e.g. filtered out.
+    pub fn focus(mut self, task: Option<String>) -> Self {
+        if let Some(index) = task.and_then(|task| self.order.iter().position(|name| *name == task))
+        {
+            self.go_to(index);
+            #[cfg(feature = "tui")]
+            self.list_state.select(Some(index));
+        }
+        self
+    }
+
+    /// Forces the headless mode, which is always on without the `tui` feature.
+    #[cfg(feature = "tui")]
+    pub fn headless(self, toggle: bool) -> Self {
+        if !toggle || self.headless {
+            return self;
+        }
+        Self {
+            terminal: new_terminal(Some(Rect::new(0, 0, 80, 24)), Box::new(io::stdout())),
+            headless: true,
+            ..self
+        }
+    }
+
+    /// Draws into a `width` x `height` buffer which is never flushed to the
+    /// terminal, see [`Snapshot`].
+    #[cfg(feature = "tui")]
+    pub fn offscreen(self, width: u16, height: u16) -> Self {
+        let area = Rect::new(0, 0, width, height);
+        Self {
+            terminal: new_terminal(Some(area), Box::new(io::sink())),
+            headless: false,
+            offscreen: Some(area),
+            ..self
+        }
+    }
+
+    /// Adds a last panel listing the state changes and reloads of every
+    /// task, one line each, to tell which one restarted first.
+    #[cfg(feature = "tui")]
+    pub fn timeline(mut self, toggle: bool) -> Self {
+        if !toggle || self.timeline {
+            return self;
+        }
+        self.panels
+            .insert(TIMELINE.to_string(), Panel::new(None, Vec::new()));
+        self.order.push(TIMELINE.to_string());
+        Self {
+            timeline: true,
+            ..self
+        }
+    }
+
+    /// Prefixes each line of the tasks with `[task] `, in the color of the
+    /// task, so that the lines tell their task in a split view or once
+    /// copied. The headless mode always prints the task.
+    #[cfg(feature = "tui")]
+    pub fn prefix_lines(self, prefix_lines: bool) -> Self {
+        Self {
+            prefix_lines,
+            ..self
+        }
+    }
+
+    /// Collapses the lines repeating the last one of their panel into it,
+    /// which counts them as `uniq -c` does, e.g. `heartbeat (x12)`. The line
+    /// keeps the time it was first printed at, and its colors.
+    #[cfg(feature = "tui")]
+    pub fn dedup(self, dedup: bool) -> Self {
+        Self { dedup, ..self }
+    }
+
+    /// Scrolls this many lines at once with the arrows and the mouse wheel,
+    /// at least one. The pages of `Ctrl` keep their size.
+    #[cfg(feature = "tui")]
+    pub fn scroll_step(self, scroll_step: u16) -> Self {
+        Self {
+            scroll_step: scroll_step.max(1),
+            ..self
+        }
+    }
+
+    /// Clears the focused panel and reruns its task with `restart_key`, as
+    /// `r` does but with the new run on an empty panel.
+    #[cfg(feature = "tui")]
+    pub fn restart_key(self, restart_key: char) -> Self {
+        Self {
+            restart_key,
+            ..self
+        }
+    }
+
+    /// Lists `tasks` in the command menu, opened with `o`, which runs the
+    /// one selected once and shows its panel. These are the tasks running
+    /// on demand only, see [`crate::config::Task::on_demand`].
+    #[cfg(feature = "tui")]
+    pub fn on_demand(self, tasks: Vec<String>) -> Self {
+        Self {
+            on_demand: tasks,
+            ..self
+        }
+    }
+
+    /// Keeps the messages received in `sink` instead of showing them, see
+    /// [`crate::testing`].
+    #[cfg(feature = "testing")]
+    pub fn sink(self, sink: ConsoleSink) -> Self {
+        Self {
+            sink: Some(sink),
+            ..self.headless(true)
         }
     }
 
+    /// Announces the state changes through `announcer`, which also writes
+    /// the output, and answers the [`Instruction`]s read from stdin.
+    pub fn a11y(self, announcer: Announcer) -> Self {
+        Self {
+            announcer: Some(announcer),
+            ..self.headless(true)
+        }
+    }
+
+    /// Exits once every task is done, i.e. exited or blocked, after `delay`
+    /// during which any key but `q` keeps whiz open. Whiz then exits with
+    /// [`ExitCode::TaskFailed`] if a task did not exit successfully.
+    pub fn exit_when_done(self, delay: Duration) -> Self {
+        Self {
+            exit_when_done: Some(delay),
+            ..self
+        }
+    }
+
+    pub fn replay(self, toggle: bool) -> Self {
+        Self {
+            replay: toggle,
+            ..self
+        }
+    }
+
+    /// Records the console events of the session, see [`crate::replay`].
+    pub fn record(mut self, mut recorder: Recorder) -> Self {
+        let (width, height) = self.screen_size();
+        recorder.header(&SessionHeader {
+            order: self
+                .order
+                .iter()
+                .filter(|name| *name != TIMELINE)
+                .cloned()
+                .collect(),
+            timestamp: self.timestamp,
+            width,
+            height,
+        });
+        Self {
+            recorder: Some(recorder),
+            ..self
+        }
+    }
+
+    /// States of the tasks, without the dynamic panels and the timeline.
+    fn task_states(&self) -> impl Iterator<Item = (&String, TaskState)> {
+        self.order
+            .iter()
+            .filter(|name| *name != TIMELINE)
+            .filter_map(|name| {
+                let panel = self.panels.get(name).filter(|panel| !panel.dynamic)?;
+                Some((name, panel.state))
+            })
+    }
+
+    fn all_done(&self) -> bool {
+        let mut states = self.task_states().peekable();
+        states.peek().is_some() && states.all(|(_, state)| state.is_finished())
+    }
+
+    /// Schedules the exit once every task is done, or cancels it when one
+    /// starts again, e.g. after a file changed.
+    fn check_done(&mut self, ctx: &mut Context<Self>) {
+        let Some(delay) = self.exit_when_done else {
+            return;
+        };
+        match (self.all_done(), self.done_exit) {
+            (true, None) => {
+                self.done_exit = Some(ctx.run_later(delay, |act, _| {
+                    act.done = true;
+                    let failed = act.task_states().any(|(_, state)| {
+                        !matches!(state, TaskState::Exited(status) if status.success())
+                    });
+                    let code = match failed {
+                        true => ExitCode::TaskFailed,
+                        false => ExitCode::Success,
+                    };
+                    System::current().stop_with_code(code.code());
+                }));
+            }
+            (false, Some(handle)) => {
+                ctx.cancel_future(handle);
+                self.done_exit = None;
+            }
+            _ => {}
+        }
+    }
+
+    /// Tells how each task ended, printed once the view is closed.
+    fn summary(&self) -> String {
+        self.task_states()
+            .map(|(name, state)| {
+                let end = match state {
+                    TaskState::Exited(status) => describe_exit(status),
+                    TaskState::Blocked => "blocked, could not start".to_string(),
+                    _ => "unfinished".to_string(),
+                };
+                format!("task {name}: {end}\n")
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "tui")]
+    fn screen_size(&mut self) -> (u16, u16) {
+        let size = self.terminal.get_frame().size();
+        (size.width, size.height)
+    }
+
+    #[cfg(feature = "tui")]
+    pub fn status_theme(self, status_theme: StatusTheme) -> Self {
+        Self {
+            status_theme,
+            ..self
+        }
+    }
+
+    /// Draws the lines tasks print on stderr in `stderr_style`, dim red by
+    /// default.
+    #[cfg(feature = "tui")]
+    pub fn stderr_style(self, stderr_style: Style) -> Self {
+        Self {
+            stderr_style,
+            ..self
+        }
+    }
+
+    #[cfg(feature = "tui")]
+    pub fn split(self, split: Split) -> Self {
+        Self { split, ..self }
+    }
+
+    #[cfg(feature = "tui")]
     pub fn up(&mut self, shift: u16) {
         let log_height = self.get_log_height();
         if let Some(focused_panel) = self.panels.get_mut(&self.index) {
-            // maximum_scroll is the number of lines
-            // overflowing in the current focused panel
-            let lines = focused_panel.line_offsets.len() as u16;
-            let maximum_scroll = lines - min(lines, log_height);
+            let lines = focused_panel.line_offsets.len();
+            let maximum_scroll = maximum_scroll(lines, log_height as usize) as u16;
 
             // `focused_panel.shift` goes from 0 until maximum_scroll
             focused_panel.shift = min(focused_panel.shift + shift, maximum_scroll);
         }
     }
 
+    #[cfg(feature = "tui")]
     pub fn down(&mut self, shift: u16) {
         if let Some(focused_panel) = self.panels.get_mut(&self.index) {
             if focused_panel.shift >= shift {
@@ -165,9 +1178,217 @@ impl ConsoleActor {
         }
     }
 
+    /// Handles the keys of the selection mode of the focused panel, `V`
+    /// entering it, and returns whether `key` was one of them.
+    #[cfg(feature = "tui")]
+    fn select(&mut self, key: &KeyEvent) -> bool {
+        let Some(panel) = self.panels.get_mut(&self.index) else {
+            return false;
+        };
+        if panel.selection.is_none() {
+            if let (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char('V')) =
+                (key.modifiers, key.code)
+            {
+                panel.start_selection();
+                return true;
+            }
+            return false;
+        }
+        match (key.modifiers, key.code) {
+            (KeyModifiers::NONE, KeyCode::Up | KeyCode::Char('k')) => {
+                panel.move_selection(true);
+                self.scroll_to_selection();
+            }
+            (KeyModifiers::NONE, KeyCode::Down | KeyCode::Char('j')) => {
+                panel.move_selection(false);
+                self.scroll_to_selection();
+            }
+            (KeyModifiers::NONE, KeyCode::Char('y')) => {
+                let text = panel.selected_text();
+                panel.selection = None;
+                if let Some(text) = text {
+                    self.copy(text);
+                }
+            }
+            (KeyModifiers::NONE, KeyCode::Esc) => panel.selection = None,
+            (KeyModifiers::CONTROL, KeyCode::Char('c'))
+            | (KeyModifiers::NONE, KeyCode::Char('q')) => {
+                return false;
+            }
+            _ => {}
+        }
+        true
+    }
+
+    /// Handles the keys of the time lock, `T` locking every panel to the
+    /// moment of the line shown at the bottom of the focused one, and
+    /// returns whether `key` was one of them. The other keys still apply,
+    /// e.g. to switch panels.
+    #[cfg(feature = "tui")]
+    fn lock_time(&mut self, key: &KeyEvent) -> bool {
+        let Some(timestamp) = self.time_lock else {
+            if let (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char('T')) =
+                (key.modifiers, key.code)
+            {
+                let bottom = self
+                    .panels
+                    .get(&self.index)
+                    .and_then(|panel| panel.bottom_timestamp());
+                self.time_lock = Some(bottom.unwrap_or_else(Local::now));
+                return true;
+            }
+            return false;
+        };
+        let Some(panel) = self.panels.get(&self.index) else {
+            return false;
+        };
+        match (key.modifiers, key.code) {
+            (KeyModifiers::NONE, KeyCode::Up | KeyCode::Char('k')) => {
+                if let Some(previous) = panel.step_from(&timestamp, true) {
+                    self.time_lock = Some(previous);
+                }
+            }
+            (KeyModifiers::NONE, KeyCode::Down | KeyCode::Char('j')) => {
+                if let Some(next) = panel.step_from(&timestamp, false) {
+                    self.time_lock = Some(next);
+                }
+            }
+            (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char('T'))
+            | (KeyModifiers::NONE, KeyCode::Esc) => {
+                // every panel follows its output again
+                self.time_lock = None;
+                for panel in self.panels.values_mut() {
+                    panel.shift = 0;
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Handles the keys of the insert mode, `i` entering it on the panel of
+    /// a task, and returns whether `key` was one of them. Every key but
+    /// `Esc`, which leaves it, goes to the stdin of the task while it is
+    /// on, see [`Input`].
+    #[cfg(feature = "tui")]
+    fn insert(&mut self, key: &KeyEvent) -> bool {
+        let command = self
+            .panels
+            .get(&self.index)
+            .and_then(|panel| panel.command.as_ref());
+        if !self.insert {
+            if (key.modifiers, key.code) == (KeyModifiers::NONE, KeyCode::Char('i'))
+                && command.is_some()
+            {
+                self.insert = true;
+                return true;
+            }
+            return false;
+        }
+        if (key.modifiers, key.code) == (KeyModifiers::NONE, KeyCode::Esc) {
+            self.insert = false;
+        } else if let (Some(command), Some(bytes)) = (command, key_bytes(key)) {
+            command.do_send(Input(bytes));
+        }
+        true
+    }
+
+    /// Handles the keys of the command menu, `o` opening it when a task
+    /// runs on demand, and returns whether `key` was one of them. Every
+    /// key goes to the menu while it is open.
+    #[cfg(feature = "tui")]
+    fn command_menu(&mut self, key: &KeyEvent) -> bool {
+        let Some(selected) = self.command_menu else {
+            if (key.modifiers, key.code) == (KeyModifiers::NONE, KeyCode::Char('o'))
+                && !self.on_demand.is_empty()
+            {
+                self.command_menu = Some(0);
+                return true;
+            }
+            return false;
+        };
+        let last = self.on_demand.len().saturating_sub(1);
+        match (key.modifiers, key.code) {
+            (KeyModifiers::NONE, KeyCode::Up | KeyCode::Char('k')) => {
+                self.command_menu = Some(selected.saturating_sub(1));
+            }
+            (KeyModifiers::NONE, KeyCode::Down | KeyCode::Char('j')) => {
+                self.command_menu = Some(min(selected + 1, last));
+            }
+            (KeyModifiers::NONE, KeyCode::Enter) => {
+                self.command_menu = None;
+                let Some(name) = self.on_demand.get(selected) else {
+                    return true;
+                };
+                if let Some(command) = self
+                    .panels
+                    .get(name)
+                    .and_then(|panel| panel.command.as_ref())
+                {
+                    command.do_send(Reload::Manual);
+                }
+                if let Some(index) = self.order.iter().position(|panel| panel == name) {
+                    self.go_to(index);
+                }
+            }
+            (KeyModifiers::NONE, KeyCode::Esc | KeyCode::Char('o' | 'q')) => {
+                self.command_menu = None;
+            }
+            _ => {}
+        }
+        true
+    }
+
+    /// Scrolls the focused panel until the end of its selection shows.
+    #[cfg(feature = "tui")]
+    fn scroll_to_selection(&mut self) {
+        let height = self.get_log_height() as usize;
+        let Some(panel) = self.panels.get_mut(&self.index) else {
+            return;
+        };
+        let Some(selection) = panel.selection else {
+            return;
+        };
+        let rows = panel.line_offsets.len();
+        let Some(row) = panel
+            .line_offsets
+            .iter()
+            .position(|line| *line == selection.cursor)
+        else {
+            return;
+        };
+        // the rows below the first one of the line, leaving room for the
+        // indicators
+        let below = rows - 1 - row;
+        let shift = panel.shift as usize;
+        if below < shift {
+            panel.shift = below as u16;
+        } else if below + 2 >= shift + height {
+            let shift = (below + 3).saturating_sub(height);
+            panel.shift = min(shift, maximum_scroll(rows, height)) as u16;
+        }
+    }
+
+    /// Copies `text` to the clipboard with the OSC 52 sequence of the
+    /// terminal, which also works over SSH.
+    #[cfg(feature = "tui")]
+    fn copy(&mut self, text: String) {
+        let sequence = format!("\x1b]52;c;{}\x07", BASE64_STANDARD.encode(&text));
+        let backend = self.terminal.backend_mut();
+        let _ = io::Write::write_all(backend, sequence.as_bytes())
+            .and_then(|_| io::Write::flush(backend));
+        self.copied = Some(text);
+    }
+
+    #[cfg(feature = "tui")]
     pub fn get_log_height(&mut self) -> u16 {
-        let frame = self.terminal.get_frame();
-        chunks(&self.mode, &self.layout_direction, &frame)[0].height
+        let height = self.log_area().height;
+        let pinned = self
+            .panels
+            .get(&self.index)
+            .map(|panel| panel.pinned_height(height))
+            .unwrap_or(0);
+        height - pinned
     }
 
     pub fn go_to(&mut self, panel_index: usize) {
@@ -183,19 +1404,25 @@ impl ConsoleActor {
             .unwrap_or(0)
     }
 
+    #[cfg(feature = "tui")]
     pub fn next(&mut self) {
         self.index
             .clone_from(&self.order[(self.idx() + 1) % self.order.len()]);
         self.list_state.select(Some(self.idx()))
     }
 
+    #[cfg(feature = "tui")]
     pub fn previous(&mut self) {
         self.index
             .clone_from(&self.order[(self.idx() + self.order.len() - 1) % self.order.len()]);
         self.list_state.select(Some(self.idx()))
     }
 
+    #[cfg(feature = "tui")]
     fn clean(&mut self) {
+        if self.headless {
+            return;
+        }
         self.terminal
             .draw(|f| {
                 let clean = Block::default().style(Style::default().fg(Color::Black));
@@ -204,53 +1431,50 @@ impl ConsoleActor {
             .unwrap();
     }
 
+    #[cfg(feature = "tui")]
     fn draw(&mut self) {
+        if self.headless {
+            return;
+        }
         let idx = self.idx();
-        if let Some(focused_panel) = &self.panels.get(&self.index) {
-            self.terminal
+        if let Some(timestamp) = self.time_lock {
+            for panel in self.panels.values_mut() {
+                panel.lock_to(&timestamp);
+            }
+        }
+        if self.panels.contains_key(&self.index) {
+            let longest = self.longest_title();
+            let frame = self
+                .terminal
                 .draw(|f| {
-                    let chunks = chunks(&self.mode, &self.layout_direction, f);
-                    let logs = &focused_panel.logs;
-                    let shift = focused_panel.shift as usize;
-                    let line_offsets = &focused_panel.line_offsets;
-                    let lines = line_offsets.len();
-                    let log_height = chunks[0].height as usize;
-
-                    let maximum_scroll = lines - min(lines, log_height);
-                    let scroll_offset = maximum_scroll - min(maximum_scroll, shift);
-                    let offset_end = min(lines, scroll_offset + log_height).wrapping_sub(1);
-
-                    let line_start = line_offsets.get(scroll_offset).cloned().unwrap_or(0);
-                    let line_end = line_offsets.get(offset_end).cloned().unwrap_or(0);
-
-                    let wrap_offset = line_offsets
-                        .get(..scroll_offset)
-                        .map(|offsets| {
-                            offsets
-                                .iter()
-                                .rev()
-                                .take_while(|&line| *line == line_start)
-                                .count()
-                        })
-                        .unwrap_or(0);
-
-                    let lines = logs
-                        .get(line_start..=line_end)
-                        .map(|logs| {
-                            logs.iter()
-                                .flat_map(|(s, kind)| {
-                                    Colorizer::new(&focused_panel.colors, kind.style())
-                                        .patch_text(s)
-                                })
-                                .collect::<Vec<_>>()
-                        })
-                        .unwrap_or_default();
-
-                    let paragraph = Paragraph::new(lines)
-                        .wrap(Wrap { trim: false })
-                        .scroll((wrap_offset as u16, 0));
-
-                    f.render_widget(paragraph, chunks[0]);
+                    let chunks = chunks(&self.mode, &self.layout_direction, f, longest);
+                    let max_chars = menu_width(f.size().width, longest) - 2;
+
+                    // the cells show the page of panels of the focused one
+                    let cells = split_cells(chunks[0], self.split);
+                    let page = idx / cells.len() * cells.len();
+                    for (cell, name) in cells.iter().zip(self.order.iter().skip(page)) {
+                        let Some(panel) = self.panels.get(name) else {
+                            continue;
+                        };
+                        let mut area = *cell;
+                        if self.split != Split::Single {
+                            let modifier = match *name == self.index {
+                                true => Modifier::BOLD,
+                                false => Modifier::DIM,
+                            };
+                            let style = Style::default().add_modifier(modifier);
+                            let block = Block::default()
+                                .borders(Borders::ALL)
+                                .border_style(style)
+                                .title(Span::styled(name.as_str(), style));
+                            area = block.inner(*cell);
+                            f.render_widget(block, *cell);
+                        }
+                        // the timeline has no status
+                        let status_theme = (name != TIMELINE).then_some(&self.status_theme);
+                        draw_logs(f, panel, area, status_theme, self.stderr_style);
+                    }
 
                     //Format titles
                     let titles: Vec<Line> = self
@@ -260,24 +1484,18 @@ impl ConsoleActor {
                             let mut span = self
                                 .panels
                                 .get(panel)
-                                .map(|p| match p.status {
-                                    Some(ExitStatus::Exited(0)) => Span::styled(
-                                        format!("{}.", panel),
-                                        Style::default().fg(Color::Green),
-                                    ),
-                                    Some(_) => Span::styled(
-                                        format!("{}!", panel),
-                                        Style::default().fg(Color::Red),
-                                    ),
-                                    None => Span::styled(format!("{}*", panel), Style::default()),
-                                })
+                                // the timeline has no status
+                                .filter(|_| panel != TIMELINE)
+                                .map(|p| self.status_theme.title(panel, p.task_status()))
                                 .unwrap_or_else(|| Span::styled(panel, Style::default()));
-                            // Replace the titles whoms length is greater than MAX_CHARS with an
-                            // ellipse
-                            span = Span::styled(
-                                ellipse_if_too_long(span.content).into_owned(),
-                                span.style,
-                            );
+                            let mut title =
+                                ellipse_if_too_long(span.content, max_chars.into()).into_owned();
+                            if let Some(label) =
+                                self.panels.get(panel).and_then(|p| p.visibility.label())
+                            {
+                                title.push_str(label);
+                            }
+                            span = Span::styled(title, span.style);
                             Line::from(span)
                         })
                         .collect();
@@ -330,33 +1548,239 @@ impl ConsoleActor {
                         }
                         AppMode::View => {}
                     };
+
+                    // over the panels, until a task is run or it is closed
+                    if let Some(selected) = self.command_menu {
+                        let area = f.size();
+                        let longest = self.on_demand.iter().map(|name| name.len()).max();
+                        let width = min(longest.unwrap_or(0).max(16) as u16 + 4, area.width);
+                        let height = min(self.on_demand.len() as u16 + 2, area.height);
+                        let popup = Rect {
+                            x: area.x + (area.width - width) / 2,
+                            y: area.y + (area.height - height) / 2,
+                            width,
+                            height,
+                        };
+                        let list = List::new(
+                            self.on_demand
+                                .iter()
+                                .map(|name| ListItem::new(name.as_str()))
+                                .collect::<Vec<_>>(),
+                        )
+                        .block(
+                            Block::default()
+                                .borders(Borders::ALL)
+                                .title(" run on demand ")
+                                .title_alignment(Alignment::Center),
+                        )
+                        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+                        let mut state = ListState::default().with_selected(Some(selected));
+                        f.render_widget(Clear, popup);
+                        f.render_stateful_widget(list, popup, &mut state);
+                    }
+                    // over the bottom row, until the time lock is left
+                    if let Some(timestamp) = self.time_lock {
+                        let area = f.size();
+                        let row = Rect {
+                            y: area.bottom().saturating_sub(1),
+                            height: 1,
+                            ..area
+                        };
+                        let banner = format!(
+                            " locked at {}, j/k to move, T to follow the output ",
+                            timestamp.format("%H:%M:%S%.3f")
+                        );
+                        let style = Style::default().add_modifier(Modifier::REVERSED);
+                        f.render_widget(Paragraph::new(Span::styled(banner, style)), row);
+                    }
+                    // over the bottom row, until it is left
+                    if self.insert {
+                        let area = f.size();
+                        let row = Rect {
+                            y: area.bottom().saturating_sub(1),
+                            height: 1,
+                            ..area
+                        };
+                        let banner = format!(" INSERT: keys go to {}, Esc to leave ", self.index);
+                        let style = Style::default().add_modifier(Modifier::REVERSED);
+                        f.render_widget(Paragraph::new(Span::styled(banner, style)), row);
+                    }
+                    // over the bottom row, until the exit or a key
+                    if let (Some(delay), Some(_)) = (self.exit_when_done, self.done_exit) {
+                        let area = f.size();
+                        let row = Rect {
+                            y: area.bottom().saturating_sub(1),
+                            height: 1,
+                            ..area
+                        };
+                        let banner = format!(
+                            " all tasks done, exiting in {}, any key to stay ",
+                            format_elapsed(delay)
+                        );
+                        let style = Style::default().add_modifier(Modifier::REVERSED);
+                        f.render_widget(Paragraph::new(Span::styled(banner, style)), row);
+                    }
                 })
                 .unwrap();
+            if self.offscreen.is_some() {
+                self.last_frame = frame.buffer.clone();
+            }
+        }
+    }
+
+    #[cfg(feature = "tui")]
+    pub fn resize_panels(&mut self, width: u16) {
+        for panel in self.panels.values_mut() {
+            panel.shift = 0;
+            panel.sync_lines(width)
+        }
+        let commands: Vec<_> = self
+            .panels
+            .values()
+            .filter_map(|panel| panel.command.clone())
+            .collect();
+        for command in commands {
+            self.send_tty_size(&command);
+        }
+    }
+
+    /// Tells `command` the size of the logs area, which its task gets as
+    /// the size of its pseudo-terminal when it sets `tty`.
+    #[cfg(feature = "tui")]
+    fn send_tty_size(&mut self, command: &Addr<CommandActor>) {
+        let area = self.log_area();
+        command.do_send(TtySize {
+            columns: area.width,
+            rows: area.height,
+        });
+    }
+
+    /// Returns the length of the longest title of the menu, with the label
+    /// of its visibility, see [`menu_width`].
+    #[cfg(feature = "tui")]
+    fn longest_title(&self) -> usize {
+        self.order
+            .iter()
+            .map(|name| {
+                let label = self.panels.get(name).and_then(|p| p.visibility.label());
+                name.chars().count() + label.map_or(0, |label| label.chars().count())
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Returns the area of the logs of a panel, next to the menu and
+    /// inside the border of its cell when split.
+    #[cfg(feature = "tui")]
+    fn log_area(&mut self) -> Rect {
+        let longest = self.longest_title();
+        let frame = self.terminal.get_frame();
+        let area = chunks(&self.mode, &self.layout_direction, &frame, longest)[0];
+        let cell = split_cells(area, self.split)[0];
+        match self.split {
+            Split::Single => cell,
+            _ => Block::default().borders(Borders::ALL).inner(cell),
         }
     }
 
-    pub fn resize_panels(&mut self, width: u16) {
-        for panel in self.panels.values_mut() {
-            panel.shift = 0;
-            panel.sync_lines(width)
+    /// Returns the width the logs are wrapped to.
+    #[cfg(feature = "tui")]
+    fn log_width(&mut self) -> u16 {
+        self.log_area().width
+    }
+
+    /// Fits the panels to a terminal of `size`, whether it reported the
+    /// resize or it was noticed by [`Self::check_size`].
+    #[cfg(feature = "tui")]
+    fn resize(&mut self, size: Rect) {
+        if self.offscreen.is_some() {
+            self.terminal = new_terminal(Some(size), Box::new(io::sink()));
+            self.offscreen = Some(size);
+        } else if !self.headless {
+            let _ = self.terminal.resize(size);
+        }
+        let width = self.log_width();
+        self.resize_panels(width);
+    }
+
+    /// Resizes if the size of the terminal is not the one last drawn,
+    /// returning whether it did.
+    #[cfg(feature = "tui")]
+    fn check_size(&mut self) -> bool {
+        if self.headless {
+            return false;
+        }
+        let size = match self.offscreen {
+            Some(size) => size,
+            None => match self.terminal.size() {
+                Ok(size) => size,
+                Err(_) => return false,
+            },
+        };
+        if size == self.terminal.get_frame().size() {
+            return false;
         }
+        self.resize(size);
+        true
     }
 
+    #[cfg(feature = "tui")]
     pub fn switch_layout(&mut self) {
         self.layout_direction = self.layout_direction.get_opposite_orientation();
-        let f = self.terminal.get_frame();
-        let chunks = chunks(&self.mode, &self.layout_direction, &f);
-        self.resize_panels(chunks[0].width);
+        let width = self.log_width();
+        self.resize_panels(width);
+    }
+    #[cfg(feature = "tui")]
+    pub fn switch_split(&mut self) {
+        self.split = self.split.next();
+        let width = self.log_width();
+        self.resize_panels(width);
     }
+    #[cfg(feature = "tui")]
     pub fn switch_mode(&mut self) {
         self.mode = self.mode.get_opposite_mode();
+        let width = self.log_width();
+        self.resize_panels(width);
+    }
+}
+
+/// Without the `tui` feature, the console is always headless and draws
+/// nothing, sized as a terminal of [`HEADLESS_SIZE`].
+#[cfg(not(feature = "tui"))]
+impl ConsoleActor {
+    pub fn headless(self, _: bool) -> Self {
+        self
+    }
+
+    fn screen_size(&mut self) -> (u16, u16) {
+        HEADLESS_SIZE
     }
+
+    fn send_tty_size(&mut self, command: &Addr<CommandActor>) {
+        let (columns, rows) = HEADLESS_SIZE;
+        command.do_send(TtySize { columns, rows });
+    }
+
+    fn draw(&mut self) {}
 }
 
 impl Actor for ConsoleActor {
     type Context = Context<Self>;
 
+    #[cfg(feature = "tui")]
     fn started(&mut self, ctx: &mut Context<Self>) {
+        if self.headless {
+            return;
+        }
+        // for the terminals which do not report their resizes
+        ctx.run_interval(SIZE_CHECK_INTERVAL, |act, _| {
+            if act.check_size() {
+                act.draw();
+            }
+        });
+        if self.offscreen.is_some() {
+            return;
+        }
         enable_raw_mode().unwrap();
         execute!(
             self.terminal.backend_mut(),
@@ -378,22 +1802,37 @@ impl Actor for ConsoleActor {
 
     fn stopped(&mut self, _: &mut Self::Context) {
         self.arbiter.stop();
-        self.clean();
-
-        execute!(
-            self.terminal.backend_mut(),
-            LeaveAlternateScreen,
-            cursor::Show,
-        )
-        .unwrap();
-        disable_raw_mode().unwrap();
+        #[cfg(feature = "tui")]
+        if !self.headless && self.offscreen.is_none() {
+            self.clean();
+
+            execute!(
+                self.terminal.backend_mut(),
+                LeaveAlternateScreen,
+                cursor::Show,
+            )
+            .unwrap();
+            disable_raw_mode().unwrap();
+        }
+        if self.done {
+            print!("{}", self.summary());
+        }
     }
 }
 
+/// Event read from the terminal by the interactive view.
+#[cfg(feature = "tui")]
 #[derive(Message, Debug)]
 #[rtype(result = "()")]
-pub struct TermEvent(Event);
+pub struct TermEvent(pub Event);
 
+/// Quits, the only terminal event without the interactive view.
+#[cfg(not(feature = "tui"))]
+#[derive(Message, Debug)]
+#[rtype(result = "()")]
+pub struct TermEvent;
+
+#[cfg(feature = "tui")]
 impl TermEvent {
     pub fn quit() -> Self {
         Self(Event::Key(KeyEvent::new(
@@ -403,26 +1842,86 @@ impl TermEvent {
     }
 }
 
+#[cfg(not(feature = "tui"))]
+impl TermEvent {
+    pub fn quit() -> Self {
+        Self
+    }
+}
+
+#[cfg(not(feature = "tui"))]
+impl Handler<TermEvent> for ConsoleActor {
+    type Result = ();
+
+    fn handle(&mut self, _: TermEvent, _: &mut Context<Self>) -> Self::Result {
+        self.panels
+            .values()
+            .filter_map(|e| e.command.as_ref())
+            .for_each(|command| command.do_send(PoisonPill));
+        System::current().stop();
+    }
+}
+
+#[cfg(feature = "tui")]
 impl Handler<TermEvent> for ConsoleActor {
     type Result = ();
 
-    fn handle(&mut self, msg: TermEvent, _: &mut Context<Self>) -> Self::Result {
+    fn handle(&mut self, msg: TermEvent, ctx: &mut Context<Self>) -> Self::Result {
+        if let Some(recorder) = &mut self.recorder {
+            if let Some(event) = RecordedEvent::term_event(&msg) {
+                recorder.record(event);
+            }
+        }
+        if let (Event::Key(e), Some(handle)) = (&msg.0, self.done_exit) {
+            let quit = matches!(
+                (e.modifiers, e.code),
+                (KeyModifiers::CONTROL, KeyCode::Char('c'))
+                    | (KeyModifiers::NONE, KeyCode::Char('q'))
+            );
+            if !quit {
+                // stays open for the rest of the session
+                ctx.cancel_future(handle);
+                self.done_exit = None;
+                self.exit_when_done = None;
+                self.draw();
+                return;
+            }
+        }
+        if let Event::Key(key) = &msg.0 {
+            if self.command_menu(key) || self.insert(key) || self.select(key) || self.lock_time(key)
+            {
+                self.draw();
+                return;
+            }
+        }
         match msg.0 {
             Event::Key(e) => match (e.modifiers, e.code) {
                 (KeyModifiers::CONTROL, KeyCode::Char('c'))
                 | (KeyModifiers::NONE, KeyCode::Char('q')) => {
                     self.panels
                         .values()
-                        .for_each(|e| e.command.do_send(PoisonPill));
+                        .filter_map(|e| e.command.as_ref())
+                        .for_each(|command| command.do_send(PoisonPill));
                     System::current().stop();
                 }
                 (KeyModifiers::NONE, KeyCode::Up | KeyCode::Char('k'))
                 | (KeyModifiers::CONTROL, KeyCode::Char('p')) => {
-                    self.up(1);
+                    self.up(self.scroll_step);
                 }
                 (KeyModifiers::NONE, KeyCode::Down | KeyCode::Char('j'))
                 | (KeyModifiers::CONTROL, KeyCode::Char('n')) => {
-                    self.down(1);
+                    self.down(self.scroll_step);
+                }
+                (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(ch))
+                    if ch == self.restart_key =>
+                {
+                    if let Some(panel) = self.panels.get_mut(&self.index) {
+                        // cleared before the reload, which the new run follows
+                        panel.clear();
+                        if let Some(command) = &panel.command {
+                            command.do_send(Reload::Manual);
+                        }
+                    }
                 }
                 (KeyModifiers::CONTROL, key_code) => match key_code {
                     KeyCode::Char('f') => {
@@ -441,46 +1940,66 @@ impl Handler<TermEvent> for ConsoleActor {
                         let log_height = self.get_log_height();
                         self.up(log_height);
                     }
+                    KeyCode::Char('r') => {
+                        if let Some(restart) = &self.restart {
+                            restart.do_send(Restart);
+                        }
+                    }
+                    // redraws everything, e.g. after another process wrote
+                    // to the terminal
+                    KeyCode::Char('l') => {
+                        self.check_size();
+                        let _ = self.terminal.clear();
+                    }
                     _ => {}
                 },
                 (KeyModifiers::NONE, key_code) => match key_code {
                     KeyCode::Char('r') => {
-                        if let Some(focused_panel) = self.panels.get(&self.index) {
-                            focused_panel.command.do_send(Reload::Manual);
+                        if let Some(command) = self
+                            .panels
+                            .get(&self.index)
+                            .and_then(|panel| panel.command.as_ref())
+                        {
+                            command.do_send(Reload::Manual);
                         }
                     }
                     KeyCode::Tab => self.switch_layout(),
                     KeyCode::Char('m') => self.switch_mode(),
+                    KeyCode::Char('s') => self.switch_split(),
+                    KeyCode::Char('v') => {
+                        let width = self.log_width();
+                        if let Some(panel) = self.panels.get_mut(&self.index) {
+                            panel.cycle_visibility(width);
+                        }
+                    }
                     KeyCode::Right | KeyCode::Char('l') => {
                         self.next();
                     }
                     KeyCode::Left | KeyCode::Char('h') => {
                         self.previous();
                     }
-                    KeyCode::Char(ch) => {
-                        if ch.is_ascii_digit() {
-                            let mut panel_index = ch.to_digit(10).unwrap() as usize;
-                            // first tab is key 1, therefore
-                            // in key 0 go to last tab
-                            if panel_index == 0 {
-                                panel_index = self.order.len() - 1;
-                            } else {
-                                panel_index -= 1;
-                            }
-                            self.go_to(panel_index);
+                    KeyCode::Char(ch) if ch.is_ascii_digit() => {
+                        let mut panel_index = ch.to_digit(10).unwrap() as usize;
+                        // first tab is key 1, therefore
+                        // in key 0 go to last tab
+                        if panel_index == 0 {
+                            panel_index = self.order.len() - 1;
+                        } else {
+                            panel_index -= 1;
                         }
+                        self.go_to(panel_index);
                     }
                     _ => {}
                 },
                 _ => {}
             },
-            Event::Resize(width, _) => self.resize_panels(width),
+            Event::Resize(width, height) => self.resize(Rect::new(0, 0, width, height)),
             Event::Mouse(e) => match e.kind {
                 MouseEventKind::ScrollUp => {
-                    self.up(1);
+                    self.up(self.scroll_step);
                 }
                 MouseEventKind::ScrollDown => {
-                    self.down(1);
+                    self.down(self.scroll_step);
                 }
                 _ => {}
             },
@@ -490,17 +2009,24 @@ impl Handler<TermEvent> for ConsoleActor {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum OutputKind {
     Service,
     Command,
+    /// Lines the task printed on stderr, when not merged into stdout.
+    Stderr,
 }
 
+#[cfg(feature = "tui")]
 impl OutputKind {
-    fn style(&self) -> Style {
+    /// Style of the lines of this kind, `stderr` being the one of
+    /// [`OutputKind::Stderr`].
+    fn style(&self, stderr: Style) -> Style {
         match self {
             OutputKind::Service => Style::default().bg(Color::DarkGray),
             OutputKind::Command => Style::default(),
+            OutputKind::Stderr => stderr,
         }
     }
 }
@@ -508,10 +2034,18 @@ impl OutputKind {
 #[derive(Message)]
 #[rtype(result = "()")]
 pub struct Output {
-    panel_name: String,
+    pub panel_name: String,
     pub message: String,
-    kind: OutputKind,
-    timestamp: DateTime<Local>,
+    #[cfg_attr(not(feature = "tui"), allow(dead_code))]
+    pub kind: OutputKind,
+    pub timestamp: DateTime<Local>,
+    /// Slot the line is pinned to at the top of the panel, see
+    /// [`crate::config::pin::Pins`].
+    pub pin: Option<usize>,
+    /// Start of a line still being printed, replaced by the next line of
+    /// the panel, see [`crate::config::stream::Stream::Raw`]. An empty one
+    /// only removes the previous one. Shown in the interactive view only.
+    pub partial: bool,
 }
 
 impl Output {
@@ -521,24 +2055,48 @@ impl Output {
             message,
             kind,
             timestamp: Local::now(),
+            pin: None,
+            partial: false,
         }
     }
+
+    pub fn pin(self, pin: Option<usize>) -> Self {
+        Self { pin, ..self }
+    }
+
+    pub fn partial(self, partial: bool) -> Self {
+        Self { partial, ..self }
+    }
 }
 
+#[cfg(feature = "tui")]
 fn wrapped_lines(message: &String, width: u16) -> usize {
     let clean = strip_ansi_escapes::strip(message);
     textwrap::wrap(str::from_utf8(&clean).unwrap(), width as usize).len()
 }
 
-// Replace the character that are max that MAX_CHARS with an ellipse ...
-fn ellipse_if_too_long(task_title: Cow<'_, str>) -> Cow<str> {
-    if task_title.len() >= MAX_CHARS {
-        let mut task_title = task_title.to_string();
-        task_title.replace_range(MAX_CHARS.., "...");
-        Cow::Owned(task_title.to_string())
-    } else {
-        task_title
+/// Returns the width of the menu listing the tasks on the side of a
+/// terminal `width` wide, fitting its `longest` title between
+/// [`MIN_MENU_WIDTH`] and [`MAX_MENU_WIDTH`] but never taking more than
+/// half of the terminal from the logs.
+#[cfg(feature = "tui")]
+fn menu_width(width: u16, longest: usize) -> u16 {
+    // the titles sit between the borders
+    let fitting = u16::try_from(longest).unwrap_or(u16::MAX).saturating_add(2);
+    fitting
+        .clamp(MIN_MENU_WIDTH, MAX_MENU_WIDTH)
+        .min(width / 2)
+        .max(2)
+}
+
+/// Cuts the titles longer than `max_chars`, ending them with `...`.
+#[cfg(feature = "tui")]
+fn ellipse_if_too_long(task_title: Cow<'_, str>, max_chars: usize) -> Cow<'_, str> {
+    if task_title.chars().count() <= max_chars {
+        return task_title;
     }
+    let kept = task_title.chars().take(max_chars.saturating_sub(3));
+    Cow::Owned(kept.chain("...".chars()).collect())
 }
 
 /// Formats a message with a timestamp in `"{timestamp}  {message}"`.
@@ -550,65 +2108,757 @@ impl Handler<Output> for ConsoleActor {
     type Result = ();
 
     fn handle(&mut self, msg: Output, _: &mut Context<Self>) -> Self::Result {
+        // the whole line comes once it ends
+        #[cfg(feature = "tui")]
+        let interactive = !self.headless;
+        #[cfg(not(feature = "tui"))]
+        let interactive = false;
+        if msg.partial && !interactive {
+            return;
+        }
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(RecordedEvent::output(&msg, recorder.redacted()));
+        }
+        #[cfg(feature = "testing")]
+        if let Some(sink) = &self.sink {
+            sink.push(ConsoleEvent::Output {
+                panel: msg.panel_name,
+                message: msg.message,
+                kind: msg.kind,
+            });
+            return;
+        }
+        #[cfg(feature = "tui")]
+        if msg.kind == OutputKind::Service && msg.message.starts_with("RELOAD: ") {
+            self.add_to_timeline(&msg.panel_name, msg.message.trim_end(), &msg.timestamp);
+        }
+        #[cfg(feature = "tui")]
+        let prefix = (self.prefix_lines && !self.headless && msg.kind != OutputKind::Service)
+            .then(|| format!("[{}] ", colored_task(&msg.panel_name)));
+        #[cfg(not(feature = "tui"))]
+        let prefix: Option<String> = None;
+        #[cfg(feature = "tui")]
+        let raw = (self.dedup && !msg.partial).then(|| msg.message.clone());
+        let folded = self.folded_panels.get(&msg.panel_name).cloned();
+        let message = match (prefix, &folded) {
+            (Some(prefix), _) => format!("{prefix}{}", msg.message),
+            (None, Some(_)) => format!("[{}] {}", msg.panel_name, msg.message),
+            (None, None) => msg.message,
+        };
+        let panel_name = folded.unwrap_or(msg.panel_name);
         let message = match self.timestamp {
-            true => format_message(&msg.message, &msg.timestamp),
-            false => msg.message,
+            true => format_message(&message, &msg.timestamp),
+            false => message,
         };
 
-        let panel = self.panels.get_mut(&msg.panel_name).unwrap();
-        let width = self.terminal.get_frame().size().width;
-        let line_count = wrapped_lines(&message, width);
-        let line_offset = panel.logs.len();
+        // always headless without the `tui` feature
+        #[cfg(feature = "tui")]
+        if !self.headless {
+            let cleared_at = self
+                .panels
+                .get(&panel_name)
+                .and_then(|panel| panel.cleared_at);
+            if cleared_at.is_some_and(|cleared_at| msg.timestamp < cleared_at) {
+                // printed by the run the panel was cleared of
+                return;
+            }
+            match (msg.partial, raw) {
+                (true, _) => self.show_partial(&panel_name, message, msg.kind, msg.timestamp),
+                (false, Some(raw)) => {
+                    self.show_deduped(&panel_name, raw, message, msg.kind, msg.timestamp, msg.pin)
+                }
+                (false, None) => {
+                    self.show_output(&panel_name, message, msg.kind, msg.timestamp, msg.pin)
+                }
+            }
+            return;
+        }
+        if let Some(announcer) = &mut self.announcer {
+            announcer.output(&panel_name, &message);
+            return;
+        }
+        println!("{} | {}", panel_name, message);
+    }
+}
 
-        panel.line_offsets.extend(vec![line_offset; line_count]);
-        panel.logs.push((message, msg.kind));
+#[cfg(feature = "tui")]
+impl ConsoleActor {
+    /// Adds a line to the panel it was printed to.
+    fn show_output(
+        &mut self,
+        panel_name: &str,
+        message: String,
+        kind: OutputKind,
+        timestamp: DateTime<Local>,
+        pin: Option<usize>,
+    ) {
+        let width = self.log_width();
+        let panel = self.panels.get_mut(panel_name).unwrap();
+        panel.drop_partial();
+        panel.repeats = None;
+        if let Some(slot) = pin {
+            panel.pinned.insert(slot, (message.clone(), kind));
+        }
+        if panel.visibility.shows(kind) {
+            let line_count = wrapped_lines(&message, width);
+            let line_offset = panel.logs.len();
+            panel.line_offsets.extend(vec![line_offset; line_count]);
+        }
+        panel.logs.push((message, kind, timestamp));
 
         self.draw();
     }
+
+    /// Adds a line to its panel unless it repeats the last one, which
+    /// counts it instead, see [`Self::dedup`]. `raw` is the line as printed
+    /// and `message` as shown.
+    fn show_deduped(
+        &mut self,
+        panel_name: &str,
+        raw: String,
+        message: String,
+        kind: OutputKind,
+        timestamp: DateTime<Local>,
+        pin: Option<usize>,
+    ) {
+        let width = self.log_width();
+        let Some(panel) = self.panels.get_mut(panel_name) else {
+            return;
+        };
+        if panel.count_repeat(&raw, kind, width) {
+            self.draw();
+            return;
+        }
+        let repeats = Repeats {
+            raw,
+            kind,
+            shown: message.clone(),
+            count: 1,
+        };
+        self.show_output(panel_name, message, kind, timestamp, pin);
+        if let Some(panel) = self.panels.get_mut(panel_name) {
+            panel.repeats = Some(repeats);
+        }
+    }
+
+    /// Shows the start of a line still being printed as the last line of
+    /// its panel, in place of the previous one, see [`Output::partial`].
+    fn show_partial(
+        &mut self,
+        panel_name: &str,
+        message: String,
+        kind: OutputKind,
+        timestamp: DateTime<Local>,
+    ) {
+        if message.is_empty() {
+            if let Some(panel) = self.panels.get_mut(panel_name) {
+                panel.drop_partial();
+            }
+            self.draw();
+            return;
+        }
+        self.show_output(panel_name, message, kind, timestamp, None);
+        if let Some(panel) = self.panels.get_mut(panel_name) {
+            panel.partial = true;
+        }
+    }
+
+    /// Adds an event of a task to the timeline, if shown.
+    fn add_to_timeline(&mut self, panel_name: &str, event: &str, timestamp: &DateTime<Local>) {
+        if !self.timeline || self.headless {
+            return;
+        }
+        let line = format_message(&format!("{}  {event}", colored_task(panel_name)), timestamp);
+        self.show_output(TIMELINE, line, OutputKind::Command, *timestamp, None);
+    }
+}
+
+/// Panel created by a pipe of `task` from its captures, at most `max_tabs`
+/// of them per task.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DynamicPanel {
+    pub task: String,
+    pub max_tabs: usize,
 }
 
 #[derive(Message)]
 #[rtype(result = "()")]
 pub struct RegisterPanel {
     pub name: String,
-    pub addr: Addr<CommandActor>,
+    pub addr: Option<Addr<CommandActor>>,
     pub colors: Vec<ColorOption>,
+    pub dynamic: Option<DynamicPanel>,
+}
+
+impl ConsoleActor {
+    /// Returns the panel to create for a dynamic panel over the cap of its
+    /// task, whose lines are then folded into it.
+    fn fold_dynamic_panel(
+        &mut self,
+        name: &str,
+        dynamic: &DynamicPanel,
+        ctx: &mut Context<Self>,
+    ) -> Option<String> {
+        let created = self.dynamic_panels.entry(dynamic.task.clone()).or_default();
+        if *created < dynamic.max_tabs {
+            *created += 1;
+            return None;
+        }
+
+        let target = match dynamic.max_tabs {
+            0 => dynamic.task.clone(),
+            _ => format!("{} (overflow)", dynamic.task),
+        };
+        if dynamic.max_tabs > 0 && !self.panels.contains_key(&target) {
+            ctx.address().do_send(Output::now(
+                dynamic.task.clone(),
+                format!(
+                    "PIPE: more than {} tabs, the next ones are folded into {}",
+                    dynamic.max_tabs, target
+                ),
+                OutputKind::Service,
+            ));
+        }
+        self.folded_panels.insert(name.to_string(), target.clone());
+        Some(target)
+    }
 }
 
 impl Handler<RegisterPanel> for ConsoleActor {
     type Result = ();
 
-    fn handle(&mut self, msg: RegisterPanel, _: &mut Context<Self>) -> Self::Result {
+    fn handle(&mut self, mut msg: RegisterPanel, ctx: &mut Context<Self>) -> Self::Result {
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(RecordedEvent::register_panel(&msg));
+        }
+        #[cfg(feature = "testing")]
+        if let Some(sink) = &self.sink {
+            sink.push(ConsoleEvent::RegisterPanel {
+                name: msg.name.clone(),
+            });
+        }
+        debug_assert!(
+            msg.addr.as_ref().is_none_or(|addr| addr.connected()),
+            "panel {} registered for a task without actor",
+            msg.name
+        );
+        if let (Some(panel), None) = (self.panels.get_mut(&msg.name), &msg.dynamic) {
+            // the task changed in the config file, or was spawned again
+            // by a restart
+            panel.colors = msg.colors;
+            if let Some(addr) = msg.addr.filter(|addr| panel.command.as_ref() != Some(addr)) {
+                panel.command = Some(addr.clone());
+                self.send_tty_size(&addr);
+            }
+            self.draw();
+            return;
+        }
+        if self.panels.contains_key(&msg.name) || self.folded_panels.contains_key(&msg.name) {
+            return;
+        }
+        let folded = match &msg.dynamic {
+            Some(dynamic) => self.fold_dynamic_panel(&msg.name, dynamic, ctx),
+            None => None,
+        };
+        if let Some(target) = folded {
+            msg.name = target;
+        }
         if !self.panels.contains_key(&msg.name) {
-            let new_panel = Panel::new(msg.addr, msg.colors);
+            if let Some(addr) = &msg.addr {
+                self.send_tty_size(addr);
+            }
+            let mut new_panel = Panel::new(msg.addr, msg.colors);
+            new_panel.dynamic = msg.dynamic.is_some();
             self.panels.insert(msg.name.clone(), new_panel);
         }
         if !self.order.contains(&msg.name) {
-            self.order.push(msg.name);
+            // the timeline stays last
+            let at = self
+                .order
+                .iter()
+                .position(|name| name == TIMELINE)
+                .unwrap_or(self.order.len());
+            self.order.insert(at, msg.name);
+        }
+        self.draw();
+    }
+}
+
+/// Removes the panel of a task, along with the tabs created by its pipes.
+#[derive(Message, Clone)]
+#[rtype(result = "()")]
+pub struct UnregisterPanel {
+    pub name: String,
+}
+
+impl Handler<UnregisterPanel> for ConsoleActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: UnregisterPanel, _: &mut Context<Self>) -> Self::Result {
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(RecordedEvent::UnregisterPanel {
+                name: msg.name.clone(),
+            });
+        }
+        #[cfg(feature = "testing")]
+        if let Some(sink) = &self.sink {
+            sink.push(ConsoleEvent::UnregisterPanel {
+                name: msg.name.clone(),
+            });
+        }
+        let command = self
+            .panels
+            .get(&msg.name)
+            .and_then(|panel| panel.command.clone());
+        let removed: Vec<String> = self
+            .panels
+            .iter()
+            .filter(|(name, panel)| {
+                **name == msg.name || (command.is_some() && panel.command == command)
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in &removed {
+            self.panels.remove(name);
+        }
+        self.order.retain(|name| !removed.contains(name));
+        self.dynamic_panels.remove(&msg.name);
+        for watcher in &self.state_watchers {
+            watcher.removed.do_send(UnregisterPanel {
+                name: msg.name.clone(),
+            });
+        }
+        self.folded_panels
+            .retain(|_, target| self.panels.contains_key(target));
+
+        if !self.panels.contains_key(&self.index) {
+            self.index = self.order.first().cloned().unwrap_or_default();
         }
         self.draw();
     }
 }
 
+/// Sets where Ctrl-r sends [`Restart`], only while the config is watched.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SetRestart(pub Recipient<Restart>);
+
+impl Handler<SetRestart> for ConsoleActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetRestart, _: &mut Context<Self>) -> Self::Result {
+        self.restart = Some(msg.0);
+    }
+}
+
+/// A line of the recording of the session, see [`Recorder`].
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ConsoleLine(pub String);
+
+/// Forwards the lines recorded from now on to `0`, until it disconnects,
+/// and returns how many were recorded before. Returns `None` when the
+/// session is not recorded.
+#[derive(Message)]
+#[rtype(result = "Option<usize>")]
+pub struct Attach(pub Recipient<ConsoleLine>);
+
+impl Handler<Attach> for ConsoleActor {
+    type Result = Option<usize>;
+
+    fn handle(&mut self, msg: Attach, _: &mut Context<Self>) -> Self::Result {
+        Some(self.recorder.as_mut()?.attach(msg.0))
+    }
+}
+
+/// Forwards the state of each task to `changed`, in the order of the
+/// menu, then every [`StateChanged`] and the tasks removed by a config
+/// reload to `removed`, until it disconnects.
 #[derive(Message)]
 #[rtype(result = "()")]
-pub struct PanelStatus {
+pub struct WatchStates {
+    pub changed: Recipient<StateChanged>,
+    pub removed: Recipient<UnregisterPanel>,
+}
+
+impl Handler<WatchStates> for ConsoleActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: WatchStates, _: &mut Context<Self>) -> Self::Result {
+        for (name, state) in self.task_states() {
+            msg.changed.do_send(StateChanged {
+                panel_name: name.clone(),
+                state,
+            });
+        }
+        self.state_watchers.push(msg);
+    }
+}
+
+/// Returns the state of each panel, in the order of the menu.
+#[derive(Message)]
+#[rtype(result = "Vec<(String, TaskState)>")]
+pub struct GetStates;
+
+impl Handler<GetStates> for ConsoleActor {
+    type Result = MessageResult<GetStates>;
+
+    fn handle(&mut self, _: GetStates, _: &mut Context<Self>) -> Self::Result {
+        MessageResult(
+            self.order
+                .iter()
+                .filter(|name| *name != TIMELINE)
+                .filter_map(|name| Some((name.clone(), self.panels.get(name)?.state)))
+                .collect(),
+        )
+    }
+}
+
+/// Describes a state change in the timeline, skipping the transient ones.
+#[cfg(feature = "tui")]
+fn timeline_event(state: TaskState) -> Option<String> {
+    match state {
+        TaskState::Pending | TaskState::Starting | TaskState::Stopping => None,
+        TaskState::WaitingOnUpstream => Some("waiting on upstream".to_string()),
+        TaskState::Queued => Some("queued for execution slot".to_string()),
+        TaskState::Running => Some("started".to_string()),
+        TaskState::Exited(ExitStatus::Exited(code)) => Some(format!("exited with code {code}")),
+        TaskState::Exited(ExitStatus::Signaled(signal)) => Some(killed_by(signal)),
+        TaskState::Exited(ExitStatus::Undetermined) => Some("killed".to_string()),
+        TaskState::Exited(ExitStatus::Other(code)) => Some(format!("exited ({code})")),
+        TaskState::Blocked => Some("blocked, could not start".to_string()),
+    }
+}
+
+/// Broadcast by a task whenever its [`TaskState`] changes.
+#[derive(Message, Clone)]
+#[rtype(result = "()")]
+pub struct StateChanged {
     pub panel_name: String,
-    pub status: Option<ExitStatus>,
+    pub state: TaskState,
 }
 
-impl Handler<PanelStatus> for ConsoleActor {
+impl Handler<StateChanged> for ConsoleActor {
     type Result = ();
 
-    fn handle(&mut self, msg: PanelStatus, ctx: &mut Context<Self>) -> Self::Result {
+    fn handle(&mut self, msg: StateChanged, ctx: &mut Context<Self>) -> Self::Result {
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(RecordedEvent::state_changed(&msg));
+        }
+        #[cfg(feature = "testing")]
+        if let Some(sink) = &self.sink {
+            sink.push(ConsoleEvent::StateChanged {
+                panel: msg.panel_name.clone(),
+                state: msg.state,
+            });
+        }
+        self.state_watchers
+            .retain(|watcher| watcher.changed.connected());
+        for watcher in &self.state_watchers {
+            watcher.changed.do_send(msg.clone());
+        }
         let focused_panel = self.panels.get_mut(&msg.panel_name).unwrap();
-        focused_panel.status = msg.status;
+        focused_panel.state = msg.state;
+        #[cfg(feature = "tui")]
+        if let Some(event) = timeline_event(msg.state) {
+            self.add_to_timeline(&msg.panel_name, &event, &Local::now());
+        }
+        if let Some(announcer) = &mut self.announcer {
+            announcer.state_changed(&msg.panel_name, msg.state);
+        }
 
-        if let Some(message) = msg.status.map(|c| format!("Status: {:?}", c)) {
+        let message = match msg.state {
+            // already announced
+            TaskState::Exited(_) if self.announcer.is_some() => None,
+            TaskState::Exited(ExitStatus::Signaled(signal)) => {
+                Some(format!("Status: {}", killed_by(signal)))
+            }
+            TaskState::Exited(status) => Some(format!("Status: {:?}", status)),
+            _ => None,
+        };
+        if let Some(message) = message.filter(|_| !self.replay) {
             ctx.address()
                 .do_send(Output::now(msg.panel_name, message, OutputKind::Service));
         }
 
+        self.check_done(ctx);
         self.draw();
     }
 }
+
+impl Handler<Instruction> for ConsoleActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: Instruction, ctx: &mut Context<Self>) -> Self::Result {
+        let Some(announcer) = &mut self.announcer else {
+            return;
+        };
+        let known = |task: &String| self.panels.contains_key(task) && task != TIMELINE;
+        match msg {
+            Instruction::Focus(Some(task)) if !known(&task) => {
+                announcer.say(&format!("no task named {task}"));
+            }
+            Instruction::Focus(task) => announcer.focus(task),
+            Instruction::Reload(task) if !known(&task) => {
+                announcer.say(&format!("no task named {task}"));
+            }
+            Instruction::Reload(task) => match &self.panels[&task].command {
+                Some(command) => {
+                    announcer.say(&format!("reloading task {task}"));
+                    command.do_send(Reload::Manual);
+                }
+                // a tab created by a pipe
+                None => announcer.say(&format!("{task} is not a task, cannot reload it")),
+            },
+            Instruction::Status => {
+                for name in self.order.iter().filter(|name| *name != TIMELINE) {
+                    if let Some(panel) = self.panels.get(name) {
+                        announcer.say(&format!("task {name}: {}", panel.state));
+                    }
+                }
+            }
+            Instruction::Stop => {
+                announcer.say("stopping");
+                ctx.notify(TermEvent::quit());
+            }
+            Instruction::Help => announcer.say(HELP),
+            Instruction::Unknown(line) => {
+                announcer.say(&format!("unknown command {line:?}, {HELP}"));
+            }
+        }
+    }
+}
+
+/// Changes the size an offscreen console reports, as a terminal resized
+/// without an event would, see [`ConsoleActor::offscreen`].
+#[cfg(feature = "tui")]
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SetOffscreenSize {
+    pub width: u16,
+    pub height: u16,
+}
+
+#[cfg(feature = "tui")]
+impl Handler<SetOffscreenSize> for ConsoleActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetOffscreenSize, _: &mut Context<Self>) -> Self::Result {
+        if self.offscreen.is_some() {
+            self.offscreen = Some(Rect::new(0, 0, msg.width, msg.height));
+        }
+    }
+}
+
+/// Returns the last frame drawn by an offscreen console.
+#[cfg(feature = "tui")]
+#[derive(Message)]
+#[rtype(result = "Buffer")]
+pub struct Snapshot;
+
+#[cfg(feature = "tui")]
+impl Handler<Snapshot> for ConsoleActor {
+    type Result = MessageResult<Snapshot>;
+
+    fn handle(&mut self, _: Snapshot, _: &mut Context<Self>) -> Self::Result {
+        MessageResult(self.last_frame.clone())
+    }
+}
+
+/// Returns the text last copied to the clipboard, which the terminal keeps
+/// out of reach.
+#[cfg(feature = "tui")]
+#[derive(Message)]
+#[rtype(result = "Option<String>")]
+pub struct Copied;
+
+#[cfg(feature = "tui")]
+impl Handler<Copied> for ConsoleActor {
+    type Result = Option<String>;
+
+    fn handle(&mut self, _: Copied, _: &mut Context<Self>) -> Self::Result {
+        self.copied.clone()
+    }
+}
+
+#[cfg(all(test, feature = "tui"))]
+mod tests {
+    use super::*;
+
+    /// Moment `seconds` after noon.
+    fn at(seconds: i64) -> DateTime<Local> {
+        let noon = Local.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        noon + chrono::Duration::seconds(seconds)
+    }
+
+    fn panel(lines: &[(&str, OutputKind)]) -> Panel {
+        let mut panel = Panel::new(None, Vec::new());
+        panel.logs = lines
+            .iter()
+            .map(|(line, kind)| (line.to_string(), *kind, at(0)))
+            .collect();
+        panel.sync_lines(80);
+        panel
+    }
+
+    /// Panel of lines printed `seconds` after noon.
+    fn timed_panel(lines: &[(&str, i64)]) -> Panel {
+        let mut panel = Panel::new(None, Vec::new());
+        panel.logs = lines
+            .iter()
+            .map(|(line, seconds)| (line.to_string(), OutputKind::Command, at(*seconds)))
+            .collect();
+        panel.sync_lines(80);
+        panel
+    }
+
+    #[test]
+    fn focuses_the_default_task() {
+        // the console runs its own arbiter
+        System::new().block_on(async {
+            let order = vec!["db".to_string(), "api".to_string()];
+            let console = |task: Option<&str>| {
+                ConsoleActor::new(order.clone(), false)
+                    .offscreen(80, 24)
+                    .focus(task.map(str::to_string))
+            };
+            let focused = console(Some("api"));
+            assert_eq!(focused.idx(), 1);
+            assert_eq!(focused.list_state.selected(), Some(1));
+
+            // a task which is not shown keeps the first one
+            assert_eq!(console(Some("web")).idx(), 0);
+            assert_eq!(console(None).idx(), 0);
+        });
+    }
+
+    #[test]
+    fn fits_the_menu_to_the_terminal() {
+        // fits the titles within its bounds
+        assert_eq!(menu_width(200, 5), MIN_MENU_WIDTH);
+        assert_eq!(menu_width(200, 28), 30);
+        assert_eq!(menu_width(400, 80), MAX_MENU_WIDTH);
+        // leaves half of a narrow terminal to the logs
+        assert_eq!(menu_width(50, 28), 25);
+        assert_eq!(menu_width(30, 5), 15);
+        assert_eq!(menu_width(1, 5), 2);
+    }
+
+    #[test]
+    fn cuts_long_titles() {
+        let cut =
+            |title: &str, max_chars| ellipse_if_too_long(title.into(), max_chars).into_owned();
+        assert_eq!(cut("api", 10), "api");
+        assert_eq!(cut("0123456789", 10), "0123456789");
+        assert_eq!(cut("0123456789a", 10), "0123456...");
+        assert_eq!(cut("tâche-très-longue", 8), "tâche...");
+    }
+
+    #[test]
+    fn locks_panels_to_the_same_moment() {
+        // api prints every second, db rarely and a line wrapped over two rows
+        let mut api = timed_panel(&[("request", 0); 10]);
+        for (second, line) in api.logs.iter_mut().enumerate() {
+            line.2 = at(second as i64);
+        }
+        let mut db = timed_panel(&[("ready", 0), (&"slow query ".repeat(10), 5), ("vacuum", 9)]);
+        assert_eq!(db.line_offsets, vec![0, 1, 1, 2]);
+
+        assert_eq!(api.bottom_timestamp(), Some(at(9)));
+        for panel in [&mut api, &mut db] {
+            panel.lock_to(&at(9));
+            assert_eq!(panel.shift, 0);
+        }
+
+        assert_eq!(api.line_at(&at(6)), Some(6));
+        assert_eq!(db.line_at(&at(6)), Some(1));
+        api.lock_to(&at(6));
+        db.lock_to(&at(6));
+        assert_eq!((api.shift, db.shift), (3, 1));
+        assert_eq!(api.bottom_timestamp(), Some(at(6)));
+        assert_eq!(db.bottom_timestamp(), Some(at(5)));
+
+        // before its first line, a panel shows its top
+        db.lock_to(&at(-1));
+        assert_eq!(db.line_at(&at(-1)), None);
+        assert_eq!(db.shift, 4);
+    }
+
+    #[test]
+    fn steps_time_lock_by_lines_shown() {
+        let mut panel = timed_panel(&[("a", 0), ("b", 2), ("c", 2), ("d", 3), ("e", 7)]);
+        panel.logs[3].1 = OutputKind::Service;
+        panel.cycle_visibility(80);
+
+        // the hidden line and the ones printed at the same time are skipped
+        assert_eq!(panel.step_from(&at(7), true), Some(at(2)));
+        assert_eq!(panel.step_from(&at(2), true), Some(at(0)));
+        assert_eq!(panel.step_from(&at(0), true), None);
+        assert_eq!(panel.step_from(&at(2), false), Some(at(7)));
+        assert_eq!(panel.step_from(&at(1), false), Some(at(2)));
+        assert_eq!(panel.step_from(&at(-1), false), Some(at(0)));
+        assert_eq!(panel.step_from(&at(7), false), None);
+    }
+
+    #[test]
+    fn selection_stays_on_its_lines_as_output_arrives() {
+        let mut panel = panel(&[
+            ("build", OutputKind::Command),
+            ("\x1b[31merror\x1b[0m: missing ;", OutputKind::Command),
+            ("Status: Exited(1)", OutputKind::Service),
+            ("retrying", OutputKind::Command),
+        ]);
+        panel.cycle_visibility(80);
+        panel.start_selection();
+        assert_eq!(
+            panel.selection,
+            Some(Selection {
+                anchor: 3,
+                cursor: 3
+            })
+        );
+        // the hidden service line is skipped
+        panel.move_selection(true);
+        assert_eq!(panel.selection.unwrap().cursor, 1);
+
+        panel
+            .logs
+            .push(("done".to_string(), OutputKind::Command, at(0)));
+        panel.sync_lines(80);
+        assert_eq!(panel.selected_text().unwrap(), "error: missing ;\nretrying");
+        panel.move_selection(false);
+        panel.move_selection(false);
+        assert_eq!(panel.selection.unwrap().cursor, 4);
+        assert_eq!(panel.selected_text().unwrap(), "retrying\ndone");
+    }
+
+    #[test]
+    fn types_keys_as_a_terminal() {
+        let key = |code, modifiers| key_bytes(&KeyEvent::new(code, modifiers));
+        assert_eq!(
+            key(KeyCode::Char('q'), KeyModifiers::NONE),
+            Some(b"q".to_vec())
+        );
+        assert_eq!(
+            key(KeyCode::Char('é'), KeyModifiers::NONE),
+            Some("é".into())
+        );
+        assert_eq!(
+            key(KeyCode::Char('A'), KeyModifiers::SHIFT),
+            Some(b"A".to_vec())
+        );
+        assert_eq!(
+            key(KeyCode::Char('c'), KeyModifiers::CONTROL),
+            Some(vec![3])
+        );
+        assert_eq!(
+            key(KeyCode::Enter, KeyModifiers::NONE),
+            Some(b"\n".to_vec())
+        );
+        assert_eq!(
+            key(KeyCode::Up, KeyModifiers::NONE),
+            Some(b"\x1b[A".to_vec())
+        );
+        assert_eq!(key(KeyCode::F(1), KeyModifiers::NONE), None);
+    }
+}
@@ -0,0 +1,201 @@
+use actix::prelude::*;
+use indexmap::IndexMap;
+use tokio::io::{AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc::{self, UnboundedSender};
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::daemon::{DaemonPaths, Listener, Request, Stream};
+use crate::file_mode::{self, FileMode};
+
+use super::console::{
+    Attach, ConsoleActor, ConsoleLine, StateChanged, TermEvent, UnregisterPanel, WatchStates,
+};
+use super::state::TaskState;
+
+/// Answers the requests of `whiz status`, `whiz stop` and `whiz attach` to
+/// a detached whiz, see [`crate::daemon`].
+pub struct ControlActor {
+    paths: DaemonPaths,
+    listener: Option<Listener>,
+    console: Addr<ConsoleActor>,
+    /// Told by the console, see [`WatchStates`].
+    states: IndexMap<String, TaskState>,
+}
+
+impl ControlActor {
+    /// Binds the control socket and writes the pid file, both given `mode`.
+    pub fn new(
+        paths: DaemonPaths,
+        console: Addr<ConsoleActor>,
+        mode: Option<FileMode>,
+    ) -> std::io::Result<Self> {
+        std::fs::create_dir_all(&paths.dir)?;
+        let listener = Listener::bind(&paths.socket)?;
+        file_mode::set_path_mode(&paths.socket, mode)?;
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        file_mode::open(&options, &paths.pid, mode)?
+            .write_all(std::process::id().to_string().as_bytes())?;
+        Ok(Self {
+            paths,
+            listener: Some(listener),
+            console,
+            states: IndexMap::new(),
+        })
+    }
+}
+
+impl Actor for ControlActor {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Context<Self>) {
+        // the states told so far are known before answering anything
+        let watch = self.console.send(WatchStates {
+            changed: ctx.address().recipient(),
+            removed: ctx.address().recipient(),
+        });
+        ctx.wait(actix::fut::wrap_future(watch).map(|_, _, _| ()));
+
+        let listener = self.listener.take().unwrap();
+        let control = ctx.address();
+        let console = self.console.clone();
+        let session: Arc<Path> = self.paths.session.clone().into();
+        // keeps the actor alive as long as whiz runs
+        ctx.spawn(
+            async move {
+                while let Ok(stream) = listener.accept().await {
+                    actix::spawn(serve(
+                        stream,
+                        control.clone(),
+                        console.clone(),
+                        session.clone(),
+                    ));
+                }
+            }
+            .into_actor(self),
+        );
+    }
+
+    fn stopped(&mut self, _: &mut Context<Self>) {
+        self.paths.cleanup();
+    }
+}
+
+impl Handler<StateChanged> for ControlActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: StateChanged, _: &mut Context<Self>) -> Self::Result {
+        self.states.insert(msg.panel_name, msg.state);
+    }
+}
+
+impl Handler<UnregisterPanel> for ControlActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: UnregisterPanel, _: &mut Context<Self>) -> Self::Result {
+        self.states.shift_remove(&msg.name);
+    }
+}
+
+/// Returns the state of each task, for `whiz status`.
+#[derive(Message)]
+#[rtype(result = "Vec<(String, TaskState)>")]
+struct GetStatus;
+
+impl Handler<GetStatus> for ControlActor {
+    type Result = MessageResult<GetStatus>;
+
+    fn handle(&mut self, _: GetStatus, _: &mut Context<Self>) -> Self::Result {
+        MessageResult(
+            self.states
+                .iter()
+                .map(|(name, state)| (name.clone(), *state))
+                .collect(),
+        )
+    }
+}
+
+/// Hands the recorded lines over to an attached client.
+struct Forwarder(UnboundedSender<String>);
+
+impl Actor for Forwarder {
+    type Context = Context<Self>;
+}
+
+impl Handler<ConsoleLine> for Forwarder {
+    type Result = ();
+
+    fn handle(&mut self, msg: ConsoleLine, ctx: &mut Context<Self>) -> Self::Result {
+        // the client is gone
+        if self.0.send(msg.0).is_err() {
+            ctx.stop();
+        }
+    }
+}
+
+async fn serve(
+    stream: Stream,
+    control: Addr<ControlActor>,
+    console: Addr<ConsoleActor>,
+    session: Arc<Path>,
+) {
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut line = String::new();
+    if BufReader::new(reader).read_line(&mut line).await.is_err() {
+        return;
+    }
+
+    match Request::parse(&line) {
+        Some(Request::Status) => {
+            let Ok(states) = control.send(GetStatus).await else {
+                return;
+            };
+            for (name, state) in states {
+                let _ = writer
+                    .write_all(format!("{name}: {state}\n").as_bytes())
+                    .await;
+            }
+        }
+        Some(Request::Stop) => {
+            let _ = writer.write_all(b"stopping\n").await;
+            let _ = writer.shutdown().await;
+            console.do_send(TermEvent::quit());
+        }
+        Some(Request::Attach) => {
+            let _ = attach(&mut writer, &console, session.to_path_buf()).await;
+        }
+        None => {
+            let _ = writer.write_all(b"unknown request\n").await;
+        }
+    }
+}
+
+/// Streams the recording of the session: the lines written so far from
+/// the file, then the new ones as they are recorded.
+async fn attach<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    console: &Addr<ConsoleActor>,
+    session: PathBuf,
+) -> std::io::Result<()> {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let forwarder = Forwarder(sender).start();
+    let Ok(Some(backfill)) = console.send(Attach(forwarder.recipient())).await else {
+        return writer.write_all(b"the session is not recorded\n").await;
+    };
+
+    let mut lines = BufReader::new(tokio::fs::File::open(session).await?).lines();
+    for _ in 0..backfill {
+        let Some(line) = lines.next_line().await? else {
+            break;
+        };
+        writer.write_all(format!("{line}\n").as_bytes()).await?;
+    }
+    while let Some(line) = receiver.recv().await {
+        writer.write_all(format!("{line}\n").as_bytes()).await?;
+    }
+    Ok(())
+}
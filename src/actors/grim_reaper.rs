@@ -1,18 +1,47 @@
 use std::collections::{HashMap, HashSet};
+use std::time::Duration;
 
 use actix::prelude::*;
 use subprocess::ExitStatus;
 
+use crate::actors::console::StateChanged;
+use crate::actors::state::describe_exit;
+use crate::exit::ExitCode;
+
 /// This is responsible for exiting whiz when all tasks are done.
 /// It `send`s it's targets `PermaDeathInvite` which and when all
 /// have been `rsvp`d, terminates the Actix runtime and thus the program.
+///
+/// Invitees only answer once their task has finished, dependents reloaded,
+/// so that tasks which are not started yet keep the program alive.
+///
+/// With a settle delay, the invitees stay up once they answered and keep
+/// telling their `StateChanged`: one starting again before the delay is
+/// over revokes its answer, and the reaper waits for it to finish again.
 pub struct GrimReaperActor {
     live_invites: HashSet<String>,
     non_zero_deaths: HashMap<String, ExitStatus>,
+    settle: Duration,
+    /// Exit once the settle delay is over, see [`StateChanged`].
+    reap_handle: Option<SpawnHandle>,
 }
 
 impl GrimReaperActor {
     pub async fn start_new<T>(targets: HashMap<String, Addr<T>>) -> anyhow::Result<()>
+    where
+        T: Actor + Handler<PermaDeathInvite>,
+        <T as actix::Actor>::Context: actix::dev::ToEnvelope<T, PermaDeathInvite>,
+    {
+        Self::start_with_settle(targets, Duration::ZERO).await
+    }
+
+    /// Same as [`Self::start_new`] but waits for `settle` once all the
+    /// invites are accepted before exiting, the invitees starting again in
+    /// the meantime being waited for as well.
+    pub async fn start_with_settle<T>(
+        targets: HashMap<String, Addr<T>>,
+        settle: Duration,
+    ) -> anyhow::Result<()>
     where
         T: Actor + Handler<PermaDeathInvite>,
         <T as actix::Actor>::Context: actix::dev::ToEnvelope<T, PermaDeathInvite>,
@@ -20,12 +49,15 @@ impl GrimReaperActor {
         let reaper_addr = GrimReaperActor {
             live_invites: targets.keys().cloned().collect(),
             non_zero_deaths: Default::default(),
+            settle,
+            reap_handle: None,
         }
         .start();
         for target in targets.values() {
             target
                 .send(PermaDeathInvite {
                     reaper_addr: reaper_addr.clone(),
+                    settles: !settle.is_zero(),
                 })
                 .await?;
         }
@@ -37,13 +69,28 @@ impl Actor for GrimReaperActor {
     type Context = Context<Self>;
 }
 
-#[derive(Message)]
+#[derive(Message, Clone)]
 #[rtype(result = "()")]
 pub struct PermaDeathInvite {
     reaper_addr: Addr<crate::actors::grim_reaper::GrimReaperActor>,
+    /// Whether the invitee stays up once it answered, see
+    /// [`Self::state_changed`].
+    settles: bool,
 }
 
 impl PermaDeathInvite {
+    /// Whether the answer can be revoked, the invitee staying up until the
+    /// settle delay is over.
+    pub fn settles(&self) -> bool {
+        self.settles
+    }
+
+    /// Tells the reaper the new state of the invitee, which waits for the
+    /// next answer of one that answered and runs again.
+    pub fn state_changed(&self, msg: StateChanged) {
+        self.reaper_addr.do_send(msg);
+    }
+
     pub fn rsvp<T, C>(self, actor_name: String, exit_status: ExitStatus, invitee_cx: &mut C)
     where
         T: Actor<Context = C> + Handler<PermaDeathInvite>,
@@ -56,7 +103,9 @@ impl PermaDeathInvite {
                 exit_status,
             })
             .expect("GrimReaperActor mailbox is closed or its mailbox full");
-        invitee_cx.stop();
+        if !self.settles {
+            invitee_cx.stop();
+        }
     }
 }
 
@@ -70,26 +119,76 @@ pub struct InviteAccepted {
 impl Handler<InviteAccepted> for GrimReaperActor {
     type Result = ();
 
-    fn handle(&mut self, evt: InviteAccepted, _: &mut Context<Self>) -> Self::Result {
+    fn handle(&mut self, evt: InviteAccepted, ctx: &mut Context<Self>) -> Self::Result {
         assert!(self.live_invites.remove(&evt.actor_name));
         if !evt.exit_status.success() {
             self.non_zero_deaths.insert(evt.actor_name, evt.exit_status);
         }
         if self.live_invites.is_empty() {
-            if let Some((_op_name, status)) = self.non_zero_deaths.iter().next() {
-                // exit with the error code of the first aberrant task
-                let code = match *status {
-                    ExitStatus::Exited(code) => code as i32,
-                    ExitStatus::Other(code) => code,
-                    ExitStatus::Signaled(code) => code as i32,
-                    ExitStatus::Undetermined => {
-                        eprintln!("ERROR: task {_op_name} exited with Undetermined status");
-                        1
-                    }
-                };
-                System::current().stop_with_code(code);
-            }
-            System::current().stop();
+            self.reap_handle = Some(ctx.run_later(self.settle, |act, _| act.reap()));
+        }
+    }
+}
+
+impl Handler<StateChanged> for GrimReaperActor {
+    type Result = ();
+
+    fn handle(&mut self, evt: StateChanged, ctx: &mut Context<Self>) -> Self::Result {
+        // only an invitee which answered revokes it by running again
+        if evt.state.is_finished() || self.live_invites.contains(&evt.panel_name) {
+            return;
+        }
+        if let Some(handle) = self.reap_handle.take() {
+            ctx.cancel_future(handle);
         }
+        self.non_zero_deaths.remove(&evt.panel_name);
+        self.live_invites.insert(evt.panel_name);
+    }
+}
+
+impl GrimReaperActor {
+    fn reap(&self) {
+        let mut deaths = Vec::from_iter(&self.non_zero_deaths);
+        deaths.sort_by_key(|(op_name, _)| *op_name);
+        for (op_name, status) in deaths {
+            eprintln!("task {op_name} {}", describe_exit(*status));
+        }
+        let code = match self.non_zero_deaths.is_empty() {
+            true => ExitCode::Success,
+            false => ExitCode::TaskFailed,
+        };
+        System::current().stop_with_code(code.code());
+    }
+}
+
+/// Maps the exit status of a primary task to the code whiz exits with,
+/// `128 + N` when killed by the signal `N` as shells do.
+pub fn exit_code(op_name: &str, status: &ExitStatus) -> i32 {
+    shell_code(status).unwrap_or_else(|| {
+        eprintln!("ERROR: task {op_name} exited with Undetermined status");
+        1
+    })
+}
+
+/// Maps an exit status to the code a shell tells, `128 + N` when killed
+/// by the signal `N`, unless it is undetermined.
+pub fn shell_code(status: &ExitStatus) -> Option<i32> {
+    match *status {
+        ExitStatus::Exited(code) => Some(code as i32),
+        ExitStatus::Other(code) => Some(code),
+        ExitStatus::Signaled(signal) => Some(128 + signal as i32),
+        ExitStatus::Undetermined => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_signals_as_shells_do() {
+        assert_eq!(exit_code("app", &ExitStatus::Exited(3)), 3);
+        assert_eq!(exit_code("app", &ExitStatus::Signaled(9)), 137);
+        assert_eq!(exit_code("app", &ExitStatus::Signaled(15)), 143);
     }
 }
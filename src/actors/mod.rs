@@ -1,4 +1,9 @@
+pub mod a11y;
 pub mod command;
 pub mod console;
+pub mod control;
 pub mod grim_reaper;
+pub mod reloader;
+pub mod slots;
+pub mod state;
 pub mod watcher;
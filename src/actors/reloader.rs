@@ -0,0 +1,277 @@
+use std::collections::{HashMap, HashSet};
+
+use actix::prelude::*;
+use anyhow::{anyhow, bail, Result};
+
+use crate::config::ops::OpsDiff;
+use crate::config::{Config, ConfigBuilder};
+use crate::exec::ExecBuilder;
+
+use super::command::prelude::*;
+use super::command::{CommandActor, Nexts, Peers, PoisonPill, Reload, UpdateTask};
+use super::console::{Output, OutputKind, UnregisterPanel};
+use super::watcher::ReloadAllOn;
+
+/// Applies the changes of the config file to the tasks, see
+/// [`crate::config::ConfigInner::diff`]:
+///
+/// - removed tasks are stopped and their panels closed,
+/// - added tasks are spawned,
+/// - changed tasks get their new definition,
+///
+/// and each added or changed task is reloaded, unless one of its upstream
+/// tasks is and will reload it once done. Unchanged tasks keep running.
+///
+/// [`Restart`] respawns every task instead, their panels being kept.
+pub struct ConfigReloaderActor {
+    builder: ConfigBuilder,
+    config: Config,
+    commands: HashMap<String, Addr<CommandActor>>,
+    console: Addr<ConsoleAct>,
+    watcher: Addr<WatcherAct>,
+    verbose: bool,
+    watch: bool,
+}
+
+impl ConfigReloaderActor {
+    pub fn new(
+        builder: ConfigBuilder,
+        config: Config,
+        commands: HashMap<String, Addr<CommandActor>>,
+        console: Addr<ConsoleAct>,
+        watcher: Addr<WatcherAct>,
+    ) -> Self {
+        Self {
+            builder,
+            config,
+            commands,
+            console,
+            watcher,
+            verbose: false,
+            watch: true,
+        }
+    }
+
+    pub fn verbose(self, toggle: bool) -> Self {
+        Self {
+            verbose: toggle,
+            ..self
+        }
+    }
+
+    pub fn globally_enable_watch(self, toggle: bool) -> Self {
+        Self {
+            watch: toggle,
+            ..self
+        }
+    }
+
+    /// Logs to the panel of the first task, as the config has none.
+    fn log_info(&self, log: String) {
+        if let Some(op_name) = self.config.active_ops().keys().next() {
+            self.console
+                .do_send(Output::now(op_name.clone(), log, OutputKind::Service));
+        }
+    }
+
+    fn apply(
+        &mut self,
+        config: Config,
+        diff: OpsDiff,
+        mut exec_builders: HashMap<String, ExecBuilder>,
+    ) -> Result<()> {
+        for op_name in &diff.removed {
+            if let Some(command) = self.commands.remove(op_name) {
+                command.do_send(PoisonPill);
+            }
+            self.console.do_send(UnregisterPanel {
+                name: op_name.clone(),
+            });
+        }
+
+        let active_ops = config.active_ops();
+        for op_name in &diff.added {
+            let task = active_ops.get(op_name).unwrap();
+            let actor = CommandActor::new(
+                task.extend(op_name.clone(), &config),
+                self.console.clone(),
+                self.watcher.clone(),
+                Vec::new(),
+                self.verbose,
+                self.watch,
+                exec_builders.remove(op_name).unwrap(),
+            )
+            .start();
+            self.commands.insert(op_name.clone(), actor);
+        }
+
+        // rewired before anything reloads, so that dependents are notified
+        for (op_name, nexts) in config.build_dag()? {
+            let nexts = nexts.iter().map(|e| self.commands[e].clone()).collect();
+            self.commands[&op_name].do_send(Nexts(nexts));
+        }
+
+        for op_name in &diff.changed {
+            let task = active_ops.get(op_name).unwrap();
+            self.commands[op_name].do_send(UpdateTask {
+                operator: task.extend(op_name.clone(), &config),
+                exec_builder: exec_builders.remove(op_name).unwrap(),
+            });
+        }
+
+        for (op_name, _) in active_ops.iter().filter(|(_, task)| task.primary) {
+            let peers = self
+                .commands
+                .iter()
+                .filter(|(other, _)| *other != op_name)
+                .map(|(_, addr)| addr.clone())
+                .collect();
+            self.commands[op_name].do_send(Peers(peers));
+        }
+
+        if self.watch {
+            let roots = active_ops
+                .iter()
+                .filter(|(_, task)| task.depends_on.resolve().is_empty())
+                .map(|(op_name, _)| self.commands[op_name].clone())
+                .collect();
+            let tasks = active_ops
+                .keys()
+                .map(|op_name| (op_name.clone(), self.commands[op_name].clone()))
+                .collect();
+            self.watcher
+                .do_send(ReloadAllOn::new(&config, roots, tasks)?);
+        }
+
+        let reloading: HashSet<&String> = diff.added.iter().chain(&diff.changed).collect();
+        for op_name in &reloading {
+            let task = active_ops.get(*op_name).unwrap();
+            if !task
+                .depends_on
+                .resolve()
+                .iter()
+                .any(|dep| reloading.contains(dep))
+            {
+                self.commands[*op_name].do_send(Reload::Config);
+            }
+        }
+
+        self.config = config;
+        Ok(())
+    }
+}
+
+impl Actor for ConfigReloaderActor {
+    type Context = Context<Self>;
+}
+
+/// New config, its diff with the previous one, the diff to apply and the
+/// exec of the tasks it spawns or updates.
+type Prepared = (Config, OpsDiff, OpsDiff, HashMap<String, ExecBuilder>);
+
+/// Builds the config from the file and the exec of the tasks to spawn or
+/// update to move from `previous` to it, checking it first.
+async fn prepare(builder: ConfigBuilder, previous: Config, restart: bool) -> Result<Prepared> {
+    let config = builder.build()?;
+    if config.active_ops().is_empty() {
+        bail!("no task to run");
+    }
+    // checked before anything is applied
+    config.build_dag()?;
+    ReloadAllOn::new(&config, Vec::new(), HashMap::new())?;
+
+    let diff = previous.diff(&config);
+    let active_ops = config.active_ops();
+    // every task is spawned again on restart
+    let applied = match restart {
+        true => OpsDiff {
+            added: active_ops.keys().cloned().collect(),
+            removed: diff.removed.clone(),
+            ..OpsDiff::default()
+        },
+        false => diff.clone(),
+    };
+    let mut exec_builders = HashMap::new();
+    for op_name in applied.added.iter().chain(&applied.changed) {
+        let task = active_ops.get(op_name).unwrap();
+        let exec_builder = ExecBuilder::new(op_name, task, &config)
+            .await
+            .map_err(|err| anyhow!("task {op_name}: {err}"))?;
+        exec_builders.insert(op_name.clone(), exec_builder);
+    }
+    Ok((config, diff, applied, exec_builders))
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ConfigChanged;
+
+impl Handler<ConfigChanged> for ConfigReloaderActor {
+    type Result = AtomicResponse<Self, ()>;
+
+    fn handle(&mut self, _: ConfigChanged, _: &mut Context<Self>) -> Self::Result {
+        let fut = prepare(self.builder.clone(), self.config.clone(), false);
+
+        // changes are applied one after the other
+        AtomicResponse::new(Box::pin(fut.into_actor(self).map(
+            |res, act, _| match res {
+                Ok((config, diff, applied, exec_builders)) => {
+                    let summary = (!diff.is_empty()).then(|| diff.to_string());
+                    match (act.apply(config, applied, exec_builders), summary) {
+                        (Ok(()), Some(summary)) => {
+                            act.log_info(format!("CONFIG: reloaded, {summary}"))
+                        }
+                        (Ok(()), None) => {}
+                        (Err(err), _) => act.log_info(format!(
+                            "CONFIG: cannot apply: {err}, press Ctrl-r to restart every task"
+                        )),
+                    }
+                }
+                Err(err) => act.log_info(format!("CONFIG: keeping the previous one: {err}")),
+            },
+        )))
+    }
+}
+
+/// Stops every task and spawns them again from the config file, as
+/// restarting whiz would, while keeping the panels of the tasks still
+/// defined.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Restart;
+
+impl Handler<Restart> for ConfigReloaderActor {
+    type Result = AtomicResponse<Self, ()>;
+
+    fn handle(&mut self, _: Restart, _: &mut Context<Self>) -> Self::Result {
+        let prepared = prepare(self.builder.clone(), self.config.clone(), true);
+        let commands: Vec<Addr<CommandActor>> = self.commands.values().cloned().collect();
+
+        let fut = async move {
+            let prepared: Prepared = prepared.await?;
+            // stopped before the new ones start, so that the last state
+            // shown in each panel is the one of the new task
+            for command in commands {
+                let _ = command.send(PoisonPill).await;
+            }
+            Ok::<_, anyhow::Error>(prepared)
+        };
+
+        AtomicResponse::new(Box::pin(fut.into_actor(self).map(
+            |res, act, _| match res {
+                Ok((config, diff, applied, exec_builders)) => {
+                    act.commands.clear();
+                    let summary = match diff.is_empty() {
+                        true => "same config".to_string(),
+                        false => diff.to_string(),
+                    };
+                    match act.apply(config, applied, exec_builders) {
+                        Ok(()) => act.log_info(format!("CONFIG: restarted, {summary}")),
+                        Err(err) => act.log_info(format!("CONFIG: cannot restart: {err}")),
+                    }
+                }
+                Err(err) => act.log_info(format!("CONFIG: cannot restart: {err}")),
+            },
+        )))
+    }
+}
@@ -0,0 +1,174 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashSet};
+
+use actix::prelude::*;
+
+/// Caps how many tasks run at once (`--max-parallel`). Tasks `Acquire` a
+/// slot before spawning each of their runs and `Release` it once they exit.
+///
+/// Waiting tasks get the slots by the number of tasks depending on them,
+/// as they unblock the most work, and then in the order they asked.
+pub struct SlotsActor {
+    max: usize,
+    running: HashSet<String>,
+    queue: BinaryHeap<Queued>,
+    /// Order of the next task to queue.
+    seq: usize,
+}
+
+impl SlotsActor {
+    pub fn new(max: usize) -> Self {
+        Self {
+            max,
+            running: HashSet::default(),
+            queue: BinaryHeap::default(),
+            seq: 0,
+        }
+    }
+
+    /// Grants the free slots to the first tasks of the queue still alive.
+    fn grant(&mut self) {
+        while self.running.len() < self.max {
+            let Some(queued) = self.queue.pop() else {
+                return;
+            };
+            if queued.addr.try_send(Slot::Granted).is_ok() {
+                self.running.insert(queued.name);
+            }
+        }
+    }
+}
+
+impl Actor for SlotsActor {
+    type Context = Context<Self>;
+}
+
+struct Queued {
+    name: String,
+    priority: usize,
+    seq: usize,
+    addr: Recipient<Slot>,
+}
+
+impl Queued {
+    fn key(&self) -> (usize, Reverse<usize>) {
+        (self.priority, Reverse(self.seq))
+    }
+}
+
+impl PartialEq for Queued {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Queued {}
+
+impl PartialOrd for Queued {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Queued {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+/// Asks a slot for the task `name`, answered with [`Slot`].
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Acquire {
+    pub name: String,
+    /// Number of tasks depending on the task.
+    pub priority: usize,
+    pub addr: Recipient<Slot>,
+}
+
+impl Handler<Acquire> for SlotsActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: Acquire, _: &mut Context<Self>) -> Self::Result {
+        // a task asking again keeps its place
+        if self.queue.iter().any(|queued| queued.name == msg.name) {
+            return;
+        }
+        let name = msg.name.clone();
+        self.queue.push(Queued {
+            name: msg.name,
+            priority: msg.priority,
+            seq: self.seq,
+            addr: msg.addr.clone(),
+        });
+        self.seq += 1;
+        self.grant();
+        if !self.running.contains(&name) {
+            msg.addr.do_send(Slot::Queued);
+        }
+    }
+}
+
+/// Frees the slot of the task `name`.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Release {
+    pub name: String,
+}
+
+impl Handler<Release> for SlotsActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: Release, _: &mut Context<Self>) -> Self::Result {
+        self.running.remove(&msg.name);
+        self.grant();
+    }
+}
+
+#[derive(Message, Debug, PartialEq)]
+#[rtype(result = "()")]
+pub enum Slot {
+    /// Every slot is taken, the task waits for one.
+    Queued,
+    /// The task can spawn, until it releases the slot.
+    Granted,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Task;
+
+    impl Actor for Task {
+        type Context = Context<Self>;
+    }
+
+    impl Handler<Slot> for Task {
+        type Result = ();
+
+        fn handle(&mut self, _: Slot, _: &mut Context<Self>) -> Self::Result {}
+    }
+
+    #[actix::test]
+    async fn prefers_most_depended_on_then_first_queued() {
+        let addr = Task.start().recipient();
+        let queued = |name: &str, priority, seq| Queued {
+            name: name.to_string(),
+            priority,
+            seq,
+            addr: addr.clone(),
+        };
+        let mut queue = BinaryHeap::from(vec![
+            queued("lint", 0, 0),
+            queued("db", 3, 1),
+            queued("test", 0, 2),
+            queued("build", 3, 3),
+        ]);
+        let mut order = Vec::new();
+        while let Some(queued) = queue.pop() {
+            order.push(queued.name);
+        }
+        assert_eq!(order, vec!["db", "build", "lint", "test"]);
+    }
+}
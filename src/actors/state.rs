@@ -0,0 +1,227 @@
+use std::fmt;
+use std::time::Duration;
+
+use subprocess::ExitStatus;
+
+/// Lifecycle of a task, owned by its `CommandActor` and broadcast through
+/// `StateChanged` to the console, which forwards it to the control socket,
+/// and to the grim reaper.
+///
+/// A process goes `Starting`, `Running`, `Stopping` and `Exited`, possibly
+/// skipping `Running` or `Stopping`. Only idle tasks (`Pending`, `Exited`,
+/// `Blocked`) can wait on their upstream or start again, so that a live
+/// process is always stopped first.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TaskState {
+    /// Not triggered yet.
+    Pending,
+    /// Waiting for some upstream tasks to finish.
+    WaitingOnUpstream,
+    /// Ready to start, waiting for an execution slot (`--max-parallel`).
+    Queued,
+    /// The process is being spawned.
+    Starting,
+    Running,
+    /// The process is being terminated or its output has closed.
+    Stopping,
+    /// Killed tasks exit with [`ExitStatus::Undetermined`].
+    Exited(ExitStatus),
+    /// The process could not be spawned, or an upstream task it needs to
+    /// succeed failed.
+    Blocked,
+}
+
+impl TaskState {
+    pub fn can_become(&self, next: &TaskState) -> bool {
+        use TaskState::*;
+
+        matches!(
+            (self, next),
+            (Pending | Queued | Exited(_) | Blocked, WaitingOnUpstream)
+                | (Pending | WaitingOnUpstream | Exited(_) | Blocked, Queued)
+                | (
+                    Pending | WaitingOnUpstream | Queued | Exited(_) | Blocked,
+                    Starting
+                )
+                | (Starting, Running)
+                | (Running, Stopping)
+                | (Starting | Running | Stopping, Exited(_))
+                | (Starting | WaitingOnUpstream | Queued | Exited(_), Blocked)
+        )
+    }
+
+    /// Returns whether the task is done until something triggers it again.
+    pub fn is_finished(&self) -> bool {
+        matches!(self, TaskState::Exited(_) | TaskState::Blocked)
+    }
+
+    /// Returns whether a process is alive, or about to be.
+    pub fn is_active(&self) -> bool {
+        matches!(
+            self,
+            TaskState::Starting | TaskState::Running | TaskState::Stopping
+        )
+    }
+}
+
+/// How a run of a task ended, which its dependents get in their
+/// environment, see [`crate::exec::upstream_env`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Finished {
+    pub status: ExitStatus,
+    pub duration: Duration,
+}
+
+/// Tells how a task exited, e.g. `exited 1` or
+/// `killed by signal 9 (SIGKILL)`.
+pub fn describe_exit(status: ExitStatus) -> String {
+    match status {
+        ExitStatus::Exited(code) => format!("exited {code}"),
+        ExitStatus::Signaled(signal) => killed_by(signal),
+        ExitStatus::Other(code) => format!("exited ({code})"),
+        ExitStatus::Undetermined => "killed".to_string(),
+    }
+}
+
+/// Tells a task was killed by `signal`, named when it is a common one, e.g.
+/// `killed by signal 9 (SIGKILL)` after the OOM killer.
+pub fn killed_by(signal: u8) -> String {
+    match signal_name(signal) {
+        Some(name) => format!("killed by signal {signal} ({name})"),
+        None => format!("killed by signal {signal}"),
+    }
+}
+
+/// Returns the name of a common signal, whose number depends on the
+/// platform.
+#[cfg(unix)]
+pub fn signal_name(signal: u8) -> Option<&'static str> {
+    let name = match signal as libc::c_int {
+        libc::SIGHUP => "SIGHUP",
+        libc::SIGINT => "SIGINT",
+        libc::SIGQUIT => "SIGQUIT",
+        libc::SIGILL => "SIGILL",
+        libc::SIGTRAP => "SIGTRAP",
+        libc::SIGABRT => "SIGABRT",
+        libc::SIGBUS => "SIGBUS",
+        libc::SIGFPE => "SIGFPE",
+        libc::SIGKILL => "SIGKILL",
+        libc::SIGUSR1 => "SIGUSR1",
+        libc::SIGSEGV => "SIGSEGV",
+        libc::SIGUSR2 => "SIGUSR2",
+        libc::SIGPIPE => "SIGPIPE",
+        libc::SIGALRM => "SIGALRM",
+        libc::SIGTERM => "SIGTERM",
+        libc::SIGXCPU => "SIGXCPU",
+        libc::SIGXFSZ => "SIGXFSZ",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// Processes are not killed by signals outside of Unix.
+#[cfg(not(unix))]
+pub fn signal_name(_: u8) -> Option<&'static str> {
+    None
+}
+
+impl fmt::Display for TaskState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TaskState::Pending => write!(f, "pending"),
+            TaskState::WaitingOnUpstream => write!(f, "waiting on upstream"),
+            TaskState::Queued => write!(f, "queued"),
+            TaskState::Starting => write!(f, "starting"),
+            TaskState::Running => write!(f, "running"),
+            TaskState::Stopping => write!(f, "stopping"),
+            TaskState::Exited(status) => write!(f, "exited ({status:?})"),
+            TaskState::Blocked => write!(f, "blocked"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use TaskState::*;
+
+    const STATES: [TaskState; 9] = [
+        Pending,
+        WaitingOnUpstream,
+        Queued,
+        Starting,
+        Running,
+        Stopping,
+        Exited(ExitStatus::Exited(0)),
+        Exited(ExitStatus::Exited(1)),
+        Blocked,
+    ];
+
+    fn allowed(from: TaskState) -> Vec<TaskState> {
+        STATES
+            .iter()
+            .filter(|next| from.can_become(next))
+            .copied()
+            .collect()
+    }
+
+    #[test]
+    fn transition_table() {
+        let exits = [Exited(ExitStatus::Exited(0)), Exited(ExitStatus::Exited(1))];
+
+        assert_eq!(allowed(Pending), vec![WaitingOnUpstream, Queued, Starting]);
+        assert_eq!(allowed(WaitingOnUpstream), vec![Queued, Starting, Blocked]);
+        assert_eq!(allowed(Queued), vec![WaitingOnUpstream, Starting, Blocked]);
+        assert_eq!(
+            allowed(Starting),
+            [&[Running][..], &exits, &[Blocked]].concat()
+        );
+        assert_eq!(allowed(Running), [&[Stopping][..], &exits].concat());
+        assert_eq!(allowed(Stopping), exits.to_vec());
+        for exit in exits {
+            assert_eq!(
+                allowed(exit),
+                vec![WaitingOnUpstream, Queued, Starting, Blocked],
+                "from {exit}"
+            );
+        }
+        assert_eq!(allowed(Blocked), vec![WaitingOnUpstream, Queued, Starting]);
+    }
+
+    #[test]
+    fn nothing_goes_back_to_pending() {
+        for state in STATES {
+            assert!(!state.can_become(&Pending), "{state} -> pending");
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn names_common_signals() {
+        assert_eq!(
+            describe_exit(ExitStatus::Signaled(libc::SIGKILL as u8)),
+            "killed by signal 9 (SIGKILL)"
+        );
+        assert_eq!(
+            killed_by(libc::SIGSEGV as u8),
+            "killed by signal 11 (SIGSEGV)"
+        );
+        assert_eq!(
+            killed_by(libc::SIGTERM as u8),
+            "killed by signal 15 (SIGTERM)"
+        );
+        assert_eq!(killed_by(libc::SIGINT as u8), "killed by signal 2 (SIGINT)");
+        assert_eq!(killed_by(64), "killed by signal 64");
+        assert_eq!(describe_exit(ExitStatus::Exited(1)), "exited 1");
+    }
+
+    #[test]
+    fn live_process_must_stop_first() {
+        for state in [Running, Stopping] {
+            assert!(!state.can_become(&WaitingOnUpstream));
+            assert!(!state.can_become(&Queued));
+            assert!(!state.can_become(&Starting));
+            assert!(!state.can_become(&Blocked));
+        }
+    }
+}
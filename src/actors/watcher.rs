@@ -1,64 +1,228 @@
 use actix::prelude::*;
 
-use globset::GlobSet;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+#[cfg(feature = "watch")]
 use ignore::gitignore::GitignoreBuilder;
+#[cfg(feature = "watch")]
 use notify::event::ModifyKind;
+#[cfg(feature = "watch")]
 use notify::{recommended_watcher, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
-use std::collections::HashSet;
+use path_absolutize::*;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+#[cfg(feature = "watch")]
+use std::time::{Duration, Instant};
 
-use super::command::{CommandActor, Reload};
+use super::command::CommandActor;
+#[cfg(feature = "watch")]
+use super::command::Reload;
+use super::reloader::ConfigChanged;
+use crate::config::watch_events::WatchEventKind;
+use crate::config::ConfigInner;
 
+/// Watches `root` (the base dir unless a watch root is set) and reloads the
+/// tasks whose globs match the changed files. Without the `watch` feature,
+/// globs are accepted but never fire.
+#[cfg_attr(not(feature = "watch"), allow(dead_code))]
 pub struct WatcherActor {
+    #[cfg(feature = "watch")]
     watcher: Option<RecommendedWatcher>,
     globs: Vec<WatchGlob>,
-    base_dir: Arc<Path>,
+    reload_all: Option<ReloadAllOn>,
+    config: Option<WatchConfig>,
+    root: Arc<Path>,
+    /// Never watches any file, see [`WatcherActor::noop`].
+    noop: bool,
+    /// Kinds of changes reloading the tasks which set none, see
+    /// [`WatchGlob::events`].
+    events: Vec<WatchEventKind>,
     // List of file paths to ignore on the watcher
     ignore: HashSet<PathBuf>,
+    /// When each reload file was last touched, see [`reload_file`].
+    #[cfg(feature = "watch")]
+    touched: HashMap<PathBuf, Instant>,
+    /// Window the events are buffered for, see [`WatcherActor::debounce`].
+    #[cfg(feature = "watch")]
+    debounce: Option<Duration>,
+    /// Events of the current window.
+    #[cfg(feature = "watch")]
+    pending: Vec<Event>,
+    /// Where the tasks the changes would reload are written instead of
+    /// reloading them, see [`WatcherActor::dry_watch`].
+    dry_watch: Option<Box<dyn Write + Send>>,
+}
+
+/// File reloading every task when touched, in the `.whiz` directory of
+/// the watch root, `reload-<task>` reloading a single task.
+pub const RELOAD_FILE: &str = "reload";
+
+/// Touching a file is reported as several events, the ones closer than
+/// this to the previous one are the same touch.
+#[cfg(feature = "watch")]
+const TOUCH_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Returns the kind of change `kind` is, `None` for the ones never
+/// reloading a task.
+#[cfg(feature = "watch")]
+fn event_kind(kind: &EventKind) -> Option<WatchEventKind> {
+    match kind {
+        EventKind::Create(_) => Some(WatchEventKind::Create),
+        EventKind::Remove(_) => Some(WatchEventKind::Remove),
+        EventKind::Modify(ModifyKind::Data(_)) | EventKind::Modify(ModifyKind::Name(_)) => {
+            Some(WatchEventKind::Modify)
+        }
+        _ => None,
+    }
+}
+
+/// Returns which task touching `path` reloads, `None` for every task, or
+/// nothing if it is not a reload file.
+#[cfg(feature = "watch")]
+fn reload_file(root: &Path, path: &Path) -> Option<Option<String>> {
+    if path.parent()? != root.join(crate::daemon::DIR) {
+        return None;
+    }
+    let name = path.file_name()?.to_str()?;
+    match name.strip_prefix(RELOAD_FILE)? {
+        "" => Some(None),
+        task => Some(Some(task.strip_prefix('-')?.to_string())),
+    }
 }
 
 impl WatcherActor {
-    pub fn new(base_dir: Arc<Path>) -> Self {
+    pub fn new(root: Arc<Path>) -> Self {
         Self {
+            #[cfg(feature = "watch")]
             watcher: None,
             globs: Vec::default(),
-            base_dir,
+            reload_all: None,
+            config: None,
+            root,
+            noop: false,
+            events: WatchEventKind::ALL.to_vec(),
             ignore: HashSet::default(),
+            #[cfg(feature = "watch")]
+            touched: HashMap::default(),
+            #[cfg(feature = "watch")]
+            debounce: None,
+            #[cfg(feature = "watch")]
+            pending: Vec::new(),
+            dry_watch: None,
+        }
+    }
+
+    /// Only reloads on these kinds of changes the tasks which set none,
+    /// every kind when empty.
+    pub fn events(mut self, events: Vec<WatchEventKind>) -> Self {
+        if !events.is_empty() {
+            self.events = events;
         }
+        self
+    }
+
+    /// Buffers the changes for this long after the first one, then
+    /// reloads each task once for all the files changed meanwhile, e.g. the
+    /// burst of events of a `git checkout`.
+    #[cfg(feature = "watch")]
+    pub fn debounce(mut self, debounce: Option<Duration>) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// Writes `changed <path> → would reload [tasks]` to `writer` for each
+    /// changed file instead of reloading the tasks, to check the globs.
+    pub fn dry_watch(mut self, writer: Box<dyn Write + Send>) -> Self {
+        self.dry_watch = Some(writer);
+        self
+    }
+
+    /// Accepts the globs of the tasks but never fires, see [`crate::testing`].
+    #[cfg(feature = "testing")]
+    pub fn noop() -> Self {
+        Self {
+            noop: true,
+            ..Self::new(Path::new(".").into())
+        }
+    }
+
+    /// Returns the task of `glob` and the ones it runs on change, in turn
+    /// following the ones they run. The tasks are found by name among the
+    /// current ones, see [`ReloadAllOn::tasks`], the config rejecting
+    /// cycles.
+    #[cfg(feature = "watch")]
+    fn runs_on_change(&self, glob: &WatchGlob) -> Vec<Addr<CommandActor>> {
+        let mut commands = vec![glob.command.clone()];
+        let Some(tasks) = self.reload_all.as_ref().map(|reload_all| &reload_all.tasks) else {
+            return commands;
+        };
+        let mut names = glob.runs.clone();
+        while let Some(name) = names.pop() {
+            let Some(command) = tasks.get(&name) else {
+                // filtered out with --run
+                continue;
+            };
+            if commands.contains(command) {
+                continue;
+            }
+            if let Some(next) = self.globs.iter().find(|glob| glob.command == *command) {
+                names.extend(next.runs.iter().cloned());
+            }
+            commands.push(command.clone());
+        }
+        commands
     }
 }
 
 impl Actor for WatcherActor {
     type Context = Context<Self>;
 
+    #[cfg(feature = "watch")]
     fn started(&mut self, ctx: &mut Context<Self>) {
+        if self.noop {
+            return;
+        }
         let addr = ctx.address();
 
-        let mut git_ignore_builder = GitignoreBuilder::new(&self.base_dir);
+        let root = self.root.clone();
+        let mut git_ignore_builder = GitignoreBuilder::new(&root);
         // add globs from `<project-root>/.gitignore`
-        git_ignore_builder.add(self.base_dir.join(".gitignore"));
+        git_ignore_builder.add(root.join(".gitignore"));
         // ignore `<project-root>/.git` folder
         git_ignore_builder.add_line(None, ".git/").unwrap();
+        // ignore the logs of a detached whiz
+        git_ignore_builder
+            .add_line(None, &format!("{}/", crate::daemon::DIR))
+            .unwrap();
         let git_ignore = git_ignore_builder.build();
 
         let mut watcher = recommended_watcher(move |res: Result<Event, notify::Error>| {
             let mut event = res.unwrap();
 
             if let Ok(git_ignore) = &git_ignore {
+                // the config file may be watched outside of the root
                 event.paths.retain(|path| {
-                    !git_ignore
-                        .matched_path_or_any_parents(path, false)
-                        .is_ignore()
+                    !path.starts_with(&root)
+                        || reload_file(&root, path).is_some()
+                        || !git_ignore
+                            .matched_path_or_any_parents(path, false)
+                            .is_ignore()
                 })
             };
 
             if !event.paths.is_empty() {
                 match event.kind {
-                    EventKind::Create(_)
-                    | EventKind::Remove(_)
-                    | EventKind::Modify(ModifyKind::Data(_))
-                    | EventKind::Modify(ModifyKind::Name(_)) => {
+                    _ if event_kind(&event.kind).is_some() => {
+                        addr.do_send(WatchEvent(event));
+                    }
+                    // touching a file only changes its times
+                    EventKind::Modify(ModifyKind::Metadata(_))
+                        if event
+                            .paths
+                            .iter()
+                            .any(|path| reload_file(&root, path).is_some()) =>
+                    {
                         addr.do_send(WatchEvent(event));
                     }
                     _ => {}
@@ -67,9 +231,7 @@ impl Actor for WatcherActor {
         })
         .unwrap();
 
-        watcher
-            .watch(&self.base_dir, RecursiveMode::Recursive)
-            .unwrap();
+        watcher.watch(&self.root, RecursiveMode::Recursive).unwrap();
 
         self.watcher = Some(watcher);
     }
@@ -81,45 +243,304 @@ pub struct WatchGlob {
     pub command: Addr<CommandActor>,
     pub on: GlobSet,
     pub off: GlobSet,
+    /// Tasks reloaded along with this one, see
+    /// [`crate::config::Task::on_change_run`].
+    pub runs: Vec<String>,
+    /// Kinds of changes reloading the task, the ones of the watcher when
+    /// empty, see [`crate::config::Task::watch_events`].
+    pub events: Vec<WatchEventKind>,
 }
 
 impl Handler<WatchGlob> for WatcherActor {
     type Result = ();
 
     fn handle(&mut self, msg: WatchGlob, _: &mut Context<Self>) -> Self::Result {
+        debug_assert!(
+            msg.command.connected(),
+            "watch glob registered for a task without actor"
+        );
+        // a task registers again when its definition changes
+        self.globs.retain(|glob| glob.command != msg.command);
         self.globs.push(msg);
     }
 }
 
+#[cfg(feature = "watch")]
 #[derive(Message)]
 #[rtype(result = "()")]
 struct WatchEvent(Event);
 
+#[cfg(feature = "watch")]
 impl Handler<WatchEvent> for WatcherActor {
     type Result = ();
 
-    fn handle(&mut self, msg: WatchEvent, _: &mut Context<Self>) -> Self::Result {
+    fn handle(&mut self, msg: WatchEvent, ctx: &mut Context<Self>) -> Self::Result {
         let WatchEvent(event) = msg;
+        let Some(window) = self.debounce else {
+            self.dispatch(vec![event]);
+            return;
+        };
+        // the first event of a burst starts the window
+        if self.pending.is_empty() {
+            ctx.run_later(window, |act, _| {
+                let events = std::mem::take(&mut act.pending);
+                act.dispatch(events);
+            });
+        }
+        self.pending.push(event);
+    }
+}
+
+#[cfg(feature = "watch")]
+impl WatcherActor {
+    /// Reloads the tasks for the changes of `events`, each task once for
+    /// the changed files it matches, listed once.
+    fn dispatch(&mut self, events: Vec<Event>) {
+        let now = Instant::now();
+        let mut reloads = Vec::new();
+        let mut changes: Vec<(Option<WatchEventKind>, PathBuf)> = Vec::new();
+        for mut event in events {
+            event.paths.retain(|path| {
+                let Some(task) = reload_file(&self.root, path) else {
+                    return true;
+                };
+                let last = self.touched.insert(path.clone(), now);
+                if last.is_none_or(|last| now.duration_since(last) > TOUCH_DEBOUNCE) {
+                    reloads.push(task);
+                }
+                false
+            });
+            let kind = event_kind(&event.kind);
+            changes.extend(event.paths.into_iter().map(|path| (kind, path)));
+        }
+        if let Some(reload_all) = &self.reload_all {
+            for task in reloads {
+                match task {
+                    Some(task) => {
+                        if let Some(command) = reload_all.tasks.get(&task) {
+                            command.do_send(Reload::Manual);
+                        }
+                    }
+                    None => {
+                        for root in &reload_all.roots {
+                            root.do_send(Reload::Manual);
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(config) = &self.config {
+            if changes.iter().any(|(_, path)| *path == config.path) {
+                config.reloader.do_send(ConfigChanged);
+            }
+        }
+
+        // tasks removed from the config are stopped
+        self.globs.retain(|glob| glob.command.connected());
+
+        if self.dry_watch.is_some() {
+            self.write_dry_watch(&changes);
+            return;
+        }
+        match self.reloads(&changes) {
+            Reloads::All(trigger) => {
+                if let Some(reload_all) = &self.reload_all {
+                    for root in &reload_all.roots {
+                        root.do_send(Reload::Watch(trigger.clone()));
+                    }
+                }
+            }
+            Reloads::Tasks(watched) => {
+                for (command, trigger) in watched {
+                    command.do_send(Reload::Watch(trigger));
+                }
+            }
+        }
+    }
+
+    /// Returns the tasks the `changes` reload along with the files
+    /// triggering each.
+    fn reloads(&self, changes: &[(Option<WatchEventKind>, PathBuf)]) -> Reloads {
+        if let Some(reload_all) = &self.reload_all {
+            let paths = changed_paths(changes, |kind, path| {
+                kind.is_some_and(|kind| self.events.contains(&kind))
+                    && !self.ignore.contains(path)
+                    && reload_all.on.is_match(path)
+            });
+
+            if !paths.is_empty() {
+                // every task reloads anyway
+                return Reloads::All(paths.join(", "));
+            }
+        }
+
+        // a task matching the files itself or run by several others
+        // reloads once
+        let mut watched: Vec<(Addr<CommandActor>, String)> = Vec::new();
         for glob in &self.globs {
-            let paths = event
-                .paths
-                .iter()
-                .filter(|path| {
-                    !self.ignore.contains(path.as_path())
-                        && glob.on.is_match(path)
-                        && !glob.off.is_match(path)
-                })
-                .collect::<Vec<_>>();
+            let events = match glob.events.is_empty() {
+                true => &self.events,
+                false => &glob.events,
+            };
+            let paths = changed_paths(changes, |kind, path| {
+                kind.is_some_and(|kind| events.contains(&kind))
+                    && !self.ignore.contains(path)
+                    && glob.on.is_match(path)
+                    && !glob.off.is_match(path)
+            });
 
             if !paths.is_empty() {
-                let trigger = paths
+                let trigger = paths.join(", ");
+                for command in self.runs_on_change(glob) {
+                    if !watched.iter().any(|(other, _)| *other == command) {
+                        watched.push((command, trigger.clone()));
+                    }
+                }
+            }
+        }
+        Reloads::Tasks(watched)
+    }
+
+    /// Writes the tasks each of the `changes` would reload, by name, see
+    /// [`WatcherActor::dry_watch`].
+    fn write_dry_watch(&mut self, changes: &[(Option<WatchEventKind>, PathBuf)]) {
+        let name = |command: &Addr<CommandActor>| {
+            self.reload_all.as_ref().and_then(|reload_all| {
+                reload_all
+                    .tasks
                     .iter()
-                    .map(|p| p.as_path().display().to_string())
-                    .collect::<Vec<_>>()
-                    .join(", ");
-                glob.command.do_send(Reload::Watch(trigger))
+                    .find(|(_, other)| *other == command)
+                    .map(|(name, _)| name.clone())
+            })
+        };
+        let mut paths: Vec<&PathBuf> = Vec::new();
+        // the changes never reloading a task, e.g. of metadata, are skipped
+        for (_, path) in changes.iter().filter(|(kind, _)| kind.is_some()) {
+            if !paths.contains(&path) {
+                paths.push(path);
             }
         }
+
+        let mut lines = Vec::new();
+        for path in paths {
+            let changes: Vec<_> = changes
+                .iter()
+                .filter(|(_, changed)| changed == path)
+                .cloned()
+                .collect();
+            let mut tasks: Vec<String> = match self.reloads(&changes) {
+                Reloads::All(_) => self
+                    .reload_all
+                    .iter()
+                    .flat_map(|reload_all| reload_all.tasks.keys().cloned())
+                    .collect(),
+                Reloads::Tasks(watched) => watched
+                    .iter()
+                    .filter_map(|(command, _)| name(command))
+                    .collect(),
+            };
+            tasks.sort();
+            lines.push(format!(
+                "changed {} → would reload [{}]\n",
+                path.display(),
+                tasks.join(", ")
+            ));
+        }
+        if let Some(writer) = &mut self.dry_watch {
+            for line in lines {
+                let _ = writer.write_all(line.as_bytes());
+            }
+            let _ = writer.flush();
+        }
+    }
+}
+
+/// Tasks reloaded for some changes, see [`WatcherActor::reloads`].
+#[cfg(feature = "watch")]
+enum Reloads {
+    /// Every task through the roots of the DAG, the files matching
+    /// `reload_all_on`.
+    All(String),
+    /// Each task with the files it matches.
+    Tasks(Vec<(Addr<CommandActor>, String)>),
+}
+
+/// Returns the paths of the `changes` which `reloads`, each once in the
+/// order they changed.
+#[cfg(feature = "watch")]
+fn changed_paths(
+    changes: &[(Option<WatchEventKind>, PathBuf)],
+    reloads: impl Fn(Option<WatchEventKind>, &Path) -> bool,
+) -> Vec<String> {
+    let mut paths: Vec<String> = Vec::new();
+    for (_, path) in changes.iter().filter(|(kind, path)| reloads(*kind, path)) {
+        let path = path.display().to_string();
+        if !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+    paths
+}
+
+/// Reloads every task when a file matching `on` changes, or the reload
+/// file is touched, through the `roots` of the DAG so that dependents only
+/// run once their upstream did. The reload file of a task reloads it in
+/// `tasks`, see [`RELOAD_FILE`].
+#[derive(Message, Clone)]
+#[rtype(result = "()")]
+pub struct ReloadAllOn {
+    pub on: GlobSet,
+    pub roots: Vec<Addr<CommandActor>>,
+    pub tasks: HashMap<String, Addr<CommandActor>>,
+}
+
+impl ReloadAllOn {
+    pub fn new(
+        config: &ConfigInner,
+        roots: Vec<Addr<CommandActor>>,
+        tasks: HashMap<String, Addr<CommandActor>>,
+    ) -> anyhow::Result<Self> {
+        let mut on = GlobSetBuilder::new();
+        for pattern in &config.reload_all_on {
+            let pattern = config.base_dir.join(pattern).absolutize()?.to_path_buf();
+            on.add(Glob::new(&pattern.to_string_lossy())?);
+        }
+        Ok(Self {
+            on: on.build()?,
+            roots,
+            tasks,
+        })
+    }
+}
+
+impl Handler<ReloadAllOn> for WatcherActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: ReloadAllOn, _: &mut Context<Self>) -> Self::Result {
+        self.reload_all = Some(msg);
+    }
+}
+
+/// Notifies `reloader` when the config file at `path` changes.
+#[derive(Message, Clone)]
+#[rtype(result = "()")]
+pub struct WatchConfig {
+    pub path: PathBuf,
+    pub reloader: Recipient<ConfigChanged>,
+}
+
+impl Handler<WatchConfig> for WatcherActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: WatchConfig, _: &mut Context<Self>) -> Self::Result {
+        #[cfg(feature = "watch")]
+        if !msg.path.starts_with(&self.root) {
+            if let Some(watcher) = &mut self.watcher {
+                let _ = watcher.watch(&msg.path, RecursiveMode::NonRecursive);
+            }
+        }
+        self.config = Some(msg);
     }
 }
 
@@ -135,3 +556,88 @@ impl Handler<IgnorePath> for WatcherActor {
         self.ignore.insert(path);
     }
 }
+
+#[cfg(all(test, feature = "watch"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_reload_files() {
+        let root = Path::new("/repo");
+
+        assert_eq!(
+            reload_file(root, Path::new("/repo/.whiz/reload")),
+            Some(None)
+        );
+        assert_eq!(
+            reload_file(root, Path::new("/repo/.whiz/reload-api")),
+            Some(Some("api".to_string()))
+        );
+        assert_eq!(reload_file(root, Path::new("/repo/.whiz/reloaded")), None);
+        assert_eq!(reload_file(root, Path::new("/repo/.whiz/whiz.log")), None);
+        assert_eq!(reload_file(root, Path::new("/repo/reload")), None);
+        assert_eq!(reload_file(root, Path::new("/repo/.whiz/dev/reload")), None);
+    }
+
+    #[test]
+    fn lists_each_changed_path_once() {
+        let change = |kind, path: &str| (Some(kind), PathBuf::from(path));
+        let changes = [
+            change(WatchEventKind::Create, "/repo/src/a.rs"),
+            change(WatchEventKind::Modify, "/repo/src/a.rs"),
+            change(WatchEventKind::Modify, "/repo/README.md"),
+            change(WatchEventKind::Remove, "/repo/src/b.rs"),
+        ];
+        let paths = changed_paths(&changes, |kind, path| {
+            kind != Some(WatchEventKind::Remove) && path.starts_with("/repo/src")
+        });
+        assert_eq!(paths, vec!["/repo/src/a.rs"]);
+    }
+
+    #[test]
+    fn buffers_events_while_debouncing() {
+        static CHANGES: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+        struct Reloader;
+        impl Actor for Reloader {
+            type Context = Context<Self>;
+        }
+        impl Handler<ConfigChanged> for Reloader {
+            type Result = ();
+
+            fn handle(&mut self, _: ConfigChanged, _: &mut Context<Self>) {
+                CHANGES.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            }
+        }
+
+        let root = std::env::temp_dir().join(format!("whiz-debounce-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let path = root.join("whiz.yaml");
+        System::new().block_on({
+            let root = root.clone();
+            async move {
+                let watcher = WatcherActor::new(root.into())
+                    .debounce(Some(Duration::from_millis(200)))
+                    .start();
+                let reloader = Reloader.start().recipient();
+                watcher
+                    .send(WatchConfig {
+                        path: path.clone(),
+                        reloader,
+                    })
+                    .await
+                    .unwrap();
+
+                let modified = EventKind::Modify(ModifyKind::Data(notify::event::DataChange::Any));
+                for _ in 0..3 {
+                    let event = Event::new(modified).add_path(path.clone());
+                    watcher.send(WatchEvent(event)).await.unwrap();
+                }
+                assert_eq!(CHANGES.load(std::sync::atomic::Ordering::Relaxed), 0);
+                actix::clock::sleep(Duration::from_millis(400)).await;
+            }
+        });
+        std::fs::remove_dir_all(root).unwrap();
+        assert_eq!(CHANGES.load(std::sync::atomic::Ordering::Relaxed), 1);
+    }
+}
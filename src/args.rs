@@ -1,4 +1,10 @@
+use std::num::NonZeroUsize;
+
 use clap::{Parser, Subcommand};
+use clap_complete::Shell;
+
+use crate::config::names::is_safe_name;
+use crate::config::watch_events::WatchEventKind;
 
 #[derive(Parser, Debug, Clone)]
 pub struct Upgrade {
@@ -9,6 +15,10 @@ pub struct Upgrade {
     /// Do not ask for version confirmation
     #[arg(short, long, default_value_t = false)]
     pub yes: bool,
+
+    /// Only check for a newer version, exiting with 10 when there is one
+    #[arg(long, default_value_t = false, conflicts_with_all = ["version", "yes"])]
+    pub check: bool,
 }
 
 #[derive(Parser, Debug, Clone)]
@@ -16,6 +26,25 @@ pub struct Graph {
     /// Draw the line using box-drawing character
     #[arg(long, short, default_value_t = false)]
     pub boxed: bool,
+
+    /// Open the graph as an SVG in the default browser instead
+    #[arg(long, default_value_t = false, conflicts_with = "boxed")]
+    pub html: bool,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct ListJobs {
+    /// Show the workdir of each job and whether it exists
+    #[arg(long, short, default_value_t = false)]
+    pub detail: bool,
+
+    /// Also check if each workdir has uncommitted changes (requires --detail)
+    #[arg(long, default_value_t = false, requires = "detail")]
+    pub git: bool,
+
+    /// Show the description of each job under it
+    #[arg(long, short, default_value_t = false, conflicts_with = "detail")]
+    pub long: bool,
 }
 
 #[derive(Parser, Debug, Clone)]
@@ -24,6 +53,72 @@ pub struct Execute {
     pub task: String,
 }
 
+#[derive(Parser, Debug, Clone)]
+pub struct DumpEnv {
+    /// Task whose environment is printed
+    #[arg()]
+    pub task: String,
+
+    /// Only print the variables differing from the ones of whiz
+    #[arg(long, default_value_t = false)]
+    pub changed: bool,
+
+    /// Hide the values of the variables whose name looks secret (TOKEN, PASSWORD, KEY...)
+    #[arg(long, default_value_t = false)]
+    pub redact: bool,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct Doctor {
+    /// Only check these tasks [default: every task]
+    #[arg()]
+    pub tasks: Vec<String>,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct Plan {
+    /// Only plan these tasks and their dependencies [default: the jobs of --run]
+    #[arg()]
+    pub tasks: Vec<String>,
+
+    /// Print the plan as JSON
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+
+    /// Also print the command, workdir and variables each task would run with
+    #[arg(long, default_value_t = false, conflicts_with = "json")]
+    pub env: bool,
+
+    /// Hide the values of the variables whose name looks secret (requires --env)
+    #[arg(long, default_value_t = false, requires = "env")]
+    pub redact: bool,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct Attach {
+    /// Only print the output of this task
+    #[arg()]
+    pub task: Option<String>,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct Completions {
+    /// Shell to complete the commands and flags of whiz in
+    #[arg()]
+    pub shell: Shell,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct Replay {
+    /// Session recorded with --record
+    #[arg()]
+    pub file: String,
+
+    /// Replay the session this many times faster than recorded
+    #[arg(long, default_value_t = 1.0)]
+    pub speed: f64,
+}
+
 /// Set of subcommands.
 #[derive(Subcommand, Debug)]
 pub enum Command {
@@ -32,10 +127,29 @@ pub enum Command {
     /// PUpgrade whizrint the graphical ascii representation
     Graph(Graph),
     /// List all the jobs set in the config file
-    ListJobs,
+    ListJobs(ListJobs),
     /// Execute a specific job; running its dependencies serially
     #[command(name = "x")]
     Execute(Execute),
+    /// Print the order the jobs would run in, without running them
+    Plan(Plan),
+    /// Print the environment a task runs with, without running it
+    DumpEnv(DumpEnv),
+    /// Check the binaries and variables the tasks require, without running them
+    Doctor(Doctor),
+    /// Replay a session recorded with --record
+    Replay(Replay),
+    /// Follow the output of whiz running in the background
+    Attach(Attach),
+    /// Print the state of the tasks of whiz running in the background
+    Status,
+    /// Stop whiz running in the background
+    Stop,
+    /// Print the completion script of a shell, e.g. `whiz completions zsh`
+    Completions(Completions),
+    /// Print the codes whiz exits with
+    #[command(hide = true)]
+    ExitCodes,
 }
 
 #[derive(Parser, Debug)]
@@ -51,8 +165,26 @@ pub struct Args {
     #[command(subcommand)]
     pub command: Option<Command>,
 
-    #[arg(short, long, default_value = "whiz.yaml")]
-    pub file: String,
+    /// Config file in YAML or TOML, or a Procfile, searched from the current
+    /// directory upward [default: $WHIZ_CONFIG, or whiz.yaml, or whiz.toml]
+    #[arg(short, long, value_name = "FILE")]
+    pub file: Option<String>,
+
+    /// Apply the overrides of this profile of the config file
+    #[arg(long, value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// Run the tasks relative to this directory instead of the one of the config file
+    #[arg(long, value_name = "DIR")]
+    pub base_dir: Option<String>,
+
+    /// Watch this directory instead of the base dir, or the `watch_root` of the config file
+    #[arg(long, value_name = "DIR")]
+    pub watch_root: Option<String>,
+
+    /// Focus the panel of this task when the view opens, or the `default_task` of the config file
+    #[arg(long, value_name = "TASK")]
+    pub focus: Option<String>,
 
     #[arg(short, long)]
     pub verbose: bool,
@@ -61,18 +193,150 @@ pub struct Args {
     /// Enable timestamps in logging
     pub timestamp: bool,
 
+    /// Print the tasks output line by line instead of the interactive view
+    #[arg(long)]
+    pub no_tui: bool,
+
+    /// Announce the state changes as plain sentences and read commands
+    /// (focus, reload, status, stop) from stdin, for screen readers (implies --no-tui)
+    #[arg(long, conflicts_with = "detach")]
+    pub a11y: bool,
+
+    /// Add a last panel listing the state changes and reloads of every task
+    #[arg(long)]
+    pub timeline: bool,
+
+    /// Prefix each line with the name of its task, colored, e.g. to copy it
+    #[arg(long)]
+    pub prefix_lines: bool,
+
+    /// Collapse the lines repeating the last one of their task into it, counted as (xN)
+    #[arg(long)]
+    pub dedup: bool,
+
+    /// Lines scrolled at once by the arrows and the mouse wheel
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+    pub scroll_step: u16,
+
+    /// Key clearing the current tab and rerunning its task
+    #[arg(long, value_name = "KEY", default_value_t = 'R', value_parser = parse_restart_key)]
+    pub restart_key: char,
+
+    /// Run in the background, logging to .whiz/whiz.log (see attach, status and stop)
+    #[arg(long)]
+    pub detach: bool,
+
+    /// Keep the state, session and socket of this instance apart, to run
+    /// several whiz in the same project
+    #[arg(long, value_name = "NAME", value_parser = parse_instance_name)]
+    pub instance_name: Option<String>,
+
+    /// Start even though another whiz runs in the same project
+    #[arg(long)]
+    pub force: bool,
+
+    /// Set on the process started by --detach
+    #[arg(long, hide = true)]
+    pub daemonized: bool,
+
+    /// Record the console events of the session to a file
+    #[arg(long, value_name = "FILE", conflicts_with = "detach")]
+    pub record: Option<String>,
+
+    /// Only record the length of the output lines, not their content (requires --record)
+    #[arg(long, requires = "record")]
+    pub record_redacted: bool,
+
     /// Run specific jobs
     #[arg(short, long, value_name = "JOB")]
     pub run: Vec<String>,
 
+    /// Run only the jobs given with --run, without their dependencies
+    #[arg(long, requires = "run")]
+    pub no_deps: bool,
+
+    /// Print what would run instead of running it, as `whiz plan --env`
+    #[arg(long, conflicts_with = "detach")]
+    pub dry_run: bool,
+
+    /// Print which tasks the changed files would reload instead of running
+    /// anything, to check the watch globs (implies --no-tui)
+    #[arg(long, conflicts_with_all = ["detach", "dry_run", "exit_after"])]
+    pub dry_watch: bool,
+
+    /// Run at most N tasks at once, the others waiting for a slot
+    #[arg(long, visible_alias = "max-concurrent", value_name = "N")]
+    pub max_parallel: Option<NonZeroUsize>,
+
+    /// Milliseconds the tasks have to exit once asked to stop before they
+    /// are killed, unless they set a kill_timeout [default: 500]
+    #[arg(long, value_name = "MS")]
+    pub kill_timeout: Option<u64>,
+
     // This disables fs watching despite any values given to the `watch` flag.
     //
     /// Whiz will exit after all tasks have finished executing.
     #[arg(long)]
     pub exit_after: bool,
 
+    /// Delay before exiting once all tasks have finished, waiting again for
+    /// the tasks starting in the meantime (requires --exit-after)
+    #[arg(long, value_name = "MS", default_value_t = 0, requires = "exit_after")]
+    pub settle_delay: u64,
+
+    /// Fail on config warnings, e.g. tasks piping to the same file,
+    /// instead of printing them
+    #[arg(long)]
+    pub strict_config: bool,
+
+    /// Exit once every task is done, after a delay in seconds showing
+    /// the final state of each task
+    #[arg(
+        long,
+        value_name = "SECS",
+        num_args = 0..=1,
+        default_missing_value = "3",
+        conflicts_with = "exit_after"
+    )]
+    pub exit_when_done: Option<u64>,
+
+    /// Reload every task when a file matching this glob changes, relative
+    /// to the directory of the config file as in reload_all_on
+    #[arg(long, value_name = "GLOB")]
+    pub reload_all_on: Vec<String>,
+
+    /// Only reload on these kinds of changes of the watched files (create,
+    /// modify, remove), unless a task sets its own `watch_events`
+    #[arg(long, value_name = "KINDS", value_delimiter = ',')]
+    pub watch_events: Vec<WatchEventKind>,
+
+    /// Buffer the changes of the watched files for this many milliseconds,
+    /// then reload each task once for all of them
+    #[arg(long, value_name = "MS")]
+    pub debounce: Option<u64>,
+
     // Globally toggle triggering task reloading from any watched files
     /// Globally enable/disable fs watching
     #[arg(long, default_value_t = true)]
     pub watch: bool,
 }
+
+/// Accepts a character no other key binding of the view uses.
+fn parse_restart_key(key: &str) -> Result<char, String> {
+    let mut chars = key.chars();
+    let (Some(key), None) = (chars.next(), chars.next()) else {
+        return Err("expected a single character".to_string());
+    };
+    if key.is_ascii_digit() || key.is_whitespace() || "qrkjlhmsvVToi".contains(key) {
+        return Err(format!("{key} is already bound"));
+    }
+    Ok(key)
+}
+
+fn parse_instance_name(name: &str) -> Result<String, String> {
+    if is_safe_name(name) {
+        Ok(name.to_string())
+    } else {
+        Err("expected letters, digits, _, -, : or ., starting with a letter or _".to_string())
+    }
+}
@@ -1,8 +1,73 @@
+#[cfg(feature = "tui")]
 use ansi_to_tui::IntoText;
 use anyhow::anyhow;
-use ratatui::style::{Color, Style};
+#[cfg(feature = "tui")]
+use ratatui::style::{Modifier, Style};
+#[cfg(feature = "tui")]
 use ratatui::text::{Line, Span, StyledGrapheme};
 use regex::Regex;
+#[cfg(feature = "tui")]
+use std::borrow::Cow;
+use std::fmt;
+#[cfg(feature = "tui")]
+use std::ops::Range;
+
+/// Color of the lines matching a [`ColorOption`], drawn with the one of
+/// ratatui of the same name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    Gray,
+    DarkGray,
+    LightRed,
+    LightGreen,
+    LightYellow,
+    LightBlue,
+    LightMagenta,
+    LightCyan,
+    White,
+    Rgb(u8, u8, u8),
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Color::Rgb(r, g, b) => write!(f, "#{r:02X}{g:02X}{b:02X}"),
+            named => write!(f, "{named:?}"),
+        }
+    }
+}
+
+#[cfg(feature = "tui")]
+impl From<Color> for ratatui::style::Color {
+    fn from(color: Color) -> Self {
+        match color {
+            Color::Black => Self::Black,
+            Color::Red => Self::Red,
+            Color::Green => Self::Green,
+            Color::Yellow => Self::Yellow,
+            Color::Blue => Self::Blue,
+            Color::Magenta => Self::Magenta,
+            Color::Cyan => Self::Cyan,
+            Color::Gray => Self::Gray,
+            Color::DarkGray => Self::DarkGray,
+            Color::LightRed => Self::LightRed,
+            Color::LightGreen => Self::LightGreen,
+            Color::LightYellow => Self::LightYellow,
+            Color::LightBlue => Self::LightBlue,
+            Color::LightMagenta => Self::LightMagenta,
+            Color::LightCyan => Self::LightCyan,
+            Color::White => Self::White,
+            Color::Rgb(r, g, b) => Self::Rgb(r, g, b),
+        }
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct ColorOption {
@@ -59,7 +124,12 @@ impl PartialEq for ColorOption {
     }
 }
 
+#[cfg(feature = "tui")]
 lazy_static::lazy_static! {
+    /// Operating system commands, ended by BEL or ST, the URI of the
+    /// hyperlinks (OSC 8) captured, empty when closing one.
+    static ref OSC: Regex = Regex::new(r"\x1b\](?:8;[^;\x07\x1b]*;([^\x07\x1b]*)|[^\x07\x1b]*)(?:\x07|\x1b\\)").unwrap();
+
     static ref COLOR_OPTIONS: Vec<ColorOption> = vec![
         ColorOption::from(("GET", "green")).unwrap(),
         ColorOption::from(("POST", "#FFA500")).unwrap(),
@@ -90,11 +160,13 @@ lazy_static::lazy_static! {
     ];
 }
 
+#[cfg(feature = "tui")]
 pub struct Colorizer<'b> {
     colors: &'b Vec<ColorOption>,
     base_style: Style,
 }
 
+#[cfg(feature = "tui")]
 impl<'b> Colorizer<'b> {
     pub fn new(colors: &'b Vec<ColorOption>, base_style: Style) -> Self {
         Self { colors, base_style }
@@ -106,11 +178,19 @@ impl<'b> Colorizer<'b> {
     ///
     /// Returns vector of patched lines.
     ///
+    /// The text of the hyperlinks is underlined, see [`split_links`].
     pub fn patch_text<'a>(&self, str: &'a str) -> Vec<Line<'a>> {
-        let text = str.into_text().unwrap().patch_style(self.base_style);
+        let (str, links) = split_links(str);
+        let text = str
+            .as_bytes()
+            .into_text()
+            .unwrap()
+            .patch_style(self.base_style);
 
         let colors = COLOR_OPTIONS.iter().chain(self.colors);
 
+        // the links are ranges of the whole text, line breaks included
+        let mut offset = 0;
         text.lines
             .iter()
             .map(|line| {
@@ -120,11 +200,37 @@ impl<'b> Colorizer<'b> {
                     styled_line =
                         self.merge_lines(&styled_line, &self.apply_color_option(&pure_str, opt));
                 }
+                if !links.is_empty() {
+                    styled_line = self.underline(&styled_line, &links, offset);
+                }
+                offset += pure_str.chars().count() + 1;
                 styled_line
             })
             .collect()
     }
 
+    /// Underlines the graphemes of `line` within `links`, ranges of chars
+    /// of the text `line` starts at `offset` of.
+    fn underline<'a, 'c>(
+        &self,
+        line: &Line<'a>,
+        links: &[Range<usize>],
+        offset: usize,
+    ) -> Line<'c> {
+        let mut position = offset;
+        let graphemes: Vec<StyledGrapheme<'_>> = line
+            .styled_graphemes(self.base_style)
+            .map(|mut grapheme| {
+                if links.iter().any(|link| link.contains(&position)) {
+                    grapheme.style = grapheme.style.add_modifier(Modifier::UNDERLINED);
+                }
+                position += grapheme.symbol.chars().count();
+                grapheme
+            })
+            .collect();
+        Self::join_graphemes(&graphemes)
+    }
+
     fn line_as_string(line: &Line) -> String {
         line.spans
             .iter()
@@ -138,7 +244,7 @@ impl<'b> Colorizer<'b> {
     }
 
     fn colored<'a>(&self, content: &'a str, color: Color) -> Span<'a> {
-        Span::styled(content, self.base_style.fg(color))
+        Span::styled(content, self.base_style.fg(color.into()))
     }
 
     ///
@@ -163,8 +269,13 @@ impl<'b> Colorizer<'b> {
             })
             .collect();
 
+        Self::join_graphemes(&merged_graphemes)
+    }
+
+    /// Groups the consecutive graphemes of the same style into spans.
+    fn join_graphemes<'c>(graphemes: &[StyledGrapheme<'_>]) -> Line<'c> {
         let mut spans = Vec::new();
-        let mut outer = merged_graphemes.iter();
+        let mut outer = graphemes.iter();
         while let Some(grapheme) = outer.next() {
             let mut content = String::from(grapheme.symbol);
             let mut inner = outer.clone();
@@ -213,9 +324,47 @@ impl<'b> Colorizer<'b> {
     }
 }
 
-#[cfg(test)]
+/// Drops the operating system commands of `line`, which the panels cannot
+/// show (the ones ended by ST would hide the rest of the line otherwise),
+/// and returns the ranges of chars of the text left, without its escape
+/// codes, that were hyperlinks (OSC 8).
+#[cfg(feature = "tui")]
+fn split_links(line: &str) -> (Cow<'_, str>, Vec<Range<usize>>) {
+    let mut text = String::new();
+    let mut links = Vec::new();
+    let mut link_start = None;
+    let mut chars = 0;
+    let mut last = 0;
+    for caps in OSC.captures_iter(line) {
+        let command = caps.get(0).unwrap();
+        let before = &line[last..command.start()];
+        text.push_str(before);
+        chars += strip_ansi_escapes::strip_str(before).chars().count();
+        last = command.end();
+        match (caps.get(1).map(|uri| uri.as_str()), link_start) {
+            (Some(""), Some(start)) => {
+                links.push(start..chars);
+                link_start = None;
+            }
+            (Some(uri), None) if !uri.is_empty() => link_start = Some(chars),
+            _ => {}
+        }
+    }
+    if last == 0 {
+        return (Cow::Borrowed(line), links);
+    }
+    let rest = &line[last..];
+    text.push_str(rest);
+    if let Some(start) = link_start {
+        links.push(start..chars + strip_ansi_escapes::strip_str(rest).chars().count());
+    }
+    (Cow::Owned(text), links)
+}
+
+#[cfg(all(test, feature = "tui"))]
 mod tests {
     use super::*;
+    use ratatui::style::{Color, Modifier};
     use std::str::FromStr;
 
     #[test]
@@ -371,4 +520,32 @@ mod tests {
         assert_eq!(patched.len(), 1);
         assert_eq!(expected, patched.first().unwrap().spans);
     }
+
+    #[test]
+    fn keeps_the_text_of_hyperlinks() {
+        let colors = Vec::new();
+        let colorizer = Colorizer::new(&colors, Style::default());
+
+        let underlined = Style::default().add_modifier(Modifier::UNDERLINED);
+
+        // ended by ST or BEL, within colored text
+        for end in ["\x1b\\", "\x07"] {
+            let line = format!(
+                "\x1b[31msee \x1b]8;id=1;https://example.com{end}the docs\x1b]8;;{end} now\x1b[0m"
+            );
+            assert_eq!(
+                colorizer.patch_text(&line),
+                vec![Line::from(vec![
+                    Span::styled("see ", Style::default().fg(Color::Red)),
+                    Span::styled("the docs", underlined.fg(Color::Red)),
+                    Span::styled(" now", Style::default().fg(Color::Red)),
+                ])],
+                "{line:?}"
+            );
+        }
+
+        // other commands, e.g. the title, are dropped
+        let patched = colorizer.patch_text("\x1b]0;building\x1b\\done");
+        assert_eq!(Colorizer::line_as_string(&patched[0]), "done");
+    }
 }
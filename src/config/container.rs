@@ -0,0 +1,386 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+use super::{ops::Ops, Lift};
+
+/// Runs the command of a task in a container instead of on the host, e.g.:
+///
+/// ```yaml
+/// container:
+///     image: node:20
+///     ports: 3000:3000
+/// ```
+///
+/// The workdir of the task is mounted at the same path in the container,
+/// which runs there, and the variables the config sets are forwarded. The
+/// container is named after the task, see [`container_name`], and removed
+/// once the task stops.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Container {
+    /// Image started with `docker run`.
+    pub image: Option<String>,
+    /// Service of the compose files started with `docker compose run`.
+    pub service: Option<String>,
+    /// Compose files, instead of the ones `docker compose` looks up.
+    #[serde(default)]
+    pub compose_file: Lift<String>,
+    /// Command line of the engine, e.g. `podman`.
+    #[serde(default = "default_engine")]
+    pub engine: String,
+    /// Other mounts, `host:container` with `host` relative to the workdir.
+    #[serde(default)]
+    pub volumes: Lift<String>,
+    /// Published ports, `host:container`.
+    #[serde(default)]
+    pub ports: Lift<String>,
+    /// Directory the workdir is mounted at, its own path by default.
+    pub workdir: Option<String>,
+    /// Other options of `run`, e.g. `--network=host`.
+    #[serde(default)]
+    pub options: Lift<String>,
+}
+
+fn default_engine() -> String {
+    "docker".to_string()
+}
+
+/// Files `docker compose` looks up from its workdir and the parents of it
+/// when none is given, each with the file it merges over it.
+const COMPOSE_FILES: [(&str, &str); 4] = [
+    ("compose.yaml", "compose.override.yaml"),
+    ("compose.yml", "compose.override.yml"),
+    ("docker-compose.yml", "docker-compose.override.yml"),
+    ("docker-compose.yaml", "docker-compose.override.yaml"),
+];
+
+/// Name of the container of the task `op_name`, unique to this instance of
+/// whiz so that it can be removed once the task stops, see
+/// [`Container::remove`].
+pub fn container_name(op_name: &str) -> String {
+    let op_name = String::from_iter(op_name.chars().map(|c| match c {
+        'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '.' | '-' => c,
+        _ => '-',
+    }));
+    format!("whiz-{op_name}-{}", std::process::id())
+}
+
+impl Container {
+    /// Returns the command running `cmd` and `args` in the container of the
+    /// task `op_name`, from the task `cwd` and with the variables of
+    /// `env_keys`, whose values are passed through the environment of the
+    /// engine rather than its arguments.
+    pub fn wrap(
+        &self,
+        op_name: &str,
+        cwd: &Path,
+        env_keys: &[String],
+        cmd: String,
+        args: Vec<String>,
+    ) -> Result<(String, Vec<String>)> {
+        let name = container_name(op_name);
+        let mut argv = Vec::new();
+        let target = match (&self.image, &self.service) {
+            (_, Some(service)) => {
+                argv.push("compose".to_string());
+                let mut files = self.compose_file.resolve();
+                if files.is_empty() {
+                    files = Vec::from_iter(
+                        default_compose_files(cwd)
+                            .with_context(|| {
+                                format!("no compose file found from {}", cwd.display())
+                            })?
+                            .into_iter()
+                            .map(|file| file.display().to_string()),
+                    );
+                }
+                // `compose run` has no `--init`, the service sets it instead
+                files.push(init_override(&name, service)?.display().to_string());
+                for file in files {
+                    argv.extend(["-f".to_string(), file]);
+                }
+                argv.extend(["run", "--rm", "--name", &name].map(String::from));
+                service
+            }
+            (Some(image), None) => {
+                // the init forwards the stop signal to the command
+                argv.extend(["run", "--rm", "--init", "--name", &name].map(String::from));
+                image
+            }
+            (None, None) => unreachable!("checked by validate_containers"),
+        };
+
+        let host = cwd.display().to_string();
+        let workdir = self.workdir.clone().unwrap_or_else(|| host.clone());
+        argv.extend([
+            "-v".to_string(),
+            format!("{host}:{workdir}"),
+            "-w".to_string(),
+            workdir,
+        ]);
+        for volume in self.volumes.resolve() {
+            argv.extend(["-v".to_string(), absolute_volume(cwd, &volume)]);
+        }
+        for port in self.ports.resolve() {
+            argv.extend(["-p".to_string(), port]);
+        }
+        for key in env_keys {
+            argv.extend(["-e".to_string(), key.clone()]);
+        }
+        argv.extend(self.options.resolve());
+
+        argv.push(target.clone());
+        argv.push(cmd);
+        argv.extend(args);
+        Ok((self.engine.clone(), argv))
+    }
+
+    /// Returns the command removing the container of the task `op_name`,
+    /// which outlives the engine when it is killed.
+    pub fn remove(&self, op_name: &str) -> std::process::Command {
+        let mut command = std::process::Command::new(&self.engine);
+        command
+            .args(["rm", "-f", &container_name(op_name)])
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null());
+        command
+    }
+}
+
+/// Returns the compose file `docker compose` would use from `cwd`, with its
+/// override when there is one, as they are no longer looked up once a file
+/// is given.
+fn default_compose_files(cwd: &Path) -> Option<Vec<PathBuf>> {
+    cwd.ancestors().find_map(|dir| {
+        COMPOSE_FILES.iter().find_map(|(file, over)| {
+            let file = dir.join(file);
+            let over = dir.join(over);
+            file.is_file()
+                .then(|| Vec::from_iter([file].into_iter().chain(over.is_file().then_some(over))))
+        })
+    })
+}
+
+/// Writes the compose file running the `service` of the container `name`
+/// with an init, which forwards the stop signal to the command, and returns
+/// its path.
+fn init_override(name: &str, service: &str) -> Result<PathBuf> {
+    let init = BTreeMap::from([("init", true)]);
+    let services = BTreeMap::from([("services", BTreeMap::from([(service, init)]))]);
+    let path = std::env::temp_dir().join(format!("{name}.compose.yaml"));
+    std::fs::write(&path, serde_yaml::to_string(&services)?)
+        .with_context(|| format!("cannot write {}", path.display()))?;
+    Ok(path)
+}
+
+/// Bind mounts need an absolute host path.
+fn absolute_volume(cwd: &Path, volume: &str) -> String {
+    match volume.split_once(':') {
+        Some((host, container)) if host.starts_with('.') => {
+            let host = path_clean::clean(cwd.join(host));
+            format!("{}:{container}", host.display())
+        }
+        _ => volume.to_string(),
+    }
+}
+
+/// Makes sure each container sets either an image or a service, and runs a
+/// command.
+pub fn validate_containers(ops: &Ops) -> Result<()> {
+    for (op_name, task) in ops {
+        let Some(container) = &task.container else {
+            continue;
+        };
+        match (&container.image, &container.service) {
+            (Some(_), Some(_)) => {
+                bail!("container of {op_name} sets both an image and a service")
+            }
+            (None, None) => {
+                bail!("container of {op_name} must set an image or a service")
+            }
+            _ => {}
+        }
+        if task.command.is_none() {
+            bail!("task {op_name} runs in a container, it must have a command");
+        }
+        if !task.limits.is_empty() {
+            bail!(
+                "limits of {op_name} are not applied in a container, set them in its options (e.g. --ulimit)"
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn container(yaml: &str) -> Container {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    fn argv(container: &Container) -> Vec<String> {
+        let (engine, args) = container
+            .wrap(
+                "api",
+                Path::new("/repo/api"),
+                &["PORT".to_string(), "TOKEN".to_string()],
+                "sh".to_string(),
+                vec!["-c".to_string(), "npm start".to_string()],
+            )
+            .unwrap();
+        let mut argv = vec![engine];
+        argv.extend(args);
+        argv
+    }
+
+    #[test]
+    fn runs_an_image() {
+        let container = container(
+            r#"
+            image: node:20
+            volumes: [./cache:/cache, /tmp:/tmp]
+            ports: 3000:3000
+            options: --network=host
+            "#,
+        );
+        assert_eq!(
+            argv(&container),
+            vec![
+                "docker",
+                "run",
+                "--rm",
+                "--init",
+                "--name",
+                container_name("api").as_str(),
+                "-v",
+                "/repo/api:/repo/api",
+                "-w",
+                "/repo/api",
+                "-v",
+                "/repo/api/cache:/cache",
+                "-v",
+                "/tmp:/tmp",
+                "-p",
+                "3000:3000",
+                "-e",
+                "PORT",
+                "-e",
+                "TOKEN",
+                "--network=host",
+                "node:20",
+                "sh",
+                "-c",
+                "npm start",
+            ]
+        );
+    }
+
+    #[test]
+    fn runs_a_compose_service() {
+        let container = container(
+            r#"
+            service: api
+            compose_file: ../compose.yaml
+            engine: podman
+            workdir: /app
+            "#,
+        );
+        let argv = argv(&container);
+        let init = std::env::temp_dir().join(format!("{}.compose.yaml", container_name("api")));
+        assert_eq!(
+            std::fs::read_to_string(&init).unwrap(),
+            "services:\n  api:\n    init: true\n"
+        );
+        assert_eq!(
+            argv,
+            vec![
+                "podman",
+                "compose",
+                "-f",
+                "../compose.yaml",
+                "-f",
+                init.display().to_string().as_str(),
+                "run",
+                "--rm",
+                "--name",
+                container_name("api").as_str(),
+                "-v",
+                "/repo/api:/app",
+                "-w",
+                "/app",
+                "-e",
+                "PORT",
+                "-e",
+                "TOKEN",
+                "api",
+                "sh",
+                "-c",
+                "npm start",
+            ]
+        );
+    }
+
+    #[test]
+    fn looks_up_the_compose_files() {
+        let dir = std::env::temp_dir().join(format!("whiz-compose-{}", std::process::id()));
+        let cwd = dir.join("api");
+        std::fs::create_dir_all(&cwd).unwrap();
+        std::fs::write(dir.join("docker-compose.yml"), "").unwrap();
+        std::fs::write(dir.join("docker-compose.override.yml"), "").unwrap();
+        assert_eq!(
+            default_compose_files(&cwd),
+            Some(vec![
+                dir.join("docker-compose.yml"),
+                dir.join("docker-compose.override.yml")
+            ])
+        );
+        std::fs::write(cwd.join("compose.yaml"), "").unwrap();
+        assert_eq!(
+            default_compose_files(&cwd),
+            Some(vec![cwd.join("compose.yaml")])
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn names_containers_after_tasks() {
+        assert_eq!(
+            container_name("web app/1"),
+            format!("whiz-web-app-1-{}", std::process::id())
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_containers() {
+        let ops = |yaml: &str| serde_yaml::from_str::<Ops>(yaml).unwrap();
+        let err = validate_containers(&ops(
+            "api: {command: npm start, container: {image: node, service: api}}",
+        ))
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "container of api sets both an image and a service"
+        );
+        let err =
+            validate_containers(&ops("api: {command: npm start, container: {}}")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "container of api must set an image or a service"
+        );
+        let err = validate_containers(&ops("api: {container: {image: node}}")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "task api runs in a container, it must have a command"
+        );
+        assert!(
+            validate_containers(&ops("api: {command: npm start, container: {image: node}}"))
+                .is_ok()
+        );
+    }
+}
@@ -0,0 +1,222 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use serde_yaml::{Mapping, Value};
+
+use super::Lift;
+
+/// Top-level keys which are settings rather than tasks. The later files
+/// replace them, or extend them key by key when they are maps (`env`).
+const SETTINGS: [&str; 8] = [
+    "env",
+    "reload_all_on",
+    "file_mode",
+    "watch_root",
+    "allow_unsafe_names",
+    "default_task",
+    "entrypoints",
+    "profiles",
+];
+
+/// Reads the config file at `path` along with the files it lists in
+/// `include`, relative to it, e.g.:
+///
+/// ```yaml
+/// include:
+///     - services/api/whiz.yaml
+///     - services/web/whiz.yaml
+/// ```
+///
+/// Each file comes after the ones it includes, in order, so that its
+/// settings win. A task may only be defined in several files as long as
+/// the definitions are the same, e.g. when two files include a third one.
+pub fn load(path: &Path) -> Result<Value> {
+    load_from(path, &mut Vec::new())
+}
+
+/// Same as [`load`], `chain` being the files including this one, to tell
+/// include cycles.
+fn load_from(path: &Path, chain: &mut Vec<PathBuf>) -> Result<Value> {
+    let canonical = path
+        .canonicalize()
+        .with_context(|| format!("cannot read {}", path.display()))?;
+    if let Some(at) = chain.iter().position(|included| *included == canonical) {
+        let cycle = chain[at..]
+            .iter()
+            .chain([&canonical])
+            .map(|path| path.display().to_string())
+            .collect::<Vec<_>>()
+            .join(" -> ");
+        bail!("include cycle: {cycle}");
+    }
+
+    let mut config =
+        super::read_value(path).with_context(|| format!("cannot read {}", path.display()))?;
+    let includes = match config.as_mapping_mut().and_then(|c| c.remove("include")) {
+        Some(includes) => serde_yaml::from_value::<Lift<String>>(includes)
+            .with_context(|| format!("{}: include must list paths", path.display()))?
+            .resolve(),
+        None => return Ok(config),
+    };
+
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut merged = Mapping::new();
+    chain.push(canonical);
+    for include in includes {
+        let included = load_from(&dir.join(&include), chain)?;
+        merge(&mut merged, included, Path::new(&include))?;
+    }
+    chain.pop();
+    merge(&mut merged, config, path)?;
+    Ok(merged.into())
+}
+
+/// Merges the config read from `source` into `merged`, see [`load`].
+fn merge(merged: &mut Mapping, config: Value, source: &Path) -> Result<()> {
+    let config = match config {
+        Value::Mapping(config) => config,
+        // an empty file
+        Value::Null => return Ok(()),
+        _ => bail!("{}: expected tasks", source.display()),
+    };
+    for (key, value) in config {
+        let name = key.as_str().unwrap_or_default();
+        if SETTINGS.contains(&name) {
+            match (merged.get_mut(&key), value) {
+                (Some(Value::Mapping(previous)), Value::Mapping(value)) => previous.extend(value),
+                (_, value) => {
+                    merged.insert(key, value);
+                }
+            }
+            continue;
+        }
+        match merged.get(&key) {
+            Some(previous) if *previous != value => {
+                bail!(
+                    "task {name} of {} differs from the one of a file included before",
+                    source.display()
+                );
+            }
+            _ => {
+                merged.insert(key, value);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RawConfig;
+
+    fn config_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("whiz-include-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        for (path, content) in files {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn merges_the_included_files_first() {
+        let dir = config_dir(
+            "merge",
+            &[
+                (
+                    "whiz.yaml",
+                    "include: [api/whiz.yaml, web.toml]\nenv: { PORT: '80' }\nall: { command: ls }",
+                ),
+                (
+                    "api/whiz.yaml",
+                    "include: ../shared.yaml\nenv: { PORT: '3000', HOST: api }\napi: { command: cargo run }",
+                ),
+                ("web.toml", "include = 'shared.yaml'\n[web]\ncommand = 'npm start'"),
+                ("shared.yaml", "db: { command: postgres }"),
+            ],
+        );
+
+        let config = load(&dir.join("whiz.yaml")).unwrap();
+        let expected: Value = serde_yaml::from_str(
+            r#"
+            db: { command: postgres }
+            env: { PORT: "80", HOST: api }
+            api: { command: cargo run }
+            web: { command: npm start }
+            all: { command: ls }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config, expected);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn depends_on_tasks_of_other_files() {
+        let dir = config_dir(
+            "depends",
+            &[
+                ("whiz.yaml", "include: [db.yaml, web.yaml]"),
+                ("db.yaml", "db: { command: postgres }"),
+                ("web.yaml", "web: { command: npm start, depends_on: db }"),
+            ],
+        );
+
+        let config = RawConfig::from_path(&dir.join("whiz.yaml"), None).unwrap();
+        assert_eq!(config.ops["web"].depends_on.resolve(), vec!["db"]);
+        // the dependency is still checked
+        std::fs::write(dir.join("db.yaml"), "postgres: { command: postgres }").unwrap();
+        assert!(RawConfig::from_path(&dir.join("whiz.yaml"), None).is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_tasks_defined_twice() {
+        let dir = config_dir(
+            "twice",
+            &[
+                ("whiz.yaml", "include: other.yaml\ndb: { command: mysql }"),
+                ("other.yaml", "db: { command: postgres }"),
+            ],
+        );
+
+        let err = load(&dir.join("whiz.yaml")).unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("whiz.yaml differs from the one of a file included before"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_include_cycles() {
+        let dir = config_dir(
+            "cycle",
+            &[
+                ("whiz.yaml", "include: a.yaml"),
+                ("a.yaml", "include: b.yaml"),
+                ("b.yaml", "include: a.yaml"),
+            ],
+        );
+
+        let err = load(&dir.join("whiz.yaml")).unwrap_err();
+        let dir = dir.canonicalize().unwrap();
+        let (a, b) = (dir.join("a.yaml"), dir.join("b.yaml"));
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "include cycle: {} -> {} -> {}",
+                a.display(),
+                b.display(),
+                a.display()
+            )
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
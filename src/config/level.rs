@@ -0,0 +1,178 @@
+use anyhow::{Context, Result};
+use indexmap::IndexMap;
+use regex::Regex;
+use serde::Deserialize;
+
+/// Severity of an output line, from the least to the most severe.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+lazy_static::lazy_static! {
+    /// Level names as most loggers print them, `FATAL` and `CRITICAL`
+    /// counting as errors.
+    static ref DEFAULT_LEVEL: Regex = Regex::new(
+        r"(?i)\b(trace|debug|info|warn(?:ing)?|error|fatal|crit(?:ical)?)\b"
+    )
+    .unwrap();
+}
+
+/// Drops the lines of a task below its `min_level`.
+///
+/// The level of a line is the first level name it contains (`INFO`,
+/// `warning`, ...), or, when the task sets `levels`, the most severe one
+/// whose regex matches it. A line without level (e.g. a stack trace) has
+/// the level of the line before it, and is kept when no line had any.
+#[derive(Clone, Debug)]
+pub struct LevelFilter {
+    min_level: Level,
+    patterns: Vec<(Level, Regex)>,
+    last: Option<Level>,
+}
+
+impl LevelFilter {
+    pub fn new(min_level: Level, patterns: &IndexMap<Level, String>) -> Result<Self> {
+        let mut patterns = patterns
+            .iter()
+            .map(|(level, pattern)| {
+                let regex = Regex::new(pattern)
+                    .with_context(|| format!("invalid level pattern {pattern:?}"))?;
+                Ok((*level, regex))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        patterns.sort_by(|(a, _), (b, _)| b.cmp(a));
+        Ok(Self {
+            min_level,
+            patterns,
+            last: None,
+        })
+    }
+
+    /// Returns the level of `line`, if it tells one.
+    pub fn detect(&self, line: &str) -> Option<Level> {
+        if !self.patterns.is_empty() {
+            return self
+                .patterns
+                .iter()
+                .find(|(_, regex)| regex.is_match(line))
+                .map(|(level, _)| *level);
+        }
+        let name = DEFAULT_LEVEL.captures(line)?[1].to_lowercase();
+        Some(match name.as_str() {
+            "trace" => Level::Trace,
+            "debug" => Level::Debug,
+            "info" => Level::Info,
+            "warn" | "warning" => Level::Warn,
+            _ => Level::Error,
+        })
+    }
+
+    /// Returns whether `line` is shown, given the lines before it.
+    pub fn keeps(&mut self, line: &str) -> bool {
+        if let Some(level) = self.detect(line) {
+            self.last = Some(level);
+        }
+        self.last.is_none_or(|level| level >= self.min_level)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kept(filter: &mut LevelFilter, lines: &[&str]) -> Vec<String> {
+        lines
+            .iter()
+            .filter(|line| filter.keeps(line))
+            .map(|line| line.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn orders_levels() {
+        assert!(Level::Trace < Level::Debug);
+        assert!(Level::Info < Level::Warn);
+        assert!(Level::Warn < Level::Error);
+    }
+
+    #[test]
+    fn detects_level_names() {
+        let filter = LevelFilter::new(Level::Info, &IndexMap::new()).unwrap();
+
+        assert_eq!(filter.detect("2024-01-01 INFO started"), Some(Level::Info));
+        assert_eq!(filter.detect("[warning] deprecated"), Some(Level::Warn));
+        assert_eq!(filter.detect("level=error msg=down"), Some(Level::Error));
+        assert_eq!(filter.detect("FATAL: out of memory"), Some(Level::Error));
+        assert_eq!(filter.detect(" DEBUG  INFO"), Some(Level::Debug));
+        assert_eq!(filter.detect("informative"), None);
+    }
+
+    #[test]
+    fn filters_out_info_keeping_errors() {
+        let mut filter = LevelFilter::new(Level::Warn, &IndexMap::new()).unwrap();
+
+        assert_eq!(
+            kept(
+                &mut filter,
+                &[
+                    "INFO listening",
+                    "ERROR connection refused",
+                    "    at connect (net.js:10)",
+                    "INFO retrying",
+                    "    after 1s",
+                    "WARN slow query",
+                ]
+            ),
+            vec![
+                "ERROR connection refused",
+                "    at connect (net.js:10)",
+                "WARN slow query",
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_lines_before_any_level() {
+        let mut filter = LevelFilter::new(Level::Error, &IndexMap::new()).unwrap();
+
+        assert_eq!(
+            kept(&mut filter, &["starting", "INFO up", "ready"]),
+            vec!["starting"]
+        );
+    }
+
+    #[test]
+    fn detects_custom_levels_most_severe_first() {
+        let patterns = IndexMap::from([
+            (Level::Info, r"^I\d{4}".to_string()),
+            (Level::Error, r"^E\d{4}|panic".to_string()),
+        ]);
+        let mut filter = LevelFilter::new(Level::Warn, &patterns).unwrap();
+
+        assert_eq!(
+            filter.detect("I0101 panic handler installed"),
+            Some(Level::Error)
+        );
+        assert_eq!(filter.detect("ERROR but not in the format"), None);
+        assert_eq!(
+            kept(
+                &mut filter,
+                &["I0101 started", "E0101 failed", "I0101 retry"]
+            ),
+            vec!["E0101 failed"]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_pattern() {
+        let patterns = IndexMap::from([(Level::Error, "(".to_string())]);
+        let err = LevelFilter::new(Level::Warn, &patterns).unwrap_err();
+        assert_eq!(err.to_string(), r#"invalid level pattern "(""#);
+    }
+}
@@ -0,0 +1,351 @@
+#[cfg(unix)]
+use std::cell::RefCell;
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail};
+use indexmap::IndexMap;
+use serde::{de, Deserialize, Deserializer};
+
+/// Resources of a task which can be limited, named as in `limits.conf`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Resource {
+    /// Open file descriptors.
+    Nofile,
+    /// Processes of the user.
+    Nproc,
+    /// Address space, in bytes.
+    As,
+    /// Stack size, in bytes.
+    Stack,
+    /// Size of the core dumps, in bytes.
+    Core,
+    /// Size of the files written, in bytes.
+    Fsize,
+    /// CPU time, in seconds.
+    Cpu,
+}
+
+const RESOURCES: [Resource; 7] = [
+    Resource::Nofile,
+    Resource::Nproc,
+    Resource::As,
+    Resource::Stack,
+    Resource::Core,
+    Resource::Fsize,
+    Resource::Cpu,
+];
+
+impl Resource {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Resource::Nofile => "nofile",
+            Resource::Nproc => "nproc",
+            Resource::As => "as",
+            Resource::Stack => "stack",
+            Resource::Core => "core",
+            Resource::Fsize => "fsize",
+            Resource::Cpu => "cpu",
+        }
+    }
+
+    /// Sizes accept the `K`, `M`, `G` and `T` suffixes.
+    fn is_size(&self) -> bool {
+        matches!(
+            self,
+            Resource::As | Resource::Stack | Resource::Core | Resource::Fsize
+        )
+    }
+
+    /// Parses a limit of this resource, or `unlimited`.
+    pub fn parse_limit(&self, value: &str) -> anyhow::Result<Limit> {
+        if value == "unlimited" {
+            return Ok(Limit::Unlimited);
+        }
+        let limit = if self.is_size() {
+            parse_size(value)
+        } else {
+            value.parse().map_err(|_| anyhow!("expected a number"))
+        };
+        limit
+            .map(Limit::Value)
+            .map_err(|err| anyhow!("invalid {} limit {value:?}: {err}", self.name()))
+    }
+
+    /// Resource of `setrlimit` setting this one.
+    #[cfg(unix)]
+    fn raw(&self) -> RawResource {
+        match self {
+            Resource::Nofile => libc::RLIMIT_NOFILE,
+            Resource::Nproc => libc::RLIMIT_NPROC,
+            Resource::As => libc::RLIMIT_AS,
+            Resource::Stack => libc::RLIMIT_STACK,
+            Resource::Core => libc::RLIMIT_CORE,
+            Resource::Fsize => libc::RLIMIT_FSIZE,
+            Resource::Cpu => libc::RLIMIT_CPU,
+        }
+    }
+}
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+type RawResource = libc::__rlimit_resource_t;
+#[cfg(all(unix, not(all(target_os = "linux", target_env = "gnu"))))]
+type RawResource = libc::c_int;
+
+impl FromStr for Resource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        RESOURCES
+            .into_iter()
+            .find(|resource| resource.name() == s)
+            .ok_or_else(|| {
+                let names = RESOURCES.map(|resource| resource.name()).join(", ");
+                anyhow!("unknown limit {s}, expected one of {names}")
+            })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Limit {
+    Value(u64),
+    Unlimited,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Limit::Value(value) => write!(f, "{value}"),
+            Limit::Unlimited => write!(f, "unlimited"),
+        }
+    }
+}
+
+/// Parses a size in bytes such as `512`, `64K` or `2G`, in powers of 1024.
+pub fn parse_size(s: &str) -> anyhow::Result<u64> {
+    let (digits, suffix) = s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()));
+    let factor: u64 = match suffix.to_ascii_uppercase().as_str() {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        "T" => 1 << 40,
+        _ => bail!("expected bytes or a K, M, G or T suffix"),
+    };
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|value| value.checked_mul(factor))
+        .ok_or_else(|| anyhow!("expected bytes or a K, M, G or T suffix"))
+}
+
+/// Resource limits applied to the process of a task, e.g.:
+///
+/// ```yaml
+/// limits:
+///     nofile: 4096
+///     nproc: 512
+///     as: 2G
+/// ```
+///
+/// They are only applied on Unix.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Limits(Vec<(Resource, Limit)>);
+
+impl Limits {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Runs `spawn`, the process it forks setting the limits (soft and
+    /// hard, as `ulimit` does) with `setrlimit` before it execs its command.
+    /// Fails without spawning anything for a limit above the hard one
+    /// whiz runs with, which only a privileged user may raise.
+    #[cfg(unix)]
+    pub fn apply<T>(&self, spawn: impl FnOnce() -> T) -> anyhow::Result<T> {
+        if self.is_empty() {
+            return Ok(spawn());
+        }
+        static HANDLER: std::sync::Once = std::sync::Once::new();
+        HANDLER.call_once(|| unsafe {
+            libc::pthread_atfork(None, None, Some(set_forked_limits));
+        });
+
+        let limits = self.rlimits()?;
+        FORKING.with(|forking| *forking.borrow_mut() = limits);
+        // the next forks of the thread run without them, even on a panic
+        struct Reset;
+        impl Drop for Reset {
+            fn drop(&mut self) {
+                FORKING.with(|forking| forking.borrow_mut().clear());
+            }
+        }
+        let _reset = Reset;
+        Ok(spawn())
+    }
+
+    #[cfg(not(unix))]
+    pub fn apply<T>(&self, spawn: impl FnOnce() -> T) -> anyhow::Result<T> {
+        Ok(spawn())
+    }
+
+    /// Returns the limits as `setrlimit` takes them.
+    #[cfg(unix)]
+    fn rlimits(&self) -> anyhow::Result<Vec<(RawResource, libc::rlimit)>> {
+        let privileged = unsafe { libc::geteuid() } == 0;
+        self.0
+            .iter()
+            .map(|(resource, limit)| {
+                let value = match limit {
+                    Limit::Value(value) => *value as libc::rlim_t,
+                    Limit::Unlimited => libc::RLIM_INFINITY,
+                };
+                let mut current = libc::rlimit {
+                    rlim_cur: 0,
+                    rlim_max: 0,
+                };
+                if unsafe { libc::getrlimit(resource.raw(), &mut current) } != 0 {
+                    return Err(std::io::Error::last_os_error().into());
+                }
+                if value > current.rlim_max && !privileged {
+                    bail!(
+                        "cannot raise the {} limit above {}",
+                        resource.name(),
+                        current.rlim_max
+                    );
+                }
+                let limit = libc::rlimit {
+                    rlim_cur: value,
+                    rlim_max: value,
+                };
+                Ok((resource.raw(), limit))
+            })
+            .collect()
+    }
+}
+
+#[cfg(unix)]
+thread_local! {
+    /// Limits of the process the thread is forking, see [`Limits::apply`].
+    static FORKING: RefCell<Vec<(RawResource, libc::rlimit)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Sets the limits of the thread which forked in the new process, before
+/// it execs anything. Runs between the fork and the exec, where nothing
+/// may allocate nor lock, and where only exiting can tell a failure.
+#[cfg(unix)]
+unsafe extern "C" fn set_forked_limits() {
+    let _ = FORKING.try_with(|forking| {
+        let Ok(limits) = forking.try_borrow() else {
+            return;
+        };
+        for (resource, limit) in limits.iter() {
+            if libc::setrlimit(*resource, limit) != 0 {
+                libc::_exit(127);
+            }
+        }
+    });
+}
+
+impl<'de> Deserialize<'de> for Limits {
+    /// Numbers are taken as is, sizes being in bytes.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Number(u64),
+            Text(String),
+        }
+
+        IndexMap::<String, Raw>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(name, value)| {
+                let resource: Resource = name.parse()?;
+                let limit = match value {
+                    Raw::Number(value) => Limit::Value(value),
+                    Raw::Text(value) => resource.parse_limit(&value)?,
+                };
+                Ok((resource, limit))
+            })
+            .collect::<anyhow::Result<_>>()
+            .map(Limits)
+            .map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_sizes() {
+        assert_eq!(parse_size("512").unwrap(), 512);
+        assert_eq!(parse_size("64K").unwrap(), 64 * 1024);
+        assert_eq!(parse_size("2g").unwrap(), 2 << 30);
+        assert_eq!(parse_size("1T").unwrap(), 1 << 40);
+        assert!(parse_size("2GB").is_err());
+        assert!(parse_size("G").is_err());
+        assert!(parse_size("-1").is_err());
+        assert!(parse_size("99999999999T").is_err());
+    }
+
+    #[test]
+    fn deserialize() {
+        let limits: Limits =
+            serde_yaml::from_str("{nofile: 4096, nproc: '512', as: 2G, cpu: unlimited}").unwrap();
+        assert_eq!(
+            limits,
+            Limits(vec![
+                (Resource::Nofile, Limit::Value(4096)),
+                (Resource::Nproc, Limit::Value(512)),
+                (Resource::As, Limit::Value(2 << 30)),
+                (Resource::Cpu, Limit::Unlimited),
+            ])
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_limits() {
+        let err = serde_yaml::from_str::<Limits>("{nofiles: 16}").unwrap_err();
+        assert!(
+            err.to_string().starts_with(
+                "unknown limit nofiles, expected one of nofile, nproc, as, stack, core, fsize, cpu"
+            ),
+            "{err}"
+        );
+        let err = serde_yaml::from_str::<Limits>("{nofile: 4K}").unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with(r#"invalid nofile limit "4K": expected a number"#),
+            "{err}"
+        );
+        let err = serde_yaml::from_str::<Limits>("{stack: 8X}").unwrap_err();
+        assert!(
+            err.to_string().starts_with(
+                r#"invalid stack limit "8X": expected bytes or a K, M, G or T suffix"#
+            ),
+            "{err}"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn applies_limits_to_the_spawned_process() {
+        let ulimit = || {
+            subprocess::Exec::shell("ulimit -n; ulimit -Hn; ulimit -f")
+                .capture()
+                .unwrap()
+                .stdout_str()
+        };
+        let before = ulimit();
+
+        let limits = Limits(vec![
+            (Resource::Nofile, Limit::Value(16)),
+            (Resource::Fsize, Limit::Value(1 << 20)),
+        ]);
+        assert_eq!(limits.apply(ulimit).unwrap(), "16\n16\n2048\n");
+        // neither whiz nor the next processes it spawns
+        assert_eq!(ulimit(), before);
+        assert_eq!(Limits::default().apply(ulimit).unwrap(), before);
+    }
+}
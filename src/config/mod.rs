@@ -3,24 +3,53 @@ use std::{
     path::{Path, PathBuf},
     str::FromStr,
     sync::Arc,
+    time::Duration,
 };
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use indexmap::IndexMap;
-use serde::Deserialize;
+use regex::Regex;
+use serde::{de, Deserialize, Deserializer};
+use subprocess::ExitStatus;
 
 use std::fs::File;
 use std::io::Read;
 
+use crate::file_mode::FileMode;
+use crate::utils::resolve_watch_root;
+
 pub mod color;
+pub mod container;
+pub mod include;
+pub mod level;
+pub mod limits;
+pub mod names;
 pub mod ops;
+pub mod pin;
 pub mod pipe;
+pub mod procfile;
+pub mod requires;
+pub mod schedule;
+pub mod status;
+pub mod stream;
+pub mod watch_events;
+
+use container::Container;
+use level::{Level, LevelFilter};
+use limits::Limits;
+use pin::Pins;
+use pipe::{Pipe, PipeCollisions};
+use requires::Requires;
+use schedule::{Every, Overlap, Schedule};
+use stream::{Encoding, Stream};
+use watch_events::WatchEventKind;
+
+use self::{
+    color::ColorOption,
+    ops::{Ops, OpsDiff},
+};
 
-use pipe::Pipe;
-
-use self::{color::ColorOption, ops::Ops};
-
-#[derive(Deserialize, Debug, Clone, Default)]
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
 #[serde(untagged)]
 pub enum Lift<T> {
     More(Vec<T>),
@@ -39,12 +68,184 @@ impl<T: std::clone::Clone> Lift<T> {
     }
 }
 
-#[derive(Deserialize, Debug, Clone)]
+/// When a dependent starts after one of the tasks it depends on.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Condition {
+    /// Once it exits, whatever its status.
+    #[default]
+    Completed,
+    /// Once it exits successfully, the dependent is blocked otherwise.
+    Success,
+}
+
+/// How many times a failing task runs again, `3` or `{count: 3, backoff_ms:
+/// 500}` to wait 500ms before the first retry and twice as long before each
+/// next one.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Retries {
+    pub count: u32,
+    /// Delay before the first retry, doubling, instead of `retry_delay`.
+    pub backoff: Option<Duration>,
+}
+
+impl<'de> Deserialize<'de> for Retries {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct Full {
+            count: u32,
+            backoff_ms: Option<u64>,
+        }
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum RawRetries {
+            Count(u32),
+            Full(Full),
+        }
+
+        let retries = RawRetries::deserialize(deserializer).map_err(|_| {
+            de::Error::custom(
+                "invalid retries, expected a count such as 3 or {count: 3, backoff_ms: 500}",
+            )
+        })?;
+        Ok(match retries {
+            RawRetries::Count(count) => Retries {
+                count,
+                backoff: None,
+            },
+            RawRetries::Full(Full { count, backoff_ms }) => Retries {
+                count,
+                backoff: backoff_ms.map(Duration::from_millis),
+            },
+        })
+    }
+}
+
+/// When a task starts again by itself once it exits, e.g. a dev server
+/// which crashed, once its `retries` ran out.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    #[default]
+    Never,
+    OnFailure,
+    Always,
+}
+
+impl RestartPolicy {
+    /// Whether a run exiting with `status` is restarted.
+    pub fn restarts(&self, status: &ExitStatus) -> bool {
+        match self {
+            RestartPolicy::Never => false,
+            RestartPolicy::OnFailure => !status.success(),
+            RestartPolicy::Always => true,
+        }
+    }
+}
+
+/// Signal a task is asked to stop with, before it is killed once its
+/// `kill_timeout` is over. Tasks are terminated outside of Unix.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum StopSignal {
+    #[default]
+    Sigterm,
+    Sigint,
+    Sigquit,
+    Sighup,
+    Sigusr1,
+    Sigusr2,
+}
+
+impl StopSignal {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StopSignal::Sigterm => "SIGTERM",
+            StopSignal::Sigint => "SIGINT",
+            StopSignal::Sigquit => "SIGQUIT",
+            StopSignal::Sighup => "SIGHUP",
+            StopSignal::Sigusr1 => "SIGUSR1",
+            StopSignal::Sigusr2 => "SIGUSR2",
+        }
+    }
+
+    #[cfg(unix)]
+    pub fn number(&self) -> libc::c_int {
+        match self {
+            StopSignal::Sigterm => libc::SIGTERM,
+            StopSignal::Sigint => libc::SIGINT,
+            StopSignal::Sigquit => libc::SIGQUIT,
+            StopSignal::Sighup => libc::SIGHUP,
+            StopSignal::Sigusr1 => libc::SIGUSR1,
+            StopSignal::Sigusr2 => libc::SIGUSR2,
+        }
+    }
+}
+
+/// Dependency given as an object instead of a name.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct Dependency {
+    task: String,
+    #[serde(default)]
+    condition: Condition,
+}
+
+/// Program `command` is passed to, split as a shell would (`python3 -u
+/// -c`) or given argument by argument (`[python3, -u, -c]`), or one of
+/// the config `entrypoints` (`{preset: py}`), see
+/// [`crate::exec::resolve_entrypoints`].
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Entrypoint {
+    Line(String),
+    Args(Vec<String>),
+    Preset { preset: String },
+}
+
+impl Entrypoint {
+    /// An empty entrypoint falls back to the default shell.
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Entrypoint::Line(line) => line.is_empty(),
+            Entrypoint::Args(args) => args.is_empty(),
+            Entrypoint::Preset { .. } => false,
+        }
+    }
+}
+
+impl std::fmt::Display for Entrypoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Entrypoint::Line(line) => write!(f, "{line}"),
+            Entrypoint::Args(args) => write!(f, "{}", args.join(" ")),
+            Entrypoint::Preset { preset } => write!(f, "{preset}"),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
 #[serde(deny_unknown_fields)]
 pub struct Task {
+    /// What the task does, shown by `list-jobs --long` and `graph`. Not
+    /// compared when the config changes as it never affects a run.
+    pub description: Option<String>,
+
     pub workdir: Option<String>,
     pub command: Option<String>,
-    pub entrypoint: Option<String>,
+    pub entrypoint: Option<Entrypoint>,
+
+    /// Shell command run to completion before each run, which is skipped
+    /// and fails when the hook fails.
+    #[serde(default)]
+    pub before: Option<String>,
+
+    /// Shell command run to completion once a run exits, before its
+    /// dependents start. Its failure is only reported.
+    #[serde(default)]
+    pub after: Option<String>,
 
     #[serde(default)]
     pub watch: Lift<String>,
@@ -52,15 +253,47 @@ pub struct Task {
     #[serde(default)]
     pub ignore: Lift<String>,
 
+    /// Kinds of changes of the watched files reloading the task, the ones
+    /// of `--watch-events` when empty.
+    #[serde(default)]
+    pub watch_events: Lift<WatchEventKind>,
+
+    /// Waits for the changes of the watched files to settle this long
+    /// before reloading the task once for all of them.
+    #[serde(default)]
+    pub debounce: Option<Every>,
+
+    /// Tasks reloaded along with this one when its watched files change,
+    /// and in turn the ones they run on change.
+    #[serde(default)]
+    pub on_change_run: Lift<String>,
+
     #[serde(default)]
     pub env: HashMap<String, String>,
 
     #[serde(default)]
     pub env_file: Lift<String>,
 
+    /// Binaries and variables the task needs to start, see [`Requires`].
+    #[serde(default)]
+    pub requires: Requires,
+
+    /// Tasks to run before this one, a name ending with `?` is an
+    /// optional dependency which is dropped when missing or filtered out.
+    /// A task can also be given as `{task: migrate, condition: success}`,
+    /// see [`Condition`].
     #[serde(default)]
     pub depends_on: Lift<String>,
 
+    /// Optional dependencies, see [`ops::resolve_optional_dependencies`].
+    #[serde(skip)]
+    pub optional_depends_on: Vec<String>,
+
+    /// Dependencies which must exit successfully for this task to start,
+    /// see [`Condition::Success`].
+    #[serde(skip)]
+    pub success_depends_on: Vec<String>,
+
     /// Map of output redirections with the format:
     /// `regular expressiong` -> `pipe`
     ///
@@ -68,13 +301,13 @@ pub struct Task {
     /// can be redirected to:
     ///
     /// - whiz: creating a new tab for the incoming messages.
-    /// Format: `whiz://{tab_name}`
+    ///   Format: `whiz://{tab_name}`
     ///
     /// - /dev/null: silence the matched content.
-    /// Format: `/dev/null` or `file:///dev/null`
+    ///   Format: `/dev/null` or `file:///dev/null`
     ///
     /// - file: saving the matched content in a log file.
-    /// Format: `path` or `file:///{path}`
+    ///   Format: `path` or `file:///{path}`
     ///
     /// # NOTE
     ///
@@ -83,8 +316,174 @@ pub struct Task {
     #[serde(default)]
     pub pipe: HashMap<String, String>,
 
+    /// Maximum number of tabs the pipes of this task can create from their
+    /// captures (e.g. `whiz://user_$1`), the next ones are folded into a
+    /// single overflow tab. `0` keeps these lines in the task tab instead.
+    #[serde(default = "default_max_dynamic_tabs")]
+    pub max_dynamic_tabs: usize,
+
     #[serde(default)]
     pub color: IndexMap<String, String>,
+
+    /// Regexes of the lines pinned at the top of the panel, see [`Pins`].
+    #[serde(default)]
+    pub pin: Lift<String>,
+
+    /// Regex of the line telling the task is ready, e.g. a service that
+    /// never exits: its dependents start then instead of when it exits.
+    #[serde(default)]
+    pub ready_when: Option<String>,
+
+    /// Drops the lines below this level, see [`LevelFilter`].
+    #[serde(default)]
+    pub min_level: Option<Level>,
+
+    /// Regexes detecting the level of the lines instead of the level
+    /// names, see [`LevelFilter`].
+    #[serde(default)]
+    pub levels: IndexMap<Level, String>,
+
+    /// Ends the whole session when this task exits, using its exit code.
+    #[serde(default)]
+    pub primary: bool,
+
+    /// Keeps the task running when it closes its output (e.g. wrappers
+    /// that `exec` another binary) until its process actually exits.
+    #[serde(default)]
+    pub long_running: bool,
+
+    /// Hides the output of the task from its tab, which still shows its
+    /// status. Pipes still apply.
+    #[serde(default)]
+    pub quiet: bool,
+
+    /// Only runs from the command menu (`o`) or by hand, never when whiz
+    /// starts, see [`ops::validate_on_demand_tasks`].
+    #[serde(default)]
+    pub on_demand: bool,
+
+    /// Runs the task until it succeeds once, e.g. a codegen at startup:
+    /// its files are not watched, and then neither its upstream reruns it
+    /// nor its reruns by hand reload its dependents. Also read as
+    /// `oneshot`.
+    #[serde(default, alias = "oneshot")]
+    pub once: bool,
+
+    /// Reads the output line by line or as it comes, see [`Stream`].
+    #[serde(default)]
+    pub stream: Stream,
+
+    /// Decodes the output from this encoding instead of UTF-8, see
+    /// [`Encoding`].
+    #[serde(default)]
+    pub encoding: Encoding,
+
+    /// Shows stderr interleaved with stdout as one stream, or else its
+    /// lines apart in their own style, which pipes and pins skip.
+    #[serde(default = "default_merge_stderr")]
+    pub merge_stderr: bool,
+
+    /// Runs the command on a pseudo-terminal instead of pipes, so that it
+    /// keeps its colors, its stderr merged. Only on Unix.
+    #[serde(default)]
+    pub tty: bool,
+
+    /// Resource limits of the process, see [`Limits`].
+    #[serde(default)]
+    pub limits: Limits,
+
+    /// Container the command runs in, see [`Container`].
+    #[serde(default)]
+    pub container: Option<Container>,
+
+    /// Runs the task on a cron schedule, or at an interval as `every`,
+    /// instead of when whiz starts, see [`Schedule`].
+    #[serde(default)]
+    pub schedule: Option<Schedule>,
+
+    /// Runs the task at this interval instead of when whiz starts, see
+    /// [`Every`].
+    #[serde(default)]
+    pub every: Option<Every>,
+
+    /// What the schedule does while the task still runs, see [`Overlap`].
+    #[serde(default)]
+    pub overlap: Overlap,
+
+    /// Runs the task again when it fails after starting with whiz or from
+    /// its upstream, before its dependents go on, see [`Retries`]. Also
+    /// read as `retry`.
+    #[serde(default, alias = "retry")]
+    pub retries: Retries,
+
+    /// Waits this long before each retry, none by default.
+    #[serde(default)]
+    pub retry_delay: Option<Every>,
+
+    /// Doubles `retry_delay` after each retry.
+    #[serde(default)]
+    pub retry_backoff: bool,
+
+    /// Starts the task again once it exits, see [`RestartPolicy`].
+    #[serde(default)]
+    pub restart: RestartPolicy,
+
+    /// Kills a run lasting longer, which then exits with
+    /// [`crate::actors::command::TIMED_OUT`].
+    #[serde(default)]
+    pub timeout: Option<Every>,
+
+    /// Time the task has to exit once asked to stop before it is killed,
+    /// the one of `--kill-timeout` by default.
+    #[serde(default, alias = "stop_grace")]
+    pub kill_timeout: Option<Every>,
+
+    /// Signal asking the task to stop, e.g. `SIGINT` for the programs
+    /// only shutting down cleanly on Ctrl-C.
+    #[serde(default)]
+    pub stop_signal: StopSignal,
+}
+
+fn default_max_dynamic_tabs() -> usize {
+    20
+}
+
+fn default_merge_stderr() -> bool {
+    true
+}
+
+impl Task {
+    /// A task with neither `command` nor `entrypoint` runs nothing and
+    /// only forwards its watch events to its dependents.
+    pub fn is_trigger_only(&self) -> bool {
+        self.command.is_none() && self.entrypoint.is_none()
+    }
+
+    /// Returns how long to wait before the `retry`-th retry, from 1.
+    pub fn retry_delay(&self, retry: u32) -> Duration {
+        let (delay, backoff) = match self.retries.backoff {
+            Some(delay) => (delay, true),
+            None => (
+                self.retry_delay
+                    .map(|Every(delay)| delay)
+                    .unwrap_or_default(),
+                self.retry_backoff,
+            ),
+        };
+        match backoff {
+            true => delay * 2u32.saturating_pow(retry.saturating_sub(1)),
+            false => delay,
+        }
+    }
+
+    /// Returns the schedule the task runs on, if any.
+    pub fn schedule(&self) -> Option<Schedule> {
+        match (&self.schedule, self.every) {
+            (Some(schedule), _) => Some(schedule.clone()),
+            (None, Some(every)) => Some(Schedule::Every(every)),
+            (None, None) => None,
+        }
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -92,6 +491,34 @@ pub struct RawConfig {
     #[serde(default)]
     pub env: HashMap<String, String>,
 
+    /// Files reloading every task when they change, relative to the
+    /// config file.
+    #[serde(default)]
+    pub reload_all_on: Lift<String>,
+
+    /// Permissions of the files whiz creates, unless a pipe sets its own.
+    #[serde(default)]
+    pub file_mode: Option<FileMode>,
+
+    /// Directory watched recursively, relative to the base dir which it
+    /// defaults to.
+    #[serde(default)]
+    pub watch_root: Option<String>,
+
+    /// Accepts task names with any character, see [`names::validate_names`].
+    #[serde(default)]
+    pub allow_unsafe_names: bool,
+
+    /// Task whose panel is focused when the view opens, instead of the
+    /// first one.
+    #[serde(default)]
+    pub default_task: Option<String>,
+
+    /// Entrypoints the tasks refer to by name, see
+    /// [`crate::exec::resolve_entrypoints`].
+    #[serde(default)]
+    pub entrypoints: HashMap<String, Entrypoint>,
+
     #[serde(flatten)]
     pub ops: IndexMap<String, Task>,
 }
@@ -99,14 +526,41 @@ pub struct RawConfig {
 #[derive(Debug, Clone)]
 pub struct ConfigInner {
     pub base_dir: Arc<Path>,
+    /// Directory watched recursively, the tasks still run relative to
+    /// `base_dir`.
+    pub watch_root: Arc<Path>,
     pub env: HashMap<String, String>,
+    pub reload_all_on: Vec<String>,
+    pub file_mode: Option<FileMode>,
+    /// All the tasks of the config file, see [`ConfigInner::active_ops`]
+    /// for the ones that should run.
     pub ops: Ops,
     pub pipes_map: HashMap<String, Vec<Pipe>>,
+    pub pins_map: HashMap<String, Pins>,
+    /// Regexes of the tasks setting `ready_when`.
+    pub ready_map: HashMap<String, Regex>,
+    /// Filters of the tasks setting a `min_level`.
+    pub level_filters: HashMap<String, LevelFilter>,
     pub colors_map: HashMap<String, Vec<ColorOption>>,
+    /// Time the tasks without `kill_timeout` have to exit once asked to
+    /// stop, see [`ConfigBuilder::kill_timeout`].
+    pub kill_timeout: Option<Duration>,
+    /// Task whose panel is focused first, see [`ConfigBuilder::default_task`].
+    pub default_task: Option<String>,
+    active: HashSet<String>,
 }
 
 impl ConfigInner {
-    pub fn from_raw(config: RawConfig, base_dir: PathBuf) -> Result<Self> {
+    pub fn from_raw(mut config: RawConfig, base_dir: PathBuf) -> Result<Self> {
+        crate::exec::resolve_entrypoints(&config.entrypoints, &mut config.ops)?;
+        container::validate_containers(&config.ops)?;
+        schedule::validate_schedules(&config.ops)?;
+        ops::validate_trigger_tasks(&config.ops)?;
+        ops::validate_on_change_run(&config.ops)?;
+        ops::validate_on_demand_tasks(&config.ops)?;
+        ops::validate_default_task(&config.ops, config.default_task.as_deref())?;
+        crate::exec::validate_entrypoints(&config.ops)?;
+
         let pipes_map = config
             .get_pipes_map()
             .context("Error while getting pipes")?;
@@ -115,14 +569,94 @@ impl ConfigInner {
             .get_colors_map()
             .context("Error while getting colors")?;
 
+        let pins_map = config.get_pins_map()?;
+        let ready_map = config.get_ready_map()?;
+        let level_filters = config.get_level_filters()?;
+
+        let watch_root = match &config.watch_root {
+            Some(dir) => resolve_watch_root(&base_dir, dir)?,
+            None => base_dir.clone(),
+        };
+
         Ok(Self {
             base_dir: base_dir.into(),
+            watch_root: watch_root.into(),
             env: config.env,
+            reload_all_on: config.reload_all_on.resolve(),
+            file_mode: config.file_mode,
+            active: config.ops.keys().cloned().collect(),
             ops: config.ops,
             pipes_map,
+            pins_map,
+            ready_map,
+            level_filters,
             colors_map,
+            kill_timeout: None,
+            default_task: config.default_task,
         })
     }
+
+    /// Restricts the active tasks to the ones provided in `run` and
+    /// their dependencies, see [`ops::filter_jobs`].
+    pub fn filter_jobs(&mut self, run: &[String]) -> Result<()> {
+        let mut ops = self.ops.clone();
+        ops::filter_jobs(&mut ops, run)?;
+        self.active = ops.into_keys().collect();
+        Ok(())
+    }
+
+    /// Restricts the active tasks to exactly the ones provided in `run`,
+    /// see [`ops::select_jobs`].
+    pub fn select_jobs(&mut self, run: &[String]) -> Result<()> {
+        let mut ops = self.ops.clone();
+        ops::select_jobs(&mut ops, run)?;
+        self.active = ops.into_keys().collect();
+        Ok(())
+    }
+
+    /// Returns the tasks that should run once filtered, in config order.
+    ///
+    /// Everything that spawns, displays or watches tasks must go through
+    /// this view so that it only refers to tasks backed by an actor.
+    pub fn active_ops(&self) -> Ops {
+        let mut ops: Ops = self
+            .ops
+            .iter()
+            .filter(|(op_name, _)| self.active.contains(*op_name))
+            .map(|(op_name, task)| (op_name.clone(), task.clone()))
+            .collect();
+        ops::prune_dependencies(&mut ops);
+        ops
+    }
+
+    /// Finds the files the pipes of several active tasks write to, see
+    /// [`PipeCollisions`].
+    pub fn pipe_collisions(&self) -> PipeCollisions {
+        let cwds = self
+            .active_ops()
+            .iter()
+            .map(|(op_name, task)| (op_name.clone(), task.get_absolute_workdir(&self.base_dir)))
+            .collect();
+        PipeCollisions::find(&self.pipes_map, &cwds)
+    }
+
+    /// Returns the active tasks to update to move to the `next` config,
+    /// every task being changed when the root `env` or `file_mode` is.
+    pub fn diff(&self, next: &ConfigInner) -> OpsDiff {
+        let mut diff = ops::diff_ops(&self.active_ops(), &next.active_ops());
+        diff.root = ops::changed_env(&self.env, &next.env);
+        if self.file_mode != next.file_mode {
+            diff.root.push("file_mode".to_string());
+        }
+        if !diff.root.is_empty() {
+            diff.changed = next
+                .active_ops()
+                .into_keys()
+                .filter(|op_name| !diff.added.contains(op_name))
+                .collect();
+        }
+        diff
+    }
 }
 
 pub type Config = Arc<ConfigInner>;
@@ -133,19 +667,156 @@ impl FromStr for RawConfig {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Self::from_reader(s.as_bytes())
+        Self::from_reader(s.as_bytes(), None)
+    }
+}
+
+/// Reads the config file at `path` in the format its name tells: a
+/// `Procfile` (see [`procfile::is_procfile`]), TOML or else YAML.
+fn read_value(path: &Path) -> Result<serde_yaml::Value> {
+    if procfile::is_procfile(path) {
+        return procfile::parse(File::open(path)?);
+    }
+    if is_toml(path) {
+        // TOML has no anchors, so there are no merge keys to apply
+        return Ok(toml::from_str(&std::fs::read_to_string(path)?)?);
+    }
+    parse_yaml(File::open(path)?)
+}
+
+fn parse_yaml(reader: impl Read) -> Result<serde_yaml::Value> {
+    let mut config: serde_yaml::Value = serde_yaml::from_reader(reader)?;
+    config.apply_merge()?;
+    Ok(config)
+}
+
+/// Whether `path` is read as TOML instead of YAML, from its extension.
+fn is_toml(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("toml"))
+}
+
+/// Removes the `profiles` section and applies the overrides of `profile`
+/// onto the tasks, each field it sets replacing the one of the task. The
+/// `env` of a profile overrides the root variables one by one.
+fn apply_profile(config: &mut serde_yaml::Value, profile: Option<&str>) -> Result<()> {
+    let Some(config) = config.as_mapping_mut() else {
+        return Ok(());
+    };
+    let profiles = config.remove("profiles");
+    let Some(profile) = profile else {
+        return Ok(());
+    };
+
+    let Some(overrides) = profiles.as_ref().and_then(|profiles| profiles.get(profile)) else {
+        let available = profiles
+            .as_ref()
+            .and_then(|profiles| profiles.as_mapping())
+            .map(|profiles| {
+                profiles
+                    .keys()
+                    .filter_map(|name| name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            })
+            .unwrap_or_default();
+        let suggestion = profiles
+            .as_ref()
+            .and_then(|profiles| profiles.as_mapping())
+            .map(|profiles| {
+                ops::did_you_mean(profile, profiles.keys().filter_map(|name| name.as_str()))
+            })
+            .unwrap_or_default();
+        bail!("unknown profile {profile}{suggestion} (available: {available})");
+    };
+    let Some(overrides) = overrides.as_mapping() else {
+        bail!("profile {profile} must map tasks to the fields they override");
+    };
+
+    for (task_name, fields) in overrides {
+        let task_name = task_name.as_str().unwrap_or_default();
+        let Some(task) = config
+            .get_mut(task_name)
+            .and_then(|task| task.as_mapping_mut())
+        else {
+            let tasks = config
+                .keys()
+                .filter_map(|name| name.as_str())
+                .filter(|name| *name != "env");
+            let suggestion = ops::did_you_mean(task_name, tasks);
+            bail!("profile {profile} overrides unknown task {task_name}{suggestion}");
+        };
+        let Some(fields) = fields.as_mapping() else {
+            bail!("profile {profile} must map {task_name} to the fields it overrides");
+        };
+        for (field, value) in fields {
+            task.insert(field.clone(), value.clone());
+        }
+    }
+    Ok(())
+}
+
+/// Replaces the dependencies given as objects by their name, returning
+/// the ones each task needs to succeed, before the tasks are parsed.
+fn split_conditions(config: &mut serde_yaml::Value) -> Result<HashMap<String, Vec<String>>> {
+    let mut conditions = HashMap::new();
+    let Some(config) = config.as_mapping_mut() else {
+        return Ok(conditions);
+    };
+    for (task_name, task) in config.iter_mut() {
+        let task_name = task_name.as_str().unwrap_or_default();
+        let Some(depends_on) = task.get_mut("depends_on") else {
+            continue;
+        };
+        let entries = match depends_on {
+            serde_yaml::Value::Sequence(entries) => entries.iter_mut().collect(),
+            depends_on => vec![depends_on],
+        };
+        for entry in entries.into_iter().filter(|entry| entry.is_mapping()) {
+            let dependency: Dependency = serde_yaml::from_value(entry.clone())
+                .with_context(|| format!("task {task_name}: invalid depends_on"))?;
+            if dependency.condition == Condition::Success {
+                let name = dependency.task.trim_end_matches('?').to_string();
+                conditions
+                    .entry(task_name.to_string())
+                    .or_insert_with(Vec::new)
+                    .push(name);
+            }
+            *entry = dependency.task.into();
+        }
     }
+    Ok(conditions)
 }
 
 impl RawConfig {
-    pub fn from_file(file: &File) -> Result<RawConfig> {
-        Self::from_reader(file)
+    pub fn from_file(file: &File, profile: Option<&str>) -> Result<RawConfig> {
+        Self::from_reader(file, profile)
+    }
+
+    /// Reads the config file at `path` in its format, along with the
+    /// files it includes, see [`include::load`].
+    pub fn from_path(path: &Path, profile: Option<&str>) -> Result<RawConfig> {
+        Self::from_value(include::load(path)?, profile)
+    }
+
+    fn from_reader(reader: impl Read, profile: Option<&str>) -> Result<RawConfig> {
+        Self::from_value(parse_yaml(reader)?, profile)
     }
 
-    fn from_reader(reader: impl Read) -> Result<RawConfig> {
-        let mut config: serde_yaml::Value = serde_yaml::from_reader(reader)?;
-        config.apply_merge()?;
+    fn from_value(mut config: serde_yaml::Value, profile: Option<&str>) -> Result<RawConfig> {
+        if config.get("include").is_some() {
+            bail!("include is only read from a config file, relative to it");
+        }
+        apply_profile(&mut config, profile)?;
+        let conditions = split_conditions(&mut config)?;
         let mut config: RawConfig = serde_yaml::from_value(config)?;
+        for (task_name, success_depends_on) in conditions {
+            if let Some(task) = config.ops.get_mut(&task_name) {
+                task.success_depends_on = success_depends_on;
+            }
+        }
+        names::validate_names(&config.ops, config.allow_unsafe_names)?;
+        ops::resolve_optional_dependencies(&mut config.ops);
 
         // make sure config file is a `Directed Acyclic Graph`
         ops::build_dag(&config.ops)?;
@@ -171,6 +842,45 @@ impl RawConfig {
         Ok(pipes)
     }
 
+    /// Parses the pins of each task, see [`Pins`].
+    pub fn get_pins_map(&self) -> Result<HashMap<String, Pins>> {
+        self.ops
+            .iter()
+            .map(|(task_name, task)| {
+                let pins = Pins::new(&task.pin.resolve())
+                    .map_err(|err| anyhow!("task {task_name}: {err}"))?;
+                Ok((task_name.to_owned(), pins))
+            })
+            .collect()
+    }
+
+    /// Parses the `ready_when` regex of each task setting one.
+    pub fn get_ready_map(&self) -> Result<HashMap<String, Regex>> {
+        self.ops
+            .iter()
+            .filter_map(|(task_name, task)| Some((task_name, task.ready_when.as_ref()?)))
+            .map(|(task_name, pattern)| {
+                let regex = Regex::new(pattern)
+                    .map_err(|err| anyhow!("task {task_name}: invalid ready_when: {err}"))?;
+                Ok((task_name.to_owned(), regex))
+            })
+            .collect()
+    }
+
+    /// Parses the level patterns of each task setting a `min_level`, see
+    /// [`LevelFilter`].
+    pub fn get_level_filters(&self) -> Result<HashMap<String, LevelFilter>> {
+        self.ops
+            .iter()
+            .filter_map(|(task_name, task)| Some((task_name, task.min_level?, &task.levels)))
+            .map(|(task_name, min_level, levels)| {
+                let filter = LevelFilter::new(min_level, levels)
+                    .map_err(|err| anyhow!("task {task_name}: {err}"))?;
+                Ok((task_name.to_owned(), filter))
+            })
+            .collect()
+    }
+
     pub fn get_colors_map(&self) -> Result<HashMap<String, Vec<ColorOption>>> {
         let mut colors = HashMap::new();
 
@@ -178,7 +888,7 @@ impl RawConfig {
             let task_color_options: Vec<ColorOption> = task
                 .color
                 .iter()
-                .filter_map(|(r, c)| ColorOption::from((&r, &c)).ok())
+                .filter_map(|(r, c)| ColorOption::from((r, c)).ok())
                 .collect();
 
             colors.insert(task_name.to_owned(), task_color_options);
@@ -195,6 +905,8 @@ impl RawConfig {
             // array used to iterate all the elements and skip removed elements
             let mut dependencies = ops::get_dependencies(&self.ops, &job_name);
             let mut simplified_dependencies = dependencies.clone();
+            // the dependent must hear about the failure of these ones itself
+            let success_depends_on = self.ops[&job_name].success_depends_on.clone();
 
             while let Some(dependency) = dependencies.pop() {
                 let child_dependencies =
@@ -204,7 +916,9 @@ impl RawConfig {
                 // remove all the dependencies that are dependency
                 // of the current `dependency`
                 dependencies.retain(|job_name| !child_dependencies.contains(job_name));
-                simplified_dependencies.retain(|job_name| !child_dependencies.contains(job_name));
+                simplified_dependencies.retain(|job_name| {
+                    !child_dependencies.contains(job_name) || success_depends_on.contains(job_name)
+                });
             }
 
             let job_operator = self.ops.get_mut(&job_name).unwrap();
@@ -212,6 +926,7 @@ impl RawConfig {
         }
     }
 
+    #[cfg(test)]
     fn filter_jobs(&mut self, run: &[String]) -> Result<()> {
         ops::filter_jobs(&mut self.ops, run)
     }
@@ -219,18 +934,40 @@ impl RawConfig {
 
 impl ConfigInner {
     pub fn build_dag(&self) -> Result<Dag> {
-        ops::build_dag(&self.ops)
+        ops::build_dag(&self.active_ops())
     }
 }
 
+#[derive(Clone)]
 pub struct ConfigBuilder {
     path: PathBuf,
     filter: Option<Vec<String>>,
+    no_deps: bool,
+    reload_all_on: Vec<String>,
+    base_dir: Option<PathBuf>,
+    watch_root: Option<PathBuf>,
+    profile: Option<String>,
+    kill_timeout: Option<Duration>,
+    default_task: Option<String>,
 }
 
 impl ConfigBuilder {
     pub fn new(path: PathBuf) -> Self {
-        Self { path, filter: None }
+        Self {
+            path,
+            filter: None,
+            no_deps: false,
+            reload_all_on: Vec::new(),
+            base_dir: None,
+            watch_root: None,
+            profile: None,
+            kill_timeout: None,
+            default_task: None,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
     }
 
     pub fn filter(mut self, filter: Vec<String>) -> Self {
@@ -238,20 +975,76 @@ impl ConfigBuilder {
         self
     }
 
+    /// Runs only the tasks of the filter, without their dependencies.
+    pub fn no_deps(mut self, toggle: bool) -> Self {
+        self.no_deps = toggle;
+        self
+    }
+
+    /// Adds to the `reload_all_on` globs of the config file.
+    pub fn reload_all_on(mut self, globs: Vec<String>) -> Self {
+        self.reload_all_on = globs;
+        self
+    }
+
+    /// Overrides the directory of the config file as the one the tasks,
+    /// watches and env files are relative to.
+    pub fn base_dir(mut self, base_dir: Option<PathBuf>) -> Self {
+        self.base_dir = base_dir;
+        self
+    }
+
+    /// Overrides the `watch_root` of the config file.
+    pub fn watch_root(mut self, watch_root: Option<PathBuf>) -> Self {
+        self.watch_root = watch_root;
+        self
+    }
+
+    /// Applies the overrides of a profile of the config file.
+    pub fn profile(mut self, profile: Option<String>) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Sets the time the tasks without `kill_timeout` have to exit once
+    /// asked to stop.
+    pub fn kill_timeout(mut self, kill_timeout: Option<Duration>) -> Self {
+        self.kill_timeout = kill_timeout;
+        self
+    }
+
+    /// Overrides the `default_task` of the config file.
+    pub fn default_task(mut self, default_task: Option<String>) -> Self {
+        self.default_task = default_task;
+        self
+    }
+
     pub fn build(self) -> Result<Config> {
-        let file = File::open(&self.path)?;
-        let mut config = RawConfig::from_file(&file)?;
+        let config = RawConfig::from_path(&self.path, self.profile.as_deref())?;
+        let base_dir = match self.base_dir {
+            Some(base_dir) => base_dir,
+            None => self.path.parent().unwrap().into(),
+        };
+        let mut config = ConfigInner::from_raw(config, base_dir)?;
+        config.reload_all_on.extend(self.reload_all_on);
+        config.kill_timeout = self.kill_timeout;
+        if let Some(task) = self.default_task {
+            ops::validate_default_task(&config.ops, Some(&task))?;
+            config.default_task = Some(task);
+        }
+        if let Some(watch_root) = self.watch_root {
+            config.watch_root = watch_root.into();
+        }
 
         if let Some(filter) = self.filter {
-            config
-                .filter_jobs(&filter)
-                .context("Error while filtering jobs")?;
+            match self.no_deps {
+                true => config.select_jobs(&filter),
+                false => config.filter_jobs(&filter),
+            }
+            .context("Error while filtering jobs")?;
         }
 
-        Ok(Arc::new(ConfigInner::from_raw(
-            config,
-            self.path.parent().unwrap().into(),
-        )?))
+        Ok(Arc::new(config))
     }
 }
 
@@ -327,6 +1120,29 @@ mod tests {
             assert_array_not_strict!(jobs, expected_jobs);
         }
 
+        #[test]
+        fn builds_levels() {
+            let config: RawConfig = CONFIG_EXAMPLE.parse().unwrap();
+
+            assert_eq!(
+                ops::build_levels(&config.ops).unwrap(),
+                vec![
+                    vec!["a", "y", "not_child_dependency"],
+                    vec!["b", "z"],
+                    vec!["c"],
+                    vec!["d", "with_alias"],
+                ]
+            );
+        }
+
+        #[test]
+        fn counts_dependents() {
+            let config: RawConfig = CONFIG_EXAMPLE.parse().unwrap();
+
+            assert_eq!(ops::count_dependents(&config.ops, "a"), 4);
+            assert_eq!(ops::count_dependents(&config.ops, "d"), 0);
+        }
+
         #[test]
         fn gets_dependencies_from_config_file() {
             let config: RawConfig = CONFIG_EXAMPLE.parse().unwrap();
@@ -397,7 +1213,9 @@ mod tests {
             let mut config: RawConfig = CONFIG_EXAMPLE.parse().unwrap();
 
             let expected_err = [
-                "job 'doesnt_exist' not found in config file.",
+                "job 'test_dependecy' not found in config file.",
+                "",
+                "did you mean: test_dependency, test?",
                 "",
                 "Valid jobs are:",
                 "  - not_test_dependency",
@@ -407,7 +1225,7 @@ mod tests {
             .join("\n");
 
             let mut err_message = String::new();
-            let run = ["doesnt_exist".to_string()];
+            let run = ["test_dependecy".to_string()];
 
             if let Err(err) = config.filter_jobs(&run) {
                 err_message = err.to_string();
@@ -416,6 +1234,34 @@ mod tests {
             assert_eq!(err_message, expected_err);
         }
 
+        #[test]
+        fn filters_active_ops() {
+            let config: RawConfig = CONFIG_EXAMPLE.parse().unwrap();
+            let mut config = ConfigInner::from_raw(config, PathBuf::from(".")).unwrap();
+
+            config.filter_jobs(&["test".to_string()]).unwrap();
+
+            let active: Vec<_> = config.active_ops().into_keys().collect();
+            assert_eq!(active, vec!["test_dependency", "test"]);
+            assert_eq!(config.ops.len(), 3);
+
+            let dag: Vec<_> = config.build_dag().unwrap().into_keys().collect();
+            assert_array_not_strict!(dag, active);
+        }
+
+        #[test]
+        fn selects_jobs_without_dependencies() {
+            let config: RawConfig = CONFIG_EXAMPLE.parse().unwrap();
+            let mut config = ConfigInner::from_raw(config, PathBuf::from(".")).unwrap();
+
+            config.select_jobs(&["test".to_string()]).unwrap();
+
+            let active = config.active_ops();
+            assert_eq!(Vec::from_iter(active.keys()), vec!["test"]);
+            assert!(active["test"].depends_on.resolve().is_empty());
+            assert!(config.select_jobs(&["doesnt_exist".to_string()]).is_err());
+        }
+
         #[test]
         fn doesnt_filter_jobs() {
             let mut config: RawConfig = CONFIG_EXAMPLE.parse().unwrap();
@@ -430,6 +1276,828 @@ mod tests {
         }
     }
 
+    mod jobs_details {
+        use super::*;
+
+        const CONFIG_EXAMPLE: &str = r#"
+            api:
+                workdir: api
+                command: echo api
+
+            web:
+                workdir: web
+                command: echo web
+
+            root:
+                command: echo root
+        "#;
+
+        #[test]
+        fn detects_missing_workdirs() {
+            let base_dir = std::env::temp_dir().join("whiz_jobs_details");
+            std::fs::create_dir_all(base_dir.join("api")).unwrap();
+            let _ = std::fs::remove_dir_all(base_dir.join("web"));
+
+            let config: RawConfig = CONFIG_EXAMPLE.parse().unwrap();
+            let details = ops::get_jobs_details(&config.ops, &base_dir, false);
+
+            let summary: Vec<_> = details
+                .iter()
+                .map(|d| (d.name.as_str(), d.workdir.as_str(), d.exists, d.dirty))
+                .collect();
+            assert_eq!(
+                summary,
+                vec![
+                    ("api", "api", true, None),
+                    ("root", ".", true, None),
+                    ("web", "web", false, None),
+                ]
+            );
+        }
+
+        #[test]
+        fn lists_descriptions() {
+            let config: RawConfig = r#"
+            api:
+                description: serves the REST API
+                command: echo api
+                depends_on: db
+
+            db:
+                description: |
+                    postgres with the seed data,
+                    reset on each run
+                command: echo db
+
+            web:
+                command: echo web
+            "#
+            .parse()
+            .unwrap();
+
+            let expected = [
+                "  - api (db)",
+                "      serves the REST API",
+                "  - db",
+                "      postgres with the seed data,",
+                "      reset on each run",
+                "  - web",
+            ]
+            .join("\n");
+            assert_eq!(ops::get_described_list_of_jobs(&config.ops), expected);
+            // only shown on demand
+            assert!(!ops::get_formatted_list_of_jobs(&config.ops).contains("REST"));
+        }
+
+        #[test]
+        fn formats_details_as_columns() {
+            let details = vec![
+                ops::JobDetail {
+                    name: "api".to_string(),
+                    workdir: "services/api".to_string(),
+                    exists: true,
+                    dirty: Some(true),
+                },
+                ops::JobDetail {
+                    name: "web".to_string(),
+                    workdir: "web".to_string(),
+                    exists: false,
+                    dirty: None,
+                },
+            ];
+
+            let expected = [
+                "  job  workdir       exists   dirty",
+                "  api  services/api  yes      yes",
+                "  web  web           missing  -",
+            ]
+            .join("\n");
+
+            assert_eq!(ops::format_jobs_details(&details), expected);
+        }
+    }
+
+    mod trigger_tasks {
+        use super::*;
+
+        fn config_from_str(s: &str) -> Result<ConfigInner> {
+            ConfigInner::from_raw(s.parse()?, PathBuf::from("."))
+        }
+
+        #[test]
+        fn accepts_trigger_task() {
+            let config = config_from_str(
+                r#"
+                sources:
+                    watch: "src/**"
+
+                build:
+                    command: echo build
+                    depends_on: sources
+                "#,
+            )
+            .unwrap();
+
+            assert!(config.ops.get("sources").unwrap().is_trigger_only());
+            assert!(!config.ops.get("build").unwrap().is_trigger_only());
+        }
+
+        #[test]
+        fn rejects_trigger_task_without_watch() {
+            let err = config_from_str(
+                r#"
+                sources: {}
+
+                build:
+                    command: echo build
+                    depends_on: sources
+                "#,
+            )
+            .unwrap_err();
+
+            assert_eq!(
+                err.to_string(),
+                "task sources has no command nor entrypoint, it must watch files"
+            );
+        }
+
+        #[test]
+        fn rejects_trigger_task_without_dependents() {
+            let err = config_from_str(
+                r#"
+                sources:
+                    watch: "src/**"
+                "#,
+            )
+            .unwrap_err();
+
+            assert_eq!(
+                err.to_string(),
+                "task sources has no command nor entrypoint, it must have dependents or run tasks on change"
+            );
+        }
+    }
+
+    mod on_demand {
+        use super::*;
+
+        fn config_from_str(s: &str) -> Result<ConfigInner> {
+            ConfigInner::from_raw(s.parse()?, PathBuf::from("."))
+        }
+
+        #[test]
+        fn rejects_on_demand_tasks_in_the_graph() {
+            let err = |config: &str| config_from_str(config).unwrap_err().to_string();
+
+            assert_eq!(
+                err(r#"
+                fmt:
+                    command: cargo fmt
+                    on_demand: true
+                    watch: "src/**"
+                "#),
+                "task fmt runs on demand, it cannot watch files nor run on a schedule"
+            );
+            assert_eq!(
+                err(r#"
+                build:
+                    command: cargo build
+                fmt:
+                    command: cargo fmt
+                    on_demand: true
+                    depends_on: build
+                "#),
+                "task fmt runs on demand, it cannot depend on other tasks"
+            );
+            assert_eq!(
+                err(r#"
+                fmt:
+                    command: cargo fmt
+                    on_demand: true
+                build:
+                    command: cargo build
+                    depends_on: fmt
+                "#),
+                "task build depends on fmt, which only runs on demand"
+            );
+            assert_eq!(
+                err(r#"
+                fmt:
+                    command: cargo fmt
+                    on_demand: true
+                build:
+                    command: cargo build
+                    watch: "src/**"
+                    on_change_run: fmt
+                "#),
+                "task build runs fmt on change, which only runs on demand"
+            );
+        }
+
+        #[test]
+        fn rejects_unknown_default_task() {
+            let err = config_from_str(
+                r#"
+                default_task: ap
+                api:
+                    command: cargo run
+                "#,
+            )
+            .unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "default_task ap is not a task, did you mean: api?"
+            );
+            assert!(config_from_str(
+                r#"
+                fmt:
+                    command: cargo fmt
+                    on_demand: true
+                build:
+                    command: cargo build
+                "#
+            )
+            .is_ok());
+        }
+    }
+
+    mod on_change_run {
+        use super::*;
+
+        fn config_from_str(s: &str) -> Result<ConfigInner> {
+            ConfigInner::from_raw(s.parse()?, PathBuf::from("."))
+        }
+
+        #[test]
+        fn accepts_tasks_run_on_change() {
+            let config = config_from_str(
+                r#"
+                schemas:
+                    watch: "schemas/**"
+                    on_change_run: client
+
+                client:
+                    command: echo client
+                    on_change_run: [docs, tests]
+
+                docs:
+                    command: echo docs
+
+                tests:
+                    command: echo tests
+                "#,
+            )
+            .unwrap();
+
+            assert_eq!(
+                config.ops["client"].on_change_run.resolve(),
+                vec!["docs", "tests"]
+            );
+        }
+
+        #[test]
+        fn rejects_unknown_tasks() {
+            let err = config_from_str(
+                r#"
+                client:
+                    command: echo client
+                    watch: "src/**"
+                    on_change_run: docs
+                "#,
+            )
+            .unwrap_err();
+
+            assert_eq!(
+                err.to_string(),
+                "task client runs unknown task docs on change"
+            );
+        }
+
+        #[test]
+        fn rejects_cycles() {
+            let err = config_from_str(
+                r#"
+                schemas:
+                    command: echo schemas
+                    on_change_run: client
+                client:
+                    command: echo client
+                    on_change_run: docs
+                docs:
+                    command: echo docs
+                    on_change_run: schemas
+                tests:
+                    command: echo tests
+                    on_change_run: client
+                "#,
+            )
+            .unwrap_err();
+
+            assert_eq!(
+                err.to_string(),
+                "cycle detected in on_change_run with one of schemas, client, docs, tests"
+            );
+        }
+    }
+
+    mod optional_dependencies {
+        use super::*;
+
+        const CONFIG_EXAMPLE: &str = r#"
+            db:
+                command: echo db
+
+            web:
+                command: echo web
+                depends_on:
+                    - db?
+                    - cache?
+
+            api:
+                command: echo api
+                depends_on: web
+        "#;
+
+        #[test]
+        fn keeps_present_optional_dependency() {
+            let config: RawConfig = CONFIG_EXAMPLE.parse().unwrap();
+
+            let web = config.ops.get("web").unwrap();
+            assert_eq!(web.depends_on.resolve(), vec!["db"]);
+            assert_eq!(web.optional_depends_on, vec!["db"]);
+
+            let dag: Vec<_> = ops::build_dag(&config.ops).unwrap().into_keys().collect();
+            assert_eq!(dag, vec!["api", "web", "db"]);
+        }
+
+        #[test]
+        fn drops_filtered_optional_dependency() {
+            let config: RawConfig = CONFIG_EXAMPLE.parse().unwrap();
+            let mut config = ConfigInner::from_raw(config, PathBuf::from(".")).unwrap();
+
+            config.filter_jobs(&["api".to_string()]).unwrap();
+
+            let active = config.active_ops();
+            assert_array_not_strict!(Vec::from_iter(active.keys()), vec!["api", "web"]);
+            assert!(active.get("web").unwrap().depends_on.resolve().is_empty());
+            assert!(config.build_dag().is_ok());
+        }
+    }
+
+    mod conditions {
+        use super::*;
+
+        #[test]
+        fn reads_dependencies_given_as_objects() {
+            let config: RawConfig = r#"
+                migrate:
+                    command: echo migrate
+                cache:
+                    command: echo cache
+                server:
+                    command: echo server
+                    depends_on:
+                        - {task: migrate, condition: success}
+                        - {task: cache, condition: completed}
+                worker:
+                    command: echo worker
+                    depends_on: {task: server}
+            "#
+            .parse()
+            .unwrap();
+
+            let server = config.ops.get("server").unwrap();
+            assert_eq!(server.depends_on.resolve(), vec!["migrate", "cache"]);
+            assert_eq!(server.success_depends_on, vec!["migrate"]);
+            let worker = config.ops.get("worker").unwrap();
+            assert_eq!(worker.depends_on.resolve(), vec!["server"]);
+            assert!(worker.success_depends_on.is_empty());
+        }
+
+        #[test]
+        fn keeps_dependencies_which_must_succeed() {
+            let config: RawConfig = r#"
+                migrate:
+                    command: echo migrate
+                seed:
+                    command: echo seed
+                    depends_on: migrate
+                server:
+                    command: echo server
+                    depends_on: [seed, {task: migrate, condition: success}]
+            "#
+            .parse()
+            .unwrap();
+
+            // not simplified away, as seed does not tell whether it failed
+            let server = config.ops.get("server").unwrap();
+            assert_eq!(server.depends_on.resolve(), vec!["seed", "migrate"]);
+        }
+
+        #[test]
+        fn rejects_unknown_conditions() {
+            let err = r#"
+                migrate:
+                    command: echo migrate
+                server:
+                    command: echo server
+                    depends_on: [{task: migrate, condition: started}]
+            "#
+            .parse::<RawConfig>()
+            .unwrap_err();
+            assert_eq!(err.to_string(), "task server: invalid depends_on");
+            assert!(format!("{err:#}").contains("unknown variant `started`"));
+        }
+    }
+
+    mod diff {
+        use super::*;
+
+        fn config_from_str(s: &str) -> ConfigInner {
+            ConfigInner::from_raw(s.parse().unwrap(), PathBuf::from(".")).unwrap()
+        }
+
+        const CONFIG_EXAMPLE: &str = r#"
+            db:
+                command: echo db
+
+            web:
+                command: echo web
+                depends_on: db
+
+            old:
+                command: echo old
+        "#;
+
+        #[test]
+        fn detects_added_removed_and_changed_tasks() {
+            let previous = config_from_str(CONFIG_EXAMPLE);
+            let next = config_from_str(
+                r#"
+                db:
+                    command: echo db
+
+                web:
+                    command: echo web --port 8080
+                    depends_on: db
+
+                new:
+                    command: echo new
+                "#,
+            );
+
+            assert_eq!(
+                previous.diff(&next),
+                OpsDiff {
+                    added: vec!["new".to_string()],
+                    removed: vec!["old".to_string()],
+                    changed: vec!["web".to_string()],
+                    fields: IndexMap::from([("web".to_string(), vec!["command".to_string()])]),
+                    root: Vec::new(),
+                }
+            );
+            assert!(next.diff(&next).is_empty());
+        }
+
+        #[test]
+        fn detects_changed_dependencies() {
+            let previous = config_from_str(CONFIG_EXAMPLE);
+            let next = config_from_str(
+                r#"
+                db:
+                    command: echo db
+
+                web:
+                    command: echo web
+
+                old:
+                    command: echo old
+                    depends_on: db
+                "#,
+            );
+
+            assert_eq!(previous.diff(&next).changed, vec!["web", "old"]);
+        }
+
+        #[test]
+        fn changes_every_task_with_the_root_env() {
+            let previous = config_from_str(CONFIG_EXAMPLE);
+            let next = config_from_str(
+                r#"
+                env:
+                    PORT: "8080"
+
+                db:
+                    command: echo db
+
+                web:
+                    command: echo web
+                    depends_on: db
+
+                old:
+                    command: echo old
+                "#,
+            );
+
+            let diff = previous.diff(&next);
+            assert_eq!(diff.changed, vec!["db", "web", "old"]);
+            assert_eq!(diff.to_string(), "changed db, web, old; root env.PORT");
+        }
+
+        #[test]
+        fn lists_changed_fields_of_each_task() {
+            let previous = config_from_str(
+                r#"
+                web:
+                    command: echo web
+                    env:
+                        PORT: "8080"
+                        HOST: localhost
+                    watch: src
+                "#,
+            );
+            let next = config_from_str(
+                r#"
+                web:
+                    command: echo web --verbose
+                    env:
+                        PORT: "8081"
+                        MODE: debug
+                        HOST: localhost
+                    watch: src
+                    quiet: true
+                "#,
+            );
+
+            assert_eq!(
+                previous.diff(&next).to_string(),
+                "changed web (command, env.MODE, env.PORT, quiet)"
+            );
+        }
+
+        #[test]
+        fn ignores_changed_descriptions() {
+            let previous = config_from_str(
+                r#"
+                web:
+                    command: echo web
+                    description: serves the app
+                "#,
+            );
+            let next = config_from_str(
+                r#"
+                web:
+                    command: echo web
+                    description: serves the app and its API
+                "#,
+            );
+
+            assert!(previous.diff(&next).is_empty());
+        }
+    }
+
+    mod profiles {
+        use super::*;
+
+        const CONFIG_EXAMPLE: &str = r#"
+            env:
+                LOG: info
+
+            api:
+                command: cargo run
+                env:
+                    PORT: "8000"
+                    MODE: debug
+                watch: src
+
+            web:
+                command: npm start
+
+            profiles:
+                prod:
+                    env:
+                        LOG: warn
+                    api:
+                        command: cargo run --release
+                        env:
+                            PORT: "80"
+                        watch: []
+        "#;
+
+        fn config_with_profile(s: &str, profile: Option<&str>) -> Result<RawConfig> {
+            RawConfig::from_reader(s.as_bytes(), profile)
+        }
+
+        #[test]
+        fn base_tasks_without_profile() {
+            let config = config_with_profile(CONFIG_EXAMPLE, None).unwrap();
+            let api = &config.ops["api"];
+            assert_eq!(api.command.as_deref(), Some("cargo run"));
+            assert_eq!(api.env.len(), 2);
+            assert_eq!(api.watch.resolve(), vec!["src"]);
+            assert_eq!(config.env["LOG"], "info");
+            assert!(!config.ops.contains_key("profiles"));
+        }
+
+        #[test]
+        fn overrides_command_and_env() {
+            let config = config_with_profile(CONFIG_EXAMPLE, Some("prod")).unwrap();
+            let api = &config.ops["api"];
+            assert_eq!(api.command.as_deref(), Some("cargo run --release"));
+            // replaced as a whole, like any other field
+            assert_eq!(
+                api.env,
+                HashMap::from([("PORT".to_string(), "80".to_string())])
+            );
+            assert!(api.watch.resolve().is_empty());
+            assert_eq!(config.env["LOG"], "warn");
+            assert_eq!(config.ops["web"].command.as_deref(), Some("npm start"));
+        }
+
+        #[test]
+        fn unknown_profile() {
+            let err = config_with_profile(CONFIG_EXAMPLE, Some("staging")).unwrap_err();
+            assert_eq!(err.to_string(), "unknown profile staging (available: prod)");
+        }
+
+        #[test]
+        fn unknown_task() {
+            let config = r#"
+                api:
+                    command: cargo run
+                profiles:
+                    dev:
+                        worker:
+                            command: cargo run --bin worker
+            "#;
+            let err = config_with_profile(config, Some("dev")).unwrap_err();
+            assert_eq!(err.to_string(), "profile dev overrides unknown task worker");
+        }
+    }
+
+    mod names {
+        use super::*;
+
+        #[test]
+        fn rejects_unsafe_names() {
+            let config = r#"
+                my task:
+                    command: echo
+                front/web:
+                    command: echo
+                "@scope":
+                    command: echo
+                1st:
+                    command: echo
+            "#;
+            let err = config.parse::<RawConfig>().unwrap_err();
+            let hint =
+                "can only have letters, digits, _, -, : and . and must start with a letter or _";
+            assert_eq!(
+                err.to_string(),
+                format!(
+                    "invalid task names:\n  \"my task\" {hint}, try \"my_task\"\n  \"front/web\" {hint}, try \"front_web\"\n  \"@scope\" {hint}, try \"_scope\"\n  \"1st\" {hint}, try \"_1st\""
+                )
+            );
+        }
+
+        #[test]
+        fn rejects_reserved_names() {
+            let config = r#"
+                whiz:
+                    command: echo
+                "*all*":
+                    command: echo
+            "#;
+            let err = config.parse::<RawConfig>().unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "invalid task names:\n  \"whiz\" is reserved\n  \"*all*\" is reserved"
+            );
+        }
+
+        #[test]
+        fn rejects_names_differing_by_case() {
+            let config = r#"
+                api:
+                    command: echo
+                API:
+                    command: echo
+            "#;
+            let err = config.parse::<RawConfig>().unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "invalid task names:\n  \"API\" only differs by case from \"api\""
+            );
+        }
+
+        #[test]
+        fn allows_unsafe_names() {
+            let config = r#"
+                allow_unsafe_names: true
+                my task:
+                    command: echo
+                "@scope/web":
+                    command: echo
+                    depends_on: my task
+            "#;
+            let config: RawConfig = config.parse().unwrap();
+            assert_eq!(
+                Vec::from_iter(config.ops.keys()),
+                vec!["my task", "@scope/web"]
+            );
+
+            // but names stay reserved
+            let config = r#"
+                allow_unsafe_names: true
+                whiz:
+                    command: echo
+            "#;
+            let err = config.parse::<RawConfig>().unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "invalid task names:\n  \"whiz\" is reserved"
+            );
+        }
+    }
+
+    mod retries {
+        use super::*;
+
+        #[test]
+        fn doubles_retry_delay_with_backoff() {
+            let config: RawConfig = r#"
+                fixed:
+                    command: echo
+                    retries: 3
+                    retry_delay: 2s
+                backoff:
+                    command: echo
+                    retries: 3
+                    retry_delay: 2s
+                    retry_backoff: true
+                none:
+                    command: echo
+                    retries: 3
+            "#
+            .parse()
+            .unwrap();
+            let delays = |name: &str| {
+                (1..=3)
+                    .map(|retry| config.ops[name].retry_delay(retry).as_millis())
+                    .collect::<Vec<_>>()
+            };
+
+            assert_eq!(delays("fixed"), vec![2_000, 2_000, 2_000]);
+            assert_eq!(delays("backoff"), vec![2_000, 4_000, 8_000]);
+            assert_eq!(delays("none"), vec![0, 0, 0]);
+        }
+
+        #[test]
+        fn reads_count_and_backoff() {
+            let config: RawConfig = r#"
+                flaky:
+                    command: echo
+                    retry: {count: 3, backoff_ms: 200}
+                    retry_delay: 2s
+            "#
+            .parse()
+            .unwrap();
+            let task = &config.ops["flaky"];
+
+            assert_eq!(task.retries.count, 3);
+            // backoff_ms wins over retry_delay
+            assert_eq!(task.retry_delay(1), Duration::from_millis(200));
+            assert_eq!(task.retry_delay(3), Duration::from_millis(800));
+
+            let err = r#"
+                flaky:
+                    command: echo
+                    retry: {count: 3, backoff: 200}
+            "#
+            .parse::<RawConfig>()
+            .unwrap_err();
+            assert!(
+                format!("{err:#}").contains(
+                    "invalid retries, expected a count such as 3 or {count: 3, backoff_ms: 500}"
+                ),
+                "{err:#}"
+            );
+        }
+
+        #[test]
+        fn reads_retry_as_retries() {
+            let config: RawConfig = r#"
+                flaky:
+                    command: echo
+                    retry: 2
+            "#
+            .parse()
+            .unwrap();
+            assert_eq!(config.ops["flaky"].retries.count, 2);
+        }
+    }
+
     mod colors {
         use regex::Regex;
 
@@ -476,4 +2144,103 @@ mod tests {
             assert_eq!(actual.get("task2").unwrap(), expected.get("task2").unwrap());
         }
     }
+
+    mod procfiles {
+        use super::*;
+
+        #[test]
+        fn reads_processes_as_tasks() {
+            let procfile = r#"
+# started with foreman
+web: bundle exec rails server -p $PORT
+worker:   bundle exec sidekiq -q default
+
+release: rails db:migrate
+"#;
+            let config =
+                RawConfig::from_value(procfile::parse(procfile.as_bytes()).unwrap(), None).unwrap();
+            let expected: RawConfig = r#"
+            web:
+                command: bundle exec rails server -p $PORT
+            worker:
+                command: bundle exec sidekiq -q default
+            release:
+                command: rails db:migrate
+            "#
+            .parse()
+            .unwrap();
+            assert_eq!(config.ops, expected.ops);
+            assert!(config.ops["web"].depends_on.resolve().is_empty());
+            assert!(config.ops["web"].watch.resolve().is_empty());
+        }
+
+        #[test]
+        fn builds_from_procfile_path() {
+            let dir = std::env::temp_dir().join(format!("whiz-procfile-{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("Procfile.dev"), "web: npm start\napi: cargo run\n").unwrap();
+
+            let config = ConfigBuilder::new(dir.join("Procfile.dev"))
+                .build()
+                .unwrap();
+            assert_eq!(config.ops.keys().collect::<Vec<_>>(), vec!["web", "api"]);
+            assert_eq!(config.ops["api"].command.as_deref(), Some("cargo run"));
+
+            std::fs::remove_dir_all(dir).unwrap();
+        }
+    }
+
+    mod toml_files {
+        use super::*;
+
+        #[test]
+        fn reads_the_same_fields_as_yaml() {
+            let toml = r#"
+env = { PORT = "3000" }
+
+[build]
+command = "cargo build"
+watch = "src/**/*.rs"
+
+[test]
+command = "cargo test"
+depends_on = ["build"]
+env = { RUST_LOG = "debug" }
+"#;
+            let config = RawConfig::from_value(toml::from_str(toml).unwrap(), None).unwrap();
+            let expected: RawConfig = r#"
+            env:
+                PORT: "3000"
+            build:
+                command: cargo build
+                watch: src/**/*.rs
+            test:
+                command: cargo test
+                depends_on: [build]
+                env:
+                    RUST_LOG: debug
+            "#
+            .parse()
+            .unwrap();
+            assert_eq!(config.ops, expected.ops);
+            assert_eq!(config.env, expected.env);
+        }
+
+        #[test]
+        fn builds_from_toml_path() {
+            let dir = std::env::temp_dir().join(format!("whiz-toml-{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("whiz.toml"), "[web]\ncommand = \"npm start\"\n").unwrap();
+
+            let config = ConfigBuilder::new(dir.join("whiz.toml")).build().unwrap();
+            assert_eq!(config.ops["web"].command.as_deref(), Some("npm start"));
+            // not valid TOML, as read by extension
+            std::fs::write(dir.join("whiz.toml"), "web:\n  command: npm start\n").unwrap();
+            assert!(ConfigBuilder::new(dir.join("whiz.toml")).build().is_err());
+
+            std::fs::remove_dir_all(dir).unwrap();
+        }
+    }
 }
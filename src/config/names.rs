@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+
+use super::ops::Ops;
+
+/// Names whiz uses itself, which no task may take.
+pub const RESERVED_NAMES: [&str; 2] = ["whiz", "*all*"];
+
+/// Whether `name` only has letters, digits, `_`, `-`, `:` and `.`, and
+/// starts with a letter or `_`. Such names fit in tag selections, `whiz://`
+/// pipes and log file paths.
+pub fn is_safe_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(is_safe_char)
+}
+
+fn is_safe_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | ':' | '.')
+}
+
+/// Replaces the characters a safe name cannot have by `_`, e.g.
+/// `my task` becomes `my_task` and `1st` becomes `_1st`.
+pub fn sanitize_name(name: &str) -> String {
+    let sanitized: String = name
+        .chars()
+        .map(|c| if is_safe_char(c) { c } else { '_' })
+        .collect();
+    match sanitized.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => sanitized,
+        _ => format!("_{sanitized}"),
+    }
+}
+
+/// Makes sure the task names are safe (see [`is_safe_name`]) unless
+/// `allow_unsafe` is set, are not reserved and do not only differ by case,
+/// their logs ending up in the same files on case-insensitive file
+/// systems. Every offending name is reported at once.
+pub fn validate_names(ops: &Ops, allow_unsafe: bool) -> Result<()> {
+    let mut errors = Vec::new();
+    let mut lowercase: HashMap<String, &str> = HashMap::new();
+
+    for op_name in ops.keys() {
+        if RESERVED_NAMES.contains(&op_name.as_str()) {
+            errors.push(format!("{op_name:?} is reserved"));
+        } else if !allow_unsafe && !is_safe_name(op_name) {
+            errors.push(format!(
+                "{op_name:?} can only have letters, digits, _, -, : and . and must start with a letter or _, try {:?}",
+                sanitize_name(op_name)
+            ));
+        }
+
+        if let Some(other) = lowercase.insert(op_name.to_lowercase(), op_name) {
+            errors.push(format!("{op_name:?} only differs by case from {other:?}"));
+        }
+    }
+
+    if !errors.is_empty() {
+        bail!("invalid task names:\n  {}", errors.join("\n  "));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn safe_names() {
+        for name in [
+            "api",
+            "web-server",
+            "db:migrate",
+            "v1.2",
+            "_private",
+            "Build",
+        ] {
+            assert!(is_safe_name(name), "{name}");
+        }
+        for name in ["", "my task", "a/b", "@scope", "1st", "-x", "é"] {
+            assert!(!is_safe_name(name), "{name}");
+        }
+    }
+
+    #[test]
+    fn sanitizes_names() {
+        assert_eq!(sanitize_name("my task"), "my_task");
+        assert_eq!(sanitize_name("front/web"), "front_web");
+        assert_eq!(sanitize_name("@scope"), "_scope");
+        assert_eq!(sanitize_name("1st"), "_1st");
+        assert_eq!(sanitize_name(""), "_");
+        assert!(is_safe_name(&sanitize_name("@1 x/y")));
+    }
+}
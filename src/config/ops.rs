@@ -1,13 +1,37 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+use std::process::Command;
 
 use anyhow::{anyhow, bail, Result};
 use indexmap::IndexMap;
 
-use super::{Dag, Task};
+use super::{Dag, Lift, Task};
 
 pub type Ops = IndexMap<String, Task>;
 
 pub fn build_dag(ops: &Ops) -> Result<Dag> {
+    let order = build_levels(ops)?.into_iter().flatten();
+
+    let dag = order
+        .map(|item| {
+            let nexts = ops
+                .iter()
+                .filter(|(_, op)| op.depends_on.resolve().contains(&item))
+                .map(|(op_name, _)| op_name.clone())
+                .collect::<Vec<_>>();
+            (item, nexts)
+        })
+        .rev()
+        .collect::<Dag>();
+    Ok(dag)
+}
+
+/// Sorts the tasks by levels, each task coming in the level after its
+/// last dependency: the tasks of a level can run in parallel once the
+/// ones of the levels before have run. Tasks keep the order of the config
+/// file within a level.
+pub fn build_levels(ops: &Ops) -> Result<Vec<Vec<String>>> {
     // dependencies
     for (op_name, task) in ops.iter() {
         for dep_op_name in task.depends_on.resolve().into_iter() {
@@ -21,7 +45,7 @@ pub fn build_dag(ops: &Ops) -> Result<Dag> {
         }
     }
 
-    let mut order: Vec<String> = Vec::new();
+    let mut levels: Vec<Vec<String>> = Vec::new();
     let mut poll = Vec::from_iter(ops.keys());
 
     while !poll.is_empty() {
@@ -29,7 +53,7 @@ pub fn build_dag(ops: &Ops) -> Result<Dag> {
             poll.into_iter().partition(|&item| {
                 get_dependencies(ops, item)
                     .iter()
-                    .all(|p| order.contains(p))
+                    .all(|p| levels.iter().any(|level| level.contains(p)))
             });
 
         if satisfied.is_empty() {
@@ -39,23 +63,318 @@ pub fn build_dag(ops: &Ops) -> Result<Dag> {
             ));
         }
 
-        order.extend(satisfied.into_iter().cloned().collect::<Vec<_>>());
+        levels.push(satisfied.into_iter().cloned().collect::<Vec<_>>());
         poll = missing;
     }
 
-    let dag = order
-        .into_iter()
-        .map(|item| {
-            let nexts = ops
-                .iter()
-                .filter(|(_, op)| op.depends_on.resolve().contains(&item))
-                .map(|(op_name, _)| op_name.clone())
-                .collect::<Vec<_>>();
-            (item, nexts)
+    Ok(levels)
+}
+
+/// Makes sure that tasks which only forward watch events
+/// (see [`Task::is_trigger_only`]) watch files and have dependents.
+pub fn validate_trigger_tasks(ops: &Ops) -> Result<()> {
+    for (op_name, task) in ops.iter().filter(|(_, task)| task.is_trigger_only()) {
+        if task.watch.resolve().is_empty() {
+            bail!("task {op_name} has no command nor entrypoint, it must watch files");
+        }
+
+        let has_dependents = ops
+            .values()
+            .any(|other| other.depends_on.resolve().contains(op_name));
+        if !has_dependents && task.on_change_run.resolve().is_empty() {
+            bail!(
+                "task {op_name} has no command nor entrypoint, it must have dependents or run tasks on change"
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Makes sure that the tasks running on demand stay out of the graph:
+/// they neither wait on nor start other tasks, and nothing but the
+/// command menu or a manual reload starts them.
+pub fn validate_on_demand_tasks(ops: &Ops) -> Result<()> {
+    for (op_name, task) in ops.iter().filter(|(_, task)| task.on_demand) {
+        if !task.watch.resolve().is_empty() || task.schedule().is_some() {
+            bail!("task {op_name} runs on demand, it cannot watch files nor run on a schedule");
+        }
+        if !task.depends_on.resolve().is_empty() {
+            bail!("task {op_name} runs on demand, it cannot depend on other tasks");
+        }
+    }
+    for (op_name, task) in ops.iter() {
+        let on_demand = |name: &&str| ops.get(*name).is_some_and(|other| other.on_demand);
+        let depends_on = task.depends_on.resolve();
+        let mut depends_on = depends_on.iter().map(|name| name.trim_end_matches('?'));
+        if let Some(other) = depends_on.find(on_demand) {
+            bail!("task {op_name} depends on {other}, which only runs on demand");
+        }
+        let on_change_run = task.on_change_run.resolve();
+        if let Some(other) = on_change_run.iter().map(String::as_str).find(on_demand) {
+            bail!("task {op_name} runs {other} on change, which only runs on demand");
+        }
+    }
+
+    Ok(())
+}
+
+/// Makes sure that the task shown first, see [`RawConfig::default_task`],
+/// exists.
+pub fn validate_default_task(ops: &Ops, default_task: Option<&str>) -> Result<()> {
+    match default_task {
+        Some(task) if !ops.contains_key(task) => bail!(
+            "default_task {task} is not a task{}",
+            did_you_mean(task, ops.keys().map(String::as_str))
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// Makes sure that the tasks run on change (see [`Task::on_change_run`])
+/// exist and never lead back to the task whose files changed.
+pub fn validate_on_change_run(ops: &Ops) -> Result<()> {
+    for (op_name, task) in ops.iter() {
+        for other in task.on_change_run.resolve() {
+            if *op_name == other {
+                bail!("task {op_name} cannot run itself on change");
+            }
+            if !ops.contains_key(&other) {
+                bail!(
+                    "task {op_name} runs unknown task {other} on change{}",
+                    did_you_mean(&other, ops.keys().map(String::as_str))
+                );
+            }
+        }
+    }
+
+    // peels the tasks which run no task left, the remaining ones loop
+    let mut left: IndexMap<&String, Vec<String>> = ops
+        .iter()
+        .map(|(op_name, task)| (op_name, task.on_change_run.resolve()))
+        .collect();
+    loop {
+        let peeled: Vec<String> = left
+            .iter()
+            .filter(|(_, runs)| runs.iter().all(|other| !left.contains_key(other)))
+            .map(|(op_name, _)| op_name.to_string())
+            .collect();
+        if peeled.is_empty() {
+            break;
+        }
+        left.retain(|op_name, _| !peeled.contains(op_name));
+    }
+    if !left.is_empty() {
+        bail!(
+            "cycle detected in on_change_run with one of {}",
+            left.keys()
+                .map(|op_name| op_name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+
+    Ok(())
+}
+
+/// Strips the `?` suffix of optional dependencies, dropping the ones
+/// which are not defined and recording the others in
+/// [`Task::optional_depends_on`].
+pub fn resolve_optional_dependencies(ops: &mut Ops) {
+    let op_names: HashSet<String> = ops.keys().cloned().collect();
+
+    for task in ops.values_mut() {
+        let mut depends_on = Vec::new();
+        for dep_op_name in task.depends_on.resolve() {
+            match dep_op_name.strip_suffix('?') {
+                Some(name) if op_names.contains(name) => {
+                    task.optional_depends_on.push(name.to_string());
+                    depends_on.push(name.to_string());
+                }
+                Some(_) => {}
+                None => depends_on.push(dep_op_name),
+            }
+        }
+        task.depends_on = Lift::More(depends_on);
+    }
+}
+
+/// Removes the dependencies which are not part of `ops`, which can only
+/// be optional ones as filtering always keeps the required ones.
+pub fn prune_dependencies(ops: &mut Ops) {
+    let op_names: HashSet<String> = ops.keys().cloned().collect();
+
+    for task in ops.values_mut() {
+        let depends_on = task
+            .depends_on
+            .resolve()
+            .into_iter()
+            .filter(|dep_op_name| op_names.contains(dep_op_name))
+            .collect();
+        task.depends_on = Lift::More(depends_on);
+    }
+}
+
+/// Tasks that differ between two configs, in the order of the new one
+/// (the old one for removed tasks).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OpsDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    /// Tasks whose definition changed, resolved dependencies included.
+    pub changed: Vec<String>,
+    /// Fields that changed of each changed task, see [`changed_fields`].
+    pub fields: IndexMap<String, Vec<String>>,
+    /// Root fields that changed, changing every task.
+    pub root: Vec<String>,
+}
+
+impl OpsDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl fmt::Display for OpsDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let changed: Vec<String> = self
+            .changed
+            .iter()
+            .map(|op_name| match self.fields.get(op_name) {
+                Some(fields) if !fields.is_empty() => format!("{op_name} ({})", fields.join(", ")),
+                _ => op_name.clone(),
+            })
+            .collect();
+        let parts = [
+            ("added", &self.added),
+            ("removed", &self.removed),
+            ("changed", &changed),
+            ("root", &self.root),
+        ];
+        let parts: Vec<String> = parts
+            .iter()
+            .filter(|(_, op_names)| !op_names.is_empty())
+            .map(|(label, op_names)| format!("{label} {}", op_names.join(", ")))
+            .collect();
+        write!(f, "{}", parts.join("; "))
+    }
+}
+
+pub fn diff_ops(old: &Ops, new: &Ops) -> OpsDiff {
+    let fields: IndexMap<String, Vec<String>> = new
+        .iter()
+        .filter_map(|(op_name, task)| {
+            let old = old.get(op_name).filter(|old| !runs_the_same(old, task))?;
+            Some((op_name.clone(), changed_fields(old, task)))
         })
-        .rev()
-        .collect::<Dag>();
-    Ok(dag)
+        .collect();
+    OpsDiff {
+        added: new
+            .keys()
+            .filter(|op_name| !old.contains_key(*op_name))
+            .cloned()
+            .collect(),
+        removed: old
+            .keys()
+            .filter(|op_name| !new.contains_key(*op_name))
+            .cloned()
+            .collect(),
+        changed: fields.keys().cloned().collect(),
+        fields,
+        root: Vec::new(),
+    }
+}
+
+/// Whether two definitions of a task only differ by what is shown about
+/// it, such as its `description`, so that it does not need to restart.
+fn runs_the_same(old: &Task, new: &Task) -> bool {
+    let run = |task: &Task| Task {
+        description: None,
+        ..task.clone()
+    };
+    run(old) == run(new)
+}
+
+/// Returns the fields that differ between two definitions of a task, the
+/// `env` ones by variable (e.g. `env.PORT`).
+pub fn changed_fields(old: &Task, new: &Task) -> Vec<String> {
+    let mut fields = Vec::new();
+    macro_rules! compare {
+        ($($field:ident),*) => {
+            $(
+                if old.$field != new.$field {
+                    fields.push(stringify!($field).to_string());
+                }
+            )*
+        };
+    }
+    compare!(
+        workdir,
+        command,
+        entrypoint,
+        before,
+        after,
+        watch,
+        ignore,
+        watch_events,
+        debounce,
+        on_change_run
+    );
+    fields.extend(changed_env(&old.env, &new.env));
+    if old.success_depends_on != new.success_depends_on && old.depends_on == new.depends_on {
+        // only the condition of a dependency changed
+        fields.push("depends_on".to_string());
+    }
+    compare!(
+        env_file,
+        requires,
+        depends_on,
+        pipe,
+        max_dynamic_tabs,
+        color,
+        pin,
+        ready_when,
+        min_level,
+        levels,
+        primary,
+        long_running,
+        quiet,
+        on_demand,
+        once,
+        stream,
+        encoding,
+        merge_stderr,
+        tty,
+        limits,
+        container,
+        schedule,
+        every,
+        overlap,
+        retries,
+        retry_delay,
+        retry_backoff,
+        restart,
+        timeout,
+        kill_timeout,
+        stop_signal
+    );
+    fields
+}
+
+/// Returns the variables that differ between two `env` blocks, as
+/// `env.NAME` sorted by name.
+pub fn changed_env(old: &HashMap<String, String>, new: &HashMap<String, String>) -> Vec<String> {
+    let mut names: Vec<&String> = old
+        .keys()
+        .chain(new.keys())
+        .filter(|name| old.get(*name) != new.get(*name))
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+        .into_iter()
+        .map(|name| format!("env.{name}"))
+        .collect()
 }
 
 /// Returns the list of dependencies of a job defined in the config file.
@@ -63,6 +382,16 @@ pub fn get_dependencies(ops: &Ops, job_name: &str) -> Vec<String> {
     ops.get(job_name).unwrap().depends_on.resolve()
 }
 
+/// Returns the list of dependencies of a job without the optional ones.
+pub fn get_required_dependencies(ops: &Ops, job_name: &str) -> Vec<String> {
+    let task = ops.get(job_name).unwrap();
+    task.depends_on
+        .resolve()
+        .into_iter()
+        .filter(|dep_op_name| !task.optional_depends_on.contains(dep_op_name))
+        .collect()
+}
+
 /// Returns a list of all the dependencies of a list of jobs, and
 /// the children dependencies of each dependency recursively.
 pub fn get_all_dependencies(ops: &Ops, jobs: &[String]) -> Vec<String> {
@@ -72,19 +401,30 @@ pub fn get_all_dependencies(ops: &Ops, jobs: &[String]) -> Vec<String> {
     // add initial dependencies
     for job_name in jobs {
         let child_dependencies = get_dependencies(ops, job_name);
-        job_dependencies.extend(child_dependencies.into_iter());
+        job_dependencies.extend(child_dependencies);
     }
 
     // add child dependencies recursively
     while let Some(job_name) = job_dependencies.pop() {
         let child_dependencies = get_dependencies(ops, &job_name);
-        job_dependencies.extend(child_dependencies.into_iter());
+        job_dependencies.extend(child_dependencies);
         all_dependencies.push(job_name);
     }
 
     all_dependencies
 }
 
+/// Returns the number of jobs depending on a job, directly or through
+/// other jobs.
+pub fn count_dependents(ops: &Ops, job_name: &str) -> usize {
+    get_jobs(ops)
+        .into_iter()
+        .filter(|other| {
+            get_all_dependencies(ops, &[other.to_string()]).contains(&job_name.to_string())
+        })
+        .count()
+}
+
 /// Returns the list of all the jobs defined in the config file.
 pub fn get_jobs(ops: &Ops) -> Vec<&String> {
     ops.iter().map(|(job_name, _)| job_name).collect()
@@ -95,19 +435,138 @@ pub fn get_jobs(ops: &Ops) -> Vec<&String> {
 pub fn get_formatted_list_of_jobs(ops: &Ops) -> String {
     let mut formatted_list_of_jobs: Vec<String> = get_jobs(ops)
         .iter()
+        .map(|job_name| format_job(ops, job_name))
+        .collect();
+    formatted_list_of_jobs.sort();
+    formatted_list_of_jobs.join("\n")
+}
+
+/// Same as [`get_formatted_list_of_jobs`] with the description of each
+/// job under it (`list-jobs --long`).
+pub fn get_described_list_of_jobs(ops: &Ops) -> String {
+    let mut job_names = get_jobs(ops);
+    job_names.sort();
+    job_names
+        .into_iter()
         .map(|job_name| {
-            let dependencies = get_dependencies(ops, job_name);
-            let mut formatted_job = format!("  - {job_name}");
+            let mut formatted_job = format_job(ops, job_name);
+            if let Some(description) = &ops[job_name].description {
+                for line in description.trim_end().lines() {
+                    formatted_job += &format!("\n      {line}");
+                }
+            }
+            formatted_job
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Formats a job and its dependencies, e.g. `  - api (db,cache)`.
+fn format_job(ops: &Ops, job_name: &str) -> String {
+    let dependencies = get_dependencies(ops, job_name);
+    let mut formatted_job = format!("  - {job_name}");
+
+    if !dependencies.is_empty() {
+        formatted_job += &format!(" ({})", dependencies.join(","));
+    }
+
+    formatted_job
+}
+
+/// Workdir information of a job, as shown by `list-jobs --detail`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobDetail {
+    pub name: String,
+    pub workdir: String,
+    pub exists: bool,
+    /// Whether the workdir has uncommitted changes, `None` when
+    /// the check was skipped or git could not answer.
+    pub dirty: Option<bool>,
+}
 
-            if !dependencies.is_empty() {
-                formatted_job += &format!(" ({})", dependencies.join(","));
+/// Returns the workdir details of all the jobs, sorted by name.
+///
+/// The git check is only performed when `git` is set and never fails:
+/// a missing git binary or a workdir outside of a repository
+/// results in an unknown dirty state.
+pub fn get_jobs_details(ops: &Ops, base_dir: &Path, git: bool) -> Vec<JobDetail> {
+    let mut details: Vec<JobDetail> = ops
+        .iter()
+        .map(|(job_name, task)| {
+            let cwd = task.get_absolute_workdir(base_dir);
+            let exists = cwd.is_dir();
+            let dirty = if git && exists {
+                is_git_dirty(&cwd)
+            } else {
+                None
+            };
+
+            JobDetail {
+                name: job_name.to_owned(),
+                workdir: task.workdir.clone().unwrap_or_else(|| ".".to_string()),
+                exists,
+                dirty,
             }
+        })
+        .collect();
+    details.sort_by(|a, b| a.name.cmp(&b.name));
+    details
+}
 
-            formatted_job
+fn is_git_dirty(cwd: &Path) -> Option<bool> {
+    let output = Command::new("git")
+        .args(["status", "--porcelain", "."])
+        .current_dir(cwd)
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    Some(!output.stdout.is_empty())
+}
+
+/// Formats the jobs details as aligned columns.
+pub fn format_jobs_details(details: &[JobDetail]) -> String {
+    let rows: Vec<[String; 4]> = details
+        .iter()
+        .map(|detail| {
+            let exists = if detail.exists { "yes" } else { "missing" };
+            let dirty = match detail.dirty {
+                Some(true) => "yes",
+                Some(false) => "no",
+                None => "-",
+            };
+            [
+                detail.name.clone(),
+                detail.workdir.clone(),
+                exists.to_string(),
+                dirty.to_string(),
+            ]
         })
         .collect();
-    formatted_list_of_jobs.sort();
-    formatted_list_of_jobs.join("\n")
+
+    let header = ["job", "workdir", "exists", "dirty"].map(String::from);
+    let mut widths = header.clone().map(|h| h.len());
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    std::iter::once(&header)
+        .chain(rows.iter())
+        .map(|row| {
+            let cells: Vec<String> = row
+                .iter()
+                .zip(widths)
+                .map(|(cell, width)| format!("{cell:width$}"))
+                .collect();
+            format!("  {}", cells.join("  ").trim_end())
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
 /// Filters the jobs to only the ones provided in `run`
@@ -118,26 +577,189 @@ pub fn get_formatted_list_of_jobs(ops: &Ops) -> String {
 ///
 /// Fails if a job in `run` is not set in the config file.
 pub fn filter_jobs(ops: &mut Ops, run: &[String]) -> Result<()> {
-    for job_name in run {
-        if ops.get(job_name).is_none() {
-            let formatted_list_of_jobs = get_formatted_list_of_jobs(ops);
-            let error_header = format!("job '{job_name}' not found in config file.");
-            let error_suggestion = format!("Valid jobs are:\n{formatted_list_of_jobs}");
-            let error_message = format!("{error_header}\n\n{error_suggestion}");
-            bail!(error_message);
-        }
-    }
+    check_jobs(ops, run)?;
 
     if !run.is_empty() {
-        let mut filtered_jobs = get_all_dependencies(ops, run);
-        filtered_jobs.extend(run.iter().cloned());
-        let filtered_jobs: HashSet<String> = HashSet::from_iter(filtered_jobs);
+        // optional dependencies are only kept if required by another job
+        let mut pending = run.to_vec();
+        let mut filtered_jobs = HashSet::new();
+        while let Some(job_name) = pending.pop() {
+            pending.extend(get_required_dependencies(ops, &job_name));
+            filtered_jobs.insert(job_name);
+        }
         *ops = ops
             .clone()
             .into_iter()
             .filter(|(job_name, _)| filtered_jobs.contains(job_name))
             .collect();
+        prune_dependencies(ops);
     }
 
     Ok(())
 }
+
+/// Same as [`filter_jobs`] without adding the dependencies, which are
+/// dropped from the kept jobs so that they start right away (`--no-deps`).
+pub fn select_jobs(ops: &mut Ops, run: &[String]) -> Result<()> {
+    check_jobs(ops, run)?;
+
+    if !run.is_empty() {
+        ops.retain(|job_name, _| run.contains(job_name));
+        prune_dependencies(ops);
+    }
+
+    Ok(())
+}
+
+/// Fails if a job in `run` is not set in the config file, suggesting the
+/// closest ones and listing the valid ones unless there are too many.
+fn check_jobs(ops: &Ops, run: &[String]) -> Result<()> {
+    for job_name in run {
+        if ops.get(job_name).is_none() {
+            bail!(job_not_found(ops, job_name));
+        }
+    }
+    Ok(())
+}
+
+/// Most names suggested for an unknown one.
+const MAX_SUGGESTIONS: usize = 5;
+
+/// Most jobs listed in full when a job is not found, beyond which only
+/// the suggestions are shown.
+const MAX_LISTED_JOBS: usize = 15;
+
+fn job_not_found(ops: &Ops, job_name: &str) -> String {
+    let mut error_message = format!("job '{job_name}' not found in config file.");
+    let suggestions = suggest(job_name, ops.keys().map(String::as_str));
+    if !suggestions.is_empty() {
+        error_message += &format!("\n\ndid you mean: {}?", suggestions.join(", "));
+    }
+
+    if ops.len() <= MAX_LISTED_JOBS {
+        let formatted_list_of_jobs = get_formatted_list_of_jobs(ops);
+        error_message += &format!("\n\nValid jobs are:\n{formatted_list_of_jobs}");
+    } else if suggestions.is_empty() {
+        error_message += &format!(
+            "\n\n{} jobs are set; run `whiz list-jobs` to see all",
+            ops.len()
+        );
+    } else {
+        error_message += &format!(
+            "\n...and {} more; run `whiz list-jobs` to see all",
+            ops.len() - suggestions.len()
+        );
+    }
+    error_message
+}
+
+/// Names among `candidates` which `name` is likely a typo of, the closest
+/// by edit distance first and at most [`MAX_SUGGESTIONS`]. A name counts
+/// as close when it is a few edits away or when one contains the other.
+pub fn suggest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Vec<&'a str> {
+    let max_distance = (name.chars().count() / 3).max(2);
+    let mut close: Vec<_> = candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, candidate)| {
+            *distance <= max_distance || candidate.contains(name) || name.contains(candidate)
+        })
+        .collect();
+    close.sort();
+    close
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| candidate)
+        .collect()
+}
+
+/// Formats the [`suggest`]ions for `name` to end an error message with,
+/// empty when there are none.
+pub fn did_you_mean<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> String {
+    let suggestions = suggest(name, candidates);
+    if suggestions.is_empty() {
+        return String::new();
+    }
+    format!(", did you mean: {}?", suggestions.join(", "))
+}
+
+/// Levenshtein distance between `a` and `b`, counted in chars.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ops(names: &[&str]) -> Ops {
+        names
+            .iter()
+            .map(|name| {
+                let task = serde_yaml::from_str(&format!("command: echo {name}")).unwrap();
+                (name.to_string(), task)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn measures_edit_distance() {
+        assert_eq!(edit_distance("test", "test"), 0);
+        assert_eq!(edit_distance("tset", "test"), 2);
+        assert_eq!(edit_distance("tests", "test"), 1);
+        assert_eq!(edit_distance("", "api"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn ranks_suggestions() {
+        let names = ["web", "test-api", "tests", "lint", "test", "docs"];
+
+        assert_eq!(suggest("tset", names), vec!["test"]);
+        assert_eq!(suggest("tst", names), vec!["test", "tests"]);
+        assert_eq!(suggest("test", names), vec!["test", "tests", "test-api"]);
+        assert_eq!(suggest("api", names), vec!["test-api"]);
+        assert!(suggest("deploy", names).is_empty());
+
+        let many = ["a1", "a2", "a3", "a4", "a5", "a6", "a7"];
+        assert_eq!(suggest("a", many), vec!["a1", "a2", "a3", "a4", "a5"]);
+
+        assert_eq!(did_you_mean("tst", names), ", did you mean: test, tests?");
+        assert_eq!(did_you_mean("deploy", names), "");
+    }
+
+    #[test]
+    fn truncates_long_lists_of_jobs() {
+        let few = ops(&["web", "tests", "test"]);
+        assert_eq!(
+            job_not_found(&few, "tst"),
+            "job 'tst' not found in config file.\n\ndid you mean: test, tests?\n\nValid jobs are:\n  - test\n  - tests\n  - web"
+        );
+
+        let names: Vec<_> = (0..MAX_LISTED_JOBS).map(|i| format!("job{i:02}")).collect();
+        let names: Vec<_> = names.iter().map(String::as_str).collect();
+        assert!(job_not_found(&ops(&names), "deploy").contains("Valid jobs are:"));
+
+        let mut names = names;
+        names.extend(["tests", "test"]);
+        let many = ops(&names);
+        assert_eq!(
+            job_not_found(&many, "tst"),
+            "job 'tst' not found in config file.\n\ndid you mean: test, tests?\n...and 15 more; run `whiz list-jobs` to see all"
+        );
+        assert_eq!(
+            job_not_found(&many, "deploy"),
+            "job 'deploy' not found in config file.\n\n17 jobs are set; run `whiz list-jobs` to see all"
+        );
+    }
+}
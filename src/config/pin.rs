@@ -0,0 +1,58 @@
+use anyhow::{Context, Result};
+use regex::Regex;
+
+/// Regexes of the `pin` field of a task, pinning the lines they match at
+/// the top of its panel.
+///
+/// Each regex keeps the latest line it matched, the pinned lines being
+/// shown in the order of the regexes. A line matching several regexes is
+/// only pinned by the first one. Pinned lines still scroll with the others.
+#[derive(Clone, Debug, Default)]
+pub struct Pins(Vec<Regex>);
+
+impl Pins {
+    pub fn new(patterns: &[String]) -> Result<Self> {
+        patterns
+            .iter()
+            .map(|pattern| Regex::new(pattern).with_context(|| format!("invalid pin {pattern:?}")))
+            .collect::<Result<_>>()
+            .map(Self)
+    }
+
+    /// Returns the slot a line is pinned to, i.e. the index of the first
+    /// regex matching it.
+    pub fn slot(&self, line: &str) -> Option<usize> {
+        self.0.iter().position(|regex| regex.is_match(line))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pins(patterns: &[&str]) -> Pins {
+        Pins::new(&patterns.iter().map(|p| p.to_string()).collect::<Vec<_>>()).unwrap()
+    }
+
+    #[test]
+    fn pins_to_first_matching_regex() {
+        let pins = pins(&[r"^progress: \d+%", r"\d+ tests? passed", "passed"]);
+
+        assert_eq!(pins.slot("progress: 42%"), Some(0));
+        assert_eq!(pins.slot("12 tests passed"), Some(1));
+        assert_eq!(pins.slot("all passed"), Some(2));
+        assert_eq!(pins.slot("compiling"), None);
+    }
+
+    #[test]
+    fn pins_nothing_by_default() {
+        assert_eq!(Pins::default().slot("progress: 42%"), None);
+    }
+
+    #[test]
+    fn rejects_invalid_regex() {
+        let err = Pins::new(&["progress: (".to_string()]).unwrap_err();
+
+        assert_eq!(err.to_string(), "invalid pin \"progress: (\"");
+    }
+}
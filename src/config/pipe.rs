@@ -1,9 +1,13 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Component, Path, PathBuf};
 use std::str::FromStr;
 
 use anyhow::anyhow;
 use regex::Regex;
 use url::Url;
 
+use crate::file_mode::FileMode;
+
 /// A pipe represents the redirection of the output of a task
 /// matched by a regular expression to an [`OutputRedirection`].
 #[derive(Clone, Debug)]
@@ -35,8 +39,9 @@ pub enum OutputRedirection {
     /// to a new virtual tab with the given name.
     Tab(String),
     /// Indicates that the output of a task should be saved
-    /// as a log file in the given path.
-    File(String),
+    /// as a log file in the given path, created with the given
+    /// permissions (`file_mode` query parameter).
+    File(String, Option<FileMode>),
 }
 
 impl FromStr for OutputRedirection {
@@ -54,11 +59,15 @@ impl FromStr for OutputRedirection {
     /// - whiz://virtual_views -> Tab
     /// - file:///dev/null -> File
     /// - ./logs/server.log -> File
+    /// - ./logs/server.log?file_mode=600 -> File
     fn from_str(redirection_uri: &str) -> anyhow::Result<Self> {
         // URIs that do not start with a scheme are considered files by default
         if redirection_uri.starts_with('/') || redirection_uri.starts_with('.') {
-            let output_redirection = OutputRedirection::File(redirection_uri.to_string());
-            return Ok(output_redirection);
+            let (path, mode) = match redirection_uri.split_once("?file_mode=") {
+                Some((path, mode)) => (path, Some(mode.parse()?)),
+                None => (redirection_uri, None),
+            };
+            return Ok(OutputRedirection::File(path.to_string(), mode));
         }
 
         let redirection_uri = Url::parse(redirection_uri)?;
@@ -74,10 +83,157 @@ impl FromStr for OutputRedirection {
 
         path += redirection_uri.path();
 
+        let mode = redirection_uri
+            .query_pairs()
+            .find(|(key, _)| key == "file_mode")
+            .map(|(_, mode)| mode.parse())
+            .transpose()?;
+
         match scheme {
             "whiz" => Ok(OutputRedirection::Tab(path)),
-            "file" => Ok(OutputRedirection::File(path)),
+            "file" => Ok(OutputRedirection::File(path, mode)),
             _ => Err(anyhow!("unsupported scheme")),
         }
     }
 }
+
+/// Files the pipes of several tasks write to, whose lines interleave
+/// without telling which task wrote them.
+#[derive(Debug, Default, PartialEq)]
+pub struct PipeCollisions {
+    /// Each file written by several tasks, to these tasks.
+    pub shared: BTreeMap<PathBuf, BTreeSet<String>>,
+    /// Tasks piping to templated paths, e.g. `./logs/$1.log`, which are
+    /// left out since their files depend on the output.
+    pub templated: BTreeSet<String>,
+}
+
+impl PipeCollisions {
+    /// Resolves the file of each pipe against the workdir of its task, as
+    /// when the lines are written, and keeps the ones several tasks share.
+    pub fn find(pipes: &HashMap<String, Vec<Pipe>>, cwds: &HashMap<String, PathBuf>) -> Self {
+        let mut collisions = Self::default();
+        let mut writers: BTreeMap<PathBuf, BTreeSet<String>> = BTreeMap::new();
+        for (task, cwd) in cwds {
+            for pipe in pipes.get(task).into_iter().flatten() {
+                let OutputRedirection::File(path, _) = &pipe.redirection else {
+                    continue;
+                };
+                if path.contains('$') {
+                    collisions.templated.insert(task.clone());
+                    continue;
+                }
+                writers
+                    .entry(resolve(cwd, path))
+                    .or_default()
+                    .insert(task.clone());
+            }
+        }
+        collisions.shared = writers
+            .into_iter()
+            .filter(|(_, tasks)| tasks.len() > 1)
+            .collect();
+        collisions
+    }
+
+    /// One warning per shared file.
+    pub fn warnings(&self) -> Vec<String> {
+        self.shared
+            .iter()
+            .map(|(path, tasks)| {
+                let tasks = tasks.iter().cloned().collect::<Vec<_>>().join(", ");
+                format!(
+                    "tasks {tasks} pipe to the same file {}, their lines will interleave: pipe them to distinct files",
+                    path.display()
+                )
+            })
+            .collect()
+    }
+}
+
+/// Joins `path` to `cwd` and removes the `.` and `..` components, without
+/// touching the filesystem as the file may not exist yet.
+fn resolve(cwd: &Path, path: &str) -> PathBuf {
+    let mut resolved = PathBuf::new();
+    for component in cwd.join(path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            component => resolved.push(component),
+        }
+    }
+    resolved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(uri: &str) -> (String, Option<FileMode>) {
+        match uri.parse::<OutputRedirection>().unwrap() {
+            OutputRedirection::File(path, mode) => (path, mode),
+            OutputRedirection::Tab(_) => panic!("{uri} is not a file"),
+        }
+    }
+
+    #[test]
+    fn file_mode_query() {
+        let mode = Some("600".parse().unwrap());
+        assert_eq!(
+            file("./logs/server.log"),
+            ("./logs/server.log".into(), None)
+        );
+        assert_eq!(
+            file("./logs/server.log?file_mode=600"),
+            ("./logs/server.log".into(), mode)
+        );
+        assert_eq!(
+            file("file:///var/log/server.log?file_mode=0o600"),
+            ("/var/log/server.log".into(), mode)
+        );
+        assert!("./server.log?file_mode=999"
+            .parse::<OutputRedirection>()
+            .is_err());
+    }
+
+    #[test]
+    fn finds_files_shared_by_tasks() {
+        let pipes = |targets: &[&str]| {
+            targets
+                .iter()
+                .map(|target| Pipe::from((&".*".to_string(), &target.to_string())).unwrap())
+                .collect::<Vec<_>>()
+        };
+        let tasks = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+        let pipes = HashMap::from([
+            ("api".to_string(), pipes(&["./app.log", "whiz://api"])),
+            ("web".to_string(), pipes(&["../app.log", "./web.log"])),
+            ("db".to_string(), pipes(&["/srv/web/web.log"])),
+            ("worker".to_string(), pipes(&["./app.log", "./logs/$1.log"])),
+            ("cache".to_string(), pipes(&["./logs/$1.log"])),
+        ]);
+        let cwds = HashMap::from([
+            ("api".to_string(), PathBuf::from("/srv")),
+            ("web".to_string(), PathBuf::from("/srv/web")),
+            ("db".to_string(), PathBuf::from("/tmp")),
+            ("worker".to_string(), PathBuf::from("/srv/worker")),
+            ("cache".to_string(), PathBuf::from("/srv")),
+        ]);
+
+        let collisions = PipeCollisions::find(&pipes, &cwds);
+        assert_eq!(
+            collisions.shared,
+            BTreeMap::from([
+                (PathBuf::from("/srv/app.log"), tasks(&["api", "web"])),
+                (PathBuf::from("/srv/web/web.log"), tasks(&["db", "web"])),
+            ])
+        );
+        assert_eq!(collisions.templated, tasks(&["cache", "worker"]));
+        assert_eq!(
+            collisions.warnings()[0],
+            "tasks api, web pipe to the same file /srv/app.log, their lines will interleave: pipe them to distinct files"
+        );
+    }
+}
@@ -0,0 +1,60 @@
+use std::{
+    io::{BufRead, BufReader, Read},
+    path::Path,
+};
+
+use anyhow::{bail, Context, Result};
+use serde_yaml::{Mapping, Value};
+
+/// Whether `path` is read as a foreman `Procfile` instead of YAML, i.e.
+/// its name is `Procfile` or starts with `Procfile.` as `Procfile.dev`.
+pub fn is_procfile(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name == "Procfile" || name.starts_with("Procfile."))
+}
+
+/// Parses the `name: command` lines of a `Procfile` into the tasks of a
+/// config, skipping blank lines and `#` comments. As a `Procfile` has no
+/// other fields, the tasks only have a `command`.
+pub fn parse(reader: impl Read) -> Result<Value> {
+    let mut tasks = Mapping::new();
+    for (index, line) in BufReader::new(reader).lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (name, command) = line
+            .split_once(':')
+            .map(|(name, command)| (name.trim(), command.trim()))
+            .filter(|(name, command)| !name.is_empty() && !command.is_empty())
+            .with_context(|| format!("Procfile line {}: expected `name: command`", index + 1))?;
+        let task = Mapping::from_iter([("command".into(), command.into())]);
+        if tasks.insert(name.into(), task.into()).is_some() {
+            bail!("Procfile line {}: duplicated process {name}", index + 1);
+        }
+    }
+    Ok(tasks.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_procfiles_by_name() {
+        assert!(is_procfile(Path::new("/app/Procfile")));
+        assert!(is_procfile(Path::new("Procfile.dev")));
+        assert!(!is_procfile(Path::new("whiz.yaml")));
+        assert!(!is_procfile(Path::new("Procfile/whiz.yaml")));
+    }
+
+    #[test]
+    fn rejects_lines_without_command() {
+        let err = parse("web: npm start\n\nworker\n".as_bytes()).unwrap_err();
+        assert_eq!(err.to_string(), "Procfile line 3: expected `name: command`");
+        let err = parse("web: a\nweb: b".as_bytes()).unwrap_err();
+        assert_eq!(err.to_string(), "Procfile line 2: duplicated process web");
+    }
+}
@@ -0,0 +1,197 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use super::Lift;
+
+/// What a task needs to start, checked once its environment is resolved
+/// and before each spawn, e.g.:
+///
+/// ```yaml
+/// requires:
+///     bins: [pnpm, docker]
+///     env: DATABASE_URL
+/// ```
+///
+/// The task is blocked instead of started when one is missing, which
+/// `whiz doctor` also reports without running anything.
+#[derive(Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Requires {
+    /// Programs looked up in the `PATH` of the task, or paths relative to
+    /// its workdir when they contain a separator.
+    #[serde(default)]
+    pub bins: Lift<String>,
+    /// Variables which must be set to a non-empty value.
+    #[serde(default)]
+    pub env: Lift<String>,
+}
+
+/// A requirement of [`Requires`] not met.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Missing {
+    Binary(String),
+    Env(String),
+    EmptyEnv(String),
+}
+
+impl fmt::Display for Missing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Missing::Binary(bin) => write!(f, "missing binary `{bin}`"),
+            Missing::Env(key) => write!(f, "missing env `{key}`"),
+            Missing::EmptyEnv(key) => write!(f, "empty env `{key}`"),
+        }
+    }
+}
+
+impl Requires {
+    /// Returns what is missing to run in `env` (the full environment of
+    /// the task, whose `PATH` binaries are looked up in) from `cwd`.
+    pub fn missing(&self, env: &HashMap<String, String>, cwd: &Path) -> Vec<Missing> {
+        let path = env.get("PATH").map(String::as_str);
+        let bins = self
+            .bins
+            .resolve()
+            .into_iter()
+            .filter(|bin| find_binary(bin, path, cwd).is_none())
+            .map(Missing::Binary);
+        let vars = self
+            .env
+            .resolve()
+            .into_iter()
+            .filter_map(|key| match env.get(&key) {
+                None => Some(Missing::Env(key)),
+                Some(value) if value.is_empty() => Some(Missing::EmptyEnv(key)),
+                Some(_) => None,
+            });
+        bins.chain(vars).collect()
+    }
+}
+
+/// Formats what is missing on a single line, telling where variables
+/// are set when one is missing.
+pub fn describe_missing(missing: &[Missing]) -> String {
+    let mut message = missing
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ");
+    if missing.iter().any(|m| !matches!(m, Missing::Binary(_))) {
+        message.push_str(" — set it in whiz.yaml env or your shell");
+    }
+    message
+}
+
+/// Looks `bin` up in the directories of `path`, or relative to `cwd` when
+/// it contains a separator as a shell would.
+pub fn find_binary(bin: &str, path: Option<&str>, cwd: &Path) -> Option<PathBuf> {
+    if bin.contains('/') || bin.contains(std::path::MAIN_SEPARATOR) {
+        let candidate = cwd.join(bin);
+        return is_executable(&candidate).then_some(candidate);
+    }
+    std::env::split_paths(path?)
+        .flat_map(|dir| {
+            let candidate = dir.join(bin);
+            #[cfg(windows)]
+            let candidates = [candidate.with_extension("exe"), candidate];
+            #[cfg(not(windows))]
+            let candidates = [candidate];
+            candidates
+        })
+        .find(|candidate| is_executable(candidate))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.metadata()
+        .map(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bin_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("whiz-requires-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn finds_executables_in_path() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let first = bin_dir("first");
+        let second = bin_dir("second");
+        std::fs::write(second.join("tool"), "").unwrap();
+        std::fs::set_permissions(second.join("tool"), PermissionsExt::from_mode(0o755)).unwrap();
+        // not executable, so skipped
+        std::fs::write(first.join("tool"), "").unwrap();
+        std::fs::write(first.join("data"), "").unwrap();
+
+        let path = std::env::join_paths([&first, &second]).unwrap();
+        let path = path.to_str();
+        assert_eq!(find_binary("tool", path, &first), Some(second.join("tool")));
+        assert_eq!(find_binary("data", path, &first), None);
+        assert_eq!(find_binary("tool", None, &first), None);
+        // relative to the workdir, not looked up
+        assert_eq!(
+            find_binary("./tool", path, &second),
+            Some(second.join("./tool"))
+        );
+        assert_eq!(find_binary("./tool", path, &first), None);
+
+        std::fs::remove_dir_all(first).unwrap();
+        std::fs::remove_dir_all(second).unwrap();
+    }
+
+    #[test]
+    fn tells_unset_from_empty_env() {
+        let requires: Requires =
+            serde_yaml::from_str("env: [DATABASE_URL, API_KEY, PORT]").unwrap();
+        let env = HashMap::from([
+            ("API_KEY".to_string(), "".to_string()),
+            ("PORT".to_string(), "3000".to_string()),
+        ]);
+        assert_eq!(
+            requires.missing(&env, Path::new(".")),
+            vec![
+                Missing::Env("DATABASE_URL".to_string()),
+                Missing::EmptyEnv("API_KEY".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn describes_what_is_missing() {
+        let requires: Requires =
+            serde_yaml::from_str("{ bins: whiz-missing-binary, env: DATABASE_URL }").unwrap();
+        let missing = requires.missing(&HashMap::new(), Path::new("."));
+        assert_eq!(
+            describe_missing(&missing),
+            "missing binary `whiz-missing-binary`; missing env `DATABASE_URL` — set it in whiz.yaml env or your shell"
+        );
+        assert_eq!(
+            describe_missing(&missing[..1]),
+            "missing binary `whiz-missing-binary`"
+        );
+        assert!(Requires::default()
+            .missing(&HashMap::new(), Path::new("."))
+            .is_empty());
+    }
+}
@@ -0,0 +1,472 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, TimeDelta, TimeZone};
+use serde::{de, Deserialize, Deserializer};
+
+use super::ops::Ops;
+
+/// A cron expression, `minute hour day month weekday`, e.g. `*/30 * * * *`.
+///
+/// Each field is `*`, a value, a range `a-b` or a list of them separated by
+/// `,`, every one of them taking an optional step (`*/15`, `8-18/2`).
+/// Months and weekdays can be named (`jan`, `mon`), Sunday being `0` or
+/// `7`. As in cron, a day matches either the day or the weekday when both
+/// are restricted.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cron {
+    expr: String,
+    /// The schedules matching the days and the weekdays, a single one
+    /// unless both are restricted.
+    schedules: Vec<cron::Schedule>,
+}
+
+impl Cron {
+    /// Returns the first time after `now` the expression matches, if it
+    /// ever does (e.g. not `0 0 30 2 *`). Times skipped by a daylight
+    /// saving change are skipped as well.
+    pub fn next_after<Tz: TimeZone>(&self, now: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        self.schedules
+            .iter()
+            // the earliest of the times repeated when the clock goes back
+            // can be the past already
+            .filter_map(|schedule| schedule.after(now).find(|next| next > now))
+            .min()
+    }
+}
+
+const FIELDS: [&str; 5] = ["minute", "hour", "day", "month", "weekday"];
+
+/// Parses the 5 fields of an expression, running at the start of the
+/// minutes it matches.
+fn schedule(fields: [&str; 5]) -> Result<cron::Schedule, cron::error::Error> {
+    format!("0 {}", fields.join(" ")).parse()
+}
+
+/// Shifts the numbered weekdays of a field, Sunday being `0` or `7`, to the
+/// ones of [`cron::Schedule`], which numbers Sunday `1`.
+fn shift_weekdays(field: &str) -> String {
+    let shift = |day: &str| match day.parse::<u32>() {
+        Ok(7) => "1".to_string(),
+        Ok(day @ 0..=6) => (day + 1).to_string(),
+        _ => day.to_string(),
+    };
+    let parts = field.split(',').map(|part| {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, Some(step)),
+            None => (part, None),
+        };
+        let range = match range.split_once('-') {
+            // Sunday ends the week as well
+            Some((from, "7")) if step.is_none() => format!("{}-7,1", shift(from)),
+            Some((from, to)) => format!("{}-{}", shift(from), shift(to)),
+            None => shift(range),
+        };
+        match step {
+            Some(step) => format!("{range}/{step}"),
+            None => range,
+        }
+    });
+    Vec::from_iter(parts).join(",")
+}
+
+impl FromStr for Cron {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            bail!("invalid schedule {s:?}, expected 5 fields: minute hour day month weekday");
+        };
+        let shifted = shift_weekdays(weekday);
+        let fields = [minute, hour, day, month, shifted.as_str()];
+        // cron would tell the expression with seconds it was given
+        for (index, name) in FIELDS.iter().enumerate() {
+            let mut alone = ["*"; 5];
+            alone[index] = fields[index];
+            if schedule(alone).is_err() {
+                let field = [minute, hour, day, month, weekday][index];
+                bail!("invalid {name} {field:?} in schedule {s:?}");
+            }
+        }
+
+        let days = match (day.starts_with('*'), weekday.starts_with('*')) {
+            (false, false) => vec![
+                [minute, hour, day, month, "*"],
+                [minute, hour, "*", month, &shifted],
+            ],
+            _ => vec![fields],
+        };
+        let schedules = days
+            .into_iter()
+            .map(|fields| schedule(fields).map_err(|err| anyhow!("invalid schedule {s:?}: {err}")));
+        Ok(Self {
+            expr: s.to_string(),
+            schedules: schedules.collect::<Result<_>>()?,
+        })
+    }
+}
+
+impl fmt::Display for Cron {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.expr)
+    }
+}
+
+impl<'de> Deserialize<'de> for Cron {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+/// An interval such as `500ms`, `90s`, `15m`, `1h30m` or `1d`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Every(pub Duration);
+
+impl FromStr for Every {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid =
+            || anyhow!("invalid interval {s:?}, expected e.g. 500ms, 30s, 15m, 1h30m or 1d");
+
+        let mut millis = 0u64;
+        let mut digits = String::new();
+        let mut chars = s.trim().chars().peekable();
+        while let Some(c) = chars.next() {
+            if c.is_ascii_digit() {
+                digits.push(c);
+                continue;
+            }
+            let unit = match c {
+                'm' if chars.next_if_eq(&'s').is_some() => 1,
+                's' => 1_000,
+                'm' => 60 * 1_000,
+                'h' => 60 * 60 * 1_000,
+                'd' => 24 * 60 * 60 * 1_000,
+                _ => return Err(invalid()),
+            };
+            let count: u64 = std::mem::take(&mut digits).parse().map_err(|_| invalid())?;
+            millis = count
+                .checked_mul(unit)
+                .and_then(|count| millis.checked_add(count))
+                .ok_or_else(invalid)?;
+        }
+        if !digits.is_empty() || millis == 0 {
+            return Err(invalid());
+        }
+        Ok(Self(Duration::from_millis(millis)))
+    }
+}
+
+impl<'de> Deserialize<'de> for Every {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+/// When a task runs on its own, from its `schedule` or `every` field.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Schedule {
+    Cron(Cron),
+    Every(Every),
+}
+
+impl FromStr for Schedule {
+    type Err = anyhow::Error;
+
+    /// Parses a cron expression, or an interval as `every` takes when it
+    /// is a single word (e.g. `30s`).
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split_whitespace().count() {
+            1 => Ok(Schedule::Every(s.parse()?)),
+            _ => Ok(Schedule::Cron(s.parse()?)),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Schedule {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+impl Schedule {
+    /// Returns when the task runs next, `now` being the time it gets
+    /// scheduled at.
+    pub fn next_after<Tz: TimeZone>(&self, now: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        match self {
+            Schedule::Cron(cron) => cron.next_after(now),
+            Schedule::Every(Every(interval)) => now
+                .clone()
+                .checked_add_signed(TimeDelta::from_std(*interval).ok()?),
+        }
+    }
+}
+
+/// What a tick of the schedule does while the previous run still goes on.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Overlap {
+    /// The tick is skipped.
+    #[default]
+    Skip,
+    /// The previous run is stopped and the task runs again.
+    Restart,
+}
+
+impl Overlap {
+    /// Whether a tick runs the task, given whether it is still `running`.
+    pub fn runs(&self, running: bool) -> bool {
+        !running || *self == Overlap::Restart
+    }
+}
+
+/// Makes sure no task sets both a `schedule` and `every`.
+pub fn validate_schedules(ops: &Ops) -> Result<()> {
+    for (op_name, task) in ops {
+        if task.schedule.is_some() && task.every.is_some() {
+            bail!("task {op_name} sets both a schedule and every, keep one of them");
+        }
+    }
+    Ok(())
+}
+
+/// Whether the task of `op_name` only ever starts from a schedule: it has
+/// one and no dependency, or all its dependencies do. Such a task may
+/// never run, so `--exit-after` does not wait for it until it did.
+pub fn starts_on_schedule(ops: &Ops, op_name: &str) -> bool {
+    let Some(task) = ops.get(op_name) else {
+        return false;
+    };
+    let depends_on = task.depends_on.resolve();
+    if depends_on.is_empty() {
+        task.schedule().is_some()
+    } else {
+        depends_on.iter().all(|dep| starts_on_schedule(ops, dep))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time).unwrap().to_utc()
+    }
+
+    fn next(expr: &str, now: &str) -> Option<String> {
+        let cron: Cron = expr.parse().unwrap();
+        cron.next_after(&at(now)).map(|next| next.to_rfc3339())
+    }
+
+    #[test]
+    fn parses_cron_fields() {
+        // 2024-03-15 is a Friday
+        let now = "2024-03-15T10:07:30Z";
+        assert_eq!(
+            next("*/30 8-18/5 * jan-mar *", now).unwrap(),
+            "2024-03-15T13:00:00+00:00"
+        );
+        assert_eq!(
+            next("5/20 10 * * *", now).unwrap(),
+            "2024-03-15T10:25:00+00:00"
+        );
+        assert_eq!(
+            next("0 0 1,15 apr mon-fri", now).unwrap(),
+            "2024-04-01T00:00:00+00:00"
+        );
+        // Sunday is both 0 and 7
+        for sunday in ["0", "7", "sun", "6-7"] {
+            assert_eq!(
+                next(&format!("0 0 * * {sunday}"), "2024-03-16T10:00:00Z").unwrap(),
+                "2024-03-17T00:00:00+00:00"
+            );
+        }
+        assert_eq!(
+            next("0 0 * * 1-5", now).unwrap(),
+            "2024-03-18T00:00:00+00:00"
+        );
+        assert_eq!(
+            next("0 0 * * 1-5/2", "2024-03-18T10:00:00Z").unwrap(),
+            "2024-03-20T00:00:00+00:00"
+        );
+
+        let cron: Cron = "5/20 * * * 7".parse().unwrap();
+        assert_eq!(cron.to_string(), "5/20 * * * 7");
+    }
+
+    #[test]
+    fn rejects_invalid_cron() {
+        let err = |expr: &str| expr.parse::<Cron>().unwrap_err().to_string();
+        assert_eq!(
+            err("* * * *"),
+            "invalid schedule \"* * * *\", expected 5 fields: minute hour day month weekday"
+        );
+        assert_eq!(
+            err("60 * * * *"),
+            "invalid minute \"60\" in schedule \"60 * * * *\""
+        );
+        assert_eq!(
+            err("* * 0 * *"),
+            "invalid day \"0\" in schedule \"* * 0 * *\""
+        );
+        assert_eq!(
+            err("* 5-1 * * *"),
+            "invalid hour \"5-1\" in schedule \"* 5-1 * * *\""
+        );
+        assert_eq!(
+            err("*/0 * * * *"),
+            "invalid minute \"*/0\" in schedule \"*/0 * * * *\""
+        );
+        assert_eq!(
+            err("* * * * funday"),
+            "invalid weekday \"funday\" in schedule \"* * * * funday\""
+        );
+        assert_eq!(
+            err("* * * * 8"),
+            "invalid weekday \"8\" in schedule \"* * * * 8\""
+        );
+    }
+
+    #[test]
+    fn computes_next_fire() {
+        // 2024-03-15 is a Friday
+        let now = "2024-03-15T10:07:30Z";
+        assert_eq!(
+            next("*/30 * * * *", now).unwrap(),
+            "2024-03-15T10:30:00+00:00"
+        );
+        assert_eq!(next("* * * * *", now).unwrap(), "2024-03-15T10:08:00+00:00");
+        assert_eq!(next("0 9 * * *", now).unwrap(), "2024-03-16T09:00:00+00:00");
+        assert_eq!(
+            next("0 0 * * mon", now).unwrap(),
+            "2024-03-18T00:00:00+00:00"
+        );
+        assert_eq!(next("0 0 1 * *", now).unwrap(), "2024-04-01T00:00:00+00:00");
+        assert_eq!(
+            next("0 12 29 2 *", now).unwrap(),
+            "2028-02-29T12:00:00+00:00"
+        );
+        // either the day or the weekday when both are restricted
+        assert_eq!(
+            next("0 0 20 * sat", now).unwrap(),
+            "2024-03-16T00:00:00+00:00"
+        );
+        // a matching minute is never the current one
+        assert_eq!(
+            next("30 10 * * *", "2024-03-15T10:30:00Z").unwrap(),
+            "2024-03-16T10:30:00+00:00"
+        );
+        assert_eq!(
+            next("59 23 31 12 *", "2024-12-31T23:59:00Z").unwrap(),
+            "2025-12-31T23:59:00+00:00"
+        );
+        assert_eq!(next("0 0 30 2 *", now), None);
+    }
+
+    #[test]
+    fn parses_intervals() {
+        let every = |s: &str| s.parse::<Every>().map(|Every(d)| d.as_secs());
+        assert_eq!(every("90s").unwrap(), 90);
+        assert_eq!(every("15m").unwrap(), 15 * 60);
+        assert_eq!(every("1h30m").unwrap(), 90 * 60);
+        assert_eq!(every("1d").unwrap(), 24 * 60 * 60);
+        let millis = |s: &str| s.parse::<Every>().map(|Every(d)| d.as_millis());
+        assert_eq!(millis("500ms").unwrap(), 500);
+        assert_eq!(millis("1m30s250ms").unwrap(), 90_250);
+        for invalid in [
+            "",
+            "15",
+            "m",
+            "0s",
+            "0ms",
+            "1w",
+            "1.5h",
+            "ms",
+            "99999999999999999d",
+            "18446744073709551615ms1ms",
+        ] {
+            assert_eq!(
+                every(invalid).unwrap_err().to_string(),
+                format!("invalid interval {invalid:?}, expected e.g. 500ms, 30s, 15m, 1h30m or 1d")
+            );
+        }
+
+        let schedule = Schedule::Every("15m".parse().unwrap());
+        assert_eq!(
+            schedule.next_after(&at("2024-03-15T10:07:30Z")).unwrap(),
+            at("2024-03-15T10:22:30Z")
+        );
+    }
+
+    #[test]
+    fn parses_schedules() {
+        let schedule = |s: &str| s.parse::<Schedule>();
+        assert_eq!(
+            schedule("30s").unwrap(),
+            Schedule::Every(Every(Duration::from_secs(30)))
+        );
+        assert_eq!(
+            schedule("*/5 * * * *").unwrap(),
+            Schedule::Cron("*/5 * * * *".parse().unwrap())
+        );
+        assert_eq!(
+            schedule("30x").unwrap_err().to_string(),
+            "invalid interval \"30x\", expected e.g. 500ms, 30s, 15m, 1h30m or 1d"
+        );
+        assert_eq!(
+            schedule("* * *").unwrap_err().to_string(),
+            "invalid schedule \"* * *\", expected 5 fields: minute hour day month weekday"
+        );
+    }
+
+    #[test]
+    fn overlap_policy() {
+        assert!(Overlap::Skip.runs(false));
+        assert!(!Overlap::Skip.runs(true));
+        assert!(Overlap::Restart.runs(false));
+        assert!(Overlap::Restart.runs(true));
+        assert_eq!(Overlap::default(), Overlap::Skip);
+    }
+
+    #[test]
+    fn finds_tasks_starting_on_schedule() {
+        let ops: Ops = serde_yaml::from_str(
+            r#"
+            db: {command: postgres}
+            backup: {command: backup, schedule: "0 * * * *", depends_on: db}
+            report: {command: report, every: 1h}
+            upload: {command: upload, depends_on: report}
+            mixed: {command: mixed, depends_on: [report, db]}
+            "#,
+        )
+        .unwrap();
+        assert!(!starts_on_schedule(&ops, "db"));
+        // started once its dependency finished
+        assert!(!starts_on_schedule(&ops, "backup"));
+        assert!(starts_on_schedule(&ops, "report"));
+        assert!(starts_on_schedule(&ops, "upload"));
+        assert!(!starts_on_schedule(&ops, "mixed"));
+
+        let err = validate_schedules(
+            &serde_yaml::from_str("backup: {command: backup, schedule: '* * * * *', every: 1m}")
+                .unwrap(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "task backup sets both a schedule and every, keep one of them"
+        );
+    }
+}
@@ -0,0 +1,371 @@
+#[cfg(feature = "tui")]
+use anyhow::{anyhow, Result};
+#[cfg(feature = "tui")]
+use ratatui::style::{Color, Modifier, Style};
+#[cfg(feature = "tui")]
+use ratatui::text::Span;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "tui")]
+use subprocess::ExitStatus;
+
+#[cfg(feature = "tui")]
+use super::color::ColorOption;
+#[cfg(feature = "tui")]
+use crate::actors::state::TaskState;
+
+/// Status of a task as displayed in the UI.
+#[cfg(feature = "tui")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TaskStatus {
+    Waiting,
+    Running,
+    Success,
+    Failure,
+}
+
+#[cfg(feature = "tui")]
+impl From<TaskState> for TaskStatus {
+    fn from(state: TaskState) -> Self {
+        match state {
+            TaskState::Pending | TaskState::WaitingOnUpstream | TaskState::Queued => Self::Waiting,
+            TaskState::Starting | TaskState::Running | TaskState::Stopping => Self::Running,
+            TaskState::Exited(ExitStatus::Exited(0)) => Self::Success,
+            TaskState::Exited(_) | TaskState::Blocked => Self::Failure,
+        }
+    }
+}
+
+/// Glyph and color of a status, as set in the global config.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct StatusGlyphConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub glyph: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    /// Background of the focused panel, with `tint_panel`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tint: Option<String>,
+}
+
+/// `status_style` section of the global config: a preset name with
+/// optional per-status overrides.
+///
+/// ```yaml
+/// status_style:
+///   preset: high-contrast
+///   tint_panel: true
+///   failure:
+///     glyph: "✗"
+///     color: "#FFA500"
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct StatusStyleConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preset: Option<String>,
+    /// Tints the focused panel after the status of its task.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tint_panel: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub success: Option<StatusGlyphConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure: Option<StatusGlyphConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub running: Option<StatusGlyphConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub waiting: Option<StatusGlyphConfig>,
+}
+
+impl StatusStyleConfig {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+#[cfg(feature = "tui")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatusGlyph {
+    pub glyph: String,
+    pub style: Style,
+    /// Style of the focused panel of a task in this status.
+    pub tint: Style,
+}
+
+#[cfg(feature = "tui")]
+impl StatusGlyph {
+    fn new(glyph: &str, style: Style) -> Self {
+        Self {
+            glyph: glyph.to_string(),
+            style,
+            tint: Style::default(),
+        }
+    }
+
+    fn tinted(self, background: Color) -> Self {
+        Self {
+            tint: Style::default().bg(background),
+            ..self
+        }
+    }
+
+    fn patch(&mut self, config: &Option<StatusGlyphConfig>) -> Result<()> {
+        if let Some(config) = config {
+            if let Some(glyph) = &config.glyph {
+                self.glyph.clone_from(glyph);
+            }
+            if let Some(color) = &config.color {
+                self.style = parse_style(color)?;
+            }
+            if let Some(tint) = &config.tint {
+                self.tint = parse_tint(tint)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses a status color, accepting the task colors as well as
+/// `default` and `dim`.
+#[cfg(feature = "tui")]
+pub fn parse_style(color: &str) -> Result<Style> {
+    match color.to_ascii_lowercase().as_str() {
+        "default" => Ok(Style::default()),
+        "dim" => Ok(Style::default().add_modifier(Modifier::DIM)),
+        _ => Ok(Style::default().fg(ColorOption::parse_color(color)?.into())),
+    }
+}
+
+/// Parses the tint of a panel, a background color or `default`.
+#[cfg(feature = "tui")]
+fn parse_tint(color: &str) -> Result<Style> {
+    match color.to_ascii_lowercase().as_str() {
+        "default" => Ok(Style::default()),
+        _ => Ok(Style::default().bg(ColorOption::parse_color(color)?.into())),
+    }
+}
+
+/// Glyphs and styles used to render task statuses on every surface.
+#[cfg(feature = "tui")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatusTheme {
+    pub success: StatusGlyph,
+    pub failure: StatusGlyph,
+    pub running: StatusGlyph,
+    pub waiting: StatusGlyph,
+    /// Whether the focused panel takes the tint of its status, which still
+    /// tells it when the tabs are hidden.
+    pub tint_panel: bool,
+}
+
+#[cfg(feature = "tui")]
+impl Default for StatusTheme {
+    fn default() -> Self {
+        Self {
+            success: StatusGlyph::new(".", Style::default().fg(Color::Green))
+                .tinted(Color::Rgb(0, 40, 0)),
+            failure: StatusGlyph::new("!", Style::default().fg(Color::Red))
+                .tinted(Color::Rgb(56, 0, 0)),
+            running: StatusGlyph::new("*", Style::default()),
+            waiting: StatusGlyph::new("…", Style::default().add_modifier(Modifier::DIM)),
+            tint_panel: false,
+        }
+    }
+}
+
+#[cfg(feature = "tui")]
+impl StatusTheme {
+    pub const PRESETS: [&'static str; 2] = ["default", "high-contrast"];
+
+    /// Returns a built-in theme by name.
+    pub fn preset(name: &str) -> Result<Self> {
+        match name {
+            "default" => Ok(Self::default()),
+            // relies on glyphs and on a blue/orange pair
+            // rather than on green/red to tell statuses apart
+            "high-contrast" => Ok(Self {
+                success: StatusGlyph::new(
+                    " ✓",
+                    Style::default()
+                        .fg(Color::LightBlue)
+                        .add_modifier(Modifier::BOLD),
+                )
+                .tinted(Color::Rgb(0, 24, 64)),
+                failure: StatusGlyph::new(
+                    " ✗",
+                    Style::default()
+                        .fg(Color::Rgb(255, 165, 0))
+                        .add_modifier(Modifier::BOLD),
+                )
+                .tinted(Color::Rgb(64, 32, 0)),
+                running: StatusGlyph::new(" ▶", Style::default()),
+                waiting: StatusGlyph::new(" …", Style::default().add_modifier(Modifier::DIM)),
+                tint_panel: false,
+            }),
+            other => Err(anyhow!(
+                "unknown status style preset {:?}, expected one of: {}",
+                other,
+                Self::PRESETS.join(", ")
+            )),
+        }
+    }
+
+    pub fn from_config(config: &StatusStyleConfig) -> Result<Self> {
+        let mut theme = Self::preset(config.preset.as_deref().unwrap_or("default"))?;
+        theme.success.patch(&config.success)?;
+        theme.failure.patch(&config.failure)?;
+        theme.running.patch(&config.running)?;
+        theme.waiting.patch(&config.waiting)?;
+        theme.tint_panel = config.tint_panel.unwrap_or(false);
+        Ok(theme)
+    }
+
+    pub fn glyph(&self, status: TaskStatus) -> &StatusGlyph {
+        match status {
+            TaskStatus::Waiting => &self.waiting,
+            TaskStatus::Running => &self.running,
+            TaskStatus::Success => &self.success,
+            TaskStatus::Failure => &self.failure,
+        }
+    }
+
+    /// Renders a task name followed by its status glyph.
+    pub fn title(&self, name: &str, status: TaskStatus) -> Span<'static> {
+        let glyph = self.glyph(status);
+        Span::styled(format!("{}{}", name, glyph.glyph), glyph.style)
+    }
+
+    /// Style of the focused panel of a task, neutral unless `tint_panel`.
+    pub fn panel_style(&self, status: TaskStatus) -> Style {
+        match self.tint_panel {
+            true => self.glyph(status).tint,
+            false => Style::default(),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "tui"))]
+mod tests {
+    use super::*;
+
+    const STATUSES: [TaskStatus; 4] = [
+        TaskStatus::Waiting,
+        TaskStatus::Running,
+        TaskStatus::Success,
+        TaskStatus::Failure,
+    ];
+
+    fn render(theme: &StatusTheme) -> Vec<(String, Style)> {
+        STATUSES
+            .iter()
+            .map(|status| {
+                let span = theme.title("api", *status);
+                (span.content.into_owned(), span.style)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn renders_default_preset() {
+        let theme = StatusTheme::preset("default").unwrap();
+
+        assert_eq!(
+            render(&theme),
+            vec![
+                (
+                    "api…".to_string(),
+                    Style::default().add_modifier(Modifier::DIM)
+                ),
+                ("api*".to_string(), Style::default()),
+                ("api.".to_string(), Style::default().fg(Color::Green)),
+                ("api!".to_string(), Style::default().fg(Color::Red)),
+            ]
+        );
+    }
+
+    #[test]
+    fn renders_high_contrast_preset() {
+        let theme = StatusTheme::preset("high-contrast").unwrap();
+
+        assert_eq!(
+            render(&theme),
+            vec![
+                (
+                    "api …".to_string(),
+                    Style::default().add_modifier(Modifier::DIM)
+                ),
+                ("api ▶".to_string(), Style::default()),
+                (
+                    "api ✓".to_string(),
+                    Style::default()
+                        .fg(Color::LightBlue)
+                        .add_modifier(Modifier::BOLD)
+                ),
+                (
+                    "api ✗".to_string(),
+                    Style::default()
+                        .fg(Color::Rgb(255, 165, 0))
+                        .add_modifier(Modifier::BOLD)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn overrides_preset_from_config() {
+        let config: StatusStyleConfig = serde_yaml::from_str(
+            r##"
+            preset: high-contrast
+            failure:
+                glyph: " x"
+                color: "#ff0000"
+            "##,
+        )
+        .unwrap();
+        let theme = StatusTheme::from_config(&config).unwrap();
+
+        assert_eq!(
+            theme.title("api", TaskStatus::Failure),
+            Span::styled("api x", Style::default().fg(Color::Rgb(255, 0, 0)))
+        );
+        assert_eq!(
+            theme.success,
+            StatusTheme::preset("high-contrast").unwrap().success
+        );
+    }
+
+    #[test]
+    fn tints_panel_per_status() {
+        let config: StatusStyleConfig = serde_yaml::from_str(
+            r##"
+            tint_panel: true
+            running:
+                tint: "#202020"
+            "##,
+        )
+        .unwrap();
+        let theme = StatusTheme::from_config(&config).unwrap();
+
+        assert_eq!(
+            STATUSES.map(|status| theme.panel_style(status)),
+            [
+                Style::default(),
+                Style::default().bg(Color::Rgb(32, 32, 32)),
+                Style::default().bg(Color::Rgb(0, 40, 0)),
+                Style::default().bg(Color::Rgb(56, 0, 0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn panel_is_neutral_unless_tinted() {
+        let theme = StatusTheme::default();
+
+        for status in STATUSES {
+            assert_eq!(theme.panel_style(status), Style::default());
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_preset() {
+        assert!(StatusTheme::preset("neon").is_err());
+    }
+}
@@ -0,0 +1,240 @@
+use std::fmt;
+use std::io::{self, BufRead};
+use std::time::Duration;
+
+use serde::{de, Deserialize, Deserializer};
+
+/// How the output of a task is read.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Stream {
+    /// Line by line, a line showing once it ends.
+    #[default]
+    Line,
+    /// As it comes, the partial last line (e.g. a prompt) showing until
+    /// the rest arrives, see [`RawLines`].
+    Raw,
+}
+
+/// Encoding the output of a task is decoded from, e.g. `windows-1252` or
+/// `shift_jis` for the Windows tools printing in the system codepage,
+/// UTF-8 by default. Only the encodings keeping ASCII as is are supported,
+/// as lines are split on its newline.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Encoding(pub &'static encoding_rs::Encoding);
+
+impl Default for Encoding {
+    fn default() -> Self {
+        Self(encoding_rs::UTF_8)
+    }
+}
+
+impl Encoding {
+    /// Decodes a line without its newline, dropping the carriage return
+    /// ending it.
+    pub fn decode_line(self, line: &[u8]) -> String {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        self.0.decode_without_bom_handling(line).0.into_owned()
+    }
+
+    /// Reads the lines of `reader`, which unlike [`BufRead::lines`] does not
+    /// fail on the bytes invalid in the encoding but replaces them.
+    pub fn lines(self, reader: impl BufRead) -> impl Iterator<Item = io::Result<String>> {
+        reader
+            .split(b'\n')
+            .map(move |line| line.map(|line| self.decode_line(&line)))
+    }
+
+    /// Length of the longest start of `bytes` not ending with a character
+    /// cut in the middle, all of them when they are invalid anyway.
+    fn complete(self, bytes: &[u8]) -> usize {
+        // characters are at most 4 bytes long
+        (0..4.min(bytes.len() + 1))
+            .map(|cut| bytes.len() - cut)
+            .find(|&len| {
+                self.0
+                    .decode_without_bom_handling_and_without_replacement(&bytes[..len])
+                    .is_some()
+            })
+            .unwrap_or(bytes.len())
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for Encoding {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let label = String::deserialize(deserializer)?;
+        match encoding_rs::Encoding::for_label(label.as_bytes()) {
+            Some(encoding) if encoding.is_ascii_compatible() => Ok(Self(encoding)),
+            Some(encoding) => Err(de::Error::custom(format!(
+                "unsupported encoding {}, lines cannot be split",
+                encoding.name()
+            ))),
+            None => Err(de::Error::custom(format!(
+                "unknown encoding {label:?}, expected e.g. utf-8, windows-1252 or shift_jis"
+            ))),
+        }
+    }
+}
+
+/// How often the partial line of a raw task is shown again while it grows.
+pub const PARTIAL_FLUSH: Duration = Duration::from_millis(50);
+
+/// Splits the bytes read from a task in `raw` mode into lines, keeping the
+/// partial last one until it ends or is flushed.
+#[derive(Debug, Default)]
+pub struct RawLines {
+    encoding: Encoding,
+    pending: Vec<u8>,
+    /// Length of `pending` when it was last flushed.
+    flushed: usize,
+}
+
+impl RawLines {
+    /// Decodes the lines from `encoding` instead of UTF-8.
+    pub fn new(encoding: Encoding) -> Self {
+        Self {
+            encoding,
+            ..Self::default()
+        }
+    }
+
+    /// Adds the bytes read and returns the lines they end.
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        self.pending.extend_from_slice(bytes);
+        let Some(end) = self.pending.iter().rposition(|byte| *byte == b'\n') else {
+            return Vec::new();
+        };
+        let rest = self.pending.split_off(end + 1);
+        let lines = std::mem::replace(&mut self.pending, rest);
+        self.flushed = 0;
+        lines[..end]
+            .split(|byte| *byte == b'\n')
+            .map(|line| self.encoding.decode_line(line))
+            .collect()
+    }
+
+    /// Returns the partial line if it grew since the last flush, without
+    /// the bytes of a character cut in the middle. Only the text after
+    /// the last carriage return shows, as a terminal would.
+    pub fn flush(&mut self) -> Option<String> {
+        // the rest of the character is still to come
+        let valid = self.encoding.complete(&self.pending);
+        if valid == 0 || valid == self.flushed {
+            return None;
+        }
+        self.flushed = valid;
+        let (partial, _) = self
+            .encoding
+            .0
+            .decode_without_bom_handling(&self.pending[..valid]);
+        let shown = partial.rsplit('\r').next().unwrap_or_default();
+        Some(shown.to_string())
+    }
+
+    /// Returns the partial line left once the output closed.
+    pub fn finish(self) -> Option<String> {
+        (!self.pending.is_empty()).then(|| self.encoding.decode_line(&self.pending))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shows_prompts_without_newline() {
+        let mut lines = RawLines::default();
+
+        assert!(lines.push(b"Press enter to continue").is_empty());
+        assert_eq!(lines.flush().unwrap(), "Press enter to continue");
+        // nothing new to show
+        assert_eq!(lines.flush(), None);
+
+        assert_eq!(
+            lines.push(b": ok\nnext"),
+            vec!["Press enter to continue: ok"]
+        );
+        assert_eq!(lines.flush().unwrap(), "next");
+        assert_eq!(lines.finish().unwrap(), "next");
+    }
+
+    #[test]
+    fn splits_lines_of_partial_writes() {
+        let mut lines = RawLines::default();
+
+        assert!(lines.push(b"com").is_empty());
+        assert_eq!(
+            lines.push(b"piling\r\nlinking\n\nrun"),
+            vec!["compiling", "linking", ""]
+        );
+        assert_eq!(lines.flush().unwrap(), "run");
+        assert!(lines.push(b"ning").is_empty());
+        assert_eq!(lines.flush().unwrap(), "running");
+    }
+
+    #[test]
+    fn never_splits_characters() {
+        let mut lines = RawLines::default();
+        let bytes = "héllo ✓".as_bytes();
+        let (start, end) = bytes.split_at(bytes.len() - 2);
+
+        assert!(lines.push(start).is_empty());
+        assert_eq!(lines.flush().unwrap(), "héllo ");
+        // still the same character cut in the middle
+        assert_eq!(lines.flush(), None);
+        assert!(lines.push(end).is_empty());
+        assert_eq!(lines.flush().unwrap(), "héllo ✓");
+
+        let mut lines = RawLines::default();
+        assert!(lines.push(&"é".as_bytes()[..1]).is_empty());
+        assert_eq!(lines.flush(), None);
+        assert_eq!(
+            lines.push(&[&"é".as_bytes()[1..], b"\n"].concat()),
+            vec!["é"]
+        );
+    }
+
+    #[test]
+    fn decodes_other_encodings() {
+        let windows_1252: Encoding = serde_yaml::from_str("cp1252").unwrap();
+        let mut lines = RawLines::new(windows_1252);
+        assert_eq!(lines.push(b"caf\xe9 cr\xe8me\r\n"), vec!["café crème"]);
+
+        let shift_jis: Encoding = serde_yaml::from_str("shift_jis").unwrap();
+        let mut lines = RawLines::new(shift_jis);
+        // 日本 cut in the middle of its second character
+        assert!(lines.push(b"\x93\xfa\x96").is_empty());
+        assert_eq!(lines.flush().unwrap(), "日");
+        assert!(lines.push(b"\x7b").is_empty());
+        assert_eq!(lines.flush().unwrap(), "日本");
+
+        // bytes invalid in UTF-8 are replaced instead of failing the line
+        let read: Vec<_> = Encoding::default()
+            .lines(&b"ok\n\xff\n"[..])
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(read, vec!["ok", "\u{fffd}"]);
+    }
+
+    #[test]
+    fn rejects_encodings_splitting_newlines() {
+        let err = serde_yaml::from_str::<Encoding>("utf-16").unwrap_err();
+        assert!(err.to_string().contains("unsupported encoding UTF-16LE"));
+        let err = serde_yaml::from_str::<Encoding>("klingon").unwrap_err();
+        assert!(err.to_string().contains("unknown encoding \"klingon\""));
+    }
+
+    #[test]
+    fn shows_progress_after_carriage_returns() {
+        let mut lines = RawLines::default();
+
+        assert!(lines.push(b"10%\r20%\r30").is_empty());
+        assert_eq!(lines.flush().unwrap(), "30");
+    }
+}
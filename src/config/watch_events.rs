@@ -0,0 +1,59 @@
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use serde::Deserialize;
+
+/// Kind of file change reloading the tasks watching the file. Renaming a
+/// file counts as a modification, so that the editors saving through a
+/// temporary file still reload with `modify` only.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchEventKind {
+    Create,
+    Modify,
+    Remove,
+}
+
+impl WatchEventKind {
+    /// Every kind, reloading on any change by default.
+    pub const ALL: [Self; 3] = [Self::Create, Self::Modify, Self::Remove];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Create => "create",
+            Self::Modify => "modify",
+            Self::Remove => "remove",
+        }
+    }
+}
+
+impl FromStr for WatchEventKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.name() == s)
+            .ok_or_else(|| {
+                let names = Self::ALL.map(|kind| kind.name()).join(", ");
+                anyhow!("unknown watch event {s}, expected one of {names}")
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_kinds() {
+        assert_eq!(
+            "modify".parse::<WatchEventKind>().unwrap(),
+            WatchEventKind::Modify
+        );
+        assert_eq!(
+            "write".parse::<WatchEventKind>().unwrap_err().to_string(),
+            "unknown watch event write, expected one of create, modify, remove"
+        );
+    }
+}
@@ -0,0 +1,322 @@
+//! Runs whiz in the background with `--detach`, its output appended to
+//! `.whiz/whiz.log` next to the tasks. The detached whiz is then driven
+//! through a control socket by `whiz attach`, `whiz status` and `whiz stop`.
+//!
+//! The detached whiz records its session to `.whiz/session.jsonl` (see
+//! [`crate::replay`]). An attached client receives that recording from the
+//! start, then the events recorded after, until whiz stops or the client
+//! leaves. Attaching again replays the whole session once more.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+
+use actix::prelude::*;
+use anyhow::{anyhow, bail, Context, Result};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+
+use crate::actors::console::{ConsoleActor, Output, OutputKind};
+use crate::args::Command;
+use crate::file_mode::{self, FileMode};
+use crate::replay::{RecordedEvent, RecordedLine, SessionHeader};
+
+/// Directory of the files of a detached whiz, relative to the base dir.
+pub const DIR: &str = ".whiz";
+
+/// Hidden flag telling whiz it is the detached process.
+pub const DAEMONIZED_FLAG: &str = "--daemonized";
+
+pub struct DaemonPaths {
+    pub base_dir: PathBuf,
+    pub dir: PathBuf,
+    /// See [`crate::lock`].
+    pub lock: PathBuf,
+    pub pid: PathBuf,
+    pub log: PathBuf,
+    pub session: PathBuf,
+    pub socket: PathBuf,
+}
+
+impl DaemonPaths {
+    /// The files of a named instance are kept in their own directory.
+    pub fn new(base_dir: &Path, instance: Option<&str>) -> Self {
+        let dir = match instance {
+            Some(instance) => base_dir.join(DIR).join(instance),
+            None => base_dir.join(DIR),
+        };
+        Self {
+            base_dir: base_dir.to_path_buf(),
+            lock: dir.join("lock"),
+            pid: dir.join("whiz.pid"),
+            log: dir.join("whiz.log"),
+            session: dir.join("session.jsonl"),
+            socket: dir.join("whiz.sock"),
+            dir,
+        }
+    }
+
+    /// Returns the pid of the detached whiz, if one answers on the socket.
+    pub async fn running(&self) -> Option<u32> {
+        connect(&self.socket).await.ok()?;
+        fs::read_to_string(&self.pid).ok()?.trim().parse().ok()
+    }
+
+    /// Removes the files left by a detached whiz, except for its logs.
+    pub fn cleanup(&self) {
+        let _ = fs::remove_file(&self.socket);
+        let _ = fs::remove_file(&self.pid);
+    }
+}
+
+/// Requests a detached whiz answers on its control socket.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Request {
+    /// Lists each task and its state.
+    Status,
+    /// Stops every task and exits.
+    Stop,
+    /// Streams the recording of the session until whiz exits.
+    Attach,
+}
+
+impl Request {
+    pub fn from_command(command: &Command) -> Option<Self> {
+        match command {
+            Command::Status => Some(Request::Status),
+            Command::Stop => Some(Request::Stop),
+            _ => None,
+        }
+    }
+
+    pub fn parse(line: &str) -> Option<Self> {
+        match line.trim() {
+            "status" => Some(Request::Status),
+            "stop" => Some(Request::Stop),
+            "attach" => Some(Request::Attach),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Request::Status => "status",
+            Request::Stop => "stop",
+            Request::Attach => "attach",
+        }
+    }
+}
+
+/// Spawns whiz again with the same arguments (but `--detach`), detached
+/// from the terminal, and returns once its control socket answers.
+pub async fn detach(paths: &DaemonPaths, mode: Option<FileMode>) -> Result<()> {
+    if let Some(pid) = paths.running().await {
+        bail!("whiz is already running in the background (pid {pid}), see `whiz status`");
+    }
+    paths.cleanup();
+    fs::create_dir_all(&paths.dir)
+        .with_context(|| format!("cannot create {}", paths.dir.display()))?;
+    let log = file_mode::open(
+        OpenOptions::new().create(true).append(true),
+        &paths.log,
+        mode,
+    )
+    .with_context(|| format!("cannot open {}", paths.log.display()))?;
+
+    let mut command = std::process::Command::new(std::env::current_exe()?);
+    command
+        .args(std::env::args_os().skip(1).filter(|arg| arg != "--detach"))
+        .arg(DAEMONIZED_FLAG)
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log);
+    // neither Ctrl-C nor closing the terminal reach the detached whiz,
+    // which leads a session of its own without a controlling terminal
+    #[cfg(unix)]
+    unsafe {
+        std::os::unix::process::CommandExt::pre_exec(&mut command, || {
+            if libc::setsid() == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    #[cfg(windows)]
+    {
+        const DETACHED_PROCESS: u32 = 0x00000008;
+        const CREATE_NEW_PROCESS_GROUP: u32 = 0x00000200;
+        std::os::windows::process::CommandExt::creation_flags(
+            &mut command,
+            DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP,
+        );
+    }
+    let mut child = command
+        .spawn()
+        .context("cannot start whiz in the background")?;
+
+    for _ in 0..100 {
+        if let Some(status) = child.try_wait()? {
+            bail!(
+                "whiz exited in the background ({status}), see {}",
+                paths.log.display()
+            );
+        }
+        if connect(&paths.socket).await.is_ok() {
+            println!(
+                "whiz is running in the background (pid {}), logs in {}",
+                child.id(),
+                paths.log.display()
+            );
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    bail!(
+        "whiz did not answer in the background, see {}",
+        paths.log.display()
+    )
+}
+
+/// Sends `request` to the detached whiz and prints its answer.
+pub async fn request(paths: &DaemonPaths, request: Request) -> Result<()> {
+    let mut lines = send(paths, request).await?;
+    while let Some(line) = lines.next_line().await? {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+/// Follows the session of the detached whiz in the interactive view, or
+/// prints the lines of `task` (or of every task without a terminal) as
+/// they come. Leaving the view leaves whiz running.
+pub async fn attach(paths: &DaemonPaths, task: Option<String>) -> Result<()> {
+    let mut lines = send(paths, Request::Attach).await?;
+    let first = lines
+        .next_line()
+        .await?
+        .ok_or_else(|| anyhow!("whiz stopped"))?;
+    let header: SessionHeader =
+        serde_json::from_str(&first).map_err(|_| anyhow!("cannot attach: {first}"))?;
+    if let Some(task) = &task {
+        if !header.order.contains(task) {
+            bail!("unknown task {task}");
+        }
+    }
+
+    if !cfg!(feature = "tui") || task.is_some() || !io::stdout().is_terminal() {
+        while let Some(line) = lines.next_line().await? {
+            let line: RecordedLine = serde_json::from_str(&line)?;
+            if let Some(text) = plain_line(&line.event, task.as_deref()) {
+                println!("{text}");
+            }
+        }
+        System::current().stop();
+        return Ok(());
+    }
+
+    let console = ConsoleActor::new(header.order.clone(), header.timestamp)
+        .replay(true)
+        .start();
+    actix::spawn(async move {
+        while let Ok(Some(line)) = lines.next_line().await {
+            let Ok(RecordedLine { event, .. }) = serde_json::from_str(&line) else {
+                continue;
+            };
+            // the view is driven by the attached terminal
+            if !event.is_term_event() {
+                let _ = event.send_to(&console);
+            }
+        }
+        console.do_send(Output::now(
+            header.order[0].clone(),
+            "ATTACH: whiz stopped, press q to leave".to_string(),
+            OutputKind::Service,
+        ));
+    });
+    Ok(())
+}
+
+/// Formats an output line of the recording as printed by `whiz attach`,
+/// prefixed by its panel unless only the lines of `task` are printed.
+pub fn plain_line(event: &RecordedEvent, task: Option<&str>) -> Option<String> {
+    let RecordedEvent::Output {
+        panel,
+        message: Some(message),
+        ..
+    } = event
+    else {
+        return None;
+    };
+    match task {
+        None => Some(format!("{panel} | {message}")),
+        Some(task) if task == panel => Some(message.clone()),
+        Some(_) => None,
+    }
+}
+
+/// Sends `request` to the detached whiz and returns the lines of its answer.
+pub async fn send(paths: &DaemonPaths, request: Request) -> Result<Lines<BufReader<Stream>>> {
+    let mut stream = connect(&paths.socket).await.map_err(|_| {
+        anyhow!(
+            "whiz is not running in the background of {}",
+            paths.base_dir.display()
+        )
+    })?;
+    stream
+        .write_all(format!("{}\n", request.as_str()).as_bytes())
+        .await?;
+    Ok(BufReader::new(stream).lines())
+}
+
+#[cfg(unix)]
+pub type Stream = tokio::net::UnixStream;
+#[cfg(not(unix))]
+pub type Stream = tokio::net::TcpStream;
+
+/// Listens on a unix socket, or on a local TCP port written to the socket
+/// file on other platforms.
+pub struct Listener {
+    #[cfg(unix)]
+    inner: tokio::net::UnixListener,
+    #[cfg(not(unix))]
+    inner: tokio::net::TcpListener,
+}
+
+impl Listener {
+    #[cfg(unix)]
+    pub fn bind(socket: &Path) -> io::Result<Self> {
+        let _ = fs::remove_file(socket);
+        Ok(Self {
+            inner: tokio::net::UnixListener::bind(socket)?,
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn bind(socket: &Path) -> io::Result<Self> {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        listener.set_nonblocking(true)?;
+        fs::write(socket, listener.local_addr()?.port().to_string())?;
+        Ok(Self {
+            inner: tokio::net::TcpListener::from_std(listener)?,
+        })
+    }
+
+    pub async fn accept(&self) -> io::Result<Stream> {
+        Ok(self.inner.accept().await?.0)
+    }
+}
+
+#[cfg(unix)]
+async fn connect(socket: &Path) -> io::Result<Stream> {
+    Stream::connect(socket).await
+}
+
+#[cfg(not(unix))]
+async fn connect(socket: &Path) -> io::Result<Stream> {
+    let port: u16 = fs::read_to_string(socket)?
+        .trim()
+        .parse()
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid control port"))?;
+    Stream::connect(("127.0.0.1", port)).await
+}
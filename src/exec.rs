@@ -1,13 +1,22 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use dotenv_parser::parse_dotenv;
+use regex::Regex;
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, HashSet},
+    ffi::OsString,
     fs,
     path::{Path, PathBuf},
 };
-use subprocess::Exec;
+use subprocess::{CaptureData, Exec, Popen, PopenConfig, Redirection};
 
-use crate::config::{Config, ConfigInner, Task};
+use crate::actors::grim_reaper::shell_code;
+use crate::actors::state::Finished;
+use crate::config::{
+    limits::Limits,
+    ops::Ops,
+    requires::{Missing, Requires},
+    Config, ConfigInner, Entrypoint, Task,
+};
 
 impl Task {
     pub fn get_absolute_workdir(&self, base_dir: &Path) -> PathBuf {
@@ -23,10 +32,13 @@ pub struct ExecBuilder {
     cwd: PathBuf,
     cmd: String,
     args: Vec<String>,
+    merge_stderr: bool,
+    /// Resource limits of the command, see [`Limits::apply`].
+    limits: Limits,
 }
 
 impl ExecBuilder {
-    pub async fn new(task: &Task, config: &Config) -> Result<Self> {
+    pub async fn new(op_name: &str, task: &Task, config: &Config) -> Result<Self> {
         let cwd = task.get_absolute_workdir(&config.base_dir);
 
         let shared_env = config.get_shared_env().await?;
@@ -37,15 +49,37 @@ impl ExecBuilder {
             .collect::<Vec<_>>();
 
         let (cmd, args) = task.get_exec_command()?;
+        let (cmd, args) = match &task.container {
+            Some(container) => {
+                // the variables of the config, not the ones of the host
+                let mut keys = Vec::from_iter(
+                    env.iter()
+                        .map(|(key, _)| key)
+                        .filter(|key| {
+                            config.env.contains_key(*key)
+                                || task.env.contains_key(*key)
+                                || std::env::var_os(key).is_none()
+                        })
+                        .cloned(),
+                );
+                keys.sort();
+                container.wrap(op_name, &cwd, &keys, cmd, args)?
+            }
+            None => (cmd, args),
+        };
 
         Ok(Self {
             cwd,
             env,
             cmd,
             args,
+            merge_stderr: task.merge_stderr,
+            limits: task.limits.clone(),
         })
     }
 
+    /// Returns the command to run in the foreground, see [`Self::popen`]
+    /// for the tasks running alongside whiz.
     pub fn build(&self) -> Result<Exec> {
         Ok(Exec::cmd(self.cmd.clone())
             .args(&self.args)
@@ -53,9 +87,139 @@ impl ExecBuilder {
             .env_extend(&self.env))
     }
 
+    /// Starts the command, its stderr merged into its piped stdout unless
+    /// the task sets `merge_stderr: false`, piping it apart. Its stdin is
+    /// piped as well, for the keys typed in its panel. On Unix,
+    /// it leads its own process group so that stopping the task also stops
+    /// the processes it spawned. `extra_env` is added to the environment of
+    /// this run only, see [`upstream_env`].
+    pub fn popen(&self, extra_env: &[(String, String)]) -> Result<Popen> {
+        let stderr = match self.merge_stderr {
+            true => Redirection::Merge,
+            false => Redirection::Pipe,
+        };
+        self.spawn(extra_env, Redirection::Pipe, Redirection::Pipe, stderr)
+    }
+
+    /// Same as [`Self::popen`], the command reading its stdin from and
+    /// printing both its stdout and its stderr on `terminal`, the slave
+    /// side of a [`crate::pty::Pty`].
+    #[cfg(unix)]
+    pub fn popen_tty(&self, extra_env: &[(String, String)], terminal: fs::File) -> Result<Popen> {
+        let stdin = Redirection::File(terminal.try_clone()?);
+        self.spawn(
+            extra_env,
+            stdin,
+            Redirection::File(terminal),
+            Redirection::Merge,
+        )
+    }
+
+    fn spawn(
+        &self,
+        extra_env: &[(String, String)],
+        stdin: Redirection,
+        stdout: Redirection,
+        stderr: Redirection,
+    ) -> Result<Popen> {
+        let mut argv = vec![OsString::from(&self.cmd)];
+        argv.extend(self.args.iter().map(OsString::from));
+        // the last value of a variable wins
+        let mut env = Vec::from_iter(std::env::vars_os());
+        env.extend(
+            self.env
+                .iter()
+                .chain(extra_env)
+                .map(|(key, value)| (key.into(), value.into())),
+        );
+
+        let config = PopenConfig {
+            stdin,
+            stdout,
+            stderr,
+            cwd: Some(self.cwd.clone().into_os_string()),
+            env: Some(env),
+            #[cfg(unix)]
+            setpgid: true,
+            ..Default::default()
+        };
+        self.within_limits(|| Ok(Popen::create(&argv, config)?))?
+    }
+
+    /// Runs `spawn`, the process it starts getting the resource limits of
+    /// the task, see [`Limits::apply`].
+    pub fn within_limits<T>(&self, spawn: impl FnOnce() -> T) -> Result<T> {
+        self.limits.apply(spawn)
+    }
+
+    /// Runs a `before` or `after` hook of the task to completion in the
+    /// shell, with the workdir and environment of the command (on the host
+    /// for the containers). Its stderr is merged into the captured stdout.
+    pub fn hook(&self, command: &str, extra_env: &[(String, String)]) -> Result<CaptureData> {
+        Ok(Exec::shell(command)
+            .cwd(&self.cwd)
+            .env_extend(&self.env)
+            .env_extend(extra_env)
+            .stdout(Redirection::Pipe)
+            .stderr(Redirection::Merge)
+            .capture()?)
+    }
+
     pub fn as_string(&self) -> String {
         format!("EXEC: {} {:?} at {:?}", self.cmd, self.args, self.cwd)
     }
+
+    /// Formats the environment the command gets as `KEY=value` lines
+    /// sorted by name (`whiz dump-env`), only the variables differing from
+    /// the ones of whiz when `changed_only` is set. `redact` hides the
+    /// values of the variables which look secret, see [`looks_secret`].
+    pub fn dump_env(&self, changed_only: bool, redact: bool) -> String {
+        let parent = HashMap::<String, String>::from_iter(std::env::vars());
+        let env = self.environment();
+
+        let mut lines: Vec<String> = env
+            .iter()
+            .filter(|(key, value)| !changed_only || parent.get(*key) != Some(value))
+            .map(|(key, value)| match redact && looks_secret(key) {
+                true => format!("{key}=***"),
+                false => format!("{key}={value}"),
+            })
+            .collect();
+        lines.sort();
+        lines.join("\n")
+    }
+
+    /// Returns the environment the command gets, the one of whiz
+    /// overridden by the variables of the task.
+    pub fn environment(&self) -> HashMap<String, String> {
+        // the last value of a variable wins, as in `popen`
+        let mut env = HashMap::from_iter(std::env::vars());
+        env.extend(self.env.iter().cloned());
+        env
+    }
+
+    /// Returns what the task lacks to start, binaries being looked up in
+    /// its own `PATH` (on the host for the containers).
+    pub fn missing(&self, requires: &Requires) -> Vec<Missing> {
+        requires.missing(&self.environment(), &self.cwd)
+    }
+}
+
+/// Whether the name of a variable tells it holds a secret, e.g.
+/// `GITHUB_TOKEN` or `DB_PASSWORD`.
+pub fn looks_secret(key: &str) -> bool {
+    let key = key.to_uppercase();
+    [
+        "SECRET",
+        "TOKEN",
+        "PASSWORD",
+        "PASSWD",
+        "KEY",
+        "CREDENTIAL",
+        "AUTH",
+    ]
+    .iter()
+    .any(|word| key.contains(word))
 }
 
 impl ConfigInner {
@@ -66,9 +230,37 @@ impl ConfigInner {
     }
 }
 
+/// Variables telling how the last run of each upstream task ended, e.g.
+/// `WHIZ_DEP_build_STATUS=0` and `WHIZ_DEP_build_DURATION_MS=1234`, the
+/// status being `128 + N` when killed by the signal `N` as shells do. The
+/// characters of the task names which variables cannot hold become `_`.
+pub fn upstream_env(runs: &BTreeMap<String, Finished>) -> Vec<(String, String)> {
+    runs.iter()
+        .flat_map(|(op_name, run)| {
+            let name: String = op_name
+                .chars()
+                .map(|c| match c.is_ascii_alphanumeric() {
+                    true => c,
+                    false => '_',
+                })
+                .collect();
+            let status = shell_code(&run.status)
+                .map(|code| (format!("WHIZ_DEP_{name}_STATUS"), code.to_string()));
+            let duration = (
+                format!("WHIZ_DEP_{name}_DURATION_MS"),
+                run.duration.as_millis().to_string(),
+            );
+            status.into_iter().chain([duration])
+        })
+        .collect()
+}
+
 impl Task {
     pub fn get_exec_command(&self) -> Result<(String, Vec<String>)> {
-        let default_entrypoint = {
+        let default_entrypoint = if self.container.is_some() {
+            // images may only have a POSIX shell
+            "sh -c"
+        } else {
             #[cfg(not(target_os = "windows"))]
             {
                 "bash -c"
@@ -80,19 +272,11 @@ impl Task {
             }
         };
 
-        let entrypoint_lex = match &self.entrypoint {
-            Some(e) => {
-                if !e.is_empty() {
-                    e.as_str()
-                } else {
-                    default_entrypoint
-                }
-            }
-            None => default_entrypoint,
-        };
-
         let entrypoint_split = {
-            let mut s = shlex::split(entrypoint_lex).unwrap();
+            let mut s = match self.entrypoint.as_ref().filter(|e| !e.is_empty()) {
+                Some(entrypoint) => entrypoint.split()?,
+                None => split_entrypoint(default_entrypoint)?,
+            };
 
             match &self.command {
                 Some(a) => {
@@ -113,6 +297,28 @@ impl Task {
         Ok((entrypoint.to_owned(), nargs))
     }
 
+    /// Warns when `command` is passed to an entrypoint which does not seem
+    /// to take it as a script, i.e. whose last token is not `-c` (or a group
+    /// of short flags ending with it, e.g. `-lc`), `/c` or `--`.
+    pub fn entrypoint_warning(&self) -> Option<String> {
+        let (Some(_), Some(entrypoint)) = (&self.command, &self.entrypoint) else {
+            return None;
+        };
+        let tokens = entrypoint.split().ok()?;
+        let last = tokens.last()?;
+        let takes_command = last == "--"
+            || last.eq_ignore_ascii_case("/c")
+            || (last.len() > 1
+                && last.starts_with('-')
+                && !last.starts_with("--")
+                && last.ends_with('c'));
+        (!takes_command).then(|| {
+            format!(
+                "entrypoint `{entrypoint}` does not end with -c, /c or --, the command is passed to it as a single argument"
+            )
+        })
+    }
+
     pub async fn get_full_env(
         &self,
         cwd: &Path,
@@ -133,7 +339,9 @@ impl Task {
             env.extend(lade_sdk::resolve(&values.collect(), shared_env)?);
         }
 
-        env.extend(lade_sdk::resolve(&self.env.clone(), shared_env)?);
+        let mut outer_env = shared_env.clone();
+        outer_env.extend(env.clone());
+        env.extend(resolve_env_references(&self.env, &outer_env, cwd)?);
         let mut env = lade_sdk::hydrate(env, cwd.to_owned()).await?;
         env.extend(shared_env.clone());
 
@@ -141,8 +349,487 @@ impl Task {
     }
 }
 
+impl Entrypoint {
+    /// Returns the program and the arguments of the entrypoint, the ones
+    /// of a list as they are.
+    pub fn split(&self) -> Result<Vec<String>> {
+        match self {
+            Entrypoint::Line(line) => split_entrypoint(line),
+            Entrypoint::Args(args) if args.first().is_none_or(|program| program.is_empty()) => {
+                bail!("entrypoint {args:?} has no program")
+            }
+            Entrypoint::Args(args) => Ok(args.clone()),
+            Entrypoint::Preset { preset } => bail!("entrypoint preset {preset} is not resolved"),
+        }
+    }
+}
+
+/// Splits an entrypoint as a shell would, pointing at the quote left open
+/// when it cannot.
+fn split_entrypoint(entrypoint: &str) -> Result<Vec<String>> {
+    let Some(tokens) = shlex::split(entrypoint) else {
+        let (reason, at) = match unbalanced_quote(entrypoint) {
+            Some(at) => ("unbalanced quote", at),
+            // a trailing backslash escapes nothing
+            None => ("trailing backslash", entrypoint.len().saturating_sub(1)),
+        };
+        let column = entrypoint[..at].chars().count();
+        bail!(
+            "{reason} in entrypoint at position {column}:\n  {entrypoint}\n  {}^",
+            " ".repeat(column)
+        );
+    };
+    if tokens.is_empty() {
+        bail!("entrypoint {entrypoint:?} is empty");
+    }
+    Ok(tokens)
+}
+
+/// Returns the byte offset of the quote `entrypoint` leaves open, if any.
+fn unbalanced_quote(entrypoint: &str) -> Option<usize> {
+    let mut open: Option<(usize, char)> = None;
+    let mut chars = entrypoint.char_indices();
+    while let Some((at, c)) = chars.next() {
+        match (open, c) {
+            // escapes are only kept in double quotes
+            (None | Some((_, '"')), '\\') => {
+                chars.next();
+            }
+            (None, '\'' | '"') => open = Some((at, c)),
+            (Some((_, quote)), c) if c == quote => open = None,
+            _ => {}
+        }
+    }
+    open.map(|(at, _)| at)
+}
+
+/// Replaces the entrypoint of the tasks naming one of the config
+/// `entrypoints` with it, e.g. `entrypoint: py` given:
+///
+/// ```yaml
+/// entrypoints:
+///     py: python3 -c
+///     js: [node, -e]
+/// ```
+///
+/// A single word naming no preset stays the program of that name, while
+/// `entrypoint: {preset: py}` fails when there is none.
+pub fn resolve_entrypoints(presets: &HashMap<String, Entrypoint>, ops: &mut Ops) -> Result<()> {
+    if let Some((name, _)) = presets
+        .iter()
+        .find(|(_, preset)| matches!(preset, Entrypoint::Preset { .. }))
+    {
+        bail!("entrypoint preset {name} cannot refer to another preset");
+    }
+    for (op_name, task) in ops.iter_mut() {
+        let preset = match &task.entrypoint {
+            Some(Entrypoint::Line(line)) => match presets.get(line.trim()) {
+                Some(preset) => preset,
+                None => continue,
+            },
+            Some(Entrypoint::Preset { preset }) => match presets.get(preset) {
+                Some(preset) => preset,
+                None => {
+                    let mut names: Vec<_> = presets.keys().map(String::as_str).collect();
+                    names.sort();
+                    match names.is_empty() {
+                        true => bail!("task {op_name}: unknown entrypoint preset {preset}, no entrypoints are set"),
+                        false => bail!(
+                            "task {op_name}: unknown entrypoint preset {preset}, expected one of {}",
+                            names.join(", ")
+                        ),
+                    }
+                }
+            },
+            _ => continue,
+        };
+        task.entrypoint = Some(preset.clone());
+    }
+    Ok(())
+}
+
+/// Makes sure the entrypoint of each task can be split, see
+/// [`Task::get_exec_command`].
+pub fn validate_entrypoints(ops: &Ops) -> Result<()> {
+    for (op_name, task) in ops.iter() {
+        if let Some(entrypoint) = task.entrypoint.as_ref().filter(|e| !e.is_empty()) {
+            if let Err(err) = entrypoint.split() {
+                bail!("task {op_name}: {err}");
+            }
+        }
+    }
+    Ok(())
+}
+
+lazy_static::lazy_static! {
+    static ref ENV_REFERENCE: Regex = Regex::new(r"\$\{?(\w+)\}?").unwrap();
+    static ref FILE_REFERENCE: Regex = Regex::new(r"\$\{file:([^}]+)\}").unwrap();
+}
+
+/// Resolves the `$VAR`/`${VAR}` references of a task `env` block, and
+/// replaces the `${file:PATH}` ones with the trimmed content of the file,
+/// relative to `cwd`, e.g. to read a token kept out of the config.
+///
+/// References to other keys of the same block are resolved first, in
+/// dependency order, and take precedence over `outer_env`. A key
+/// referencing itself (e.g. `PATH: $PATH:./bin`) refers to the outer
+/// value. Unknown references resolve to an empty string, the content of
+/// the files is taken as is.
+///
+/// Fails if the references of the block form a cycle, or if a referenced
+/// file cannot be read.
+pub fn resolve_env_references(
+    env: &HashMap<String, String>,
+    outer_env: &HashMap<String, String>,
+    cwd: &Path,
+) -> Result<HashMap<String, String>> {
+    fn visit(
+        key: &str,
+        env: &HashMap<String, String>,
+        cwd: &Path,
+        context: &mut HashMap<String, String>,
+        resolved: &mut HashSet<String>,
+        path: &mut Vec<String>,
+    ) -> Result<()> {
+        if resolved.contains(key) {
+            return Ok(());
+        }
+        if let Some(start) = path.iter().position(|k| k == key) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(key.to_string());
+            bail!("cycle detected in env references: {}", cycle.join(" -> "));
+        }
+
+        let value = &env[key];
+        path.push(key.to_string());
+        let variables = FILE_REFERENCE.replace_all(value, "");
+        for reference in ENV_REFERENCE.captures_iter(&variables) {
+            let name = &reference[1];
+            if name != key && env.contains_key(name) {
+                visit(name, env, cwd, context, resolved, path)?;
+            }
+        }
+        path.pop();
+
+        let mut resolved_value = String::new();
+        let mut last = 0;
+        for reference in FILE_REFERENCE.captures_iter(value) {
+            let whole = reference.get(0).unwrap();
+            resolved_value.push_str(&lade_sdk::resolve_one(
+                &value[last..whole.start()],
+                context,
+            )?);
+            let file = cwd.join(&reference[1]);
+            let content = fs::read_to_string(&file)
+                .with_context(|| format!("cannot read {} for env {key}", file.display()))?;
+            resolved_value.push_str(content.trim());
+            last = whole.end();
+        }
+        resolved_value.push_str(&lade_sdk::resolve_one(&value[last..], context)?);
+        context.insert(key.to_string(), resolved_value);
+        resolved.insert(key.to_string());
+        Ok(())
+    }
+
+    let mut context = outer_env.clone();
+    let mut resolved = HashSet::new();
+
+    let mut keys: Vec<&String> = env.keys().collect();
+    keys.sort();
+    for key in keys {
+        visit(key, env, cwd, &mut context, &mut resolved, &mut Vec::new())?;
+    }
+
+    Ok(env
+        .keys()
+        .map(|key| (key.clone(), context[key].clone()))
+        .collect())
+}
+
 pub fn get_env() -> HashMap<String, String> {
     let mut env = HashMap::new();
     env.insert("RUST_LOG".to_string(), "info".to_string());
     env
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env_of(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn detects_secret_names() {
+        assert!(looks_secret("GITHUB_TOKEN"));
+        assert!(looks_secret("db_password"));
+        assert!(looks_secret("AWS_SECRET_ACCESS_KEY"));
+        assert!(!looks_secret("PATH"));
+        assert!(!looks_secret("PORT"));
+    }
+
+    #[test]
+    fn resolves_chained_references() {
+        let env = env_of(&[
+            ("URL", "http://${HOST}:${PORT}/api"),
+            ("HOST", "$DOMAIN"),
+            ("PORT", "8080"),
+            ("PATH", "$PATH:./bin"),
+        ]);
+        let outer = env_of(&[("DOMAIN", "localhost"), ("PATH", "/usr/bin")]);
+
+        let resolved = resolve_env_references(&env, &outer, Path::new(".")).unwrap();
+
+        assert_eq!(resolved["URL"], "http://localhost:8080/api");
+        assert_eq!(resolved["HOST"], "localhost");
+        assert_eq!(resolved["PORT"], "8080");
+        assert_eq!(resolved["PATH"], "/usr/bin:./bin");
+    }
+
+    fn task_with(entrypoint: &str, command: Option<&str>) -> Task {
+        let mut task: Task = serde_yaml::from_str("{}").unwrap();
+        task.entrypoint = Some(Entrypoint::Line(entrypoint.to_string()));
+        task.command = command.map(str::to_string);
+        task
+    }
+
+    #[test]
+    fn points_at_unbalanced_quote() {
+        let task = task_with("bash -c '", Some("echo hi"));
+
+        let err = task.get_exec_command().unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "unbalanced quote in entrypoint at position 8:\n  bash -c '\n          ^"
+        );
+        assert_eq!(unbalanced_quote(r#"sh -c "it's""#), None);
+        assert_eq!(unbalanced_quote(r#"sh -c "a \" b"#), Some(6));
+        assert!(split_entrypoint("sh \\").is_err());
+        assert!(split_entrypoint("  ").is_err());
+    }
+
+    #[test]
+    fn splits_quoted_entrypoint() {
+        let task = task_with(
+            r#"env "GREETING=hello world" sh -c"#,
+            Some("echo $GREETING"),
+        );
+
+        let (cmd, args) = task.get_exec_command().unwrap();
+
+        assert_eq!(cmd, "env");
+        assert_eq!(
+            args,
+            vec!["GREETING=hello world", "sh", "-c", "echo $GREETING"]
+        );
+        assert!(task_with(r#"python -c ""#, None)
+            .get_exec_command()
+            .is_err());
+    }
+
+    #[test]
+    fn splits_entrypoint_lines_and_lists_alike() {
+        let config: crate::config::RawConfig = r#"
+            line:
+                entrypoint: python3 -u -c
+                command: print('hi')
+            list:
+                entrypoint: [python3, -u, -c]
+                command: print('hi')
+            quoted:
+                entrypoint: [env, "GREETING=it's me", sh, -c]
+                command: echo $GREETING
+            "#
+        .parse()
+        .unwrap();
+        let ops = &config.ops;
+
+        let line = ops["line"].get_exec_command().unwrap();
+        assert_eq!(line, ops["list"].get_exec_command().unwrap());
+        assert_eq!(
+            line,
+            (
+                "python3".to_string(),
+                vec!["-u".into(), "-c".into(), "print('hi')".into()]
+            )
+        );
+        // the quote would be left open in a line
+        assert_eq!(
+            ops["quoted"].get_exec_command().unwrap().1,
+            vec!["GREETING=it's me", "sh", "-c", "echo $GREETING"]
+        );
+        assert_eq!(ops["list"].entrypoint_warning(), None);
+        assert!(Entrypoint::Args(vec!["".into(), "-c".into()])
+            .split()
+            .is_err());
+    }
+
+    #[test]
+    fn resolves_entrypoint_presets() {
+        let config: crate::config::RawConfig = r#"
+            entrypoints:
+                py: python3 -c
+                js: [node, -e]
+            script:
+                entrypoint: py
+                command: print('hi')
+            explicit:
+                entrypoint: { preset: js }
+                command: console.log('hi')
+            literal:
+                entrypoint: sh -c
+                command: echo hi
+            "#
+        .parse()
+        .unwrap();
+
+        let config = ConfigInner::from_raw(config, PathBuf::from(".")).unwrap();
+        let command = |name: &str| config.ops[name].get_exec_command().unwrap();
+        assert_eq!(
+            command("script"),
+            ("python3".into(), vec!["-c".into(), "print('hi')".into()])
+        );
+        assert_eq!(
+            command("explicit"),
+            ("node".into(), vec!["-e".into(), "console.log('hi')".into()])
+        );
+        assert_eq!(
+            command("literal"),
+            ("sh".into(), vec!["-c".into(), "echo hi".into()])
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_entrypoint_presets() {
+        let config: crate::config::RawConfig = r#"
+            entrypoints:
+                py: python3 -c
+                js: node -e
+            script:
+                entrypoint: { preset: rb }
+                command: puts 'hi'
+            "#
+        .parse()
+        .unwrap();
+
+        let err = ConfigInner::from_raw(config, PathBuf::from(".")).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "task script: unknown entrypoint preset rb, expected one of js, py"
+        );
+    }
+
+    #[test]
+    fn rejects_unbalanced_entrypoint_at_load() {
+        let config: crate::config::RawConfig = r#"
+            api:
+                entrypoint: "bash -c '"
+                command: echo hi
+            "#
+        .parse()
+        .unwrap();
+
+        let err = ConfigInner::from_raw(config, PathBuf::from(".")).unwrap_err();
+
+        assert!(err
+            .to_string()
+            .starts_with("task api: unbalanced quote in entrypoint at position 8"));
+    }
+
+    #[test]
+    fn warns_about_entrypoint_without_command_flag() {
+        for entrypoint in ["bash -c", "bash -lc", "cmd /C", "npx --"] {
+            assert_eq!(
+                task_with(entrypoint, Some("echo hi")).entrypoint_warning(),
+                None,
+                "{entrypoint}"
+            );
+        }
+        assert!(task_with("python", Some("main.py"))
+            .entrypoint_warning()
+            .is_some());
+        assert!(task_with("bash --verbose", Some("echo hi"))
+            .entrypoint_warning()
+            .is_some());
+        // nothing is passed without command
+        assert_eq!(task_with("python main.py", None).entrypoint_warning(), None);
+    }
+
+    #[test]
+    fn fails_on_reference_cycle() {
+        let env = env_of(&[("A", "${B}"), ("B", "${C}"), ("C", "$A")]);
+
+        let err = resolve_env_references(&env, &HashMap::new(), Path::new(".")).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "cycle detected in env references: A -> B -> C -> A"
+        );
+    }
+
+    #[test]
+    fn reads_referenced_files() {
+        let dir = std::env::temp_dir().join(format!("whiz-env-files-{}", std::process::id()));
+        fs::create_dir_all(dir.join(".secrets")).unwrap();
+        fs::write(dir.join(".secrets/token"), "s3cr$t\n").unwrap();
+        let env = env_of(&[
+            ("TOKEN", "${file:.secrets/token}"),
+            ("AUTH", "Bearer ${file:.secrets/token} for $USER"),
+            ("USER", "api"),
+        ]);
+
+        let resolved = resolve_env_references(&env, &HashMap::new(), &dir).unwrap();
+        assert_eq!(resolved["TOKEN"], "s3cr$t");
+        assert_eq!(resolved["AUTH"], "Bearer s3cr$t for api");
+
+        let env = env_of(&[("TOKEN", "${file:.secrets/missing}")]);
+        let err = resolve_env_references(&env, &HashMap::new(), &dir).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "cannot read {} for env TOKEN",
+                dir.join(".secrets/missing").display()
+            )
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn tells_how_upstream_tasks_ended() {
+        let runs = BTreeMap::from([
+            (
+                "build".to_string(),
+                Finished {
+                    status: subprocess::ExitStatus::Exited(3),
+                    duration: std::time::Duration::from_millis(1234),
+                },
+            ),
+            (
+                "db:migrate".to_string(),
+                Finished {
+                    status: subprocess::ExitStatus::Signaled(9),
+                    duration: std::time::Duration::from_secs(2),
+                },
+            ),
+        ]);
+        assert_eq!(
+            upstream_env(&runs),
+            vec![
+                ("WHIZ_DEP_build_STATUS".to_string(), "3".to_string()),
+                ("WHIZ_DEP_build_DURATION_MS".to_string(), "1234".to_string()),
+                ("WHIZ_DEP_db_migrate_STATUS".to_string(), "137".to_string()),
+                (
+                    "WHIZ_DEP_db_migrate_DURATION_MS".to_string(),
+                    "2000".to_string()
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,128 @@
+//! Codes whiz exits with, the same in every mode so that scripts can tell a
+//! failing task from a broken config. `whiz exit-codes` prints them.
+
+use std::fmt;
+
+use anyhow::Result;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(i32)]
+pub enum ExitCode {
+    Success = 0,
+    /// A task failed with `--exit-after`, `--exit-when-done` or `whiz x`,
+    /// or `whiz doctor` found a task missing what it requires.
+    /// A `primary` task passes its own code through instead.
+    TaskFailed = 1,
+    /// The command line or the config is invalid, e.g. a missing config
+    /// file, an unknown task or a dependency cycle.
+    Usage = 2,
+    /// Whiz itself failed, e.g. it could not reach the background whiz.
+    Internal = 3,
+    /// `whiz upgrade --check` found a newer version.
+    UpdateAvailable = 10,
+}
+
+impl ExitCode {
+    pub const ALL: [Self; 5] = [
+        Self::Success,
+        Self::TaskFailed,
+        Self::Usage,
+        Self::Internal,
+        Self::UpdateAvailable,
+    ];
+
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Self::Success => "success",
+            Self::TaskFailed => "a task failed (a primary task passes its own code)",
+            Self::Usage => "invalid command line or config",
+            Self::Internal => "whiz itself failed",
+            Self::UpdateAvailable => "upgrade --check found a newer version",
+        }
+    }
+
+    /// Makes whiz exit with this code on `error`.
+    pub fn wrap(self, error: impl Into<anyhow::Error>) -> anyhow::Error {
+        anyhow::Error::new(Exit {
+            code: self,
+            error: error.into(),
+        })
+    }
+
+    /// Returns the code to exit with on `error`, internal unless it was
+    /// wrapped with another one.
+    pub fn of(error: &anyhow::Error) -> Self {
+        error
+            .chain()
+            .find_map(|err| err.downcast_ref::<Exit>())
+            .map_or(Self::Internal, |exit| exit.code)
+    }
+
+    /// Formats the codes one per line, as printed by `whiz exit-codes`.
+    pub fn table() -> String {
+        Self::ALL
+            .iter()
+            .map(|code| format!("{:>3}  {}\n", code.code(), code.description()))
+            .collect()
+    }
+}
+
+/// Error tagged with the code whiz exits with, see [`ExitCode::wrap`].
+#[derive(Debug)]
+struct Exit {
+    code: ExitCode,
+    error: anyhow::Error,
+}
+
+impl fmt::Display for Exit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.error)
+    }
+}
+
+impl std::error::Error for Exit {}
+
+pub trait WithExitCode<T> {
+    /// Makes whiz exit with `code` on the error, see [`ExitCode::wrap`].
+    fn exit_code(self, code: ExitCode) -> Result<T>;
+}
+
+impl<T, E: Into<anyhow::Error>> WithExitCode<T> for std::result::Result<T, E> {
+    fn exit_code(self, code: ExitCode) -> Result<T> {
+        self.map_err(|err| code.wrap(err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{anyhow, Context};
+
+    use super::*;
+
+    #[test]
+    fn finds_the_code_of_an_error() {
+        let usage = Err::<(), _>(anyhow!("unknown task api")).exit_code(ExitCode::Usage);
+        let usage = usage.context("cannot plan").unwrap_err();
+
+        assert_eq!(ExitCode::of(&usage), ExitCode::Usage);
+        assert_eq!(ExitCode::of(&anyhow!("socket closed")), ExitCode::Internal);
+        assert_eq!(
+            ExitCode::Usage
+                .wrap(anyhow!("unknown task api"))
+                .to_string(),
+            "unknown task api"
+        );
+    }
+
+    #[test]
+    fn prints_table() {
+        assert_eq!(
+            ExitCode::table(),
+            "  0  success\n  1  a task failed (a primary task passes its own code)\n  2  invalid command line or config\n  3  whiz itself failed\n 10  upgrade --check found a newer version\n"
+        );
+    }
+}
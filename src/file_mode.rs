@@ -0,0 +1,194 @@
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail};
+use serde::{de, Deserialize, Deserializer};
+
+/// Unix permissions given to the files whiz creates (e.g. `0o600`), written
+/// in octal. They are not applied on other platforms.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FileMode(u32);
+
+impl FileMode {
+    pub fn new(mode: u32) -> anyhow::Result<Self> {
+        if mode > 0o777 {
+            bail!("file mode {mode:o} is not within 0o000 and 0o777");
+        }
+        Ok(Self(mode))
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+}
+
+impl FromStr for FileMode {
+    type Err = anyhow::Error;
+
+    /// Parses an octal mode, with or without its `0o` or `0` prefix.
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let digits = s.strip_prefix("0o").unwrap_or(s);
+        let mode = u32::from_str_radix(digits, 8)
+            .map_err(|_| anyhow!("invalid file mode {s:?}, expected octal such as 0o600"))?;
+        Self::new(mode)
+    }
+}
+
+impl fmt::Display for FileMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0o{:03o}", self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for FileMode {
+    /// Reads the digits as octal whether they are quoted or not, `600`
+    /// being `0o600` as for `chmod`.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Octal;
+
+        impl de::Visitor<'_> for Octal {
+            type Value = FileMode;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an octal file mode such as 0o600")
+            }
+
+            fn visit_str<E: de::Error>(self, mode: &str) -> Result<FileMode, E> {
+                mode.parse().map_err(E::custom)
+            }
+
+            // the formats reading the digits as a decimal number
+            fn visit_u64<E: de::Error>(self, mode: u64) -> Result<FileMode, E> {
+                self.visit_str(&mode.to_string())
+            }
+
+            fn visit_i64<E: de::Error>(self, mode: i64) -> Result<FileMode, E> {
+                self.visit_str(&mode.to_string())
+            }
+        }
+
+        deserializer.deserialize_str(Octal)
+    }
+}
+
+/// Opens `path` with `options`, giving it `mode` if the file is created,
+/// whatever the umask. Existing files keep their permissions.
+pub fn open(options: &OpenOptions, path: &Path, mode: Option<FileMode>) -> io::Result<File> {
+    let Some(mode) = mode else {
+        return options.open(path);
+    };
+    let created = !path.exists();
+    // created with the mode (narrowed by the umask) so that it is never
+    // readable by more than asked, then widened to the mode if need be
+    #[cfg(unix)]
+    let options = &{
+        let mut options = options.clone();
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, mode.bits());
+        options
+    };
+    let file = options.open(path)?;
+    if created {
+        set_mode(&file, mode)?;
+    }
+    Ok(file)
+}
+
+/// Gives `mode` to a path whiz created, such as a socket.
+pub fn set_path_mode(path: &Path, mode: Option<FileMode>) -> io::Result<()> {
+    match mode {
+        #[cfg(unix)]
+        Some(mode) => {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode.bits()))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(unix)]
+fn set_mode(file: &File, mode: FileMode) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    file.set_permissions(std::fs::Permissions::from_mode(mode.bits()))
+}
+
+#[cfg(not(unix))]
+fn set_mode(_: &File, _: FileMode) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!("600".parse::<FileMode>().unwrap(), FileMode(0o600));
+        assert_eq!("0600".parse::<FileMode>().unwrap(), FileMode(0o600));
+        assert_eq!("0o640".parse::<FileMode>().unwrap(), FileMode(0o640));
+        assert!("0o800".parse::<FileMode>().is_err());
+        assert!("1777".parse::<FileMode>().is_err());
+        assert_eq!(FileMode(0o600).to_string(), "0o600");
+    }
+
+    #[test]
+    fn deserialize() {
+        let modes: Vec<FileMode> = serde_yaml::from_str("[0o600, '640', 400, 444, 0600]").unwrap();
+        assert_eq!(
+            modes,
+            vec![
+                FileMode(0o600),
+                FileMode(0o640),
+                FileMode(0o400),
+                FileMode(0o444),
+                FileMode(0o600)
+            ]
+        );
+        assert!(serde_yaml::from_str::<FileMode>("800").is_err());
+
+        // TOML reads the digits as a decimal number
+        #[derive(Deserialize)]
+        struct Config {
+            file_mode: FileMode,
+        }
+        let config: Config = toml::from_str("file_mode = 600").unwrap();
+        assert_eq!(config.file_mode, FileMode(0o600));
+    }
+
+    #[cfg(unix)]
+    mod unix {
+        use super::*;
+        use std::os::unix::fs::PermissionsExt;
+
+        fn mode_of(path: &Path) -> u32 {
+            std::fs::metadata(path).unwrap().permissions().mode() & 0o777
+        }
+
+        #[test]
+        fn applies_mode_on_creation() {
+            let dir = std::env::temp_dir().join(format!("whiz-file-mode-{}", std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            let path = dir.join("created.log");
+            let _ = std::fs::remove_file(&path);
+            let mut options = OpenOptions::new();
+            options.create(true).append(true);
+
+            open(&options, &path, Some(FileMode(0o600))).unwrap();
+            assert_eq!(mode_of(&path), 0o600);
+
+            // ignores the umask
+            let wide = dir.join("wide.log");
+            let _ = std::fs::remove_file(&wide);
+            open(&options, &wide, Some(FileMode(0o666))).unwrap();
+            assert_eq!(mode_of(&wide), 0o666);
+
+            // existing files are left as they are
+            open(&options, &path, Some(FileMode(0o644))).unwrap();
+            assert_eq!(mode_of(&path), 0o600);
+
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+    }
+}
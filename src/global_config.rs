@@ -6,9 +6,24 @@ use std::path::Path;
 
 use tokio::fs;
 
+use crate::actors::console::Split;
+use crate::config::status::StatusStyleConfig;
+
 #[derive(Deserialize, Serialize)]
 pub struct GlobalConfig {
     pub update_check: DateTime<Utc>,
+
+    #[serde(default, skip_serializing_if = "StatusStyleConfig::is_empty")]
+    pub status_style: StatusStyleConfig,
+
+    /// Split of the log area whiz starts with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub split: Option<Split>,
+
+    /// Color of the lines tasks print on stderr when they do not merge it
+    /// into stdout, a task color, `default` or `dim`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stderr_color: Option<String>,
 }
 
 impl GlobalConfig {
@@ -20,6 +35,9 @@ impl GlobalConfig {
         } else {
             let config = GlobalConfig {
                 update_check: Utc::now(),
+                status_style: StatusStyleConfig::default(),
+                split: None,
+                stderr_color: None,
             };
             config.save(path).await?;
             Ok(config)
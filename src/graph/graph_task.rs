@@ -2,6 +2,7 @@ use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 
 pub struct Graph<'a> {
+    tasks: &'a [Task],
     pub independent_tasks: Vec<&'a Task>,
     nodes_dictionary: HashMap<String, usize>,
     edges: Vec<(usize, usize)>,
@@ -14,12 +15,18 @@ impl<'a> Graph<'a> {
         Self::populate_node_dictionary(&mut nodes_dictionary, &dependent_tasks);
         let edges = Self::build_edges(&dependent_tasks, &nodes_dictionary);
         Self {
+            tasks: tasks_list,
             independent_tasks,
             nodes_dictionary,
             edges,
         }
     }
 
+    /// All the tasks of the graph, independent ones included.
+    pub fn tasks(&self) -> &[Task] {
+        self.tasks
+    }
+
     pub fn nodes(&self) -> HashMap<&usize, &String> {
         self.nodes_dictionary
             .iter()
@@ -72,6 +79,27 @@ impl<'a> Graph<'a> {
         });
     }
 
+    /// Lists the tasks which have a description with it under the graph,
+    /// one per line, sorted by name.
+    pub fn format_descriptions(&self) -> String {
+        let mut described: Vec<_> = self
+            .tasks
+            .iter()
+            .filter_map(|task| Some((&task.name, task.description.as_ref()?)))
+            .collect();
+        if described.is_empty() {
+            return String::new();
+        }
+        described.sort();
+        described
+            .iter()
+            .fold("\n".to_string(), |list, (name, description)| {
+                let description = description.split_whitespace().collect::<Vec<_>>().join(" ");
+                format!("{list}\n|{name}|: {description}")
+            })
+            + "\n"
+    }
+
     pub fn format_independent_task(&self) -> String {
         //Format the indipendent tasks on the first line
         if self.independent_tasks.is_empty() {
@@ -85,22 +113,14 @@ impl<'a> Graph<'a> {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
-pub struct TaskFile {
-    #[serde(flatten)]
-    file: HashMap<String, DependsOn>,
-}
-
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
-pub struct DependsOn {
-    pub depends_on: Vec<String>,
-}
-
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Task {
     pub name: String,
 
     pub depends_on: Vec<String>,
+
+    #[serde(default)]
+    pub description: Option<String>,
 }
 
 impl Task {
@@ -141,6 +161,7 @@ mod helpers_tests {
                     .iter()
                     .map(|refer| refer.to_string())
                     .collect::<Vec<String>>(),
+                description: None,
             }
         }
     }
@@ -222,14 +243,17 @@ mod helpers_tests {
             Task {
                 name: "0.1_task".into(),
                 depends_on: vec![],
+                description: None,
             },
             Task {
                 name: "0.5_task".into(),
                 depends_on: vec![],
+                description: None,
             },
             Task {
                 name: "0.11_task".into(),
                 depends_on: vec![],
+                description: None,
             },
         ]
         .iter()
@@ -241,16 +265,19 @@ mod helpers_tests {
         let one = Task {
             name: "one".to_owned(),
             depends_on: vec![],
+            description: None,
         };
 
         let two = Task {
             name: "two".to_owned(),
             depends_on: vec!["one".to_owned()],
+            description: None,
         };
 
         let three = Task {
             name: "three".to_owned(),
             depends_on: vec!["one".to_owned(), "two".to_owned()],
+            description: None,
         };
 
         let dependent_dictionary: HashMap<String, usize> = HashMap::from([
@@ -271,4 +298,17 @@ mod helpers_tests {
         assert_eq!(dependencies_for_two, Some(vec![(1, 2)]));
         assert_eq!(dependencies_for_three, Some(vec![(1, 3), (2, 3)]));
     }
+
+    #[test]
+    fn lists_descriptions_under_the_graph() {
+        let mut tasks = Task::from_formatted(&[("web", &["api"]), ("api", &[]), ("lint", &[])]);
+        assert_eq!(Graph::from_tasks_list(&tasks).format_descriptions(), "");
+
+        tasks[0].description = Some("serves the\nfrontend".to_string());
+        tasks[1].description = Some("REST API".to_string());
+        assert_eq!(
+            Graph::from_tasks_list(&tasks).format_descriptions(),
+            "\n\n|api|: REST API\n|web|: serves the frontend\n"
+        );
+    }
 }
@@ -0,0 +1,192 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use super::Graph;
+
+const MARGIN: usize = 20;
+const NODE_HEIGHT: usize = 36;
+const LAYER_GAP: usize = 80;
+const ROW_GAP: usize = 20;
+/// Width of a character of the node names, in the monospace font used.
+const GLYPH_WIDTH: usize = 9;
+
+const STYLE: &str = r#"
+body { margin: 0; font-family: monospace; background: #fafafa; }
+.node rect { fill: #fff; stroke: #555; stroke-width: 1.5; rx: 6; }
+.node text { font-size: 15px; dominant-baseline: middle; text-anchor: middle; }
+.edge { fill: none; stroke: #999; stroke-width: 1.5; }
+.node.active rect { fill: #e3f2fd; stroke: #1565c0; }
+.edge.active { stroke: #1565c0; stroke-width: 2.5; }
+"#;
+
+/// Highlights the dependencies and dependents of the hovered task.
+const SCRIPT: &str = r#"
+document.querySelectorAll('.node').forEach(node => {
+  const task = node.dataset.task;
+  const edges = document.querySelectorAll(`.edge[data-from="${task}"], .edge[data-to="${task}"]`);
+  node.addEventListener('mouseenter', () => {
+    node.classList.add('active');
+    edges.forEach(edge => edge.classList.add('active'));
+  });
+  node.addEventListener('mouseleave', () => {
+    node.classList.remove('active');
+    edges.forEach(edge => edge.classList.remove('active'));
+  });
+});
+"#;
+
+/// Renders the graph as an SVG in a standalone HTML page, the tasks laid
+/// out left to right after their dependencies, with their description
+/// shown on hover. The tasks without dependencies nor dependents come
+/// first, as in the terminal.
+pub fn render_html(graph: &Graph) -> String {
+    let nodes = graph.nodes();
+    let edges = graph.edges();
+
+    // each task is one layer after its furthest dependency
+    let mut layers: HashMap<usize, usize> = nodes.keys().map(|id| (**id, 0)).collect();
+    for _ in 0..nodes.len() {
+        let mut changed = false;
+        for (from, to) in &edges {
+            let layer = layers[from] + 1;
+            if layers[to] < layer {
+                layers.insert(**to, layer);
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    let mut columns: BTreeMap<usize, Vec<&str>> = BTreeMap::new();
+    for task in &graph.independent_tasks {
+        columns.entry(0).or_default().push(&task.name);
+    }
+    let mut dependent: Vec<_> = nodes.iter().collect();
+    dependent.sort_by_key(|(_, name)| name.as_str());
+    for (id, name) in dependent {
+        columns.entry(layers[id]).or_default().push(name);
+    }
+
+    let longest = graph
+        .tasks()
+        .iter()
+        .map(|task| task.name.chars().count())
+        .max()
+        .unwrap_or(0);
+    let node_width = (longest * GLYPH_WIDTH + 2 * ROW_GAP).max(100);
+    let mut positions: HashMap<&str, (usize, usize)> = HashMap::new();
+    for (layer, names) in &columns {
+        for (row, name) in names.iter().enumerate() {
+            let x = MARGIN + layer * (node_width + LAYER_GAP);
+            let y = MARGIN + row * (NODE_HEIGHT + ROW_GAP);
+            positions.insert(name, (x, y));
+        }
+    }
+    let width = positions.values().map(|(x, _)| x + node_width).max();
+    let height = positions.values().map(|(_, y)| y + NODE_HEIGHT).max();
+    let descriptions: HashMap<&str, &str> = graph
+        .tasks()
+        .iter()
+        .filter_map(|task| Some((task.name.as_str(), task.description.as_deref()?)))
+        .collect();
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}">"#,
+        width.unwrap_or(0) + MARGIN,
+        height.unwrap_or(0) + MARGIN,
+    );
+    svg.push_str(
+        r##"<defs><marker id="arrow" viewBox="0 0 10 10" refX="10" refY="5" markerWidth="8" markerHeight="8" orient="auto"><path d="M0,0 L10,5 L0,10 z" fill="#999"/></marker></defs>"##,
+    );
+    svg.push('\n');
+    for (from, to) in &edges {
+        let (from, to) = (nodes[from].as_str(), nodes[to].as_str());
+        let (x1, y1) = positions[from];
+        let (x2, y2) = positions[to];
+        let (x1, y1, y2) = (x1 + node_width, y1 + NODE_HEIGHT / 2, y2 + NODE_HEIGHT / 2);
+        let middle = (x1 + x2) / 2;
+        let _ = writeln!(
+            svg,
+            r#"<path class="edge" data-from="{}" data-to="{}" marker-end="url(#arrow)" d="M{x1},{y1} C{middle},{y1} {middle},{y2} {x2},{y2}"/>"#,
+            escape(from),
+            escape(to),
+        );
+    }
+    for (task, (x, y)) in columns
+        .values()
+        .flatten()
+        .map(|name| (name, positions[name]))
+    {
+        let name = escape(task);
+        let title = match descriptions.get(task) {
+            Some(description) => format!("{name}: {}", escape(description)),
+            None => name.clone(),
+        };
+        let _ = writeln!(
+            svg,
+            r#"<g class="node" data-task="{name}"><title>{title}</title><rect x="{x}" y="{y}" width="{node_width}" height="{NODE_HEIGHT}"/><text x="{}" y="{}">{name}</text></g>"#,
+            x + node_width / 2,
+            y + NODE_HEIGHT / 2,
+        );
+    }
+    svg.push_str("</svg>");
+
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>whiz graph</title>\n<style>{STYLE}</style>\n</head>\n<body>\n{svg}\n<script>{SCRIPT}</script>\n</body>\n</html>\n"
+    )
+}
+
+/// Writes the page of [`render_html`] to a temporary file and opens it in
+/// the default browser, returning the path of the file.
+pub fn open_in_browser(graph: &Graph) -> Result<PathBuf, Box<dyn Error>> {
+    let path = std::env::temp_dir().join(format!("whiz-graph-{}.html", std::process::id()));
+    std::fs::write(&path, render_html(graph))?;
+    open::that(&path)?;
+    Ok(path)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::Task;
+
+    #[test]
+    fn renders_every_task() {
+        let input: &[(&str, &[&str])] = &[
+            ("db", &[]),
+            ("api", &["db"]),
+            ("web", &["api", "db"]),
+            ("docs", &[]),
+        ];
+        let tasks = Task::from_formatted(input);
+        let graph = Graph::from_tasks_list(&tasks);
+        let html = render_html(&graph);
+
+        for name in ["db", "api", "web", "docs"] {
+            assert!(html.contains(&format!(r#"data-task="{name}""#)), "{name}");
+        }
+        assert_eq!(html.matches(r#"class="edge""#).count(), 3);
+        assert!(html.contains(r#"data-from="api" data-to="web""#));
+    }
+
+    #[test]
+    fn escapes_descriptions() {
+        let input: &[(&str, &[&str])] = &[("db", &[]), ("api", &["db"])];
+        let mut tasks = Task::from_formatted(input);
+        tasks[0].description = Some("<postgres> & \"redis\"".to_string());
+        let html = render_html(&Graph::from_tasks_list(&tasks));
+        assert!(html.contains("<title>db: &lt;postgres&gt; &amp; &quot;redis&quot;</title>"));
+    }
+}
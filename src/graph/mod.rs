@@ -1,6 +1,7 @@
 pub use graph_task::{Graph, Task};
 use ratatui::prelude::{CrosstermBackend, Terminal};
 use std::error::Error;
+use std::path::PathBuf;
 use termgraph::fdisplay;
 
 use ui::{Drawer, Model, TaskFormatter};
@@ -8,8 +9,31 @@ use ui::{Drawer, Model, TaskFormatter};
 use self::ui::LineFormat;
 
 pub mod graph_task;
+mod html;
 mod ui;
 
+/// Narrowest width the graph is laid out in, the view scrolling instead.
+const MIN_GRAPH_WIDTH: usize = 40;
+
+/// Returns the width the layers of the graph are laid out in to fit a
+/// terminal `columns` wide inside the border of the view, without limit
+/// when its size is unknown. It always fits the widest node, named
+/// `widest` characters long, which termgraph requires.
+fn graph_width(columns: Option<u16>, widest: usize) -> usize {
+    let fitting = match columns {
+        Some(columns) => usize::from(columns).saturating_sub(2),
+        None => usize::MAX,
+    };
+    // the node is framed as `|name|` and needs three glyphs more
+    fitting.max(MIN_GRAPH_WIDTH).max(widest + 6)
+}
+
+/// Opens the graph of `tasks_list` as an SVG in the default browser,
+/// see [`html::render_html`].
+pub fn open_graph_in_browser(tasks_list: Vec<Task>) -> Result<PathBuf, Box<dyn Error>> {
+    html::open_in_browser(&Graph::from_tasks_list(&tasks_list))
+}
+
 pub fn draw_graph(tasks_list: Vec<Task>, boxed: bool) -> Result<(), Box<dyn Error>> {
     let boxed = match boxed {
         true => LineFormat::Boxed,
@@ -18,7 +42,14 @@ pub fn draw_graph(tasks_list: Vec<Task>, boxed: bool) -> Result<(), Box<dyn Erro
     let graph = Graph::from_tasks_list(&tasks_list);
 
     //use termgraph to generate the ascii representation
+    let columns = crossterm::terminal::size().ok().map(|(columns, _)| columns);
+    let widest = graph
+        .nodes()
+        .values()
+        .map(|name| name.chars().count())
+        .max();
     let config = termgraph::Config::new(TaskFormatter::new(), 200)
+        .max_glyphs_per_layer(graph_width(columns, widest.unwrap_or(0)))
         .line_glyphs(TaskFormatter::from_commandline(boxed));
     let mut ascii_graph = termgraph::DirectedGraph::new();
     ascii_graph.add_nodes(graph.nodes());
@@ -27,6 +58,7 @@ pub fn draw_graph(tasks_list: Vec<Task>, boxed: bool) -> Result<(), Box<dyn Erro
     // Write graphics into the buffer
     let mut formatted_ascii_graph = Vec::new();
     fdisplay(&ascii_graph, &config, &mut formatted_ascii_graph);
+    formatted_ascii_graph.extend(graph.format_descriptions().into_bytes());
 
     //Start ratatui initializaion
     crossterm::terminal::enable_raw_mode()?;
@@ -57,3 +89,18 @@ pub fn draw_graph(tasks_list: Vec<Task>, boxed: bool) -> Result<(), Box<dyn Erro
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fits_the_graph_to_the_terminal() {
+        assert_eq!(graph_width(Some(120), 10), 118);
+        assert_eq!(graph_width(Some(1000), 10), 998);
+        // scrolled instead of squeezed
+        assert_eq!(graph_width(Some(20), 10), MIN_GRAPH_WIDTH);
+        assert_eq!(graph_width(Some(60), 70), 76);
+        assert_eq!(graph_width(None, 10), usize::MAX);
+    }
+}
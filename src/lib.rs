@@ -1,9 +1,19 @@
 pub mod actors;
 pub mod args;
 pub mod config;
+pub mod daemon;
 pub mod exec;
+pub mod exit;
+pub mod file_mode;
 pub mod global_config;
+pub mod lock;
+pub mod plan;
+#[cfg(unix)]
+pub mod pty;
+pub mod replay;
 pub mod serial_mode;
+#[cfg(feature = "testing")]
+pub mod testing;
 pub mod utils;
 
 #[cfg(test)]
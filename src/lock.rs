@@ -0,0 +1,163 @@
+//! Keeps a single whiz running per project (or per `--instance-name`), as
+//! two instances would reload the tasks twice, fight over their ports and
+//! overwrite the files of each other in `.whiz/`.
+//!
+//! The lock is an advisory lock of the operating system on `.whiz/lock`,
+//! released as soon as whiz exits, however it exits. The file itself tells
+//! who holds the lock and is left behind: a lock file whose holder is gone
+//! is simply taken over.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, Write};
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Local};
+
+use crate::exit::ExitCode;
+use crate::file_mode::{self, FileMode};
+
+/// The lock of a running whiz, held until dropped.
+#[derive(Debug)]
+pub struct InstanceLock {
+    _file: File,
+}
+
+/// Who holds a lock, as written in its file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Holder {
+    pub pid: u32,
+    pub started_at: DateTime<Local>,
+}
+
+impl Holder {
+    fn current() -> Self {
+        Self {
+            pid: std::process::id(),
+            started_at: Local::now(),
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        let mut lines = s.lines();
+        let pid = lines.next()?.trim().parse().ok()?;
+        let started_at = DateTime::parse_from_rfc3339(lines.next()?.trim()).ok()?;
+        Some(Self {
+            pid,
+            started_at: started_at.with_timezone(&Local),
+        })
+    }
+}
+
+impl InstanceLock {
+    /// Takes the lock at `path`, or fails telling who holds it. With
+    /// `force`, whiz starts anyway without the lock.
+    pub fn acquire(path: &Path, mode: Option<FileMode>, force: bool) -> Result<Option<Self>> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("cannot create {}", dir.display()))?;
+        }
+        let mut options = OpenOptions::new();
+        options.read(true).write(true).create(true).truncate(false);
+        let mut file = file_mode::open(&options, path, mode)
+            .with_context(|| format!("cannot open {}", path.display()))?;
+
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(std::fs::TryLockError::WouldBlock) if force => return Ok(None),
+            Err(std::fs::TryLockError::WouldBlock) => {
+                let mut content = String::new();
+                let _ = file.read_to_string(&mut content);
+                let holder = match Holder::parse(&content) {
+                    Some(Holder { pid, started_at }) => format!(
+                        " (pid {pid}, started at {})",
+                        started_at.format("%Y-%m-%d %H:%M:%S")
+                    ),
+                    None => String::new(),
+                };
+                return Err(ExitCode::Usage.wrap(anyhow!(
+                    "whiz is already running here{holder}, use --instance-name to run another instance or --force"
+                )));
+            }
+            Err(std::fs::TryLockError::Error(err)) => {
+                return Err(err).with_context(|| format!("cannot lock {}", path.display()));
+            }
+        }
+
+        write_holder(&mut file, &Holder::current())
+            .with_context(|| format!("cannot write {}", path.display()))?;
+        Ok(Some(Self { _file: file }))
+    }
+}
+
+fn write_holder(file: &mut File, holder: &Holder) -> io::Result<()> {
+    file.set_len(0)?;
+    file.rewind()?;
+    writeln!(file, "{}\n{}", holder.pid, holder.started_at.to_rfc3339())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn lock_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("whiz-lock-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir.join(".whiz").join("lock")
+    }
+
+    fn holder(path: &Path) -> Holder {
+        Holder::parse(&std::fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn refuses_a_held_lock() {
+        let path = lock_path("held");
+        let lock = InstanceLock::acquire(&path, None, false).unwrap();
+        assert!(lock.is_some());
+        assert_eq!(holder(&path).pid, std::process::id());
+
+        let err = InstanceLock::acquire(&path, None, false).unwrap_err();
+        let message = err.to_string();
+        assert!(
+            message.starts_with(&format!(
+                "whiz is already running here (pid {}, started at ",
+                std::process::id()
+            )),
+            "{message}"
+        );
+        assert!(message.ends_with("use --instance-name to run another instance or --force"));
+
+        // released once dropped
+        drop(lock);
+        assert!(InstanceLock::acquire(&path, None, false).unwrap().is_some());
+        std::fs::remove_dir_all(path.parent().unwrap().parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn reclaims_a_stale_lock() {
+        let path = lock_path("stale");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        // left by a whiz which is gone
+        std::fs::write(&path, "4194304\n2024-01-01T00:00:00+00:00\n").unwrap();
+
+        let lock = InstanceLock::acquire(&path, None, false).unwrap();
+        assert!(lock.is_some());
+        assert_eq!(holder(&path).pid, std::process::id());
+        std::fs::remove_dir_all(path.parent().unwrap().parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn forces_past_a_held_lock() {
+        let path = lock_path("force");
+        let _lock = InstanceLock::acquire(&path, None, false).unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+
+        let forced = InstanceLock::acquire(&path, None, true).unwrap();
+        assert!(forced.is_none());
+        // the holder stays the first whiz
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), written);
+        std::fs::remove_dir_all(path.parent().unwrap().parent().unwrap()).unwrap();
+    }
+}
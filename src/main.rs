@@ -1,33 +1,63 @@
 use actix::prelude::*;
 use anyhow::anyhow;
+use anyhow::Context;
 use anyhow::Ok;
 use anyhow::Result;
+#[cfg(feature = "self-update")]
 use chrono::{Duration, Utc};
-use clap::Parser;
+use clap::{CommandFactory, Parser};
+#[cfg(feature = "self-update")]
 use self_update::{backends::github::Update, cargo_crate_version, update::UpdateStatus};
+#[cfg(feature = "self-update")]
 use semver::Version;
 use std::eprintln;
-use tokio::time::{sleep, Duration as TokioDuration};
-use whiz::actors::command::CommandActorsBuilder;
+use std::num::NonZeroUsize;
+#[cfg(feature = "self-update")]
+use tokio::time::sleep;
+use tokio::time::Duration as TokioDuration;
+use whiz::actors::command::{self, CommandActorsBuilder};
 use whiz::config::ops;
+use whiz::config::requires::describe_missing;
+#[cfg(feature = "tui")]
+use whiz::config::status::{parse_style, StatusTheme};
 use whiz::config::ConfigBuilder;
+use whiz::daemon::{self, DaemonPaths, Request};
+use whiz::exec::ExecBuilder;
+use whiz::exit::{ExitCode, WithExitCode};
+#[cfg(any(feature = "tui", feature = "self-update"))]
+use whiz::global_config::GlobalConfig;
+use whiz::lock::InstanceLock;
+use whiz::plan::Plan;
+use whiz::replay::{self, Recorder};
 use whiz::serial_mode;
-use whiz::utils::find_config_path;
+use whiz::utils::{resolve_base_dir, resolve_config_path, resolve_watch_root};
 use whiz::{
-    actors::{console::ConsoleActor, watcher::WatcherActor},
+    actors::{
+        a11y::{read_instructions, Announcer},
+        console::{ConsoleActor, SetRestart, TermEvent},
+        control::ControlActor,
+        reloader::ConfigReloaderActor,
+        watcher::{WatchConfig, WatcherActor},
+    },
     args::Command,
     config::Config,
-    global_config::GlobalConfig,
 };
+#[cfg(feature = "tui")]
 mod graph;
 
-use whiz::args::Args;
+use whiz::args::{Args, Upgrade};
 
-async fn upgrade_check() -> Result<()> {
+#[cfg(any(feature = "tui", feature = "self-update"))]
+fn global_config_path() -> std::path::PathBuf {
     let project = directories::ProjectDirs::from("com", "zifeo", "whiz")
         .expect("cannot get directory for projet");
 
-    let config_path = project.config_local_dir().join("config.yml");
+    project.config_local_dir().join("config.yml")
+}
+
+#[cfg(feature = "self-update")]
+async fn upgrade_check() -> Result<()> {
+    let config_path = global_config_path();
     let mut local_config = GlobalConfig::load(config_path.clone()).await?;
 
     if local_config.update_check + Duration::days(1) < Utc::now() {
@@ -58,40 +88,114 @@ async fn upgrade_check() -> Result<()> {
     Ok(())
 }
 
-fn main() -> Result<()> {
+#[cfg(feature = "self-update")]
+fn upgrade(opts: Upgrade) -> Result<ExitCode> {
+    if opts.check {
+        let current_version = cargo_crate_version!();
+        let latest = Update::configure()
+            .repo_owner("zifeo")
+            .repo_name("whiz")
+            .bin_name("whiz")
+            .current_version(current_version)
+            .build()?
+            .get_latest_release()?;
+        if Version::parse(&latest.version)? > Version::parse(current_version)? {
+            println!(
+                "New whiz update available: {current_version} -> {}",
+                latest.version
+            );
+            return Ok(ExitCode::UpdateAvailable);
+        }
+        println!("Already up to date!");
+        return Ok(ExitCode::Success);
+    }
+
+    let mut update = Update::configure();
+    update
+        .repo_owner("zifeo")
+        .repo_name("whiz")
+        .bin_name("whiz")
+        .show_download_progress(true)
+        .current_version(cargo_crate_version!())
+        .no_confirm(opts.yes);
+
+    if let Some(version) = opts.version {
+        update.target_version_tag(&format!("v{version}"));
+    }
+
+    match update.build()?.update_extended()? {
+        UpdateStatus::UpToDate => println!("Already up to date!"),
+        UpdateStatus::Updated(release) => {
+            println!("Updated successfully to {}!", release.version);
+            println!(
+                "Release notes: https://github.com/zifeo/whiz/releases/tag/{}",
+                release.name
+            );
+        }
+    };
+    Ok(ExitCode::Success)
+}
+
+#[cfg(not(feature = "self-update"))]
+fn upgrade(_: Upgrade) -> Result<ExitCode> {
+    Err(anyhow!("whiz was built without the self-update feature")).exit_code(ExitCode::Usage)
+}
+
+/// Prints where the config is read from and the tasks run, for `--version --verbose`.
+fn print_paths(args: &Args) {
+    let cwd = std::env::current_dir().unwrap();
+    let config_path = resolve_config_path(&cwd, args.file.as_deref());
+    match &config_path {
+        Result::Ok(path) => println!("config: {}", path.display()),
+        Err(err) => println!("config: {}", err),
+    }
+    let base_dir = match &args.base_dir {
+        Some(dir) => resolve_base_dir(&cwd, dir),
+        None => config_path.map(|path| path.parent().unwrap().to_path_buf()),
+    };
+    match base_dir {
+        Result::Ok(dir) => println!("base dir: {}", dir.display()),
+        Err(err) => println!("base dir: {}", err),
+    }
+}
+
+/// Prints `err` and returns the code whiz exits with on it.
+fn report(err: anyhow::Error) -> ExitCode {
+    eprintln!("{}", err);
+    ExitCode::of(&err)
+}
+
+fn main() {
     let args = Args::parse();
 
     if args.version {
         println!("whiz {}", env!("CARGO_PKG_VERSION"));
-        return Ok(());
+        if args.verbose {
+            print_paths(&args);
+        }
+        return;
     }
 
-    if let Some(Command::Upgrade(opts)) = args.command {
-        let mut update = Update::configure();
-        update
-            .repo_owner("zifeo")
-            .repo_name("whiz")
-            .bin_name("whiz")
-            .show_download_progress(true)
-            .current_version(cargo_crate_version!())
-            .no_confirm(opts.yes);
-
-        if let Some(version) = opts.version {
-            update.target_version_tag(&format!("v{version}"));
-        }
-
-        match update.build()?.update_extended()? {
-            UpdateStatus::UpToDate => println!("Already up to date!"),
-            UpdateStatus::Updated(release) => {
-                println!("Updated successfully to {}!", release.version);
-                println!(
-                    "Release notes: https://github.com/zifeo/whiz/releases/tag/{}",
-                    release.name
-                );
-            }
-        };
-        return Ok(());
-    };
+    match args.command {
+        Some(Command::Upgrade(opts)) => {
+            let code = upgrade(opts).unwrap_or_else(report);
+            std::process::exit(code.code());
+        }
+        Some(Command::ExitCodes) => {
+            print!("{}", ExitCode::table());
+            return;
+        }
+        Some(Command::Completions(opts)) => {
+            clap_complete::generate(
+                opts.shell,
+                &mut Args::command(),
+                "whiz",
+                &mut std::io::stdout(),
+            );
+            return;
+        }
+        _ => {}
+    }
 
     let system = System::with_tokio_rt(|| {
         tokio::runtime::Builder::new_multi_thread()
@@ -103,13 +207,16 @@ fn main() -> Result<()> {
     });
 
     Arbiter::current().spawn(async {
-        run(args).await.unwrap_or_else(|e| {
-            eprintln!("{}", e);
-            System::current().stop_with_code(1);
-        });
+        if let Err(err) = run(args).await {
+            System::current().stop_with_code(report(err).code());
+        }
     });
 
-    let code = system.run_with_code()?;
+    let code = system
+        .run_with_code()
+        .unwrap_or_else(|err| report(err.into()).code());
+    // the children still in their grace period outlive the system
+    command::wait_for_stopping_children();
     std::process::exit(code);
 }
 
@@ -123,78 +230,389 @@ async fn run(args: Args) -> Result<()> {
             .unwrap(),
     );
 
+    #[cfg(feature = "self-update")]
     upgrade_check()
         .await
         .unwrap_or_else(|e| eprintln!("cannot check for update: {}", e));
 
-    let config = ConfigBuilder::new(find_config_path(
-        &std::env::current_dir().unwrap(),
-        &args.file,
-    )?)
-    .build()?;
+    // a replay does not need the config of the recorded project
+    if let Some(Command::Replay(opts)) = args.command.as_ref() {
+        return replay::start(opts).await;
+    }
+
+    let cwd = std::env::current_dir().unwrap();
+    let config_path = resolve_config_path(&cwd, args.file.as_deref()).exit_code(ExitCode::Usage)?;
+    let base_dir = match &args.base_dir {
+        Some(dir) => Some(resolve_base_dir(&cwd, dir).exit_code(ExitCode::Usage)?),
+        None => None,
+    };
+    let daemon_paths = DaemonPaths::new(
+        base_dir
+            .as_deref()
+            .unwrap_or_else(|| config_path.parent().unwrap()),
+        args.instance_name.as_deref(),
+    );
+
+    // the background whiz may be running another version of the config
+    if let Some(Command::Attach(opts)) = args.command.as_ref() {
+        return daemon::attach(&daemon_paths, opts.task.clone()).await;
+    }
+    if let Some(request) = args.command.as_ref().and_then(Request::from_command) {
+        daemon::request(&daemon_paths, request).await?;
+        System::current().stop_with_code(0);
+        return Ok(());
+    }
+
+    let watch_root = match &args.watch_root {
+        Some(dir) => Some(resolve_watch_root(&cwd, dir).exit_code(ExitCode::Usage)?),
+        None => None,
+    };
+
+    let builder = ConfigBuilder::new(config_path)
+        .base_dir(base_dir)
+        .watch_root(watch_root)
+        .profile(args.profile.clone())
+        .filter(args.run.clone())
+        .no_deps(args.no_deps)
+        .reload_all_on(args.reload_all_on.clone())
+        .kill_timeout(args.kill_timeout.map(TokioDuration::from_millis))
+        .default_task(args.focus.clone());
+    let config = builder.clone().build().exit_code(ExitCode::Usage)?;
+    check_config(&config, &args)?;
+
+    if args.dry_run && args.command.is_none() {
+        let plan = Plan::new(&config, &[]).exit_code(ExitCode::Usage)?;
+        println!("{}", plan.format_resolved(&config, false).await?);
+        System::current().stop_with_code(0);
+        return Ok(());
+    }
 
     let Some(command) = args.command.as_ref() else {
-        return start_default_mode(config, args).await;
+        let lock = InstanceLock::acquire(&daemon_paths.lock, config.file_mode, args.force)?;
+        if lock.is_none() {
+            eprintln!("another whiz is running here, starting anyway");
+        }
+        if args.detach {
+            // the detached whiz takes it over
+            drop(lock);
+            daemon::detach(&daemon_paths, config.file_mode).await?;
+            System::current().stop_with_code(0);
+            return Ok(());
+        }
+        // held until whiz exits
+        std::mem::forget(lock);
+        return start_default_mode(config, builder, args, daemon_paths).await;
     };
 
     match command {
-        Command::Upgrade(_) => {
+        Command::Upgrade(_)
+        | Command::ExitCodes
+        | Command::Completions(_)
+        | Command::Replay(_)
+        | Command::Attach(_)
+        | Command::Status
+        | Command::Stop => {
             unreachable!();
         }
 
-        Command::ListJobs => {
-            let formatted_list_of_jobs = ops::get_formatted_list_of_jobs(&config.ops);
+        Command::ListJobs(opts) => {
+            let formatted_list_of_jobs = if opts.detail {
+                let details = ops::get_jobs_details(&config.ops, &config.base_dir, opts.git);
+                ops::format_jobs_details(&details)
+            } else if opts.long {
+                ops::get_described_list_of_jobs(&config.ops)
+            } else {
+                ops::get_formatted_list_of_jobs(&config.ops)
+            };
             println!("List of jobs:\n{formatted_list_of_jobs}");
             System::current().stop_with_code(0);
             Ok(())
         }
 
+        #[cfg(feature = "tui")]
         Command::Graph(opts) => {
             let filtered_tasks: Vec<graph::Task> = config
-                .ops
+                .active_ops()
                 .iter()
                 .map(|task| graph::Task {
                     name: task.0.to_owned(),
                     depends_on: task.1.depends_on.resolve(),
+                    description: task.1.description.clone(),
                 })
                 .collect();
 
-            match graph::draw_graph(filtered_tasks, opts.boxed)
-                .map_err(|err| anyhow!("Error visualizing graph: {}", err))
-            {
-                Result::Ok(..) => {
-                    System::current().stop_with_code(0);
-                    Ok(())
-                }
-                Err(e) => {
-                    System::current().stop_with_code(1);
-                    Err(e)
+            if opts.html {
+                let path = graph::open_graph_in_browser(filtered_tasks)
+                    .map_err(|err| anyhow!("Error opening graph: {}", err))?;
+                println!("Graph written to {}", path.display());
+            } else {
+                graph::draw_graph(filtered_tasks, opts.boxed)
+                    .map_err(|err| anyhow!("Error visualizing graph: {}", err))?;
+            }
+            System::current().stop_with_code(0);
+            Ok(())
+        }
+
+        #[cfg(not(feature = "tui"))]
+        Command::Graph(_) => {
+            Err(anyhow!("whiz was built without the tui feature")).exit_code(ExitCode::Usage)
+        }
+
+        Command::Plan(opts) => {
+            let plan = Plan::new(&config, &opts.tasks).exit_code(ExitCode::Usage)?;
+            if opts.json {
+                println!("{}", serde_json::to_string_pretty(&plan)?);
+            } else if opts.env {
+                println!("{}", plan.format_resolved(&config, opts.redact).await?);
+            } else {
+                println!("{}", plan.format_levels());
+            }
+            System::current().stop_with_code(0);
+            Ok(())
+        }
+
+        Command::DumpEnv(opts) => {
+            let Some(task) = config.ops.get(&opts.task) else {
+                let suggestion =
+                    ops::did_you_mean(&opts.task, config.ops.keys().map(String::as_str));
+                return Err(anyhow!("unknown task {}{suggestion}", opts.task))
+                    .exit_code(ExitCode::Usage);
+            };
+            let exec_builder = ExecBuilder::new(&opts.task, task, &config)
+                .await
+                .with_context(|| format!("cannot resolve the env of {}", opts.task))?;
+            println!("{}", exec_builder.dump_env(opts.changed, opts.redact));
+            System::current().stop_with_code(0);
+            Ok(())
+        }
+
+        Command::Doctor(opts) => {
+            let mut names = match opts.tasks.is_empty() {
+                true => Vec::from_iter(config.ops.keys().cloned()),
+                false => opts.tasks.clone(),
+            };
+            names.sort();
+            let mut failed = false;
+            for name in names {
+                let Some(task) = config.ops.get(&name) else {
+                    let suggestion =
+                        ops::did_you_mean(&name, config.ops.keys().map(String::as_str));
+                    return Err(anyhow!("unknown task {name}{suggestion}"))
+                        .exit_code(ExitCode::Usage);
+                };
+                let exec_builder = ExecBuilder::new(&name, task, &config)
+                    .await
+                    .with_context(|| format!("cannot resolve the env of {name}"))?;
+                let missing = exec_builder.missing(&task.requires);
+                if missing.is_empty() {
+                    println!("{name}: ok");
+                } else {
+                    println!("{name}: {}", describe_missing(&missing));
+                    failed = true;
                 }
             }
+            let code = match failed {
+                true => ExitCode::TaskFailed,
+                false => ExitCode::Success,
+            };
+            System::current().stop_with_code(code.code());
+            Ok(())
         }
 
         Command::Execute(opts) => {
-            serial_mode::start(opts, config).await?;
-            System::current().stop_with_code(0);
+            let code = serial_mode::start(opts, config).await?;
+            System::current().stop_with_code(code.code());
             Ok(())
         }
     }
 }
 
-async fn start_default_mode(config: Config, args: Args) -> Result<()> {
-    let console =
-        ConsoleActor::new(Vec::from_iter(config.ops.keys().cloned()), args.timestamp).start();
-    let watcher = WatcherActor::new(config.base_dir.clone()).start();
+/// Applies the options of the interactive view, from the global config.
+#[cfg(feature = "tui")]
+async fn view_options(console: ConsoleActor) -> Result<ConsoleActor> {
+    let global_config = GlobalConfig::load(global_config_path()).await?;
+    let status_theme = StatusTheme::from_config(&global_config.status_style)
+        .map_err(|err| anyhow!("invalid status_style in global config: {}", err))
+        .exit_code(ExitCode::Usage)?;
+    let mut console = console
+        .status_theme(status_theme)
+        .split(global_config.split.unwrap_or_default());
+    if let Some(color) = &global_config.stderr_color {
+        let style = parse_style(color)
+            .map_err(|err| anyhow!("invalid stderr_color in global config: {}", err))
+            .exit_code(ExitCode::Usage)?;
+        console = console.stderr_style(style);
+    }
+    Ok(console)
+}
+
+/// Quits as `q` does on Ctrl-C, and on `SIGTERM` and `SIGHUP` on Unix.
+async fn quit_on_signal(console: Addr<ConsoleActor>) {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let (Result::Ok(mut terminate), Result::Ok(mut hangup)) = (
+            signal(SignalKind::terminate()),
+            signal(SignalKind::hangup()),
+        ) else {
+            return;
+        };
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+            _ = hangup.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    if tokio::signal::ctrl_c().await.is_err() {
+        return;
+    }
+    console.do_send(TermEvent::quit());
+}
+
+/// Prints the warnings about the config, or fails on them with
+/// `--strict-config`.
+fn check_config(config: &Config, args: &Args) -> Result<()> {
+    let collisions = config.pipe_collisions();
+    if args.verbose && !collisions.templated.is_empty() {
+        let tasks = Vec::from_iter(collisions.templated.iter().cloned()).join(", ");
+        eprintln!("templated pipe files of {tasks} are not checked for collisions");
+    }
+    let warnings = collisions.warnings();
+    if args.strict_config && !warnings.is_empty() {
+        return Err(anyhow!("{}", warnings.join("\n"))).exit_code(ExitCode::Usage);
+    }
+    for warning in warnings {
+        eprintln!("{warning}");
+    }
+    Ok(())
+}
 
-    let cmds = CommandActorsBuilder::new(config, console.clone(), watcher)
+async fn start_default_mode(
+    config: Config,
+    builder: ConfigBuilder,
+    args: Args,
+    daemon_paths: DaemonPaths,
+) -> Result<()> {
+    let mut console = ConsoleActor::new(
+        Vec::from_iter(config.active_ops().into_keys()),
+        args.timestamp,
+    )
+    .headless(args.no_tui || args.daemonized || args.dry_watch)
+    .focus(config.default_task.clone());
+    if args.a11y {
+        console = console.a11y(Announcer::new(Box::new(std::io::stdout())));
+    }
+    if let Some(delay) = args.exit_when_done {
+        console = console.exit_when_done(TokioDuration::from_secs(delay));
+    }
+    #[cfg(feature = "tui")]
+    {
+        console = view_options(console)
+            .await?
+            .timeline(args.timeline)
+            .prefix_lines(args.prefix_lines)
+            .dedup(args.dedup)
+            .scroll_step(args.scroll_step)
+            .restart_key(args.restart_key)
+            .on_demand(
+                config
+                    .active_ops()
+                    .into_iter()
+                    .filter(|(_, task)| task.on_demand)
+                    .map(|(name, _)| name)
+                    .collect(),
+            );
+    }
+    // the recording of a detached whiz is what `whiz attach` follows
+    if args.daemonized {
+        console = console.record(Recorder::create(
+            &daemon_paths.session,
+            false,
+            config.file_mode,
+        )?);
+    }
+    if let Some(path) = &args.record {
+        console = console.record(Recorder::create(
+            path,
+            args.record_redacted,
+            config.file_mode,
+        )?);
+    }
+    let console = console.start();
+    // the tasks lead their own process group, so signals sent to the one of
+    // whiz stop them through the console
+    actix::spawn(quit_on_signal(console.clone()));
+    if args.a11y {
+        let stdin = tokio::io::BufReader::new(tokio::io::stdin());
+        actix::spawn(read_instructions(stdin, console.clone()));
+    }
+    if args.daemonized {
+        ControlActor::new(daemon_paths, console.clone(), config.file_mode)
+            .map_err(|err| anyhow!("cannot open the control socket: {}", err))?
+            .start();
+    }
+    let mut watcher =
+        WatcherActor::new(config.watch_root.clone()).events(args.watch_events.clone());
+    if args.dry_watch {
+        watcher = watcher.dry_watch(Box::new(std::io::stdout()));
+    }
+    #[cfg(feature = "watch")]
+    let watcher = watcher.debounce(args.debounce.map(TokioDuration::from_millis));
+    let watcher = watcher.start();
+
+    #[cfg(not(unix))]
+    if args.verbose && config.file_mode.is_some() {
+        eprintln!("file_mode is not applied on this platform");
+    }
+
+    let watch = if args.exit_after { false } else { args.watch };
+    #[cfg(not(feature = "watch"))]
+    if watch
+        && config
+            .active_ops()
+            .values()
+            .any(|task| !task.watch.resolve().is_empty())
+    {
+        eprintln!("whiz was built without the watch feature, file changes will not reload tasks");
+    }
+
+    let cmds = CommandActorsBuilder::new(config.clone(), console.clone(), watcher.clone())
         .verbose(args.verbose)
-        .globally_enable_watch(if args.exit_after { false } else { args.watch })
+        .globally_enable_watch(watch)
+        .max_parallel(args.max_parallel.map(NonZeroUsize::get))
+        .dry_watch(args.dry_watch)
         .build()
         .await
-        .map_err(|err| anyhow!("error spawning commands: {}", err))?;
+        .map_err(|err| anyhow!("error spawning commands: {}", err))
+        .exit_code(ExitCode::Usage)?;
+
+    // a changed config would start the new tasks
+    if watch && !args.dry_watch {
+        let path = builder.path().to_path_buf();
+        let reloader = ConfigReloaderActor::new(
+            builder,
+            config,
+            cmds.clone(),
+            console.clone(),
+            watcher.clone(),
+        )
+        .verbose(args.verbose)
+        .start();
+        console.do_send(SetRestart(reloader.clone().recipient()));
+        watcher.do_send(WatchConfig {
+            path,
+            reloader: reloader.recipient(),
+        });
+    }
 
     if args.exit_after {
-        whiz::actors::grim_reaper::GrimReaperActor::start_new(cmds).await?;
+        whiz::actors::grim_reaper::GrimReaperActor::start_with_settle(
+            cmds,
+            TokioDuration::from_millis(args.settle_delay),
+        )
+        .await?;
     }
 
     Ok(())
@@ -0,0 +1,289 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use indexmap::IndexMap;
+use serde::Serialize;
+
+use crate::config::{ops, Config, ConfigInner};
+use crate::exec::ExecBuilder;
+
+/// Version of the JSON printed by `whiz plan --json`, bumped whenever a
+/// field changes or goes away.
+pub const PLAN_VERSION: u32 = 1;
+
+/// What whiz would run, in which order, without running anything.
+#[derive(Serialize, Debug)]
+pub struct Plan {
+    pub version: u32,
+    /// Tasks by levels, see [`ops::build_levels`].
+    pub levels: Vec<Vec<String>>,
+    /// Tasks in the order of the levels.
+    pub tasks: IndexMap<String, PlannedTask>,
+    pub edges: Vec<Edge>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PlannedTask {
+    /// Program and arguments of the command, missing for the tasks only
+    /// forwarding watch events. The command of a container is not wrapped
+    /// into the engine invocation, and the env is left out.
+    pub argv: Option<Vec<String>>,
+    pub cwd: PathBuf,
+}
+
+/// `to` runs after `from`.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Edge {
+    pub from: String,
+    pub to: String,
+}
+
+impl Plan {
+    /// Plans the active tasks of `config`, only `tasks` and their
+    /// dependencies when some are given.
+    pub fn new(config: &ConfigInner, tasks: &[String]) -> Result<Self> {
+        let mut config = config.clone();
+        if !tasks.is_empty() {
+            config.filter_jobs(tasks)?;
+        }
+        let active_ops = config.active_ops();
+
+        let levels = ops::build_levels(&active_ops)?;
+        let mut planned = IndexMap::new();
+        let mut edges = Vec::new();
+        for name in levels.iter().flatten() {
+            let task = &active_ops[name];
+            let argv = match task.is_trigger_only() {
+                true => None,
+                false => {
+                    let (cmd, args) = task.get_exec_command()?;
+                    Some([vec![cmd], args].concat())
+                }
+            };
+            let cwd = task.get_absolute_workdir(&config.base_dir);
+            planned.insert(name.clone(), PlannedTask { argv, cwd });
+            edges.extend(task.depends_on.resolve().into_iter().map(|from| Edge {
+                from,
+                to: name.clone(),
+            }));
+        }
+
+        Ok(Self {
+            version: PLAN_VERSION,
+            levels,
+            tasks: planned,
+            edges,
+        })
+    }
+
+    /// Formats the levels one per line, e.g. `2. api, web`.
+    pub fn format_levels(&self) -> String {
+        self.levels
+            .iter()
+            .enumerate()
+            .map(|(i, level)| format!("{}. {}", i + 1, level.join(", ")))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Formats the levels as [`Self::format_levels`], each task followed
+    /// by the command it would run, with its workdir, and the variables
+    /// it gets unlike whiz (as `whiz dump-env --changed`), resolved from
+    /// `config` without running anything.
+    pub async fn format_resolved(&self, config: &Config, redact: bool) -> Result<String> {
+        let mut lines = Vec::new();
+        for (i, level) in self.levels.iter().enumerate() {
+            lines.push(format!("{}. {}", i + 1, level.join(", ")));
+            for name in level {
+                let task = &config.ops[name];
+                if task.is_trigger_only() {
+                    lines.push(format!("   {name}: no command"));
+                    continue;
+                }
+                let exec = ExecBuilder::new(name, task, config)
+                    .await
+                    .with_context(|| format!("cannot resolve {name}"))?;
+                lines.push(format!("   {name}: {}", exec.as_string()));
+                let env = exec.dump_env(true, redact);
+                lines.extend(env.lines().map(|line| format!("      {line}")));
+            }
+        }
+        Ok(lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::sync::Arc;
+
+    use crate::config::RawConfig;
+
+    use super::*;
+
+    const CONFIG: &str = r#"
+        db:
+            command: docker compose up db
+
+        migrations:
+            workdir: api
+            command: cargo run --bin migrate
+            depends_on: db
+
+        sources:
+            watch: web/**
+
+        web:
+            workdir: web
+            entrypoint: npm run
+            command: dev
+            depends_on: sources
+
+        api:
+            workdir: api
+            command: cargo run
+            depends_on: [db, migrations]
+        "#;
+
+    fn plan(tasks: &[&str]) -> Plan {
+        let raw: RawConfig = CONFIG.parse().unwrap();
+        let config = ConfigInner::from_raw(raw, Path::new("/repo").to_path_buf()).unwrap();
+        let tasks = tasks.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+        Plan::new(&config, &tasks).unwrap()
+    }
+
+    #[test]
+    fn serializes_plan() {
+        let json = serde_json::to_string_pretty(&plan(&[])).unwrap();
+        assert_eq!(
+            json,
+            r#"{
+  "version": 1,
+  "levels": [
+    [
+      "db",
+      "sources"
+    ],
+    [
+      "migrations",
+      "web"
+    ],
+    [
+      "api"
+    ]
+  ],
+  "tasks": {
+    "db": {
+      "argv": [
+        "bash",
+        "-c",
+        "docker compose up db"
+      ],
+      "cwd": "/repo"
+    },
+    "sources": {
+      "argv": null,
+      "cwd": "/repo"
+    },
+    "migrations": {
+      "argv": [
+        "bash",
+        "-c",
+        "cargo run --bin migrate"
+      ],
+      "cwd": "/repo/api"
+    },
+    "web": {
+      "argv": [
+        "npm",
+        "run",
+        "dev"
+      ],
+      "cwd": "/repo/web"
+    },
+    "api": {
+      "argv": [
+        "bash",
+        "-c",
+        "cargo run"
+      ],
+      "cwd": "/repo/api"
+    }
+  },
+  "edges": [
+    {
+      "from": "db",
+      "to": "migrations"
+    },
+    {
+      "from": "sources",
+      "to": "web"
+    },
+    {
+      "from": "migrations",
+      "to": "api"
+    }
+  ]
+}"#
+        );
+    }
+
+    #[test]
+    fn plans_tasks_with_their_dependencies() {
+        let plan = plan(&["migrations"]);
+
+        assert_eq!(plan.levels, vec![vec!["db"], vec!["migrations"]]);
+        assert_eq!(
+            plan.edges,
+            vec![Edge {
+                from: "db".to_string(),
+                to: "migrations".to_string()
+            }]
+        );
+    }
+
+    #[actix::test]
+    async fn formats_resolved_tasks() {
+        let raw: RawConfig = r#"
+            env:
+                API_TOKEN: secret
+            db:
+                command: postgres
+                env:
+                    PGPORT: "5432"
+            sources:
+                watch: web/**
+            api:
+                command: cargo run
+                depends_on: [db, sources]
+            "#
+        .parse()
+        .unwrap();
+        let base_dir = std::env::current_dir().unwrap();
+        let config = Arc::new(ConfigInner::from_raw(raw, base_dir.clone()).unwrap());
+        let plan = Plan::new(&config, &[]).unwrap();
+
+        let resolved = plan.format_resolved(&config, true).await.unwrap();
+        assert_eq!(
+            resolved,
+            format!(
+                r#"1. db, sources
+   db: EXEC: bash ["-c", "postgres"] at {base_dir:?}
+      API_TOKEN=***
+      PGPORT=5432
+   sources: no command
+2. api
+   api: EXEC: bash ["-c", "cargo run"] at {base_dir:?}
+      API_TOKEN=***"#
+            )
+        );
+    }
+
+    #[test]
+    fn formats_levels() {
+        assert_eq!(
+            plan(&[]).format_levels(),
+            "1. db, sources\n2. migrations, web\n3. api"
+        );
+    }
+}
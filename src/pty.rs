@@ -0,0 +1,126 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::fd::{AsRawFd, FromRawFd, RawFd};
+
+/// A pseudo-terminal the tasks setting `tty: true` run on, so that the
+/// tools which check whether they print to a terminal keep their colors.
+pub struct Pty {
+    /// Side whiz reads the output of the task from and resizes.
+    pub master: File,
+    /// Side given to the task as its stdout and stderr.
+    pub slave: File,
+}
+
+impl Pty {
+    /// Opens a pseudo-terminal of `size`, which keeps `\n` as is instead of
+    /// translating it to `\r\n` so that lines read as from a pipe.
+    pub fn open(size: (u16, u16)) -> io::Result<Self> {
+        let (mut master, mut slave) = (0, 0);
+        let winsize = winsize(size);
+        let opened = unsafe {
+            libc::openpty(
+                &mut master,
+                &mut slave,
+                std::ptr::null_mut(),
+                std::ptr::null(),
+                &winsize,
+            )
+        };
+        if opened != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let pty = unsafe {
+            Self {
+                master: File::from_raw_fd(master),
+                slave: File::from_raw_fd(slave),
+            }
+        };
+        // the other tasks spawned meanwhile must not keep the slave open,
+        // which would never end the output of this one
+        close_on_exec(master)?;
+        close_on_exec(slave)?;
+
+        let mut termios = unsafe { std::mem::zeroed::<libc::termios>() };
+        if unsafe { libc::tcgetattr(slave, &mut termios) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        termios.c_oflag &= !libc::ONLCR;
+        if unsafe { libc::tcsetattr(slave, libc::TCSANOW, &termios) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(pty)
+    }
+}
+
+/// Sets the size the task reads from its terminal. The task is not the
+/// leader of a session on it, so it reads the new size next time it asks
+/// rather than on a `SIGWINCH`.
+pub fn resize(master: &File, size: (u16, u16)) -> io::Result<()> {
+    let winsize = winsize(size);
+    match unsafe { libc::ioctl(master.as_raw_fd(), libc::TIOCSWINSZ, &winsize) } {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+/// Reads the output of a task from the master side, ending once the task
+/// and the processes it spawned closed the slave side.
+pub struct Reader(pub File);
+
+impl Read for Reader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.0.read(buf) {
+            // Linux fails instead of reading the end of the output
+            Err(err) if err.raw_os_error() == Some(libc::EIO) => Ok(0),
+            read => read,
+        }
+    }
+}
+
+fn winsize((columns, rows): (u16, u16)) -> libc::winsize {
+    libc::winsize {
+        ws_row: rows,
+        ws_col: columns,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    }
+}
+
+fn close_on_exec(fd: RawFd) -> io::Result<()> {
+    match unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) } {
+        -1 => Err(io::Error::last_os_error()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+
+    #[test]
+    fn reads_lines_as_from_a_pipe() {
+        let pty = Pty::open((80, 24)).unwrap();
+        let is_tty = unsafe { libc::isatty(pty.slave.as_raw_fd()) };
+        assert_eq!(is_tty, 1);
+
+        let mut slave = pty.slave;
+        slave.write_all(b"first\nsecond\n").unwrap();
+        drop(slave);
+        let lines: Vec<_> = BufReader::new(Reader(pty.master))
+            .lines()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(lines, vec!["first", "second"]);
+    }
+
+    #[test]
+    fn resizes_the_terminal() {
+        let pty = Pty::open((80, 24)).unwrap();
+        resize(&pty.master, (120, 40)).unwrap();
+
+        let mut size = winsize((0, 0));
+        unsafe { libc::ioctl(pty.slave.as_raw_fd(), libc::TIOCGWINSZ, &mut size) };
+        assert_eq!((size.ws_col, size.ws_row), (120, 40));
+    }
+}
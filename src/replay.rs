@@ -0,0 +1,474 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, LineWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use actix::prelude::*;
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Local};
+#[cfg(feature = "tui")]
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers, MouseEvent, MouseEventKind};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use subprocess::ExitStatus;
+
+#[cfg(feature = "tui")]
+use crate::actors::console::TermEvent;
+use crate::actors::console::{
+    ConsoleActor, ConsoleLine, DynamicPanel, Output, OutputKind, RegisterPanel, StateChanged,
+    UnregisterPanel,
+};
+use crate::actors::state::TaskState;
+use crate::args::Replay;
+use crate::config::color::ColorOption;
+use crate::file_mode::{self, FileMode};
+
+/// First line of a recording, describing the console to replay into.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SessionHeader {
+    pub order: Vec<String>,
+    pub timestamp: bool,
+    pub width: u16,
+    pub height: u16,
+}
+
+/// Console-bound event of a recording, at `t` milliseconds
+/// from the start of the session.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RecordedLine {
+    pub t: u64,
+    #[serde(flatten)]
+    pub event: RecordedEvent,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RecordedEvent {
+    Output {
+        panel: String,
+        /// Missing when the recording is redacted.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        message: Option<String>,
+        len: usize,
+        kind: OutputKind,
+        timestamp: DateTime<Local>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pin: Option<usize>,
+    },
+    RegisterPanel {
+        name: String,
+        colors: Vec<(String, String)>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        dynamic: Option<DynamicPanel>,
+    },
+    UnregisterPanel {
+        name: String,
+    },
+    StateChanged {
+        panel: String,
+        state: RecordedState,
+    },
+    Key {
+        code: String,
+        modifiers: u8,
+    },
+    Scroll {
+        up: bool,
+    },
+    Resize {
+        width: u16,
+        height: u16,
+    },
+}
+
+/// Serializable mirror of [`ExitStatus`].
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(tag = "kind", content = "code", rename_all = "snake_case")]
+pub enum RecordedExit {
+    Exited(u32),
+    Signaled(u8),
+    Other(i32),
+    Undetermined,
+}
+
+impl From<ExitStatus> for RecordedExit {
+    fn from(status: ExitStatus) -> Self {
+        match status {
+            ExitStatus::Exited(code) => Self::Exited(code),
+            ExitStatus::Signaled(signal) => Self::Signaled(signal),
+            ExitStatus::Other(code) => Self::Other(code),
+            ExitStatus::Undetermined => Self::Undetermined,
+        }
+    }
+}
+
+impl From<RecordedExit> for ExitStatus {
+    fn from(status: RecordedExit) -> Self {
+        match status {
+            RecordedExit::Exited(code) => Self::Exited(code),
+            RecordedExit::Signaled(signal) => Self::Signaled(signal),
+            RecordedExit::Other(code) => Self::Other(code),
+            RecordedExit::Undetermined => Self::Undetermined,
+        }
+    }
+}
+
+/// Serializable mirror of [`TaskState`].
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(tag = "state", content = "exit", rename_all = "snake_case")]
+pub enum RecordedState {
+    Pending,
+    WaitingOnUpstream,
+    Queued,
+    Starting,
+    Running,
+    Stopping,
+    Exited(RecordedExit),
+    Blocked,
+}
+
+impl From<TaskState> for RecordedState {
+    fn from(state: TaskState) -> Self {
+        match state {
+            TaskState::Pending => Self::Pending,
+            TaskState::WaitingOnUpstream => Self::WaitingOnUpstream,
+            TaskState::Queued => Self::Queued,
+            TaskState::Starting => Self::Starting,
+            TaskState::Running => Self::Running,
+            TaskState::Stopping => Self::Stopping,
+            TaskState::Exited(status) => Self::Exited(status.into()),
+            TaskState::Blocked => Self::Blocked,
+        }
+    }
+}
+
+impl From<RecordedState> for TaskState {
+    fn from(state: RecordedState) -> Self {
+        match state {
+            RecordedState::Pending => Self::Pending,
+            RecordedState::WaitingOnUpstream => Self::WaitingOnUpstream,
+            RecordedState::Queued => Self::Queued,
+            RecordedState::Starting => Self::Starting,
+            RecordedState::Running => Self::Running,
+            RecordedState::Stopping => Self::Stopping,
+            RecordedState::Exited(status) => Self::Exited(status.into()),
+            RecordedState::Blocked => Self::Blocked,
+        }
+    }
+}
+
+#[cfg(feature = "tui")]
+fn key_code_name(code: KeyCode) -> Option<String> {
+    let name = match code {
+        KeyCode::Char(ch) => format!("char:{ch}"),
+        KeyCode::Up => "up".to_string(),
+        KeyCode::Down => "down".to_string(),
+        KeyCode::Left => "left".to_string(),
+        KeyCode::Right => "right".to_string(),
+        KeyCode::Tab => "tab".to_string(),
+        KeyCode::Enter => "enter".to_string(),
+        KeyCode::Esc => "esc".to_string(),
+        KeyCode::Backspace => "backspace".to_string(),
+        _ => return None,
+    };
+    Some(name)
+}
+
+#[cfg(feature = "tui")]
+fn key_code_from_name(name: &str) -> Option<KeyCode> {
+    let code = match name {
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "tab" => KeyCode::Tab,
+        "enter" => KeyCode::Enter,
+        "esc" => KeyCode::Esc,
+        "backspace" => KeyCode::Backspace,
+        other => KeyCode::Char(other.strip_prefix("char:")?.chars().next()?),
+    };
+    Some(code)
+}
+
+impl RecordedEvent {
+    pub fn output(msg: &Output, redacted: bool) -> Self {
+        Self::Output {
+            panel: msg.panel_name.clone(),
+            message: (!redacted).then(|| msg.message.clone()),
+            len: msg.message.len(),
+            kind: msg.kind,
+            timestamp: msg.timestamp,
+            pin: msg.pin,
+        }
+    }
+
+    pub fn register_panel(msg: &RegisterPanel) -> Self {
+        Self::RegisterPanel {
+            name: msg.name.clone(),
+            colors: msg
+                .colors
+                .iter()
+                .map(|c| (c.regex.as_str().to_string(), c.color.to_string()))
+                .collect(),
+            dynamic: msg.dynamic.clone(),
+        }
+    }
+
+    pub fn state_changed(msg: &StateChanged) -> Self {
+        Self::StateChanged {
+            panel: msg.panel_name.clone(),
+            state: msg.state.into(),
+        }
+    }
+
+    /// Returns `None` for terminal events which have no effect on the console.
+    #[cfg(feature = "tui")]
+    pub fn term_event(msg: &TermEvent) -> Option<Self> {
+        match &msg.0 {
+            Event::Key(key) => Some(Self::Key {
+                code: key_code_name(key.code)?,
+                modifiers: key.modifiers.bits(),
+            }),
+            Event::Mouse(mouse) => match mouse.kind {
+                MouseEventKind::ScrollUp => Some(Self::Scroll { up: true }),
+                MouseEventKind::ScrollDown => Some(Self::Scroll { up: false }),
+                _ => None,
+            },
+            Event::Resize(width, height) => Some(Self::Resize {
+                width: *width,
+                height: *height,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the event comes from the terminal rather than whiz.
+    pub fn is_term_event(&self) -> bool {
+        matches!(
+            self,
+            RecordedEvent::Key { .. } | RecordedEvent::Scroll { .. } | RecordedEvent::Resize { .. }
+        )
+    }
+
+    /// Sends the event to the console as the original message.
+    pub fn send_to(self, console: &Addr<ConsoleActor>) -> Result<()> {
+        match self {
+            RecordedEvent::Output {
+                panel,
+                message,
+                len,
+                kind,
+                timestamp,
+                pin,
+            } => {
+                let message = message.unwrap_or_else(|| "x".repeat(len));
+                console.do_send(Output {
+                    panel_name: panel,
+                    message,
+                    kind,
+                    timestamp,
+                    pin,
+                    partial: false,
+                });
+            }
+            RecordedEvent::RegisterPanel {
+                name,
+                colors,
+                dynamic,
+            } => {
+                let colors = colors
+                    .iter()
+                    .map(|(regex, color)| {
+                        Ok(ColorOption::new(
+                            Regex::new(regex)?,
+                            ColorOption::parse_color(color)
+                                .map_err(|_| anyhow!("bad color {color}"))?,
+                        ))
+                    })
+                    .collect::<Result<_>>()?;
+                console.do_send(RegisterPanel {
+                    name,
+                    addr: None,
+                    colors,
+                    dynamic,
+                });
+            }
+            RecordedEvent::UnregisterPanel { name } => {
+                console.do_send(UnregisterPanel { name });
+            }
+            RecordedEvent::StateChanged { panel, state } => {
+                console.do_send(StateChanged {
+                    panel_name: panel,
+                    state: state.into(),
+                });
+            }
+            #[cfg(feature = "tui")]
+            RecordedEvent::Key { code, modifiers } => {
+                let code =
+                    key_code_from_name(&code).ok_or_else(|| anyhow!("unknown key {code}"))?;
+                let modifiers = KeyModifiers::from_bits_truncate(modifiers);
+                console.do_send(TermEvent(Event::Key(KeyEvent::new(code, modifiers))));
+            }
+            #[cfg(feature = "tui")]
+            RecordedEvent::Scroll { up } => {
+                let kind = if up {
+                    MouseEventKind::ScrollUp
+                } else {
+                    MouseEventKind::ScrollDown
+                };
+                console.do_send(TermEvent(Event::Mouse(MouseEvent {
+                    kind,
+                    column: 0,
+                    row: 0,
+                    modifiers: KeyModifiers::NONE,
+                })));
+            }
+            #[cfg(feature = "tui")]
+            RecordedEvent::Resize { width, height } => {
+                console.do_send(TermEvent(Event::Resize(width, height)));
+            }
+            // the headless console has no view for them to drive
+            #[cfg(not(feature = "tui"))]
+            RecordedEvent::Key { .. }
+            | RecordedEvent::Scroll { .. }
+            | RecordedEvent::Resize { .. } => {}
+        }
+        Ok(())
+    }
+}
+
+/// Appends the console-bound events of a session to a JSONL file.
+pub struct Recorder {
+    file: LineWriter<File>,
+    started_at: Instant,
+    redacted: bool,
+    /// Lines written so far, header included.
+    lines: usize,
+    /// Receive each line once written, see [`crate::daemon`].
+    attached: Vec<Recipient<ConsoleLine>>,
+}
+
+impl Recorder {
+    pub fn create<P: AsRef<Path>>(path: P, redacted: bool, mode: Option<FileMode>) -> Result<Self> {
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        let file = file_mode::open(&options, path.as_ref(), mode)
+            .with_context(|| format!("cannot create recording {:?}", path.as_ref()))?;
+        Ok(Self {
+            file: LineWriter::new(file),
+            started_at: Instant::now(),
+            redacted,
+            lines: 0,
+            attached: Vec::new(),
+        })
+    }
+
+    pub fn redacted(&self) -> bool {
+        self.redacted
+    }
+
+    pub fn header(&mut self, header: &SessionHeader) {
+        self.write_line(serde_json::to_string(header));
+    }
+
+    pub fn record(&mut self, event: RecordedEvent) {
+        let line = RecordedLine {
+            t: self.started_at.elapsed().as_millis() as u64,
+            event,
+        };
+        self.write_line(serde_json::to_string(&line));
+    }
+
+    /// Forwards the lines written from now on to `recipient` and returns
+    /// how many were written before, to read back from the file.
+    pub fn attach(&mut self, recipient: Recipient<ConsoleLine>) -> usize {
+        self.attached.push(recipient);
+        self.lines
+    }
+
+    fn write_line(&mut self, line: serde_json::Result<String>) {
+        // a broken recording must not take the session down
+        let Ok(line) = line else {
+            return;
+        };
+        if writeln!(self.file, "{line}").is_ok() {
+            self.lines += 1;
+        }
+        self.attached.retain(|attached| attached.connected());
+        for attached in &self.attached {
+            attached.do_send(ConsoleLine(line.clone()));
+        }
+    }
+}
+
+/// Reads a recording made with `--record`.
+pub fn read_session<P: AsRef<Path>>(path: P) -> Result<(SessionHeader, Vec<RecordedLine>)> {
+    let file = File::open(path.as_ref())
+        .with_context(|| format!("cannot open recording {:?}", path.as_ref()))?;
+    let mut lines = BufReader::new(file).lines();
+
+    let header = lines.next().ok_or_else(|| anyhow!("empty recording"))??;
+    let header: SessionHeader =
+        serde_json::from_str(&header).context("invalid recording header")?;
+
+    let events = lines
+        .enumerate()
+        .map(|(i, line)| {
+            serde_json::from_str(&line?)
+                .with_context(|| format!("invalid recording event on line {}", i + 2))
+        })
+        .collect::<Result<_>>()?;
+
+    Ok((header, events))
+}
+
+/// Feeds the recorded events to the console, `speed` times faster than
+/// they were recorded or without waiting when `None`.
+pub async fn play(
+    console: &Addr<ConsoleActor>,
+    events: Vec<RecordedLine>,
+    speed: Option<f64>,
+) -> Result<()> {
+    let mut last = 0;
+    for RecordedLine { t, event } in events {
+        if let Some(speed) = speed {
+            let delay = t.saturating_sub(last) as f64 / speed;
+            tokio::time::sleep(Duration::from_millis(delay as u64)).await;
+        }
+        last = t;
+        event.send_to(console)?;
+    }
+    Ok(())
+}
+
+/// Warns when the terminal replaying a session is not the size it was
+/// recorded in, as its lines then wrap and lay out differently.
+#[cfg(feature = "tui")]
+fn size_mismatch(header: &SessionHeader, (width, height): (u16, u16)) -> Option<String> {
+    ((width, height) != (header.width, header.height)).then(|| {
+        format!(
+            "the session was recorded in a {}x{} terminal but this one is {width}x{height}, \
+             lines may wrap differently",
+            header.width, header.height
+        )
+    })
+}
+
+pub async fn start(opts: &Replay) -> Result<()> {
+    if opts.speed <= 0.0 {
+        return Err(anyhow!("replay speed must be positive"));
+    }
+
+    let (header, events) = read_session(&opts.file)?;
+    #[cfg(feature = "tui")]
+    if let Some(warning) = crossterm::terminal::size()
+        .ok()
+        .and_then(|size| size_mismatch(&header, size))
+    {
+        eprintln!("{warning}");
+    }
+    let console = ConsoleActor::new(header.order, header.timestamp)
+        .replay(true)
+        .start();
+    play(&console, events, Some(opts.speed)).await
+}
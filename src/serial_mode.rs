@@ -1,10 +1,16 @@
-use actix::System;
 use anyhow::{anyhow, Result};
-use crossterm::style::Stylize;
 
+use crate::exit::{ExitCode, WithExitCode};
 use crate::{args::Execute, config::Config, exec::ExecBuilder};
 
-pub async fn start(opts: &Execute, config: Config) -> Result<()> {
+/// Wraps `text` in the escape sequences of the ANSI `color`, e.g. 36 for cyan.
+fn paint(text: &str, color: u8) -> String {
+    format!("\x1b[{color}m{text}\x1b[0m")
+}
+
+/// Runs the task of `opts` after its dependencies, one at a time, stopping
+/// at the first one failing.
+pub async fn start(opts: &Execute, config: Config) -> Result<ExitCode> {
     let mut queue: Vec<String> = Vec::new();
     queue.push(opts.task.clone());
 
@@ -18,7 +24,8 @@ pub async fn start(opts: &Execute, config: Config) -> Result<()> {
         let task = config
             .ops
             .get(&task_name)
-            .ok_or_else(|| anyhow!("Task not found: {}", task_name))?;
+            .ok_or_else(|| anyhow!("Task not found: {}", task_name))
+            .exit_code(ExitCode::Usage)?;
 
         if executed_tasks.contains(&task_name) {
             continue;
@@ -38,10 +45,13 @@ pub async fn start(opts: &Execute, config: Config) -> Result<()> {
 
         println!(
             "---------------- Starting task {task} ---------------",
-            task = task_name.as_str().cyan(),
+            task = paint(&task_name, 36),
         );
 
-        let exec_builder = ExecBuilder::new(task, &config).await?;
+        let exec_builder = ExecBuilder::new(&task_name, task, &config)
+            .await
+            .map_err(|err| anyhow!("task {task_name}: {err}"))
+            .exit_code(ExitCode::Usage)?;
 
         let exit_status = tokio::task::spawn_blocking(move || {
             let exec = exec_builder
@@ -49,28 +59,29 @@ pub async fn start(opts: &Execute, config: Config) -> Result<()> {
                 .unwrap()
                 .stdout(subprocess::Redirection::None)
                 .stderr(subprocess::Redirection::None);
-            exec.join().unwrap()
+            exec_builder.within_limits(|| exec.join()).unwrap().unwrap()
         })
         .await?;
 
         let prefix = if exit_status.success() {
-            "✓".green()
+            paint("✓", 32)
         } else {
-            "✖️".red()
+            paint("✖️", 31)
         };
 
         println!(
             "---- {prefix} Task {task} exited with status {status} ----",
-            task = task_name.as_str().cyan(),
-            status = format!("{:?}", exit_status).yellow(),
+            task = paint(&task_name, 36),
+            status = paint(&format!("{:?}", exit_status), 33),
         );
 
+        // the dependents of a failed task would fail as well
         if !exit_status.success() {
-            System::current().stop_with_code(1);
+            return Ok(ExitCode::TaskFailed);
         }
 
         executed_tasks.push(task_name.clone());
     }
 
-    Ok(())
+    Ok(ExitCode::Success)
 }
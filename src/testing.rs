@@ -0,0 +1,192 @@
+//! Runs the tasks of a config without a terminal nor watching files, for
+//! the integration tests of the tools built on whiz. Requires the `testing`
+//! feature.
+//!
+//! ```no_run
+//! use whiz::testing::{config_from_str, Harness};
+//!
+//! actix::System::new().block_on(async {
+//!     let config = config_from_str("hello:\n    command: echo hello", ".").unwrap();
+//!     let harness = Harness::start(config).await.unwrap();
+//!     harness.wait_for_exit("hello").await.unwrap();
+//!     assert_eq!(harness.sink.output("hello"), vec!["hello"]);
+//! });
+//! ```
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::actors::console::OutputKind;
+use crate::actors::state::TaskState;
+
+/// Message received by a [`ConsoleSink`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConsoleEvent {
+    Output {
+        panel: String,
+        message: String,
+        kind: OutputKind,
+    },
+    RegisterPanel {
+        name: String,
+    },
+    UnregisterPanel {
+        name: String,
+    },
+    StateChanged {
+        panel: String,
+        state: TaskState,
+    },
+}
+
+/// Keeps the messages received by a console instead of showing them, see
+/// [`crate::actors::console::ConsoleActor::sink`].
+#[derive(Debug, Clone, Default)]
+pub struct ConsoleSink(Arc<Mutex<Vec<ConsoleEvent>>>);
+
+impl ConsoleSink {
+    pub(crate) fn push(&self, event: ConsoleEvent) {
+        self.0.lock().unwrap().push(event);
+    }
+
+    /// Returns the messages received so far, in order.
+    pub fn events(&self) -> Vec<ConsoleEvent> {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// Returns the lines printed by the task of `panel`, without the ones
+    /// of whiz.
+    pub fn output(&self, panel: &str) -> Vec<String> {
+        self.events()
+            .into_iter()
+            .filter_map(|event| match event {
+                ConsoleEvent::Output {
+                    panel: name,
+                    message,
+                    kind: OutputKind::Command | OutputKind::Stderr,
+                } if name == panel => Some(message),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the states the task of `panel` went through.
+    pub fn states(&self, panel: &str) -> Vec<TaskState> {
+        self.events()
+            .into_iter()
+            .filter_map(|event| match event {
+                ConsoleEvent::StateChanged { panel: name, state } if name == panel => Some(state),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Waits until `condition` holds for the messages received, returning
+    /// whether it did within `timeout`.
+    pub async fn wait_for(
+        &self,
+        timeout: Duration,
+        condition: impl Fn(&[ConsoleEvent]) -> bool,
+    ) -> bool {
+        let started_at = std::time::Instant::now();
+        loop {
+            if condition(&self.events()) {
+                return true;
+            }
+            if started_at.elapsed() > timeout {
+                return false;
+            }
+            actix::clock::sleep(Duration::from_millis(10)).await;
+        }
+    }
+}
+
+// the tests of whiz itself wire the tasks to mocked actors
+#[cfg(not(test))]
+pub use self::harness::*;
+
+#[cfg(not(test))]
+mod harness {
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use actix::prelude::*;
+    use anyhow::{anyhow, Result};
+
+    use super::{ConsoleEvent, ConsoleSink};
+    use crate::actors::command::{CommandActor, CommandActorsBuilder};
+    use crate::actors::console::ConsoleActor;
+    use crate::actors::watcher::WatcherActor;
+    use crate::config::{Config, ConfigInner, RawConfig};
+
+    /// How long [`Harness::wait_for_exit`] waits for a task.
+    pub const EXIT_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Parses a config file, its tasks running relative to `base_dir`.
+    pub fn config_from_str(config: &str, base_dir: impl Into<PathBuf>) -> Result<Config> {
+        let raw: RawConfig = config.parse()?;
+        Ok(Arc::new(ConfigInner::from_raw(raw, base_dir.into())?))
+    }
+
+    /// The tasks of a config wired to a [`ConsoleSink`] and to a watcher
+    /// which never fires. Reloads are triggered by sending
+    /// [`crate::actors::command::Reload`] to the commands.
+    pub struct Harness {
+        pub console: Addr<ConsoleActor>,
+        pub watcher: Addr<WatcherActor>,
+        pub commands: HashMap<String, Addr<CommandActor>>,
+        pub sink: ConsoleSink,
+    }
+
+    impl Harness {
+        /// Starts the active tasks of `config`, within a running actix
+        /// system.
+        pub async fn start(config: Config) -> Result<Self> {
+            let sink = ConsoleSink::default();
+            let console = ConsoleActor::new(Vec::from_iter(config.active_ops().into_keys()), false)
+                .sink(sink.clone())
+                .start();
+            let watcher = WatcherActor::noop().start();
+            let commands = CommandActorsBuilder::new(config, console.clone(), watcher.clone())
+                .build()
+                .await?;
+            Ok(Self {
+                console,
+                watcher,
+                commands,
+                sink,
+            })
+        }
+
+        /// Returns the command of `task`.
+        pub fn command(&self, task: &str) -> Result<&Addr<CommandActor>> {
+            self.commands
+                .get(task)
+                .ok_or_else(|| anyhow!("unknown task {task}"))
+        }
+
+        /// Waits for `task` to finish (see [`crate::actors::state::TaskState::is_finished`]),
+        /// its output included, up to [`EXIT_TIMEOUT`].
+        pub async fn wait_for_exit(&self, task: &str) -> Result<()> {
+            self.command(task)?;
+            let finished = self
+                .sink
+                .wait_for(EXIT_TIMEOUT, |events| {
+                    events.iter().any(|event| {
+                        matches!(
+                            event,
+                            ConsoleEvent::StateChanged { panel, state }
+                                if panel == task && state.is_finished()
+                        )
+                    })
+                })
+                .await;
+            match finished {
+                true => Ok(()),
+                false => Err(anyhow!("task {task} did not finish in {EXIT_TIMEOUT:?}")),
+            }
+        }
+    }
+}
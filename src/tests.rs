@@ -6,15 +6,36 @@ use anyhow::{Ok, Result};
 
 use subprocess::ExitStatus;
 
-use crate::actors::command::{CommandActorsBuilder, WaitStatus};
-use crate::actors::console::{OutputKind, RegisterPanel};
-use crate::actors::watcher::WatchGlob;
+use crate::actors::a11y::{read_instructions, Announcer, HELP};
+use crate::actors::command::{
+    CommandActorsBuilder, GetState, PoisonPill, Reload, TtySize, WaitStatus,
+};
+#[cfg(feature = "tui")]
+use crate::actors::console::{Copied, SetOffscreenSize, Snapshot, Split};
+#[cfg(feature = "tui")]
+use crate::actors::console::{DynamicPanel, GetStates};
+use crate::actors::console::{OutputKind, RegisterPanel, UnregisterPanel};
+use crate::actors::control::ControlActor;
+use crate::actors::reloader::{ConfigChanged, ConfigReloaderActor, Restart};
+use crate::actors::state::TaskState;
+use crate::actors::watcher::{ReloadAllOn, WatchGlob};
 use crate::args::Args;
-use crate::config::{ConfigInner, RawConfig};
-use crate::utils::find_config_path;
+#[cfg(feature = "tui")]
+use crate::config::color::ColorOption;
+use crate::config::container::container_name;
+#[cfg(feature = "watch")]
+use crate::config::watch_events::WatchEventKind;
+use crate::config::{ConfigBuilder, ConfigInner, RawConfig, RestartPolicy};
+use crate::daemon::{self, DaemonPaths, Request};
+use crate::exec::ExecBuilder;
+use crate::exit::ExitCode;
+#[cfg(feature = "tui")]
+use crate::replay;
+use crate::replay::{RecordedLine, Recorder, SessionHeader};
+use crate::utils::{find_config_path, resolve_base_dir, resolve_config_path, CONFIG_ENV};
 use crate::{
     actors::{
-        console::{ConsoleActor, Output, PanelStatus, TermEvent},
+        console::{ConsoleActor, Output, StateChanged, TermEvent},
         grim_reaper::GrimReaperActor,
         watcher::WatcherActor,
     },
@@ -23,6 +44,8 @@ use crate::{
 use actix::{actors::mocker::Mocker, prelude::*};
 use assert_cmd::Command;
 use clap::CommandFactory;
+#[cfg(feature = "tui")]
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
 
 fn within_system<F: Future<Output = Result<()>>>(f: F) {
     let system = System::new();
@@ -55,12 +78,83 @@ fn verify_cli() {
     Args::command().debug_assert()
 }
 
+#[test]
+fn prints_completions_without_config() {
+    let mut cmd = Command::cargo_bin("whiz").unwrap();
+    let output = cmd
+        .args(["--file", "missing.yaml", "completions", "bash"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let script = String::from_utf8(output.stdout).unwrap();
+    assert!(script.contains("_whiz()"), "{script}");
+    assert!(script.contains("--dry-run"));
+}
+
 #[test]
 fn end_to_end() {
     let mut cmd = Command::cargo_bin("whiz").unwrap();
     cmd.arg("-h").assert().success();
 }
 
+#[test]
+fn exits_with_documented_codes() {
+    let dir = env::temp_dir().join(format!("whiz-exit-codes-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("whiz.yaml"),
+        r#"
+        ok:
+            command: echo ok
+        fail:
+            command: exit 3
+        after_fail:
+            command: echo after fail
+            depends_on: fail
+        "#,
+    )
+    .unwrap();
+    let whiz = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("whiz").unwrap();
+        cmd.current_dir(&dir).args(args);
+        cmd
+    };
+    let code = |code: ExitCode| code.code();
+
+    whiz(&["--exit-after", "--no-tui", "--run", "ok"])
+        .assert()
+        .code(code(ExitCode::Success));
+    // whatever the code of the task
+    whiz(&["--exit-after", "--no-tui"])
+        .assert()
+        .code(code(ExitCode::TaskFailed));
+    let serial = whiz(&["x", "after_fail"])
+        .assert()
+        .code(code(ExitCode::TaskFailed));
+    let stdout = String::from_utf8_lossy(&serial.get_output().stdout).into_owned();
+    assert!(!stdout.contains("after fail"), "{stdout}");
+
+    whiz(&["--unknown-flag"])
+        .assert()
+        .code(code(ExitCode::Usage));
+    whiz(&["--file", "missing.yaml"])
+        .assert()
+        .code(code(ExitCode::Usage));
+    whiz(&["x", "missing"]).assert().code(code(ExitCode::Usage));
+    whiz(&["plan", "missing"])
+        .assert()
+        .code(code(ExitCode::Usage));
+    // nothing runs in the background
+    whiz(&["status"]).assert().code(code(ExitCode::Internal));
+
+    whiz(&["exit-codes"])
+        .assert()
+        .code(code(ExitCode::Success))
+        .stdout(ExitCode::table());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
 fn config_from_str(s: &str) -> Result<Config> {
     let raw: RawConfig = s.parse()?;
     Ok(Arc::new(ConfigInner::from_raw(raw, env::current_dir()?)?))
@@ -83,11 +177,12 @@ fn hello() {
             },
             _msg: RegisterPanel => Some(()),
             _msg: TermEvent => Some(()),
-            _msg: PanelStatus => Some(()),
+            _msg: StateChanged => Some(()),
         });
 
         let watcher = mock_actor!(WatcherActor, {
             _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
         });
 
         console
@@ -110,91 +205,4806 @@ fn hello() {
 }
 
 #[test]
-fn test_grim_reaper() {
-    let system = System::with_tokio_rt(|| {
-        tokio::runtime::Builder::new_multi_thread()
-            .worker_threads(2)
-            .max_blocking_threads(1)
-            .enable_all()
+fn long_running_survives_closed_stdout() {
+    within_system(async move {
+        let config = config_from_str(
+            r#"
+            wrapper:
+                entrypoint: 'python3 -c'
+                command: 'import os, time; print("up", flush=True); os.close(1); os.close(2); time.sleep(2)'
+                long_running: true
+            "#,
+        )?;
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                println!("---{:?}", msg.message);
+                Some(())
+            },
+            _msg: RegisterPanel => Some(()),
+            _msg: TermEvent => Some(()),
+            _msg: StateChanged => Some(()),
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
+        });
+
+        let commands = CommandActorsBuilder::new(config, console, watcher)
             .build()
-            .unwrap()
+            .await?;
+
+        let status = commands.get("wrapper").unwrap().send(WaitStatus).await??;
+        assert_eq!(status, ExitStatus::Exited(0));
+
+        Ok(())
     });
+}
 
-    let fut = async move {
-        let config_raw = r#"
-test:
-    entrypoint: 'python3 -c'
-    command: 'print("hello whiz")'
-long_test_dep:
-    entrypoint: 'python3 -c'
-    command: 'import time; time.sleep(1); print("wake up")'
-long_test:
-    entrypoint: 'python3 -c'
-    command: 'print("my que to enter")'
-    depends_on:
-        - long_test_dep"#;
-        let config: Config = config_from_str(config_raw)?;
+#[cfg(unix)]
+#[test]
+fn runs_task_in_container() {
+    within_system(async move {
+        let config = config_from_str(
+            r#"
+            env:
+                LOG: info
+            api:
+                command: npm start
+                env:
+                    PORT: "3000"
+                container:
+                    image: node:20
+                    # prints the command it would run
+                    engine: echo
+            "#,
+        )?;
+
+        let exec = ExecBuilder::new("api", &config.ops["api"], &config).await?;
+        let mut output = String::new();
+        std::io::Read::read_to_string(exec.popen(&[])?.stdout.as_mut().unwrap(), &mut output)?;
+        let cwd = env::current_dir()?.display().to_string();
+        // without the variables of the host
+        assert_eq!(
+            output.trim_end(),
+            format!(
+                "run --rm --init --name {} -v {cwd}:{cwd} -w {cwd} -e LOG -e PORT node:20 sh -c npm start",
+                container_name("api")
+            )
+        );
+        Ok(())
+    });
+}
+
+#[test]
+fn scheduled_task_runs_on_its_own() {
+    static LINES: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+    within_system(async move {
+        let config = config_from_str(
+            r#"
+            backup:
+                command: sleep 1.5
+                every: 1s
+            "#,
+        )?;
 
         let console = mock_actor!(ConsoleActor, {
             msg: Output => {
-                println!("---{:?}", msg.message);
+                LINES.lock().unwrap().push(msg.message.clone());
                 Some(())
             },
-            _msg: PanelStatus => Some(()),
             _msg: RegisterPanel => Some(()),
-            _msg: TermEvent => Some(()),
+            _msg: StateChanged => Some(()),
         });
 
         let watcher = mock_actor!(WatcherActor, {
             _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
         });
 
         let commands = CommandActorsBuilder::new(config, console, watcher)
             .build()
             .await?;
+        let backup = commands.get("backup").unwrap();
 
-        GrimReaperActor::start_new(commands).await?;
+        // not started with whiz
+        actix::clock::sleep(std::time::Duration::from_millis(500)).await;
+        assert_eq!(backup.send(GetState).await?, TaskState::Pending);
+
+        // runs on the first tick, which the second one leaves running
+        let skipped = "SCHEDULE: still running, skipping this run";
+        for _ in 0..150 {
+            if LINES.lock().unwrap().iter().any(|line| line == skipped) {
+                break;
+            }
+            actix::clock::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert_eq!(backup.send(GetState).await?, TaskState::Running);
         Ok(())
-    };
+    });
 
-    Arbiter::current().spawn(async { fut.await.unwrap() });
+    let lines = LINES.lock().unwrap();
+    let position = |line: &str| lines.iter().position(|l| l == line);
+    assert!(lines[0].starts_with("SCHEDULE: next run at "), "{lines:?}");
+    let reloaded = position("RELOAD: scheduled").unwrap();
+    let skipped = position("SCHEDULE: still running, skipping this run").unwrap();
+    assert!(reloaded < skipped, "{lines:?}");
+}
 
-    let timer = std::time::SystemTime::now();
-    assert_eq!(0, system.run_with_code().unwrap());
-    let elapsed = timer.elapsed().unwrap();
+#[test]
+fn schedule_reruns_at_an_interval() {
+    static LINES: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+    within_system(async move {
+        let config = config_from_str(
+            r#"
+            sync:
+                command: echo synced
+                schedule: 200ms
+            "#,
+        )?;
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                LINES.lock().unwrap().push(msg.message.clone());
+                Some(())
+            },
+            _msg: RegisterPanel => Some(()),
+            _msg: StateChanged => Some(()),
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
+        });
+
+        let _commands = CommandActorsBuilder::new(config, console, watcher)
+            .build()
+            .await?;
+        for _ in 0..100 {
+            let runs = LINES
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|l| *l == "synced")
+                .count();
+            if runs >= 3 {
+                break;
+            }
+            actix::clock::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        Ok(())
+    });
+
+    let lines = LINES.lock().unwrap();
+    let runs = lines.iter().filter(|line| *line == "synced").count();
+    assert!(runs >= 3, "{lines:?}");
+    // each run is a tick, the last one may not have printed yet
+    let ticks = lines.iter().filter(|line| *line == "RELOAD: scheduled");
+    assert!(ticks.count() >= runs, "{lines:?}");
+}
+
+#[test]
+fn quiet_task_runs_without_output() {
+    static LINES: std::sync::Mutex<Vec<(String, String)>> = std::sync::Mutex::new(Vec::new());
+    static FINISHED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+    within_system(async move {
+        let config = config_from_str(
+            r#"
+            proxy:
+                command: echo noise && echo shown
+                quiet: true
+                pipe:
+                    "^shown$": whiz://piped
+            "#,
+        )?;
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                LINES
+                    .lock()
+                    .unwrap()
+                    .push((msg.panel_name.clone(), msg.message.clone()));
+                Some(())
+            },
+            _msg: RegisterPanel => Some(()),
+            msg: StateChanged => {
+                if msg.state.is_finished() {
+                    FINISHED.store(true, std::sync::atomic::Ordering::SeqCst);
+                }
+                Some(())
+            },
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
+        });
+
+        let commands = CommandActorsBuilder::new(config, console, watcher)
+            .build()
+            .await?;
+
+        let status = commands.get("proxy").unwrap().send(WaitStatus).await??;
+        assert_eq!(status, ExitStatus::Exited(0));
+        while !FINISHED.load(std::sync::atomic::Ordering::SeqCst) {
+            actix::clock::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        Ok(())
+    });
+
+    let lines = LINES.lock().unwrap();
     assert!(
-        elapsed.as_millis() >= 1000,
-        "test took less than a second: {elapsed:?}"
+        !lines.iter().any(|(_, message)| message == "noise"),
+        "{lines:?}"
+    );
+    assert!(
+        lines.contains(&("piped".to_string(), "shown".to_string())),
+        "{lines:?}"
     );
 }
 
 #[test]
-fn config_search_recursive() {
-    assert!(env::current_dir().is_ok());
-    let previous_cwd = env::current_dir().unwrap().as_path().display().to_string();
+fn raw_stream_shows_prompts() {
+    static LINES: std::sync::Mutex<Vec<(String, bool)>> = std::sync::Mutex::new(Vec::new());
+    static FINISHED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
 
-    // change current working directory to {root_app}/src
-    assert!(env::set_current_dir(Path::new("src")).is_ok());
-    assert!(env::current_dir().is_ok());
+    within_system(async move {
+        let config = config_from_str(
+            r#"
+            setup:
+                command: "printf 'Press enter to continue'; sleep 0.5; printf ': ok\\n'"
+                stream: raw
+            "#,
+        )?;
 
-    // cwd as string
-    let new_cwd = env::current_dir().unwrap().as_path().display().to_string();
-    println!(" Working directory set to {}", new_cwd);
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                if msg.kind == OutputKind::Command {
+                    LINES.lock().unwrap().push((msg.message.clone(), msg.partial));
+                }
+                Some(())
+            },
+            _msg: RegisterPanel => Some(()),
+            msg: StateChanged => {
+                if msg.state.is_finished() {
+                    FINISHED.store(true, std::sync::atomic::Ordering::SeqCst);
+                }
+                Some(())
+            },
+        });
 
-    let config_name = "whiz.yaml";
-    let expected_if_exist = Path::new(&new_cwd).join(config_name).display().to_string();
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
+        });
 
-    let config_path = find_config_path(&env::current_dir().unwrap(), config_name).unwrap();
-    let config_path_got = config_path.display().to_string();
+        let commands = CommandActorsBuilder::new(config, console, watcher)
+            .build()
+            .await?;
 
-    println!(" Config file located at {}", config_path_got);
-    println!(
-        " Path \"{}\" should be different from \"{}\"",
-        config_path_got, expected_if_exist
+        let status = commands.get("setup").unwrap().send(WaitStatus).await??;
+        assert_eq!(status, ExitStatus::Exited(0));
+        while !FINISHED.load(std::sync::atomic::Ordering::SeqCst) {
+            actix::clock::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        Ok(())
+    });
+
+    // the prompt shows before the line ends, then the whole line replaces it
+    let lines = LINES.lock().unwrap();
+    let lines: Vec<_> = lines
+        .iter()
+        .map(|(message, partial)| (message.as_str(), *partial))
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            ("Press enter to continue", true),
+            ("", true),
+            ("Press enter to continue: ok", false),
+        ]
     );
-    assert_ne!(config_path_got, expected_if_exist);
+}
 
-    // reset cwd to be safe
-    assert!(env::set_current_dir(Path::new(&previous_cwd)).is_ok());
-    println!(" Working directory reset to {}", previous_cwd);
+#[test]
+fn decodes_output_in_encoding() {
+    static LINES: std::sync::Mutex<Vec<(String, OutputKind)>> = std::sync::Mutex::new(Vec::new());
+    static FINISHED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+    within_system(async move {
+        let config = config_from_str(
+            r#"
+            legacy:
+                command: "printf 'caf\\351\\n'; printf 'cr\\350me\\n' >&2"
+                encoding: windows-1252
+                merge_stderr: false
+            "#,
+        )?;
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                if matches!(msg.kind, OutputKind::Command | OutputKind::Stderr) {
+                    LINES.lock().unwrap().push((msg.message.clone(), msg.kind));
+                }
+                Some(())
+            },
+            _msg: RegisterPanel => Some(()),
+            msg: StateChanged => {
+                if msg.state.is_finished() {
+                    FINISHED.store(true, std::sync::atomic::Ordering::SeqCst);
+                }
+                Some(())
+            },
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
+        });
+
+        let commands = CommandActorsBuilder::new(config, console, watcher)
+            .build()
+            .await?;
+
+        let status = commands.get("legacy").unwrap().send(WaitStatus).await??;
+        assert_eq!(status, ExitStatus::Exited(0));
+        while !FINISHED.load(std::sync::atomic::Ordering::SeqCst) {
+            actix::clock::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        Ok(())
+    });
+
+    let mut lines = LINES.lock().unwrap().clone();
+    lines.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(
+        lines,
+        [
+            ("café".to_string(), OutputKind::Command),
+            ("crème".to_string(), OutputKind::Stderr),
+        ]
+    );
+}
+
+#[test]
+fn dumps_resolved_env() {
+    within_system(async move {
+        let config = config_from_str(
+            r#"
+            env:
+                GREETING: hello
+            api:
+                command: echo api
+                env:
+                    MESSAGE: ${GREETING} world
+                    API_TOKEN: abc123
+            "#,
+        )?;
+        let exec_builder = ExecBuilder::new("api", &config.ops["api"], &config).await?;
+
+        let env = exec_builder.dump_env(false, false);
+        let lines: Vec<_> = env.lines().collect();
+        assert!(lines.contains(&"MESSAGE=hello world"), "{env}");
+        assert!(lines.contains(&"GREETING=hello"), "{env}");
+        assert!(lines.contains(&"API_TOKEN=abc123"), "{env}");
+        assert!(lines.iter().any(|line| line.starts_with("PATH=")), "{env}");
+        let mut sorted = lines.clone();
+        sorted.sort();
+        assert_eq!(lines, sorted);
+
+        let changed = exec_builder.dump_env(true, true);
+        assert_eq!(
+            changed.lines().collect::<Vec<_>>(),
+            ["API_TOKEN=***", "GREETING=hello", "MESSAGE=hello world"]
+        );
+        Ok(())
+    });
+}
+
+#[test]
+fn blocks_tasks_missing_requirements() {
+    static MESSAGES: std::sync::Mutex<Vec<(String, String)>> = std::sync::Mutex::new(Vec::new());
+
+    within_system(async move {
+        let config = config_from_str(
+            r#"
+            env:
+                GREETING: hello
+                DATABASE_URL: ""
+            api:
+                command: echo api
+                requires:
+                    bins: [sh, whiz-missing-binary]
+                    env: [GREETING, DATABASE_URL]
+            web:
+                command: echo web
+                requires:
+                    bins: sh
+                    env: GREETING
+            "#,
+        )?;
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                MESSAGES.lock().unwrap().push((msg.panel_name.clone(), msg.message.clone()));
+                Some(())
+            },
+            _msg: RegisterPanel => Some(()),
+            _msg: StateChanged => Some(()),
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
+        });
+
+        let commands = CommandActorsBuilder::new(config, console, watcher)
+            .build()
+            .await?;
+        let status = commands.get("api").unwrap().send(WaitStatus).await??;
+        assert_eq!(status, ExitStatus::Other(1));
+        let status = commands.get("web").unwrap().send(WaitStatus).await??;
+        assert_eq!(status, ExitStatus::Exited(0));
+        // leave time for the output to be dispatched
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        Ok(())
+    });
+
+    let messages = MESSAGES.lock().unwrap();
+    assert!(
+        messages.contains(&(
+            "api".to_string(),
+            "Cannot start: missing binary `whiz-missing-binary`; empty env `DATABASE_URL` — set it in whiz.yaml env or your shell".to_string()
+        )),
+        "{messages:?}"
+    );
+    assert!(!messages.contains(&("api".to_string(), "api".to_string())));
+    assert!(messages.contains(&("web".to_string(), "web".to_string())));
+}
+
+#[test]
+fn runs_on_demand_tasks_only_when_asked() {
+    static OUTPUTS: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+    within_system(async move {
+        let config = config_from_str(
+            r#"
+            api:
+                command: echo api
+            fmt:
+                command: echo formatted
+                on_demand: true
+            "#,
+        )?;
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                OUTPUTS.lock().unwrap().push(msg.message.clone());
+                Some(())
+            },
+            _msg: RegisterPanel => Some(()),
+            _msg: StateChanged => Some(()),
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
+        });
+
+        let commands = CommandActorsBuilder::new(config, console, watcher)
+            .build()
+            .await?;
+        let api = commands.get("api").unwrap();
+        let fmt = commands.get("fmt").unwrap();
+        assert_eq!(api.send(WaitStatus).await??, ExitStatus::Exited(0));
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        assert_eq!(fmt.send(GetState).await?, TaskState::Pending);
+        assert!(!OUTPUTS.lock().unwrap().contains(&"formatted".to_string()));
+
+        fmt.send(Reload::Manual).await?;
+        assert_eq!(fmt.send(WaitStatus).await??, ExitStatus::Exited(0));
+        // leave time for the output to be dispatched
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        assert!(OUTPUTS.lock().unwrap().contains(&"formatted".to_string()));
+        Ok(())
+    });
+}
+
+#[test]
+fn kills_tasks_running_past_their_timeout() {
+    static MESSAGES: std::sync::Mutex<Vec<(String, String)>> = std::sync::Mutex::new(Vec::new());
+
+    within_system(async move {
+        let config = config_from_str(
+            r#"
+            hang:
+                # ignores the polite stop, so it is killed
+                command: trap '' TERM; echo started; sleep 30
+                timeout: 1s
+            after:
+                command: echo after
+                depends_on: hang
+            "#,
+        )?;
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                MESSAGES.lock().unwrap().push((msg.panel_name.clone(), msg.message.clone()));
+                Some(())
+            },
+            _msg: RegisterPanel => Some(()),
+            _msg: StateChanged => Some(()),
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
+        });
+
+        let started = std::time::Instant::now();
+        let commands = CommandActorsBuilder::new(config, console, watcher)
+            .build()
+            .await?;
+        let hang = commands.get("hang").unwrap();
+        assert_eq!(hang.send(WaitStatus).await??, ExitStatus::Exited(124));
+        assert!(started.elapsed() < std::time::Duration::from_secs(10));
+        assert_eq!(
+            hang.send(GetState).await?,
+            TaskState::Exited(ExitStatus::Exited(124))
+        );
+        // the dependents go on as after any failure
+        let status = commands.get("after").unwrap().send(WaitStatus).await??;
+        assert_eq!(status, ExitStatus::Exited(0));
+        Ok(())
+    });
+
+    let messages = MESSAGES.lock().unwrap();
+    assert!(
+        messages.contains(&("hang".to_string(), "timed out after 1s".to_string())),
+        "{messages:?}"
+    );
+}
+
+#[test]
+fn starts_dependents_once_ready() {
+    static MESSAGES: std::sync::Mutex<Vec<(String, String)>> = std::sync::Mutex::new(Vec::new());
+
+    within_system(async move {
+        let config = config_from_str(
+            r#"
+            db:
+                command: echo booting; echo accepting connections; echo still accepting connections; sleep 30
+                ready_when: accepting connections
+            api:
+                command: echo api
+                depends_on: db
+            "#,
+        )?;
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                MESSAGES.lock().unwrap().push((msg.panel_name.clone(), msg.message.clone()));
+                Some(())
+            },
+            _msg: RegisterPanel => Some(()),
+            _msg: StateChanged => Some(()),
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
+        });
+
+        let commands = CommandActorsBuilder::new(config, console, watcher)
+            .build()
+            .await?;
+        let api = commands.get("api").unwrap();
+        assert_eq!(api.send(WaitStatus).await??, ExitStatus::Exited(0));
+        // while the service keeps running
+        let db = commands.get("db").unwrap();
+        assert_eq!(db.send(GetState).await?, TaskState::Running);
+
+        // a reload makes it ready again
+        db.send(Reload::Manual).await?;
+        actix::clock::sleep(std::time::Duration::from_millis(500)).await;
+        assert_eq!(api.send(WaitStatus).await??, ExitStatus::Exited(0));
+        db.send(PoisonPill).await?;
+        Ok(())
+    });
+
+    let messages = MESSAGES.lock().unwrap();
+    let ready = (
+        "db".to_string(),
+        "READY (matched /accepting connections/)".to_string(),
+    );
+    let api = ("api".to_string(), "api".to_string());
+    assert_eq!(
+        messages.iter().filter(|m| **m == ready).count(),
+        2,
+        "{messages:?}"
+    );
+    assert_eq!(
+        messages.iter().filter(|m| **m == api).count(),
+        2,
+        "{messages:?}"
+    );
+}
+
+#[test]
+fn starts_dependents_once_ready_on_stderr() {
+    static MESSAGES: std::sync::Mutex<Vec<(String, String)>> = std::sync::Mutex::new(Vec::new());
+
+    within_system(async move {
+        let config = config_from_str(
+            r#"
+            db:
+                command: echo booting; echo accepting connections >&2; sleep 30
+                ready_when: accepting connections
+                merge_stderr: false
+            api:
+                command: echo api
+                depends_on: db
+            "#,
+        )?;
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                MESSAGES.lock().unwrap().push((msg.panel_name.clone(), msg.message.clone()));
+                Some(())
+            },
+            _msg: RegisterPanel => Some(()),
+            _msg: StateChanged => Some(()),
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
+        });
+
+        let commands = CommandActorsBuilder::new(config, console, watcher)
+            .build()
+            .await?;
+        let api = commands.get("api").unwrap();
+        assert_eq!(api.send(WaitStatus).await??, ExitStatus::Exited(0));
+        let db = commands.get("db").unwrap();
+        assert_eq!(db.send(GetState).await?, TaskState::Running);
+        db.send(PoisonPill).await?;
+        Ok(())
+    });
+
+    let messages = MESSAGES.lock().unwrap();
+    let ready = (
+        "db".to_string(),
+        "READY (matched /accepting connections/)".to_string(),
+    );
+    assert!(messages.contains(&ready), "{messages:?}");
+}
+
+#[test]
+fn blocks_dependents_of_failed_tasks() {
+    static MESSAGES: std::sync::Mutex<Vec<(String, String)>> = std::sync::Mutex::new(Vec::new());
+
+    within_system(async move {
+        let config = config_from_str(
+            r#"
+            migrate:
+                command: exit 3
+            server:
+                command: echo server
+                depends_on: [{task: migrate, condition: success}]
+            worker:
+                command: echo worker
+                depends_on: server
+            report:
+                command: echo report
+                depends_on: migrate
+            "#,
+        )?;
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                MESSAGES.lock().unwrap().push((msg.panel_name.clone(), msg.message.clone()));
+                Some(())
+            },
+            _msg: RegisterPanel => Some(()),
+            _msg: StateChanged => Some(()),
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
+        });
+
+        let commands = CommandActorsBuilder::new(config, console, watcher)
+            .build()
+            .await?;
+        let server = commands.get("server").unwrap();
+        assert_eq!(server.send(WaitStatus).await??, ExitStatus::Other(1));
+        assert_eq!(server.send(GetState).await?, TaskState::Blocked);
+        // only the dependents which asked for it are blocked
+        let status = commands.get("report").unwrap().send(WaitStatus).await??;
+        assert_eq!(status, ExitStatus::Exited(0));
+        let status = commands.get("worker").unwrap().send(WaitStatus).await??;
+        assert_eq!(status, ExitStatus::Exited(0));
+        Ok(())
+    });
+
+    let messages = MESSAGES.lock().unwrap();
+    assert!(
+        messages.contains(&("server".to_string(), "blocked: migrate failed".to_string())),
+        "{messages:?}"
+    );
+    assert!(
+        !messages.contains(&("server".to_string(), "server".to_string())),
+        "{messages:?}"
+    );
+}
+
+#[test]
+fn reads_stderr_apart_unless_merged() {
+    static MESSAGES: std::sync::Mutex<Vec<(String, String, OutputKind)>> =
+        std::sync::Mutex::new(Vec::new());
+
+    within_system(async move {
+        let config = config_from_str(
+            r#"
+            merged:
+                command: echo out; echo err >&2
+            split:
+                command: echo out; echo err >&2; sleep 0.2
+                merge_stderr: false
+            "#,
+        )?;
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                MESSAGES.lock().unwrap().push((msg.panel_name.clone(), msg.message.clone(), msg.kind));
+                Some(())
+            },
+            _msg: RegisterPanel => Some(()),
+            _msg: StateChanged => Some(()),
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
+        });
+
+        let commands = CommandActorsBuilder::new(config, console, watcher)
+            .build()
+            .await?;
+        for name in ["merged", "split"] {
+            let status = commands.get(name).unwrap().send(WaitStatus).await??;
+            assert_eq!(status, ExitStatus::Exited(0));
+        }
+        Ok(())
+    });
+
+    let messages = MESSAGES.lock().unwrap();
+    let line = |panel: &str, message: &str, kind| (panel.to_string(), message.to_string(), kind);
+    for expected in [
+        line("merged", "out", OutputKind::Command),
+        line("merged", "err", OutputKind::Command),
+        line("split", "out", OutputKind::Command),
+        line("split", "err", OutputKind::Stderr),
+    ] {
+        assert!(messages.contains(&expected), "{expected:?} in {messages:?}");
+    }
+}
+
+#[test]
+fn runs_hooks_around_the_command() {
+    static MESSAGES: std::sync::Mutex<Vec<(String, String)>> = std::sync::Mutex::new(Vec::new());
+
+    within_system(async move {
+        let config = config_from_str(
+            r#"
+            build:
+                before: echo preparing
+                command: echo building
+                after: echo cleaning
+            broken:
+                before: echo cannot prepare; exit 3
+                command: echo never
+                after: echo never either
+            report:
+                command: echo report
+                depends_on:
+                    - task: broken
+                      condition: success
+            "#,
+        )?;
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                MESSAGES.lock().unwrap().push((msg.panel_name.clone(), msg.message.clone()));
+                Some(())
+            },
+            _msg: RegisterPanel => Some(()),
+            _msg: StateChanged => Some(()),
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
+        });
+
+        let commands = CommandActorsBuilder::new(config, console, watcher)
+            .build()
+            .await?;
+        let build = commands.get("build").unwrap();
+        assert_eq!(build.send(WaitStatus).await??, ExitStatus::Exited(0));
+        let broken = commands.get("broken").unwrap();
+        assert_eq!(broken.send(WaitStatus).await??, ExitStatus::Exited(3));
+        let report = commands.get("report").unwrap();
+        actix::clock::sleep(std::time::Duration::from_millis(200)).await;
+        assert_eq!(report.send(GetState).await?, TaskState::Blocked);
+        Ok(())
+    });
+
+    let messages = MESSAGES.lock().unwrap();
+    let build = messages
+        .iter()
+        .filter(|(panel, message)| panel == "build" && !message.starts_with("exited"))
+        .map(|(_, message)| message.split(" (pid").next().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        build,
+        vec![
+            "before `echo preparing`",
+            "preparing",
+            "started `echo building`",
+            "building",
+            "after `echo cleaning`",
+            "cleaning",
+        ],
+        "{messages:?}"
+    );
+    let broken = messages
+        .iter()
+        .filter(|(panel, _)| panel == "broken")
+        .map(|(_, message)| message.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        broken,
+        vec![
+            "before `echo cannot prepare; exit 3`",
+            "cannot prepare",
+            "before exited 3, not started",
+        ],
+        "{messages:?}"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn runs_tty_tasks_on_a_terminal() {
+    static MESSAGES: std::sync::Mutex<Vec<(String, String)>> = std::sync::Mutex::new(Vec::new());
+
+    within_system(async move {
+        let config = config_from_str(
+            r#"
+            colored:
+                command: sh -c 'test -t 1 && echo terminal; sleep 0.5; stty size <&1'
+                tty: true
+            piped:
+                command: sh -c 'test -t 1 && echo terminal || echo pipe'
+            "#,
+        )?;
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                MESSAGES.lock().unwrap().push((msg.panel_name.clone(), msg.message.clone()));
+                Some(())
+            },
+            _msg: RegisterPanel => Some(()),
+            _msg: StateChanged => Some(()),
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
+        });
+
+        let commands = CommandActorsBuilder::new(config, console, watcher)
+            .build()
+            .await?;
+        let colored = commands.get("colored").unwrap();
+        // as the console does once the panel resized
+        colored
+            .send(TtySize {
+                columns: 100,
+                rows: 30,
+            })
+            .await?;
+        assert_eq!(colored.send(WaitStatus).await??, ExitStatus::Exited(0));
+        let piped = commands.get("piped").unwrap();
+        assert_eq!(piped.send(WaitStatus).await??, ExitStatus::Exited(0));
+        Ok(())
+    });
+
+    let messages = MESSAGES.lock().unwrap();
+    let output = |task: &str| {
+        messages
+            .iter()
+            .filter(|(panel, message)| {
+                panel == task && !message.starts_with("started") && !message.starts_with("exited")
+            })
+            .map(|(_, message)| message.as_str())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        output("colored"),
+        vec!["terminal", "30 100"],
+        "{messages:?}"
+    );
+    assert_eq!(output("piped"), vec!["pipe"], "{messages:?}");
+}
+
+#[test]
+fn gives_dependents_how_their_upstream_ended() {
+    static MESSAGES: std::sync::Mutex<Vec<(String, String)>> = std::sync::Mutex::new(Vec::new());
+
+    within_system(async move {
+        let config = config_from_str(
+            r#"
+            build:
+                command: sleep 0.2; exit 3
+            report:
+                command: echo "status $WHIZ_DEP_build_STATUS in $WHIZ_DEP_build_DURATION_MS"
+                depends_on: build
+            "#,
+        )?;
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                MESSAGES.lock().unwrap().push((msg.panel_name.clone(), msg.message.clone()));
+                Some(())
+            },
+            _msg: RegisterPanel => Some(()),
+            _msg: StateChanged => Some(()),
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
+        });
+
+        let commands = CommandActorsBuilder::new(config, console, watcher)
+            .build()
+            .await?;
+        let status = commands.get("report").unwrap().send(WaitStatus).await??;
+        assert_eq!(status, ExitStatus::Exited(0));
+        Ok(())
+    });
+
+    let messages = MESSAGES.lock().unwrap();
+    let line = messages
+        .iter()
+        .find_map(|(panel, message)| {
+            (panel == "report")
+                .then(|| message.strip_prefix("status 3 in "))
+                .flatten()
+        })
+        .unwrap_or_else(|| panic!("{messages:?}"));
+    let duration: u64 = line.parse().unwrap();
+    assert!((200..5000).contains(&duration), "{duration}");
+}
+
+#[test]
+fn waits_kill_timeout_before_killing() {
+    static MESSAGES: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+    within_system(async move {
+        let config = config_from_str(
+            r#"
+            db:
+                # takes its time to shut down
+                command: trap 'sleep 1; echo flushed; exit 0' TERM; echo started; sleep 30 & wait
+                kill_timeout: 3s
+            "#,
+        )?;
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                MESSAGES.lock().unwrap().push(msg.message.clone());
+                Some(())
+            },
+            _msg: RegisterPanel => Some(()),
+            _msg: StateChanged => Some(()),
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
+        });
+
+        let commands = CommandActorsBuilder::new(config, console, watcher)
+            .build()
+            .await?;
+        let seen = |message: &str| MESSAGES.lock().unwrap().iter().any(|m| m == message);
+        let wait_for = |message: &'static str| async move {
+            for _ in 0..100 {
+                if seen(message) {
+                    return true;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            }
+            false
+        };
+
+        assert!(wait_for("started").await);
+        // restarting stops the running child first
+        commands.get("db").unwrap().send(Reload::Manual).await?;
+        assert!(wait_for("flushed").await, "{:?}", MESSAGES.lock().unwrap());
+        commands.get("db").unwrap().send(PoisonPill).await?;
+        Ok(())
+    });
+}
+
+#[test]
+fn keeps_other_tasks_running_during_stop_grace() {
+    static TICKS: std::sync::Mutex<Vec<std::time::Instant>> = std::sync::Mutex::new(Vec::new());
+    static MESSAGES: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+    within_system(async move {
+        let config = config_from_str(
+            r#"
+            stuck:
+                command: trap '' TERM; echo stuck started; sleep 30 & wait
+                stop_grace: 2s
+            ticker:
+                command: while true; do echo tick; sleep 0.1; done
+            "#,
+        )?;
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                if msg.panel_name == "ticker" {
+                    TICKS.lock().unwrap().push(std::time::Instant::now());
+                }
+                MESSAGES.lock().unwrap().push(msg.message.clone());
+                Some(())
+            },
+            _msg: RegisterPanel => Some(()),
+            _msg: StateChanged => Some(()),
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
+        });
+
+        let commands = CommandActorsBuilder::new(config, console, watcher)
+            .verbose(true)
+            .build()
+            .await?;
+        let seen = |message: &str| MESSAGES.lock().unwrap().iter().any(|m| m == message);
+        let wait_for = |message: &'static str| async move {
+            for _ in 0..100 {
+                if seen(message) {
+                    return true;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            }
+            false
+        };
+
+        assert!(wait_for("stuck started").await);
+        assert!(wait_for("tick").await);
+        let stopping = std::time::Instant::now();
+        commands.get("stuck").unwrap().send(Reload::Manual).await?;
+        assert!(wait_for("STOP: still running after 2000ms, sent SIGKILL").await);
+
+        // the ticker printed all along the grace period of the stuck task
+        let ticks = TICKS.lock().unwrap().clone();
+        assert!(ticks.iter().filter(|tick| **tick >= stopping).count() >= 10);
+        let longest = ticks.windows(2).map(|w| w[1] - w[0]).max().unwrap();
+        assert!(longest < std::time::Duration::from_secs(1), "{longest:?}");
+        for task in ["stuck", "ticker"] {
+            commands.get(task).unwrap().send(PoisonPill).await?;
+        }
+        Ok(())
+    });
+}
+
+#[test]
+fn sends_the_stop_signal_then_kills() {
+    static MESSAGES: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+    within_system(async move {
+        let config = config_from_str(
+            r#"
+            api:
+                # only shuts down cleanly on Ctrl-C
+                command: trap 'echo interrupted; exit 0' INT; echo api started; sleep 30 & wait
+                stop_signal: SIGINT
+                stop_grace: 3s
+            stuck:
+                command: trap '' TERM; echo stuck started; sleep 30 & wait
+                stop_grace: 200ms
+            "#,
+        )?;
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                MESSAGES.lock().unwrap().push(msg.message.clone());
+                Some(())
+            },
+            _msg: RegisterPanel => Some(()),
+            _msg: StateChanged => Some(()),
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
+        });
+
+        let commands = CommandActorsBuilder::new(config, console, watcher)
+            .verbose(true)
+            .build()
+            .await?;
+        let seen = |message: &str| MESSAGES.lock().unwrap().iter().any(|m| m == message);
+        let wait_for = |message: &'static str| async move {
+            for _ in 0..100 {
+                if seen(message) {
+                    return true;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            }
+            false
+        };
+
+        assert!(wait_for("api started").await);
+        assert!(wait_for("stuck started").await);
+        for task in ["api", "stuck"] {
+            commands.get(task).unwrap().send(Reload::Manual).await?;
+        }
+        assert!(
+            wait_for("interrupted").await,
+            "{:?}",
+            MESSAGES.lock().unwrap()
+        );
+        assert!(seen("STOP: sent SIGINT"));
+        assert!(seen("STOP: sent SIGTERM"));
+        assert!(wait_for("STOP: still running after 200ms, sent SIGKILL").await);
+        // api exited within its grace period
+        assert_eq!(
+            MESSAGES
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|m| m.ends_with("sent SIGKILL"))
+                .count(),
+            1
+        );
+        for task in ["api", "stuck"] {
+            commands.get(task).unwrap().send(PoisonPill).await?;
+        }
+        Ok(())
+    });
+}
+
+#[test]
+fn trigger_task_starts_dependents() {
+    within_system(async move {
+        let config = config_from_str(
+            r#"
+            sources:
+                watch: "src/**"
+
+            build:
+                command: ls
+                depends_on: sources
+            "#,
+        )?;
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                println!("---{:?}", msg.message);
+                Some(())
+            },
+            _msg: RegisterPanel => Some(()),
+            _msg: TermEvent => Some(()),
+            _msg: StateChanged => Some(()),
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
+        });
+
+        let commands = CommandActorsBuilder::new(config, console, watcher)
+            .build()
+            .await?;
+
+        let status = commands.get("sources").unwrap().send(WaitStatus).await??;
+        assert_eq!(status, ExitStatus::Exited(0));
+
+        let status = commands.get("build").unwrap().send(WaitStatus).await??;
+        assert_eq!(status, ExitStatus::Exited(0));
+
+        Ok(())
+    });
+}
+
+#[test]
+fn run_filter_with_pipes() {
+    static PANELS: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+    within_system(async move {
+        let raw: RawConfig = r#"
+            db:
+                command: echo db
+                watch: "*"
+
+            web:
+                command: echo "[db] connected"
+                watch: "*"
+                pipe:
+                    '^\[db\]': whiz://web_db
+
+            api:
+                command: echo api
+                watch: "*"
+                depends_on: db
+            "#
+        .parse()?;
+        let mut config = ConfigInner::from_raw(raw, env::current_dir()?)?;
+        config.filter_jobs(&["web".to_string()])?;
+        let config = Arc::new(config);
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                println!("---{:?}", msg.message);
+                Some(())
+            },
+            msg: RegisterPanel => {
+                assert!(msg.addr.as_ref().unwrap().connected());
+                PANELS.lock().unwrap().push(msg.name.clone());
+                Some(())
+            },
+            _msg: TermEvent => Some(()),
+            _msg: StateChanged => Some(()),
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            msg: WatchGlob => {
+                assert!(msg.command.connected());
+                Some(())
+            },
+            _msg: ReloadAllOn => Some(()),
+        });
+
+        let commands = CommandActorsBuilder::new(config, console, watcher)
+            .build()
+            .await?;
+        assert_eq!(Vec::from_iter(commands.keys()), vec!["web"]);
+
+        commands.get("web").unwrap().send(WaitStatus).await??;
+        // leave time for the piped output to be dispatched
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        let mut panels = PANELS.lock().unwrap().clone();
+        panels.dedup();
+        assert_eq!(panels, vec!["web", "web_db"]);
+
+        Ok(())
+    });
+}
+
+#[test]
+fn no_deps_runs_named_task_only() {
+    static OUTPUTS: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+    within_system(async move {
+        let raw: RawConfig = r#"
+            db:
+                command: echo db
+
+            api:
+                command: echo api
+                depends_on: db
+            "#
+        .parse()?;
+        let mut config = ConfigInner::from_raw(raw, env::current_dir()?)?;
+        config.select_jobs(&["api".to_string()])?;
+        let config = Arc::new(config);
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                OUTPUTS.lock().unwrap().push(msg.message.clone());
+                Some(())
+            },
+            _msg: RegisterPanel => Some(()),
+            _msg: StateChanged => Some(()),
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
+        });
+
+        let commands = CommandActorsBuilder::new(config, console, watcher)
+            .build()
+            .await?;
+        assert_eq!(Vec::from_iter(commands.keys()), vec!["api"]);
+
+        // starts without waiting on db
+        let status = commands.get("api").unwrap().send(WaitStatus).await??;
+        assert_eq!(status, ExitStatus::Exited(0));
+        // leave time for the output to be dispatched
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        Ok(())
+    });
+
+    let outputs = OUTPUTS.lock().unwrap();
+    assert!(outputs.contains(&"api".to_string()), "{outputs:?}");
+    assert!(!outputs.contains(&"db".to_string()), "{outputs:?}");
+}
+
+#[test]
+fn retries_failed_task_before_dependents() {
+    static EVENTS: std::sync::Mutex<Vec<(String, String)>> = std::sync::Mutex::new(Vec::new());
+
+    let dir = env::temp_dir().join(format!("whiz-retries-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    within_system({
+        let dir = dir.clone();
+        async move {
+            let raw: RawConfig = r#"
+            flaky:
+                command: n=$(($(cat count 2>/dev/null || echo 0) + 1)); echo $n > count; [ $n -ge 3 ]
+                retries: 3
+
+            after:
+                command: echo after
+                depends_on: flaky
+            "#
+            .parse()?;
+            let config = Arc::new(ConfigInner::from_raw(raw, dir)?);
+
+            let console = mock_actor!(ConsoleActor, {
+                msg: Output => {
+                    let event = msg.message.split(' ').take(2).collect::<Vec<_>>().join(" ");
+                    EVENTS.lock().unwrap().push((msg.panel_name.clone(), event));
+                    Some(())
+                },
+                _msg: RegisterPanel => Some(()),
+                _msg: StateChanged => Some(()),
+            });
+
+            let watcher = mock_actor!(WatcherActor, {
+                _msg: WatchGlob => Some(()),
+                _msg: ReloadAllOn => Some(()),
+            });
+
+            let commands = CommandActorsBuilder::new(config, console, watcher)
+                .build()
+                .await?;
+            let status = commands.get("flaky").unwrap().send(WaitStatus).await??;
+            assert_eq!(status, ExitStatus::Exited(0));
+            let status = commands.get("after").unwrap().send(WaitStatus).await??;
+            assert_eq!(status, ExitStatus::Exited(0));
+            // leave time for the output to be dispatched
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            Ok(())
+        }
+    });
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let events = EVENTS.lock().unwrap();
+    let flaky = events
+        .iter()
+        .filter(|(panel, _)| panel == "flaky")
+        .map(|(_, event)| event.as_str())
+        .filter(|event| !event.starts_with("started"))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        flaky,
+        vec!["exited 1", "RETRY 1/3", "exited 1", "RETRY 2/3", "exited 0"],
+        "{events:?}"
+    );
+    // the dependent only runs once its upstream succeeded
+    let started_after = events
+        .iter()
+        .position(|(panel, event)| panel == "after" && event.starts_with("started"));
+    let last_flaky = events.iter().rposition(|(panel, _)| panel == "flaky");
+    assert!(started_after > last_flaky, "{events:?}");
+}
+
+#[test]
+fn does_not_retry_runs_after_a_change() {
+    static EVENTS: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+    let dir = env::temp_dir().join(format!("whiz-retries-watch-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    within_system({
+        let dir = dir.clone();
+        async move {
+            let raw: RawConfig = r#"
+            test:
+                command: n=$(($(cat count 2>/dev/null || echo 0) + 1)); echo $n > count; [ $n -ne 2 ]
+                retry: {count: 2, backoff_ms: 100}
+            "#
+            .parse()?;
+            let config = Arc::new(ConfigInner::from_raw(raw, dir)?);
+
+            let console = mock_actor!(ConsoleActor, {
+                msg: Output => {
+                    if !msg.message.starts_with("started") {
+                        EVENTS.lock().unwrap().push(msg.message.clone());
+                    }
+                    Some(())
+                },
+                _msg: RegisterPanel => Some(()),
+                _msg: StateChanged => Some(()),
+            });
+
+            let watcher = mock_actor!(WatcherActor, {
+                _msg: WatchGlob => Some(()),
+                _msg: ReloadAllOn => Some(()),
+            });
+
+            let commands = CommandActorsBuilder::new(config, console, watcher)
+                .build()
+                .await?;
+            let test = commands.get("test").unwrap();
+            assert_eq!(test.send(WaitStatus).await??, ExitStatus::Exited(0));
+
+            // the run after the change fails, and is left failed
+            test.send(Reload::Watch("src/lib.rs".to_string())).await?;
+            for _ in 0..100 {
+                let failed = EVENTS
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .any(|event| event.starts_with("exited 1"));
+                if failed {
+                    break;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            }
+            // a retry would have come by now
+            tokio::time::sleep(std::time::Duration::from_millis(400)).await;
+            Ok(())
+        }
+    });
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let events = EVENTS.lock().unwrap();
+    let events = events
+        .iter()
+        .filter(|event| !event.starts_with("RELOAD"))
+        .map(|event| event.split(" in ").next().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(events, vec!["exited 0", "exited 1"], "{events:?}");
+}
+
+#[test]
+fn restarts_crashed_task_on_failure() {
+    static EVENTS: std::sync::Mutex<Vec<(String, String)>> = std::sync::Mutex::new(Vec::new());
+
+    let dir = env::temp_dir().join(format!("whiz-restart-on-failure-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    within_system({
+        let dir = dir.clone();
+        async move {
+            let raw: RawConfig = r#"
+            server:
+                command: n=$(($(cat count 2>/dev/null || echo 0) + 1)); echo $n > count; [ $n -ge 2 ]
+                restart: on-failure
+            "#
+            .parse()?;
+            let config = Arc::new(ConfigInner::from_raw(raw, dir)?);
+
+            let console = mock_actor!(ConsoleActor, {
+                msg: Output => {
+                    let event = msg.message.split(' ').take(3).collect::<Vec<_>>().join(" ");
+                    EVENTS.lock().unwrap().push((msg.panel_name.clone(), event));
+                    Some(())
+                },
+                _msg: RegisterPanel => Some(()),
+                _msg: StateChanged => Some(()),
+            });
+
+            let watcher = mock_actor!(WatcherActor, {
+                _msg: WatchGlob => Some(()),
+                _msg: ReloadAllOn => Some(()),
+            });
+
+            let _commands = CommandActorsBuilder::new(config, console, watcher)
+                .build()
+                .await?;
+            // the status is the one of the first run, so wait for the second
+            for _ in 0..100 {
+                let done = EVENTS
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .any(|(_, event)| event.starts_with("exited 0"));
+                if done {
+                    break;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            }
+            // a success is not restarted
+            tokio::time::sleep(std::time::Duration::from_millis(1200)).await;
+            Ok(())
+        }
+    });
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let events = EVENTS.lock().unwrap();
+    let server = events
+        .iter()
+        .map(|(_, event)| event.as_str())
+        .filter(|event| !event.starts_with("started"))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        server,
+        vec!["exited 1 in", "restarting (attempt 1)", "exited 0 in"],
+        "{events:?}"
+    );
+}
+
+#[test]
+fn parses_restart_policies() {
+    let raw: RawConfig = r#"
+    a:
+        command: echo a
+    b:
+        command: echo b
+        restart: always
+    c:
+        command: echo c
+        restart: on-failure
+    "#
+    .parse()
+    .unwrap();
+    let policy = |name: &str| raw.ops.get(name).unwrap().restart;
+    assert_eq!(policy("a"), RestartPolicy::Never);
+    assert_eq!(policy("b"), RestartPolicy::Always);
+    assert_eq!(policy("c"), RestartPolicy::OnFailure);
+    assert!(RestartPolicy::Always.restarts(&ExitStatus::Exited(0)));
+    assert!(!RestartPolicy::OnFailure.restarts(&ExitStatus::Exited(0)));
+    assert!(RestartPolicy::OnFailure.restarts(&ExitStatus::Signaled(9)));
+    assert!("a:\n  command: a\n  restart: sometimes"
+        .parse::<RawConfig>()
+        .is_err());
+}
+
+#[test]
+fn names_the_task_failing_to_build() {
+    within_system(async move {
+        let raw: RawConfig = r#"
+            api:
+                command: echo $A
+                env:
+                    A: $B
+                    B: $A
+            "#
+        .parse()?;
+        let config = Arc::new(ConfigInner::from_raw(raw, env::current_dir()?)?);
+
+        let console = mock_actor!(ConsoleActor, {
+            _msg: RegisterPanel => Some(()),
+        });
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
+        });
+
+        let err = CommandActorsBuilder::new(config, console, watcher)
+            .build()
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("task api: cycle detected in env references"),
+            "{err}"
+        );
+        Ok(())
+    });
+}
+
+#[test]
+fn max_parallel_runs_tasks_one_at_a_time() {
+    static EVENTS: std::sync::Mutex<Vec<(String, String)>> = std::sync::Mutex::new(Vec::new());
+    static QUEUED: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+    within_system(async move {
+        let raw: RawConfig = r#"
+            a:
+                command: sleep 0.2
+
+            b:
+                command: sleep 0.2
+                depends_on: a
+
+            c:
+                command: sleep 0.2
+                depends_on: a
+
+            d:
+                command: sleep 0.2
+                depends_on: [b, c]
+
+            x:
+                command: sleep 0.2
+            "#
+        .parse()?;
+        let config = Arc::new(ConfigInner::from_raw(raw, env::current_dir()?)?);
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                let event = msg.message.split(' ').next().unwrap().to_string();
+                if event == "started" || event == "exited" {
+                    EVENTS.lock().unwrap().push((msg.panel_name.clone(), event));
+                }
+                Some(())
+            },
+            _msg: RegisterPanel => Some(()),
+            msg: StateChanged => {
+                if msg.state == TaskState::Queued {
+                    QUEUED.lock().unwrap().push(msg.panel_name.clone());
+                }
+                Some(())
+            },
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
+        });
+
+        let commands = CommandActorsBuilder::new(config, console, watcher)
+            .max_parallel(Some(1))
+            .build()
+            .await?;
+        for command in commands.values() {
+            let status = command.send(WaitStatus).await??;
+            assert_eq!(status, ExitStatus::Exited(0));
+        }
+        // leave time for the output to be dispatched
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        Ok(())
+    });
+
+    let events = EVENTS.lock().unwrap();
+    assert_eq!(events.len(), 10, "{events:?}");
+    // each task exits before the next one starts
+    for pair in events.chunks(2) {
+        assert_eq!(pair[0].0, pair[1].0, "{events:?}");
+        assert_eq!(
+            (pair[0].1.as_str(), pair[1].1.as_str()),
+            ("started", "exited")
+        );
+    }
+    let order = events
+        .iter()
+        .step_by(2)
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>();
+    let position = |name| order.iter().position(|e| *e == name).unwrap();
+    assert!(position("a") < position("b"), "{order:?}");
+    assert!(position("a") < position("c"), "{order:?}");
+    assert!(position("b") < position("d"), "{order:?}");
+    assert!(position("c") < position("d"), "{order:?}");
+    // one of a and x, then one of b and c, waited for the other
+    let queued = QUEUED.lock().unwrap();
+    assert!(queued.len() >= 2, "{queued:?}");
+}
+
+#[test]
+fn max_parallel_holds_for_manual_reloads() {
+    static MESSAGES: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+    within_system(async move {
+        let config = config_from_str(
+            r#"
+            server:
+                command: echo server started; sleep 30
+            # depending on the server, which then goes first
+            client:
+                command: echo client
+                depends_on: server
+            lint:
+                command: echo lint
+            "#,
+        )?;
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                MESSAGES.lock().unwrap().push(msg.message.clone());
+                Some(())
+            },
+            _msg: RegisterPanel => Some(()),
+            _msg: StateChanged => Some(()),
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
+        });
+
+        let commands = CommandActorsBuilder::new(config, console, watcher)
+            .max_parallel(Some(1))
+            .build()
+            .await?;
+        let count = |message: &str| {
+            MESSAGES
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|m| *m == message)
+                .count()
+        };
+        let wait_for = |message: &'static str, times: usize| async move {
+            for _ in 0..40 {
+                if count(message) == times {
+                    return true;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            }
+            false
+        };
+
+        assert!(wait_for("server started", 1).await);
+        // the reload waits for the slot of the server as well
+        let runs = count("lint");
+        commands.get("lint").unwrap().send(Reload::Manual).await?;
+        assert!(
+            !wait_for("lint", runs + 1).await,
+            "{:?}",
+            MESSAGES.lock().unwrap()
+        );
+        commands.get("server").unwrap().send(PoisonPill).await?;
+        assert!(
+            wait_for("lint", runs + 1).await,
+            "{:?}",
+            MESSAGES.lock().unwrap()
+        );
+        for task in ["client", "lint"] {
+            commands.get(task).unwrap().send(PoisonPill).await?;
+        }
+        Ok(())
+    });
+}
+
+#[test]
+fn test_grim_reaper() {
+    let system = System::with_tokio_rt(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .max_blocking_threads(1)
+            .enable_all()
+            .build()
+            .unwrap()
+    });
+
+    let fut = async move {
+        let config_raw = r#"
+test:
+    entrypoint: 'python3 -c'
+    command: 'print("hello whiz")'
+long_test_dep:
+    entrypoint: 'python3 -c'
+    command: 'import time; time.sleep(1); print("wake up")'
+long_test:
+    entrypoint: 'python3 -c'
+    command: 'print("my que to enter")'
+    depends_on:
+        - long_test_dep"#;
+        let config: Config = config_from_str(config_raw)?;
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                println!("---{:?}", msg.message);
+                Some(())
+            },
+            _msg: StateChanged => Some(()),
+            _msg: RegisterPanel => Some(()),
+            _msg: TermEvent => Some(()),
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
+        });
+
+        let commands = CommandActorsBuilder::new(config, console, watcher)
+            .build()
+            .await?;
+
+        GrimReaperActor::start_new(commands).await?;
+        Ok(())
+    };
+
+    Arbiter::current().spawn(async { fut.await.unwrap() });
+
+    let timer = std::time::SystemTime::now();
+    assert_eq!(0, system.run_with_code().unwrap());
+    let elapsed = timer.elapsed().unwrap();
+    assert!(
+        elapsed.as_millis() >= 1000,
+        "test took less than a second: {elapsed:?}"
+    );
+}
+
+#[test]
+fn primary_task_ends_session() {
+    let system = System::with_tokio_rt(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .max_blocking_threads(1)
+            .enable_all()
+            .build()
+            .unwrap()
+    });
+
+    let fut = async move {
+        let config_raw = r#"
+app:
+    entrypoint: 'python3 -c'
+    command: 'import sys; sys.exit(3)'
+    primary: true
+db:
+    entrypoint: 'python3 -c'
+    command: 'import time; time.sleep(30)'"#;
+        let config: Config = config_from_str(config_raw)?;
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                println!("---{:?}", msg.message);
+                Some(())
+            },
+            _msg: StateChanged => Some(()),
+            _msg: RegisterPanel => Some(()),
+            _msg: TermEvent => Some(()),
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
+        });
+
+        CommandActorsBuilder::new(config, console, watcher)
+            .build()
+            .await?;
+        Ok(())
+    };
+
+    Arbiter::current().spawn(async { fut.await.unwrap() });
+
+    let timer = std::time::SystemTime::now();
+    assert_eq!(3, system.run_with_code().unwrap());
+    let elapsed = timer.elapsed().unwrap();
+    assert!(
+        elapsed.as_secs() < 10,
+        "session outlived its primary task: {elapsed:?}"
+    );
+}
+
+#[test]
+fn grim_reaper_waits_for_every_task() {
+    static FINISHED: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+    let system = System::with_tokio_rt(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .max_blocking_threads(1)
+            .enable_all()
+            .build()
+            .unwrap()
+    });
+
+    let fut = async move {
+        let config_raw = r#"
+instant:
+    entrypoint: 'python3 -c'
+    command: 'print("instant")'
+instant_dep:
+    entrypoint: 'python3 -c'
+    command: 'print("instant dependent")'
+    depends_on: instant
+delayed:
+    entrypoint: 'python3 -c'
+    command: 'import time; time.sleep(1)'
+delayed_dep:
+    entrypoint: 'python3 -c'
+    command: 'print("delayed dependent")'
+    depends_on:
+        - delayed
+        - instant"#;
+        let config: Config = config_from_str(config_raw)?;
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                println!("---{:?}", msg.message);
+                Some(())
+            },
+            msg: StateChanged => {
+                if let TaskState::Exited(_) = msg.state {
+                    FINISHED.lock().unwrap().push(msg.panel_name.clone());
+                }
+                Some(())
+            },
+            _msg: RegisterPanel => Some(()),
+            _msg: TermEvent => Some(()),
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
+        });
+
+        let commands = CommandActorsBuilder::new(config, console, watcher)
+            .build()
+            .await?;
+
+        GrimReaperActor::start_with_settle(commands, std::time::Duration::from_millis(200)).await?;
+        Ok(())
+    };
+
+    Arbiter::current().spawn(async { fut.await.unwrap() });
+
+    assert_eq!(0, system.run_with_code().unwrap());
+
+    let mut finished = FINISHED.lock().unwrap().clone();
+    finished.sort();
+    assert_eq!(
+        finished,
+        vec!["delayed", "delayed_dep", "instant", "instant_dep"]
+    );
+}
+
+#[test]
+fn grim_reaper_waits_for_tasks_starting_again_while_settling() {
+    static OUTPUTS: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+    static EXITS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+    let system = System::with_tokio_rt(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .max_blocking_threads(1)
+            .enable_all()
+            .build()
+            .unwrap()
+    });
+
+    let fut = async move {
+        let config = config_from_str(
+            r#"
+            codegen:
+                command: sleep 0.3 && echo generated
+            "#,
+        )?;
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                OUTPUTS.lock().unwrap().push(msg.message.clone());
+                Some(())
+            },
+            msg: StateChanged => {
+                if let TaskState::Exited(_) = msg.state {
+                    EXITS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                }
+                Some(())
+            },
+            _msg: RegisterPanel => Some(()),
+            _msg: TermEvent => Some(()),
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
+        });
+
+        let commands = CommandActorsBuilder::new(config, console, watcher)
+            .build()
+            .await?;
+        let codegen = commands.get("codegen").unwrap().clone();
+        GrimReaperActor::start_with_settle(commands, std::time::Duration::from_millis(1000))
+            .await?;
+
+        // once it answered, it runs again before the delay is over
+        while EXITS.load(std::sync::atomic::Ordering::SeqCst) == 0 {
+            actix::clock::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        codegen.send(Reload::Manual).await?;
+        Ok(())
+    };
+
+    Arbiter::current().spawn(async { fut.await.unwrap() });
+    assert_eq!(0, system.run_with_code().unwrap());
+
+    // the reaper waited for the second run to end
+    let outputs = OUTPUTS.lock().unwrap();
+    let generated = outputs.iter().filter(|output| *output == "generated");
+    assert_eq!(generated.count(), 2, "{outputs:?}");
+    assert_eq!(EXITS.load(std::sync::atomic::Ordering::SeqCst), 2);
+}
+
+#[test]
+fn reload_with_pending_death_invite() {
+    let system = System::with_tokio_rt(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .max_blocking_threads(1)
+            .enable_all()
+            .build()
+            .unwrap()
+    });
+
+    let fut = async move {
+        let config_raw = r#"
+slow:
+    entrypoint: 'python3 -c'
+    command: 'import time; time.sleep(0.5)'"#;
+        let config: Config = config_from_str(config_raw)?;
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                println!("---{:?}", msg.message);
+                Some(())
+            },
+            _msg: StateChanged => Some(()),
+            _msg: RegisterPanel => Some(()),
+            _msg: TermEvent => Some(()),
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
+        });
+
+        let commands = CommandActorsBuilder::new(config, console, watcher)
+            .build()
+            .await?;
+        let slow = commands.get("slow").unwrap().clone();
+
+        GrimReaperActor::start_new(commands).await?;
+        // the invite is pending on the first child, reload a new one
+        slow.send(Reload::Manual).await?;
+        Ok(())
+    };
+
+    Arbiter::current().spawn(async { fut.await.unwrap() });
+
+    let timer = std::time::SystemTime::now();
+    assert_eq!(0, system.run_with_code().unwrap());
+    let elapsed = timer.elapsed().unwrap();
+    assert!(
+        elapsed.as_millis() >= 500,
+        "reaper did not wait for the reloaded child: {elapsed:?}"
+    );
+}
+
+#[test]
+fn runs_once_tasks_only_once() {
+    static OUTPUTS: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+    static WATCHES: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+    let system = System::with_tokio_rt(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .max_blocking_threads(1)
+            .enable_all()
+            .build()
+            .unwrap()
+    });
+
+    let fut = async move {
+        let config = config_from_str(
+            r#"
+            setup:
+                command: echo setup
+            codegen:
+                command: echo codegen
+                depends_on: setup
+                watch: src
+                oneshot: true
+            api:
+                command: echo api
+                depends_on: codegen
+            "#,
+        )?;
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                OUTPUTS.lock().unwrap().push(msg.message.clone());
+                Some(())
+            },
+            _msg: StateChanged => Some(()),
+            _msg: RegisterPanel => Some(()),
+            _msg: TermEvent => Some(()),
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => {
+                WATCHES.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                Some(())
+            },
+            _msg: ReloadAllOn => Some(()),
+        });
+
+        let commands = CommandActorsBuilder::new(config, console, watcher)
+            .build()
+            .await?;
+        let api = commands.get("api").unwrap().clone();
+        assert_eq!(api.send(WaitStatus).await??, ExitStatus::Exited(0));
+
+        // neither a change nor its upstream runs it again
+        let codegen = commands.get("codegen").unwrap();
+        codegen
+            .send(Reload::Watch("src/main.rs".to_string()))
+            .await?;
+        commands.get("setup").unwrap().send(Reload::Manual).await?;
+        actix::clock::sleep(std::time::Duration::from_millis(300)).await;
+
+        GrimReaperActor::start_new(commands).await?;
+        Ok(())
+    };
+
+    Arbiter::current().spawn(async { fut.await.unwrap() });
+    assert_eq!(0, system.run_with_code().unwrap());
+
+    let outputs = OUTPUTS.lock().unwrap();
+    let count = |line: &str| outputs.iter().filter(|output| *output == line).count();
+    assert_eq!(count("setup"), 2, "{outputs:?}");
+    assert_eq!(count("codegen"), 1, "{outputs:?}");
+    assert_eq!(count("api"), 1, "{outputs:?}");
+    assert_eq!(WATCHES.load(std::sync::atomic::Ordering::Relaxed), 0);
+}
+
+#[test]
+fn debounces_watched_changes() {
+    static MESSAGES: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+    within_system(async move {
+        let config = config_from_str(
+            r#"
+            build:
+                command: echo built
+                debounce: 300ms
+            "#,
+        )?;
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                MESSAGES.lock().unwrap().push(msg.message.clone());
+                Some(())
+            },
+            _msg: RegisterPanel => Some(()),
+            _msg: StateChanged => Some(()),
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
+        });
+
+        let commands = CommandActorsBuilder::new(config, console, watcher)
+            .build()
+            .await?;
+        let build = commands.get("build").unwrap();
+        assert_eq!(build.send(WaitStatus).await??, ExitStatus::Exited(0));
+
+        for files in ["src/a.rs", "src/b.rs, src/a.rs", "src/c.rs"] {
+            build.send(Reload::Watch(files.to_string())).await?;
+            actix::clock::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        actix::clock::sleep(std::time::Duration::from_millis(500)).await;
+
+        // a manual reload does not wait, nor runs again for the changes
+        build.send(Reload::Watch("src/d.rs".to_string())).await?;
+        build.send(Reload::Manual).await?;
+        actix::clock::sleep(std::time::Duration::from_millis(500)).await;
+        Ok(())
+    });
+
+    let messages = MESSAGES.lock().unwrap();
+    let reloads = messages
+        .iter()
+        .filter(|message| message.starts_with("RELOAD"))
+        .map(|message| message.trim_end())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        reloads,
+        vec![
+            "RELOAD: file changed: src/a.rs, src/b.rs, src/c.rs",
+            "RELOAD: manual",
+        ],
+        "{messages:?}"
+    );
+    let runs = messages
+        .iter()
+        .filter(|message| *message == "built")
+        .count();
+    assert_eq!(runs, 3, "{messages:?}");
+}
+
+#[test]
+fn warns_about_unmatched_watch_globs() {
+    static WARNINGS: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+    within_system(async move {
+        let config = config_from_str(
+            r#"
+            api:
+                command: ls
+                watch:
+                    - src/**/*.rs
+                    - scr/**/*.rs
+            "#,
+        )?;
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                if msg.message.starts_with("WATCH:") {
+                    WARNINGS.lock().unwrap().push(msg.message.clone());
+                }
+                Some(())
+            },
+            _msg: RegisterPanel => Some(()),
+            _msg: StateChanged => Some(()),
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
+        });
+
+        let _commands = CommandActorsBuilder::new(config, console.clone(), watcher)
+            .build()
+            .await?;
+
+        while WARNINGS.lock().unwrap().is_empty() {
+            actix::clock::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        // flushes the warnings sent along the first one
+        console
+            .send(Output::now(
+                "api".to_string(),
+                "done".to_string(),
+                OutputKind::Command,
+            ))
+            .await?;
+        Ok(())
+    });
+
+    let warnings = WARNINGS.lock().unwrap();
+    assert_eq!(warnings.len(), 1, "{warnings:?}");
+    assert!(warnings[0].contains("scr/**/*.rs"));
+}
+
+#[cfg(feature = "watch")]
+#[test]
+fn reload_all_on_reloads_every_task() {
+    static RUNS: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+    static RELOAD_ALL: std::sync::Mutex<Option<ReloadAllOn>> = std::sync::Mutex::new(None);
+
+    let runs = |task: &str| {
+        RUNS.lock()
+            .unwrap()
+            .iter()
+            .filter(|run| run.as_str() == task)
+            .count()
+    };
+
+    within_system(async move {
+        let dir = env::temp_dir().join(format!("whiz-reload-all-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let config = config_from_str(&format!(
+            r#"
+            reload_all_on: {}/*.env
+            db:
+                command: echo db
+            api:
+                command: echo api
+                depends_on: db
+            "#,
+            dir.display()
+        ))?;
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                if msg.kind == OutputKind::Command {
+                    RUNS.lock().unwrap().push(msg.message.clone());
+                }
+                Some(())
+            },
+            _msg: RegisterPanel => Some(()),
+            _msg: StateChanged => Some(()),
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            msg: ReloadAllOn => {
+                *RELOAD_ALL.lock().unwrap() = Some(*msg);
+                Some(())
+            },
+        });
+
+        let _commands = CommandActorsBuilder::new(config, console, watcher)
+            .build()
+            .await?;
+
+        // the commands are wired to a mocked watcher, forward the
+        // registration to a real one
+        let reload_all = loop {
+            if let Some(reload_all) = RELOAD_ALL.lock().unwrap().take() {
+                break reload_all;
+            }
+            actix::clock::sleep(std::time::Duration::from_millis(20)).await;
+        };
+        let watcher = WatcherActor::new(dir.clone().into()).start();
+        watcher.send(reload_all).await?;
+
+        let wait_runs = |count: usize| async move {
+            for _ in 0..250 {
+                if runs("db") >= count && runs("api") >= count {
+                    return;
+                }
+                actix::clock::sleep(std::time::Duration::from_millis(20)).await;
+            }
+        };
+        wait_runs(1).await;
+        std::fs::write(dir.join("shared.env"), "KEY=value")?;
+        wait_runs(2).await;
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    });
+
+    // creating the file may be reported as several events
+    assert!(runs("db") >= 2);
+    assert!(runs("api") >= 2);
+}
+
+#[cfg(feature = "watch")]
+#[test]
+fn touching_reload_files_reloads_tasks() {
+    static RUNS: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+    static RELOAD_ALL: std::sync::Mutex<Option<ReloadAllOn>> = std::sync::Mutex::new(None);
+
+    let runs = |task: &str| {
+        RUNS.lock()
+            .unwrap()
+            .iter()
+            .filter(|run| run.as_str() == task)
+            .count()
+    };
+
+    within_system(async move {
+        let dir = env::temp_dir().join(format!("whiz-reload-file-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join(daemon::DIR))?;
+        let config = config_from_str(
+            r#"
+            db:
+                command: echo db
+            api:
+                command: echo api
+                depends_on: db
+            "#,
+        )?;
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                if msg.kind == OutputKind::Command {
+                    RUNS.lock().unwrap().push(msg.message.clone());
+                }
+                Some(())
+            },
+            _msg: RegisterPanel => Some(()),
+            _msg: StateChanged => Some(()),
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            msg: ReloadAllOn => {
+                *RELOAD_ALL.lock().unwrap() = Some(*msg);
+                Some(())
+            },
+        });
+
+        let _commands = CommandActorsBuilder::new(config, console, watcher)
+            .build()
+            .await?;
+
+        // the commands are wired to a mocked watcher, forward the
+        // registration to a real one
+        let reload_all = loop {
+            if let Some(reload_all) = RELOAD_ALL.lock().unwrap().take() {
+                break reload_all;
+            }
+            actix::clock::sleep(std::time::Duration::from_millis(20)).await;
+        };
+        let watcher = WatcherActor::new(dir.clone().into()).start();
+        watcher.send(reload_all).await?;
+
+        let wait_runs = |db: usize, api: usize| async move {
+            for _ in 0..250 {
+                if runs("db") >= db && runs("api") >= api {
+                    return;
+                }
+                actix::clock::sleep(std::time::Duration::from_millis(20)).await;
+            }
+        };
+        wait_runs(1, 1).await;
+        std::fs::write(dir.join(daemon::DIR).join("reload-api"), "")?;
+        wait_runs(1, 2).await;
+        assert_eq!((runs("db"), runs("api")), (1, 2));
+
+        std::fs::write(dir.join(daemon::DIR).join("reload"), "")?;
+        wait_runs(2, 3).await;
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    });
+
+    // touching a file is reported as a single reload
+    assert_eq!(runs("db"), 2);
+    assert_eq!(runs("api"), 3);
+}
+
+#[cfg(feature = "watch")]
+#[test]
+fn changes_run_other_tasks() {
+    static RUNS: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+    static GLOBS: std::sync::Mutex<Vec<WatchGlob>> = std::sync::Mutex::new(Vec::new());
+    static RELOAD_ALL: std::sync::Mutex<Option<ReloadAllOn>> = std::sync::Mutex::new(None);
+
+    let runs = |task: &str| {
+        RUNS.lock()
+            .unwrap()
+            .iter()
+            .filter(|run| run.as_str() == task)
+            .count()
+    };
+
+    within_system(async move {
+        let dir = env::temp_dir().join(format!("whiz-on-change-run-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("schemas"))?;
+        let config = config_from_str(&format!(
+            r#"
+            schemas:
+                command: echo schemas
+                watch: {}/schemas/*.json
+                on_change_run: client
+            client:
+                command: echo client
+                on_change_run: [docs, tests]
+            docs:
+                command: echo docs
+            tests:
+                command: echo tests
+                watch: {}/schemas/*.json
+            other:
+                command: echo other
+            "#,
+            dir.display(),
+            dir.display()
+        ))?;
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                if msg.kind == OutputKind::Command {
+                    RUNS.lock().unwrap().push(msg.message.clone());
+                }
+                Some(())
+            },
+            _msg: RegisterPanel => Some(()),
+            _msg: StateChanged => Some(()),
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            msg: WatchGlob => {
+                GLOBS.lock().unwrap().push(*msg);
+                Some(())
+            },
+            msg: ReloadAllOn => {
+                *RELOAD_ALL.lock().unwrap() = Some(*msg);
+                Some(())
+            },
+        });
+
+        let _commands = CommandActorsBuilder::new(config, console, watcher)
+            .build()
+            .await?;
+
+        // the commands are wired to a mocked watcher, forward the
+        // registrations to a real one
+        let reload_all = loop {
+            if let Some(reload_all) = RELOAD_ALL.lock().unwrap().take() {
+                break reload_all;
+            }
+            actix::clock::sleep(std::time::Duration::from_millis(20)).await;
+        };
+        let watcher = WatcherActor::new(dir.clone().into()).start();
+        watcher.send(reload_all).await?;
+        let globs = std::mem::take(&mut *GLOBS.lock().unwrap());
+        assert_eq!(globs.len(), 3);
+        for glob in globs {
+            watcher.send(glob).await?;
+        }
+
+        let wait_runs = |count: usize| async move {
+            for _ in 0..250 {
+                if ["schemas", "client", "docs", "tests"]
+                    .iter()
+                    .all(|task| runs(task) >= count)
+                {
+                    return;
+                }
+                actix::clock::sleep(std::time::Duration::from_millis(20)).await;
+            }
+        };
+        wait_runs(1).await;
+        // moving the file in is reported as a single event, unlike writing it
+        let staged = dir.with_extension("json");
+        std::fs::write(&staged, "{}")?;
+        std::fs::rename(&staged, dir.join("schemas").join("user.json"))?;
+        wait_runs(2).await;
+        // the last runs would have come by now
+        actix::clock::sleep(std::time::Duration::from_millis(300)).await;
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    });
+
+    // tests runs once even though it also watches the file
+    let runs = ["schemas", "client", "docs", "tests", "other"].map(runs);
+    assert_eq!(runs, [2, 2, 2, 2, 1]);
+}
+
+#[cfg(feature = "watch")]
+#[test]
+fn dry_watch_prints_the_tasks_changes_would_reload() {
+    static RUNS: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+    static GLOBS: std::sync::Mutex<Vec<WatchGlob>> = std::sync::Mutex::new(Vec::new());
+    static RELOAD_ALL: std::sync::Mutex<Option<ReloadAllOn>> = std::sync::Mutex::new(None);
+
+    #[derive(Clone, Default)]
+    struct Printed(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Printed {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            std::io::Result::Ok(())
+        }
+    }
+
+    let printed = Printed::default();
+    let dir = env::temp_dir().join(format!("whiz-dry-watch-{}", std::process::id()));
+    within_system({
+        let (printed, dir) = (printed.clone(), dir.clone());
+        async move {
+            std::fs::create_dir_all(dir.join("schemas"))?;
+            let config = config_from_str(&format!(
+                r#"
+                schemas:
+                    command: echo schemas
+                    watch: {}/schemas/*.json
+                    on_change_run: client
+                client:
+                    command: echo client
+                docs:
+                    command: echo docs
+                    watch: {}/docs/*.md
+                "#,
+                dir.display(),
+                dir.display()
+            ))?;
+
+            let console = mock_actor!(ConsoleActor, {
+                msg: Output => {
+                    if msg.kind == OutputKind::Command {
+                        RUNS.lock().unwrap().push(msg.message.clone());
+                    }
+                    Some(())
+                },
+                _msg: RegisterPanel => Some(()),
+                _msg: StateChanged => Some(()),
+            });
+
+            let watcher = mock_actor!(WatcherActor, {
+                msg: WatchGlob => {
+                    GLOBS.lock().unwrap().push(*msg);
+                    Some(())
+                },
+                msg: ReloadAllOn => {
+                    *RELOAD_ALL.lock().unwrap() = Some(*msg);
+                    Some(())
+                },
+            });
+
+            let _commands = CommandActorsBuilder::new(config, console, watcher)
+                .dry_watch(true)
+                .build()
+                .await?;
+
+            // the commands are wired to a mocked watcher, forward the
+            // registrations to a real one
+            let reload_all = loop {
+                if let Some(reload_all) = RELOAD_ALL.lock().unwrap().take() {
+                    break reload_all;
+                }
+                actix::clock::sleep(std::time::Duration::from_millis(20)).await;
+            };
+            let watcher = WatcherActor::new(dir.clone().into())
+                .dry_watch(Box::new(printed.clone()))
+                .start();
+            watcher.send(reload_all).await?;
+            let globs = std::mem::take(&mut *GLOBS.lock().unwrap());
+            assert_eq!(globs.len(), 2);
+            for glob in globs {
+                watcher.send(glob).await?;
+            }
+
+            // moving the file in is reported as a single event, unlike writing it
+            let staged = dir.with_extension("json");
+            std::fs::write(&staged, "{}")?;
+            std::fs::rename(&staged, dir.join("schemas").join("user.json"))?;
+            for _ in 0..250 {
+                if !printed.0.lock().unwrap().is_empty() {
+                    break;
+                }
+                actix::clock::sleep(std::time::Duration::from_millis(20)).await;
+            }
+            // the tasks would have run by now
+            actix::clock::sleep(std::time::Duration::from_millis(300)).await;
+            Ok(())
+        }
+    });
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let printed = String::from_utf8(printed.0.lock().unwrap().clone()).unwrap();
+    assert_eq!(
+        printed,
+        format!(
+            "changed {} → would reload [client, schemas]\n",
+            dir.join("schemas").join("user.json").display()
+        )
+    );
+    assert!(RUNS.lock().unwrap().is_empty());
+}
+
+#[cfg(feature = "watch")]
+#[test]
+fn reloads_on_chosen_watch_events() {
+    static RUNS: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+    static GLOBS: std::sync::Mutex<Vec<WatchGlob>> = std::sync::Mutex::new(Vec::new());
+
+    let runs = |task: &str| {
+        RUNS.lock()
+            .unwrap()
+            .iter()
+            .filter(|run| run.as_str() == task)
+            .count()
+    };
+
+    within_system(async move {
+        let dir = env::temp_dir().join(format!("whiz-watch-events-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let config = config_from_str(&format!(
+            r#"
+            created:
+                command: echo created
+                watch: {0}/*.txt
+                watch_events: create
+            modified:
+                command: echo modified
+                watch: {0}/*.txt
+                watch_events: [modify]
+            global:
+                command: echo global
+                watch: {0}/*.txt
+            "#,
+            dir.display(),
+        ))?;
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                if msg.kind == OutputKind::Command {
+                    RUNS.lock().unwrap().push(msg.message.clone());
+                }
+                Some(())
+            },
+            _msg: RegisterPanel => Some(()),
+            _msg: StateChanged => Some(()),
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            msg: WatchGlob => {
+                GLOBS.lock().unwrap().push(*msg);
+                Some(())
+            },
+            _msg: ReloadAllOn => Some(()),
+        });
+
+        let _commands = CommandActorsBuilder::new(config, console, watcher)
+            .build()
+            .await?;
+
+        // the commands are wired to a mocked watcher, forward the
+        // registrations to a real one only reloading on modifications
+        let watcher = WatcherActor::new(dir.clone().into())
+            .events(vec![WatchEventKind::Modify])
+            .start();
+        let globs = loop {
+            if GLOBS.lock().unwrap().len() == 3 {
+                break std::mem::take(&mut *GLOBS.lock().unwrap());
+            }
+            actix::clock::sleep(std::time::Duration::from_millis(20)).await;
+        };
+        for glob in globs {
+            watcher.send(glob).await?;
+        }
+
+        let wait_runs = |task: &'static str, count: usize| async move {
+            for _ in 0..250 {
+                if runs(task) >= count {
+                    return;
+                }
+                actix::clock::sleep(std::time::Duration::from_millis(20)).await;
+            }
+        };
+        for task in ["created", "modified", "global"] {
+            wait_runs(task, 1).await;
+        }
+
+        // creating an empty file is a pure create event
+        std::fs::File::create(dir.join("user.txt"))?;
+        wait_runs("created", 2).await;
+        // the other reloads would have come by now
+        actix::clock::sleep(std::time::Duration::from_millis(300)).await;
+        assert_eq!(runs("modified"), 1);
+        assert_eq!(runs("global"), 1);
+
+        std::fs::write(dir.join("user.txt"), "jane")?;
+        wait_runs("modified", 2).await;
+        wait_runs("global", 2).await;
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    });
+
+    assert_eq!(runs("created"), 2);
+    assert!(runs("modified") >= 2);
+    assert!(runs("global") >= 2);
+}
+
+#[cfg(feature = "watch")]
+#[test]
+fn watches_watch_root_only() {
+    static RUNS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+    static GLOB: std::sync::Mutex<Option<WatchGlob>> = std::sync::Mutex::new(None);
+
+    let runs = || RUNS.load(std::sync::atomic::Ordering::SeqCst);
+
+    within_system(async move {
+        let dir = env::temp_dir().join(format!("whiz-watch-root-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("ci"))?;
+        std::fs::create_dir_all(dir.join("src"))?;
+        let path = dir.join("ci").join("whiz.yaml");
+        std::fs::write(
+            &path,
+            r#"
+            watch_root: ../src
+            build:
+                command: echo build
+                watch:
+                    - "*.rs"
+                    - ../src/*.rs
+            "#,
+        )?;
+        let config = ConfigBuilder::new(path).build()?;
+        assert_eq!(*config.watch_root, *dir.join("src"));
+        assert_eq!(*config.base_dir, *dir.join("ci"));
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                if msg.kind == OutputKind::Command && msg.message == "build" {
+                    RUNS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                }
+                Some(())
+            },
+            _msg: RegisterPanel => Some(()),
+            _msg: StateChanged => Some(()),
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            msg: WatchGlob => {
+                *GLOB.lock().unwrap() = Some(*msg);
+                Some(())
+            },
+            _msg: ReloadAllOn => Some(()),
+        });
+
+        let watch_root = config.watch_root.clone();
+        let _commands = CommandActorsBuilder::new(config, console, watcher)
+            .build()
+            .await?;
+
+        // the commands are wired to a mocked watcher, forward the
+        // registration to a real one
+        let glob = loop {
+            if let Some(glob) = GLOB.lock().unwrap().take() {
+                break glob;
+            }
+            actix::clock::sleep(std::time::Duration::from_millis(20)).await;
+        };
+        let watcher = WatcherActor::new(watch_root).start();
+        watcher.send(glob).await?;
+
+        let wait_runs = |count: usize| async move {
+            for _ in 0..250 {
+                if runs() >= count {
+                    return;
+                }
+                actix::clock::sleep(std::time::Duration::from_millis(20)).await;
+            }
+        };
+        wait_runs(1).await;
+
+        // matches the task globs, but outside of the watch root
+        std::fs::write(dir.join("ci").join("main.rs"), "fn main() {}")?;
+        actix::clock::sleep(std::time::Duration::from_millis(500)).await;
+        assert_eq!(runs(), 1);
+
+        std::fs::write(dir.join("src").join("lib.rs"), "")?;
+        wait_runs(2).await;
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    });
+
+    assert!(runs() >= 2);
+}
+
+#[test]
+fn reloads_changed_config() {
+    static OUTPUTS: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+    static UNREGISTERED: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+    let count = |line: &str| {
+        OUTPUTS
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|output| output.as_str() == line)
+            .count()
+    };
+
+    within_system(async move {
+        let dir = env::temp_dir().join(format!("whiz-reload-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("whiz.yaml");
+        std::fs::write(
+            &path,
+            r#"
+            db:
+                command: echo db-1
+            api:
+                command: echo api-1
+                depends_on: db
+            old:
+                command: echo old-1
+            "#,
+        )?;
+        let builder = ConfigBuilder::new(path.clone());
+        let config = builder.clone().build()?;
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                OUTPUTS.lock().unwrap().push(msg.message.clone());
+                Some(())
+            },
+            _msg: RegisterPanel => Some(()),
+            _msg: StateChanged => Some(()),
+            msg: UnregisterPanel => {
+                UNREGISTERED.lock().unwrap().push(msg.name.clone());
+                Some(())
+            },
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
+        });
+
+        let commands = CommandActorsBuilder::new(config.clone(), console.clone(), watcher.clone())
+            .build()
+            .await?;
+        let old = commands.get("old").unwrap().clone();
+        let reloader =
+            ConfigReloaderActor::new(builder, config, commands, console, watcher).start();
+
+        let wait_for = |lines: &'static [&'static str]| async move {
+            for _ in 0..250 {
+                if lines.iter().all(|line| count(line) > 0) {
+                    return;
+                }
+                actix::clock::sleep(std::time::Duration::from_millis(20)).await;
+            }
+        };
+        wait_for(&["db-1", "api-1", "old-1"]).await;
+
+        std::fs::write(
+            &path,
+            r#"
+            db:
+                command: echo db-1
+            api:
+                command: echo api-2
+                depends_on: db
+            new:
+                command: echo new-1
+                depends_on: db
+            "#,
+        )?;
+        reloader.send(ConfigChanged).await?;
+        wait_for(&["api-2", "new-1"]).await;
+
+        assert!(!old.connected());
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    });
+
+    assert_eq!(count("db-1"), 1, "unchanged task was restarted");
+    assert_eq!(count("api-2"), 1);
+    assert_eq!(count("new-1"), 1);
+    assert_eq!(*UNREGISTERED.lock().unwrap(), vec!["old"]);
+    assert_eq!(
+        count("CONFIG: reloaded, added new; removed old; changed api (command)"),
+        1
+    );
+}
+
+#[test]
+fn restarts_every_task() {
+    static OUTPUTS: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+    static UNREGISTERED: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+    let count = |line: &str| {
+        OUTPUTS
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|output| output.as_str() == line)
+            .count()
+    };
+
+    within_system(async move {
+        let dir = env::temp_dir().join(format!("whiz-restart-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("whiz.yaml");
+        std::fs::write(
+            &path,
+            r#"
+            db:
+                command: echo db-1
+            old:
+                command: echo old-1
+            "#,
+        )?;
+        let builder = ConfigBuilder::new(path.clone());
+        let config = builder.clone().build()?;
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                OUTPUTS.lock().unwrap().push(msg.message.clone());
+                Some(())
+            },
+            _msg: RegisterPanel => Some(()),
+            _msg: StateChanged => Some(()),
+            msg: UnregisterPanel => {
+                UNREGISTERED.lock().unwrap().push(msg.name.clone());
+                Some(())
+            },
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
+        });
+
+        let commands = CommandActorsBuilder::new(config.clone(), console.clone(), watcher.clone())
+            .build()
+            .await?;
+        let db = commands.get("db").unwrap().clone();
+        let reloader =
+            ConfigReloaderActor::new(builder, config, commands, console, watcher).start();
+
+        let wait_for = |line: &'static str, times: usize| async move {
+            for _ in 0..250 {
+                if count(line) >= times {
+                    return;
+                }
+                actix::clock::sleep(std::time::Duration::from_millis(20)).await;
+            }
+        };
+        wait_for("db-1", 1).await;
+
+        std::fs::write(
+            &path,
+            r#"
+            db:
+                command: echo db-1
+            "#,
+        )?;
+        reloader.send(Restart).await?;
+        wait_for("db-1", 2).await;
+
+        assert!(!db.connected());
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    });
+
+    assert_eq!(count("db-1"), 2, "unchanged task was not restarted");
+    assert_eq!(*UNREGISTERED.lock().unwrap(), vec!["old"]);
+    assert_eq!(count("CONFIG: restarted, removed old"), 1);
+}
+
+#[test]
+fn task_states_are_broadcast() {
+    static STATES: std::sync::Mutex<Vec<(String, TaskState)>> = std::sync::Mutex::new(Vec::new());
+
+    within_system(async move {
+        let config = config_from_str(
+            r#"
+            ok:
+                command: ls
+            missing:
+                command: ls
+                workdir: does/not/exist
+            "#,
+        )?;
+
+        let console = mock_actor!(ConsoleActor, {
+            _msg: Output => Some(()),
+            _msg: RegisterPanel => Some(()),
+            msg: StateChanged => {
+                STATES.lock().unwrap().push((msg.panel_name.clone(), msg.state));
+                Some(())
+            },
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
+        });
+
+        let commands = CommandActorsBuilder::new(config, console, watcher)
+            .build()
+            .await?;
+
+        let missing = commands.get("missing").unwrap().send(WaitStatus).await?;
+        assert_eq!(missing?, ExitStatus::Other(1));
+        commands.get("ok").unwrap().send(WaitStatus).await??;
+
+        // the exit is finalized once the output is drained
+        while !STATES
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|(name, state)| name == "ok" && state.is_finished())
+        {
+            actix::clock::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        Ok(())
+    });
+
+    let states = STATES.lock().unwrap();
+    let of = |task: &str| -> Vec<TaskState> {
+        states
+            .iter()
+            .filter(|(name, _)| name == task)
+            .map(|(_, state)| *state)
+            .collect()
+    };
+    assert_eq!(of("missing"), vec![TaskState::Starting, TaskState::Blocked]);
+    assert_eq!(
+        of("ok"),
+        vec![
+            TaskState::Starting,
+            TaskState::Running,
+            TaskState::Stopping,
+            TaskState::Exited(ExitStatus::Exited(0)),
+        ]
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn applies_task_limits() {
+    static LINES: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+    static FINISHED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+    within_system(async move {
+        let config = config_from_str(
+            r#"
+            files:
+                entrypoint: 'python3 -c'
+                command: 'files = [open("/dev/null") for _ in range(32)]'
+                limits:
+                    nofile: 16
+            "#,
+        )?;
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                LINES.lock().unwrap().push(msg.message.clone());
+                Some(())
+            },
+            _msg: RegisterPanel => Some(()),
+            msg: StateChanged => {
+                if msg.state.is_finished() {
+                    FINISHED.store(true, std::sync::atomic::Ordering::SeqCst);
+                }
+                Some(())
+            },
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
+        });
+
+        let commands = CommandActorsBuilder::new(config, console, watcher)
+            .build()
+            .await?;
+
+        let status = commands.get("files").unwrap().send(WaitStatus).await??;
+        assert_eq!(status, ExitStatus::Exited(1));
+        while !FINISHED.load(std::sync::atomic::Ordering::SeqCst) {
+            actix::clock::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        Ok(())
+    });
+
+    let lines = LINES.lock().unwrap();
+    assert!(
+        lines
+            .iter()
+            .any(|line| line.contains("Too many open files")),
+        "{lines:?}"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn no_child_survives_actor_stop() {
+    static PIDS: std::sync::Mutex<Vec<(String, u32)>> = std::sync::Mutex::new(Vec::new());
+
+    // zombies are dead, only waiting for init to reap them
+    fn is_alive(pid: u32) -> bool {
+        let stat = std::process::Command::new("ps")
+            .args(["-o", "stat=", "-p", &pid.to_string()])
+            .output()
+            .unwrap();
+        let stat = String::from_utf8_lossy(&stat.stdout);
+        !stat.trim().is_empty() && !stat.trim().starts_with('Z')
+    }
+
+    within_system(async move {
+        // the background sleep is not a child of the actor but of its shell
+        let config = config_from_str(
+            r#"
+            stopped:
+                command: 'sleep 30 & echo $!; wait'
+            dropped:
+                command: 'sleep 30 & echo $!; wait'
+            "#,
+        )?;
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                if let std::result::Result::Ok(pid) = msg.message.parse() {
+                    PIDS.lock().unwrap().push((msg.panel_name.clone(), pid));
+                }
+                Some(())
+            },
+            _msg: RegisterPanel => Some(()),
+            _msg: StateChanged => Some(()),
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
+        });
+
+        let commands = CommandActorsBuilder::new(config, console, watcher)
+            .build()
+            .await?;
+
+        while PIDS.lock().unwrap().len() < 2 {
+            actix::clock::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        commands.get("stopped").unwrap().send(PoisonPill).await?;
+        // `dropped` goes away with the system
+        Ok(())
+    });
+
+    for (task, pid) in PIDS.lock().unwrap().iter() {
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(2);
+        while is_alive(*pid) && std::time::Instant::now() < deadline {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        assert!(!is_alive(*pid), "the sleep of {task} survived");
+    }
+}
+
+#[cfg(feature = "tui")]
+#[test]
+fn caps_dynamic_tabs() {
+    within_system(async move {
+        let console = ConsoleActor::new(vec!["api".to_string()], false)
+            .offscreen(80, 12)
+            .start();
+        let screen = |buffer: ratatui::buffer::Buffer| {
+            buffer
+                .content
+                .chunks(buffer.area.width as usize)
+                .map(|row| row.iter().map(|cell| cell.symbol()).collect::<String>())
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+
+        console
+            .send(RegisterPanel {
+                name: "api".to_string(),
+                addr: None,
+                colors: vec![],
+                dynamic: None,
+            })
+            .await?;
+        for user in 1..=4 {
+            let name = format!("user_{user}");
+            console
+                .send(RegisterPanel {
+                    name: name.clone(),
+                    addr: None,
+                    colors: vec![],
+                    dynamic: Some(DynamicPanel {
+                        task: "api".to_string(),
+                        max_tabs: 2,
+                    }),
+                })
+                .await?;
+            console
+                .send(Output::now(name, "login".to_string(), OutputKind::Command))
+                .await?;
+        }
+
+        let api = screen(console.send(Snapshot).await?);
+        assert!(api.contains("│ api… │ user_1… │ user_2… │ api (overflow)… "));
+        assert!(!api.contains("user_3"));
+        assert!(api.contains("PIPE: more than 2 tabs"));
+
+        for _ in 0..3 {
+            console
+                .send(TermEvent(Event::Key(KeyEvent::new(
+                    KeyCode::Right,
+                    KeyModifiers::NONE,
+                ))))
+                .await?;
+        }
+        let overflow = screen(console.send(Snapshot).await?);
+        assert!(overflow.contains("[user_3] login"));
+        assert!(overflow.contains("[user_4] login"));
+        Ok(())
+    });
+}
+
+#[cfg(feature = "tui")]
+#[test]
+fn exits_when_every_task_is_done() {
+    let system = System::new();
+
+    let fut = async move {
+        let console = ConsoleActor::new(vec!["api".to_string(), "build".to_string()], false)
+            .offscreen(80, 12)
+            .timeline(true)
+            .exit_when_done(std::time::Duration::from_millis(300))
+            .start();
+        let screen = |buffer: ratatui::buffer::Buffer| {
+            buffer
+                .content
+                .iter()
+                .map(|cell| cell.symbol())
+                .collect::<String>()
+        };
+        let state = |task: &str, state| StateChanged {
+            panel_name: task.to_string(),
+            state,
+        };
+
+        for name in ["api", "build"] {
+            console
+                .send(RegisterPanel {
+                    name: name.to_string(),
+                    addr: None,
+                    colors: vec![],
+                    dynamic: None,
+                })
+                .await?;
+        }
+        // dynamic panels are never done
+        console
+            .send(RegisterPanel {
+                name: "user_1".to_string(),
+                addr: None,
+                colors: vec![],
+                dynamic: Some(DynamicPanel {
+                    task: "api".to_string(),
+                    max_tabs: 2,
+                }),
+            })
+            .await?;
+        console
+            .send(state("api", TaskState::Exited(ExitStatus::Exited(0))))
+            .await?;
+        console.send(state("build", TaskState::Running)).await?;
+        assert!(!screen(console.send(Snapshot).await?).contains("all tasks done"));
+
+        console
+            .send(state("build", TaskState::Exited(ExitStatus::Exited(1))))
+            .await?;
+        assert!(screen(console.send(Snapshot).await?)
+            .contains("all tasks done, exiting in 300ms, any key to stay"));
+
+        // a restart postpones the exit
+        console.send(state("build", TaskState::Running)).await?;
+        tokio::time::sleep(std::time::Duration::from_millis(400)).await;
+        assert!(!screen(console.send(Snapshot).await?).contains("all tasks done"));
+        console
+            .send(state("build", TaskState::Exited(ExitStatus::Exited(1))))
+            .await?;
+        Ok(())
+    };
+
+    Arbiter::current().spawn(async { fut.await.unwrap() });
+
+    let timer = std::time::SystemTime::now();
+    assert_eq!(ExitCode::TaskFailed.code(), system.run_with_code().unwrap());
+    let elapsed = timer.elapsed().unwrap();
+    assert!(
+        elapsed.as_millis() >= 700,
+        "exited before the delay: {elapsed:?}"
+    );
+}
+
+#[cfg(feature = "tui")]
+#[test]
+fn prefixes_lines_with_their_task() {
+    within_system(async move {
+        let console = ConsoleActor::new(vec!["api".to_string()], false)
+            .offscreen(40, 8)
+            .prefix_lines(true)
+            .start();
+        let row = |buffer: &ratatui::buffer::Buffer, y: u16| {
+            (0..buffer.area.width)
+                .map(|x| buffer.get(x, y).symbol())
+                .collect::<String>()
+        };
+
+        console
+            .send(RegisterPanel {
+                name: "api".to_string(),
+                addr: None,
+                colors: vec![],
+                dynamic: None,
+            })
+            .await?;
+        console
+            .send(Output::now(
+                "api".to_string(),
+                "listening".to_string(),
+                OutputKind::Command,
+            ))
+            .await?;
+        console
+            .send(Output::now(
+                "api".to_string(),
+                "RELOAD: manual".to_string(),
+                OutputKind::Service,
+            ))
+            .await?;
+
+        let snapshot = console.send(Snapshot).await?;
+        assert_eq!(row(&snapshot, 0).trim_end(), "[api] listening");
+        // only the name is colored
+        assert_eq!(snapshot.get(0, 0).fg, ratatui::style::Color::Reset);
+        assert_ne!(snapshot.get(1, 0).fg, ratatui::style::Color::Reset);
+        assert_eq!(snapshot.get(6, 0).fg, ratatui::style::Color::Reset);
+        // the lines of whiz are not prefixed
+        assert!(row(&snapshot, 1).starts_with("RELOAD: manual"));
+        Ok(())
+    });
+}
+
+#[cfg(feature = "tui")]
+#[test]
+fn scrolls_by_the_scroll_step() {
+    within_system(async move {
+        let console = ConsoleActor::new(vec!["api".to_string()], false)
+            .offscreen(40, 8)
+            .scroll_step(3)
+            .start();
+        let last_line = |buffer: &ratatui::buffer::Buffer| {
+            (0..buffer.area.height)
+                .filter_map(|y| {
+                    let row = (0..buffer.area.width)
+                        .map(|x| buffer.get(x, y).symbol())
+                        .collect::<String>();
+                    row.trim().strip_prefix("line ")?.parse::<usize>().ok()
+                })
+                .max()
+        };
+
+        console
+            .send(RegisterPanel {
+                name: "api".to_string(),
+                addr: None,
+                colors: vec![],
+                dynamic: None,
+            })
+            .await?;
+        for i in 0..20 {
+            console
+                .send(Output::now(
+                    "api".to_string(),
+                    format!("line {i}"),
+                    OutputKind::Command,
+                ))
+                .await?;
+        }
+        assert_eq!(last_line(&console.send(Snapshot).await?), Some(19));
+
+        let key = |code| TermEvent(Event::Key(KeyEvent::new(code, KeyModifiers::NONE)));
+        console.send(key(KeyCode::Up)).await?;
+        assert_eq!(last_line(&console.send(Snapshot).await?), Some(16));
+        console.send(key(KeyCode::Char('k'))).await?;
+        assert_eq!(last_line(&console.send(Snapshot).await?), Some(13));
+        console.send(key(KeyCode::Char('j'))).await?;
+        assert_eq!(last_line(&console.send(Snapshot).await?), Some(16));
+        Ok(())
+    });
+}
+
+#[cfg(feature = "tui")]
+#[test]
+fn runs_tasks_from_the_command_menu() {
+    within_system(async move {
+        let console = ConsoleActor::new(
+            vec!["api".to_string(), "fmt".to_string(), "lint".to_string()],
+            false,
+        )
+        .offscreen(40, 10)
+        .on_demand(vec!["fmt".to_string(), "lint".to_string()])
+        .start();
+        let rows = |buffer: &ratatui::buffer::Buffer| {
+            (0..buffer.area.height)
+                .map(|y| {
+                    (0..buffer.area.width)
+                        .map(|x| buffer.get(x, y).symbol())
+                        .collect::<String>()
+                })
+                .collect::<Vec<_>>()
+        };
+        let key = |code| TermEvent(Event::Key(KeyEvent::new(code, KeyModifiers::NONE)));
+
+        for name in ["api", "fmt", "lint"] {
+            console
+                .send(RegisterPanel {
+                    name: name.to_string(),
+                    addr: None,
+                    colors: vec![],
+                    dynamic: None,
+                })
+                .await?;
+            console
+                .send(Output::now(
+                    name.to_string(),
+                    format!("{name} output"),
+                    OutputKind::Command,
+                ))
+                .await?;
+        }
+
+        console.send(key(KeyCode::Char('o'))).await?;
+        let menu = rows(&console.send(Snapshot).await?);
+        assert!(
+            menu.iter().any(|row| row.contains("run on demand")),
+            "{menu:#?}"
+        );
+        assert!(menu.iter().any(|row| row.contains("│lint")), "{menu:#?}");
+        assert!(!menu.iter().any(|row| row.contains("│api")), "{menu:#?}");
+
+        // the keys go to the menu, not to the panels
+        console.send(key(KeyCode::Char('j'))).await?;
+        console.send(key(KeyCode::Enter)).await?;
+        let view = rows(&console.send(Snapshot).await?);
+        assert_eq!(view[0].trim_end(), "lint output");
+        assert!(
+            !view.iter().any(|row| row.contains("run on demand")),
+            "{view:#?}"
+        );
+
+        // closed without running anything
+        console.send(key(KeyCode::Char('o'))).await?;
+        console.send(key(KeyCode::Esc)).await?;
+        let view = rows(&console.send(Snapshot).await?);
+        assert_eq!(view[0].trim_end(), "lint output");
+        Ok(())
+    });
+}
+
+#[cfg(feature = "tui")]
+#[test]
+fn collapses_repeated_lines() {
+    within_system(async move {
+        let console = ConsoleActor::new(vec!["api".to_string()], false)
+            .offscreen(40, 8)
+            .dedup(true)
+            .start();
+        console
+            .send(RegisterPanel {
+                name: "api".to_string(),
+                addr: None,
+                colors: vec![],
+                dynamic: None,
+            })
+            .await?;
+        let line = |message: &str| {
+            Output::now("api".to_string(), message.to_string(), OutputKind::Command)
+        };
+        for message in ["retrying", "retrying", "retrying", "up", "retrying"] {
+            console.send(line(message)).await?;
+        }
+
+        let buffer = console.send(Snapshot).await?;
+        let rows = buffer
+            .content
+            .chunks(buffer.area.width as usize)
+            .map(|row| {
+                let row = row.iter().map(|cell| cell.symbol()).collect::<String>();
+                row.trim_end().to_string()
+            })
+            .filter(|row| !row.is_empty())
+            .collect::<Vec<_>>();
+        assert!(
+            rows.starts_with(&[
+                "retrying (x3)".to_string(),
+                "up".to_string(),
+                "retrying".to_string(),
+            ]),
+            "{rows:?}"
+        );
+        Ok(())
+    });
+}
+
+#[cfg(feature = "tui")]
+#[test]
+fn clears_the_panel_and_reruns_its_task() {
+    static MESSAGES: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+    within_system(async move {
+        let config = config_from_str(
+            r#"
+            api:
+                command: echo api
+            "#,
+        )?;
+        let mock_console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                MESSAGES.lock().unwrap().push(msg.message.clone());
+                Some(())
+            },
+            _msg: RegisterPanel => Some(()),
+            _msg: StateChanged => Some(()),
+        });
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
+        });
+        let commands = CommandActorsBuilder::new(config, mock_console, watcher)
+            .build()
+            .await?;
+        let api = commands.get("api").unwrap();
+        api.send(WaitStatus).await??;
+
+        let console = ConsoleActor::new(vec!["api".to_string()], false)
+            .offscreen(40, 6)
+            .restart_key('x')
+            .start();
+        console
+            .send(RegisterPanel {
+                name: "api".to_string(),
+                addr: Some(api.clone()),
+                colors: vec![],
+                dynamic: None,
+            })
+            .await?;
+        console
+            .send(Output::now(
+                "api".to_string(),
+                "old run".to_string(),
+                OutputKind::Command,
+            ))
+            .await?;
+        let late = Output::now("api".to_string(), "late".to_string(), OutputKind::Command);
+
+        let key = TermEvent(Event::Key(KeyEvent::new(
+            KeyCode::Char('x'),
+            KeyModifiers::NONE,
+        )));
+        console.send(key).await?;
+        // printed before the panel was cleared
+        console.send(late).await?;
+        let buffer = console.send(Snapshot).await?;
+        let first_row = (0..buffer.area.width)
+            .map(|x| buffer.get(x, 0).symbol())
+            .collect::<String>();
+        assert_eq!(first_row.trim_end(), "");
+
+        actix::clock::sleep(std::time::Duration::from_millis(200)).await;
+        assert_eq!(api.send(WaitStatus).await??, ExitStatus::Exited(0));
+        Ok(())
+    });
+
+    let messages = MESSAGES.lock().unwrap();
+    assert!(
+        messages.contains(&"RELOAD: manual".to_string()),
+        "{messages:?}"
+    );
+    assert_eq!(messages.iter().filter(|m| *m == "api").count(), 2);
+}
+
+#[cfg(feature = "tui")]
+#[test]
+fn forwards_keys_in_insert_mode() {
+    static MESSAGES: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+    within_system(async move {
+        let config = config_from_str(
+            r#"
+            api:
+                command: read line; echo "got $line"
+            "#,
+        )?;
+        let mock_console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                MESSAGES.lock().unwrap().push(msg.message.clone());
+                Some(())
+            },
+            _msg: RegisterPanel => Some(()),
+            _msg: StateChanged => Some(()),
+        });
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
+        });
+        let commands = CommandActorsBuilder::new(config, mock_console, watcher)
+            .build()
+            .await?;
+        let api = commands.get("api").unwrap();
+
+        let console = ConsoleActor::new(vec!["api".to_string()], false)
+            .offscreen(40, 6)
+            .start();
+        console
+            .send(RegisterPanel {
+                name: "api".to_string(),
+                addr: Some(api.clone()),
+                colors: vec![],
+                dynamic: None,
+            })
+            .await?;
+        let last_row = || async {
+            let buffer = console.send(Snapshot).await.unwrap();
+            (0..buffer.area.width)
+                .map(|x| buffer.get(x, buffer.area.height - 1).symbol())
+                .collect::<String>()
+        };
+
+        // the keys bound otherwise go to the task as well
+        for code in [
+            KeyCode::Char('i'),
+            KeyCode::Char('q'),
+            KeyCode::Char('r'),
+            KeyCode::Enter,
+        ] {
+            let key = TermEvent(Event::Key(KeyEvent::new(code, KeyModifiers::NONE)));
+            console.send(key).await?;
+        }
+        assert!(last_row().await.contains("INSERT"));
+        let esc = TermEvent(Event::Key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE)));
+        console.send(esc).await?;
+        assert!(!last_row().await.contains("INSERT"));
+
+        assert_eq!(api.send(WaitStatus).await??, ExitStatus::Exited(0));
+        Ok(())
+    });
+
+    let messages = MESSAGES.lock().unwrap();
+    assert!(messages.contains(&"got qr".to_string()), "{messages:?}");
+    assert!(!messages.contains(&"RELOAD: manual".to_string()));
+}
+
+#[cfg(feature = "tui")]
+#[test]
+fn shows_partial_lines_until_they_end() {
+    within_system(async move {
+        let console = ConsoleActor::new(vec!["setup".to_string()], false)
+            .offscreen(40, 8)
+            .start();
+        let row = |buffer: &ratatui::buffer::Buffer, y: u16| {
+            (0..buffer.area.width)
+                .map(|x| buffer.get(x, y).symbol())
+                .collect::<String>()
+        };
+        let line = |message: &str, partial: bool| {
+            Output::now(
+                "setup".to_string(),
+                message.to_string(),
+                OutputKind::Command,
+            )
+            .partial(partial)
+        };
+
+        console
+            .send(RegisterPanel {
+                name: "setup".to_string(),
+                addr: None,
+                colors: vec![],
+                dynamic: None,
+            })
+            .await?;
+        console.send(line("installing", false)).await?;
+        console.send(line("Press enter", true)).await?;
+        let snapshot = console.send(Snapshot).await?;
+        assert_eq!(row(&snapshot, 1).trim_end(), "Press enter");
+
+        console.send(line("Press enter to continue", true)).await?;
+        let snapshot = console.send(Snapshot).await?;
+        assert_eq!(row(&snapshot, 1).trim_end(), "Press enter to continue");
+        assert_eq!(row(&snapshot, 2).trim(), "");
+
+        console.send(line("", true)).await?;
+        console
+            .send(line("Press enter to continue: ok", false))
+            .await?;
+        let snapshot = console.send(Snapshot).await?;
+        assert_eq!(row(&snapshot, 0).trim_end(), "installing");
+        assert_eq!(row(&snapshot, 1).trim_end(), "Press enter to continue: ok");
+        assert_eq!(row(&snapshot, 2).trim(), "");
+        Ok(())
+    });
+}
+
+#[cfg(feature = "tui")]
+#[test]
+fn selects_lines_to_copy() {
+    within_system(async move {
+        let console = ConsoleActor::new(vec!["api".to_string()], false)
+            .offscreen(40, 8)
+            .start();
+        let key = |code| TermEvent(Event::Key(KeyEvent::new(code, KeyModifiers::NONE)));
+        let selected = |buffer: &ratatui::buffer::Buffer| {
+            (0..buffer.area.height)
+                .filter(|y| {
+                    buffer
+                        .get(0, *y)
+                        .modifier
+                        .contains(ratatui::style::Modifier::REVERSED)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        console
+            .send(RegisterPanel {
+                name: "api".to_string(),
+                addr: None,
+                colors: vec![],
+                dynamic: None,
+            })
+            .await?;
+        for line in ["compiling", "error: missing ;", "retrying"] {
+            console
+                .send(Output::now(
+                    "api".to_string(),
+                    line.to_string(),
+                    OutputKind::Command,
+                ))
+                .await?;
+        }
+
+        console.send(key(KeyCode::Char('V'))).await?;
+        console.send(key(KeyCode::Char('k'))).await?;
+        assert_eq!(selected(&console.send(Snapshot).await?), vec![1, 2]);
+
+        // the selection stays on its lines, and j and k keep selecting
+        console
+            .send(Output::now(
+                "api".to_string(),
+                "done".to_string(),
+                OutputKind::Command,
+            ))
+            .await?;
+        assert_eq!(selected(&console.send(Snapshot).await?), vec![1, 2]);
+
+        console.send(key(KeyCode::Char('y'))).await?;
+        assert_eq!(
+            console.send(Copied).await?.as_deref(),
+            Some("error: missing ;\nretrying")
+        );
+        assert!(selected(&console.send(Snapshot).await?).is_empty());
+
+        // Esc leaves without copying
+        console.send(key(KeyCode::Char('V'))).await?;
+        assert_eq!(selected(&console.send(Snapshot).await?), vec![3]);
+        console.send(key(KeyCode::Esc)).await?;
+        assert!(selected(&console.send(Snapshot).await?).is_empty());
+        assert_eq!(
+            console.send(Copied).await?.as_deref(),
+            Some("error: missing ;\nretrying")
+        );
+        Ok(())
+    });
+}
+
+#[cfg(feature = "tui")]
+#[test]
+fn timeline_lists_events_of_every_task() {
+    within_system(async move {
+        let console = ConsoleActor::new(vec!["db".to_string(), "api".to_string()], false)
+            .offscreen(60, 12)
+            .timeline(true)
+            .start();
+        let rows = |buffer: ratatui::buffer::Buffer| {
+            let time = regex::Regex::new(r"\d{2}:\d{2}:\d{2}\.\d{3}").unwrap();
+            buffer
+                .content
+                .chunks(buffer.area.width as usize)
+                .map(|row| {
+                    let row = row.iter().map(|cell| cell.symbol()).collect::<String>();
+                    time.replace_all(row.trim_end(), "00:00:00.000")
+                        .into_owned()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        for name in ["db", "api"] {
+            console
+                .send(RegisterPanel {
+                    name: name.to_string(),
+                    addr: None,
+                    colors: vec![],
+                    dynamic: None,
+                })
+                .await?;
+        }
+        let state = |name: &str, state: TaskState| StateChanged {
+            panel_name: name.to_string(),
+            state,
+        };
+        console.send(state("db", TaskState::Starting)).await?;
+        console
+            .send(state("api", TaskState::WaitingOnUpstream))
+            .await?;
+        console.send(state("db", TaskState::Running)).await?;
+        console.send(state("db", TaskState::Stopping)).await?;
+        console
+            .send(state("db", TaskState::Exited(ExitStatus::Exited(0))))
+            .await?;
+        console.send(state("api", TaskState::Starting)).await?;
+        console.send(state("api", TaskState::Running)).await?;
+        console
+            .send(Output::now(
+                "db".to_string(),
+                "RELOAD: file changed: schema.sql ".to_string(),
+                OutputKind::Service,
+            ))
+            .await?;
+        // not an event
+        console
+            .send(Output::now(
+                "api".to_string(),
+                "listening".to_string(),
+                OutputKind::Command,
+            ))
+            .await?;
+        console
+            .send(state("api", TaskState::Exited(ExitStatus::Exited(1))))
+            .await?;
+        // a panel created afterwards goes before the timeline
+        console
+            .send(RegisterPanel {
+                name: "worker".to_string(),
+                addr: None,
+                colors: vec![],
+                dynamic: None,
+            })
+            .await?;
+
+        // the last tab
+        console
+            .send(TermEvent(Event::Key(KeyEvent::new(
+                KeyCode::Char('0'),
+                KeyModifiers::NONE,
+            ))))
+            .await?;
+        let snapshot = console.send(Snapshot).await?;
+        // the task names are colored
+        assert_ne!(snapshot.get(14, 0).fg, ratatui::style::Color::Reset);
+        assert_eq!(snapshot.get(17, 0).fg, ratatui::style::Color::Reset);
+        assert_eq!(
+            rows(snapshot),
+            vec![
+                "00:00:00.000  api  waiting on upstream",
+                "00:00:00.000  db  started",
+                "00:00:00.000  db  exited with code 0",
+                "00:00:00.000  api  started",
+                "00:00:00.000  db  RELOAD: file changed: schema.sql",
+                "00:00:00.000  api  exited with code 1",
+                "",
+                "",
+                "",
+                "┌──────────────────────────────────────────────────────────┐",
+                "│ db. │ api! │ worker… │ (timeline)                        │",
+                "└──────────────────────────────────────────────────────────┘",
+            ]
+        );
+        assert_eq!(
+            console.send(GetStates).await?,
+            vec![
+                ("db".to_string(), TaskState::Exited(ExitStatus::Exited(0))),
+                ("api".to_string(), TaskState::Exited(ExitStatus::Exited(1))),
+                ("worker".to_string(), TaskState::Pending),
+            ]
+        );
+        Ok(())
+    });
+}
+
+#[cfg(feature = "tui")]
+#[test]
+fn notices_resizes_without_event() {
+    within_system(async move {
+        let console = ConsoleActor::new(vec!["api".to_string()], false)
+            .offscreen(80, 12)
+            .start();
+        let rows = |buffer: ratatui::buffer::Buffer| {
+            buffer
+                .content
+                .chunks(buffer.area.width as usize)
+                .map(|row| row.iter().map(|cell| cell.symbol()).collect::<String>())
+                .collect::<Vec<_>>()
+        };
+
+        console
+            .send(RegisterPanel {
+                name: "api".to_string(),
+                addr: None,
+                colors: vec![],
+                dynamic: None,
+            })
+            .await?;
+        for letter in ["a", "b", "c", "d"] {
+            console
+                .send(Output::now(
+                    "api".to_string(),
+                    letter.repeat(100),
+                    OutputKind::Command,
+                ))
+                .await?;
+        }
+        console
+            .send(Output::now(
+                "api".to_string(),
+                "end".to_string(),
+                OutputKind::Command,
+            ))
+            .await?;
+        // 9 lines of logs fit at this width
+        assert!(rows(console.send(Snapshot).await?)[0].starts_with("aaaa"));
+
+        console
+            .send(SetOffscreenSize {
+                width: 40,
+                height: 12,
+            })
+            .await?;
+        actix::clock::sleep(std::time::Duration::from_millis(1200)).await;
+
+        // 13 lines now, scrolled to the last one
+        let screen = rows(console.send(Snapshot).await?);
+        assert_eq!(screen[0].chars().count(), 40);
+        assert_eq!(screen[0].trim_end(), "▲ 5 more");
+        assert!(screen[1].starts_with("bbbb"));
+        assert!(screen[8].starts_with("end"));
+
+        // a redraw keeps the layout
+        console
+            .send(TermEvent(Event::Key(KeyEvent::new(
+                KeyCode::Char('l'),
+                KeyModifiers::CONTROL,
+            ))))
+            .await?;
+        assert_eq!(rows(console.send(Snapshot).await?), screen);
+        Ok(())
+    });
+}
+
+#[cfg(feature = "tui")]
+#[test]
+fn indicates_rows_hidden_above_and_below() {
+    within_system(async move {
+        // 6 rows of logs above the tabs
+        let console = ConsoleActor::new(vec!["api".to_string()], false)
+            .offscreen(30, 9)
+            .start();
+        let logs = |buffer: ratatui::buffer::Buffer| {
+            buffer
+                .content
+                .chunks(buffer.area.width as usize)
+                .take(6)
+                .map(|row| {
+                    let row = row.iter().map(|cell| cell.symbol()).collect::<String>();
+                    row.trim_end().to_string()
+                })
+                .collect::<Vec<_>>()
+        };
+        let print = |from: usize, to: usize| {
+            let console = console.clone();
+            async move {
+                for i in from..=to {
+                    console
+                        .send(Output::now(
+                            "api".to_string(),
+                            format!("line {i}"),
+                            OutputKind::Command,
+                        ))
+                        .await?;
+                }
+                Ok(())
+            }
+        };
+        let press = |code: KeyCode, modifiers: KeyModifiers| {
+            console.send(TermEvent(Event::Key(KeyEvent::new(code, modifiers))))
+        };
+
+        console
+            .send(RegisterPanel {
+                name: "api".to_string(),
+                addr: None,
+                colors: vec![],
+                dynamic: None,
+            })
+            .await?;
+
+        // nothing hidden, no row taken
+        print(1, 3).await?;
+        assert_eq!(
+            logs(console.send(Snapshot).await?),
+            vec!["line 1", "line 2", "line 3", "", "", ""]
+        );
+
+        // hidden above
+        print(4, 10).await?;
+        let snapshot = console.send(Snapshot).await?;
+        assert!(snapshot
+            .get(0, 0)
+            .modifier
+            .contains(ratatui::style::Modifier::DIM));
+        assert!(!snapshot
+            .get(0, 1)
+            .modifier
+            .contains(ratatui::style::Modifier::DIM));
+        assert_eq!(
+            logs(snapshot),
+            vec![
+                "▲ 5 more",
+                "line 6",
+                "line 7",
+                "line 8",
+                "line 9",
+                "line 10"
+            ]
+        );
+
+        // hidden above and below, following the output
+        press(KeyCode::Char('k'), KeyModifiers::NONE).await?;
+        press(KeyCode::Char('k'), KeyModifiers::NONE).await?;
+        assert_eq!(
+            logs(console.send(Snapshot).await?),
+            vec![
+                "▲ 4 more",
+                "line 5",
+                "line 6",
+                "line 7",
+                "line 8",
+                "▼ 2 more"
+            ]
+        );
+        print(11, 11).await?;
+        assert_eq!(
+            logs(console.send(Snapshot).await?),
+            vec![
+                "▲ 5 more",
+                "line 6",
+                "line 7",
+                "line 8",
+                "line 9",
+                "▼ 2 more"
+            ]
+        );
+
+        // hidden below, up to the first line
+        press(KeyCode::Char('b'), KeyModifiers::CONTROL).await?;
+        assert_eq!(
+            logs(console.send(Snapshot).await?),
+            vec!["line 1", "line 2", "line 3", "line 4", "line 5", "▼ 6 more"]
+        );
+        Ok(())
+    });
+}
+
+#[cfg(feature = "tui")]
+#[test]
+fn pins_latest_matching_lines_above_the_logs() {
+    within_system(async move {
+        // 6 rows of logs above the tabs
+        let console = ConsoleActor::new(vec!["api".to_string()], false)
+            .offscreen(30, 9)
+            .start();
+        let logs = |buffer: ratatui::buffer::Buffer| {
+            buffer
+                .content
+                .chunks(buffer.area.width as usize)
+                .take(6)
+                .map(|row| {
+                    let row = row.iter().map(|cell| cell.symbol()).collect::<String>();
+                    row.trim_end().to_string()
+                })
+                .collect::<Vec<_>>()
+        };
+        let print = |line: &str, pin: Option<usize>| {
+            console.send(
+                Output::now("api".to_string(), line.to_string(), OutputKind::Command).pin(pin),
+            )
+        };
+
+        console
+            .send(RegisterPanel {
+                name: "api".to_string(),
+                addr: None,
+                colors: vec![],
+                dynamic: None,
+            })
+            .await?;
+
+        print("listening on 8080", Some(1)).await?;
+        print("starting", None).await?;
+        print("3 errors", Some(0)).await?;
+        print("0 errors", Some(0)).await?;
+        let separator = "─".repeat(30);
+        assert_eq!(
+            logs(console.send(Snapshot).await?),
+            vec![
+                "0 errors",
+                "listening on 8080",
+                separator.as_str(),
+                "▲ 2 more",
+                "3 errors",
+                "0 errors"
+            ]
+        );
+        Ok(())
+    });
+}
+
+#[cfg(feature = "tui")]
+#[test]
+fn cycles_the_kinds_of_lines_shown() {
+    within_system(async move {
+        // 6 rows of logs above the tabs
+        let console = ConsoleActor::new(vec!["api".to_string()], false)
+            .offscreen(30, 9)
+            .start();
+        let rows = |buffer: ratatui::buffer::Buffer| {
+            buffer
+                .content
+                .chunks(buffer.area.width as usize)
+                .map(|row| {
+                    let row = row.iter().map(|cell| cell.symbol()).collect::<String>();
+                    row.trim_end().to_string()
+                })
+                .collect::<Vec<_>>()
+        };
+        let print = |line: &str, kind: OutputKind| {
+            console.send(Output::now("api".to_string(), line.to_string(), kind))
+        };
+        let press = || {
+            console.send(TermEvent(Event::Key(KeyEvent::new(
+                KeyCode::Char('v'),
+                KeyModifiers::NONE,
+            ))))
+        };
+
+        console
+            .send(RegisterPanel {
+                name: "api".to_string(),
+                addr: None,
+                colors: vec![],
+                dynamic: None,
+            })
+            .await?;
+        print("Waiting on db", OutputKind::Service).await?;
+        for i in 1..=3 {
+            print(&format!("line {i}"), OutputKind::Command).await?;
+        }
+        print("RELOAD: db", OutputKind::Service).await?;
+        for i in 4..=6 {
+            print(&format!("line {i}"), OutputKind::Command).await?;
+        }
+
+        let screen = rows(console.send(Snapshot).await?);
+        assert_eq!(screen[0], "▲ 3 more");
+        assert_eq!(screen[5], "line 6");
+        assert!(screen[7].contains("api") && !screen[7].contains('['));
+
+        press().await?;
+        let screen = rows(console.send(Snapshot).await?);
+        assert_eq!(
+            screen[..6],
+            ["line 1", "line 2", "line 3", "line 4", "line 5", "line 6"]
+        );
+        assert!(screen[7].contains("[command]"));
+
+        // lines printed meanwhile are accounted for the visibility
+        print("line 7", OutputKind::Command).await?;
+        print("exited", OutputKind::Service).await?;
+        assert_eq!(rows(console.send(Snapshot).await?)[0], "▲ 2 more");
+
+        press().await?;
+        let screen = rows(console.send(Snapshot).await?);
+        assert_eq!(
+            screen[..6],
+            ["Waiting on db", "RELOAD: db", "exited", "", "", ""]
+        );
+        assert!(screen[7].contains("[service]"));
+
+        press().await?;
+        let screen = rows(console.send(Snapshot).await?);
+        assert_eq!(screen[0], "▲ 5 more");
+        assert_eq!(screen[5], "exited");
+        Ok(())
+    });
+}
+
+#[cfg(feature = "tui")]
+#[test]
+fn splits_the_log_area_between_panels() {
+    within_system(async move {
+        let names = ["api", "db", "web"].map(String::from).to_vec();
+        // 3 rows of logs in each cell, above the tabs
+        let console = ConsoleActor::new(names.clone(), false)
+            .offscreen(40, 8)
+            .split(Split::Columns)
+            .start();
+        let rows = |buffer: ratatui::buffer::Buffer| {
+            buffer
+                .content
+                .chunks(buffer.area.width as usize)
+                .take(5)
+                .map(|row| {
+                    let row = row.iter().map(|cell| cell.symbol()).collect::<String>();
+                    row.trim_end().to_string()
+                })
+                .collect::<Vec<_>>()
+        };
+        let press = |code: KeyCode| {
+            console.send(TermEvent(Event::Key(KeyEvent::new(
+                code,
+                KeyModifiers::NONE,
+            ))))
+        };
+
+        for name in &names {
+            console
+                .send(RegisterPanel {
+                    name: name.clone(),
+                    addr: None,
+                    colors: vec![],
+                    dynamic: None,
+                })
+                .await?;
+            for i in 1..=4 {
+                console
+                    .send(Output::now(
+                        name.clone(),
+                        format!("{name} {i}"),
+                        OutputKind::Command,
+                    ))
+                    .await?;
+            }
+        }
+
+        assert_eq!(
+            rows(console.send(Snapshot).await?),
+            vec![
+                "┌api───────────────┐┌db────────────────┐",
+                "│▲ 2 more          ││▲ 2 more          │",
+                "│api 3             ││db 3              │",
+                "│api 4             ││db 4              │",
+                "└──────────────────┘└──────────────────┘",
+            ]
+        );
+
+        // only the focused cell scrolls
+        press(KeyCode::Char('l')).await?;
+        press(KeyCode::Char('k')).await?;
+        let screen = rows(console.send(Snapshot).await?);
+        assert_eq!(screen[2], "│api 3             ││db 3              │");
+        assert_eq!(screen[3], "│api 4             ││▼ 1 more          │");
+
+        // the next page
+        press(KeyCode::Char('l')).await?;
+        assert_eq!(
+            rows(console.send(Snapshot).await?)[..4],
+            [
+                "┌web───────────────┐",
+                "│▲ 2 more          │",
+                "│web 3             │",
+                "│web 4             │"
+            ]
+        );
+
+        // back to a single panel
+        press(KeyCode::Char('s')).await?;
+        press(KeyCode::Char('s')).await?;
+        assert_eq!(
+            rows(console.send(Snapshot).await?),
+            vec!["web 1", "web 2", "web 3", "web 4", ""]
+        );
+        Ok(())
+    });
+}
+
+#[cfg(feature = "tui")]
+#[test]
+fn record_and_replay_session() {
+    within_system(async move {
+        let path = env::temp_dir().join(format!("whiz-session-{}.jsonl", std::process::id()));
+        let console = ConsoleActor::new(vec!["api".to_string(), "db".to_string()], false)
+            .offscreen(60, 12)
+            .record(Recorder::create(&path, false, None)?)
+            .start();
+
+        for name in ["api", "db"] {
+            console
+                .send(RegisterPanel {
+                    name: name.to_string(),
+                    addr: None,
+                    colors: vec![ColorOption::from(("GET", "green"))?],
+                    dynamic: None,
+                })
+                .await?;
+        }
+        for i in 0..20 {
+            console
+                .send(Output::now(
+                    "api".to_string(),
+                    format!("GET /{i}"),
+                    OutputKind::Command,
+                ))
+                .await?;
+        }
+        console
+            .send(StateChanged {
+                panel_name: "db".to_string(),
+                state: TaskState::Exited(ExitStatus::Exited(1)),
+            })
+            .await?;
+        for code in [KeyCode::Up, KeyCode::Char('l'), KeyCode::Tab] {
+            console
+                .send(TermEvent(Event::Key(KeyEvent::new(
+                    code,
+                    KeyModifiers::NONE,
+                ))))
+                .await?;
+        }
+        let recorded = console.send(Snapshot).await?;
+        assert!(recorded.content.iter().any(|cell| cell.symbol() == "!"));
+
+        let (header, events) = replay::read_session(&path)?;
+        std::fs::remove_file(&path)?;
+        let replayed = ConsoleActor::new(header.order, header.timestamp)
+            .offscreen(header.width, header.height)
+            .replay(true)
+            .start();
+        replay::play(&replayed, events, None).await?;
+
+        assert_eq!(replayed.send(Snapshot).await?, recorded);
+        Ok(())
+    });
+}
+
+#[test]
+fn base_dir_overrides_config_dir() {
+    static OUTPUTS: std::sync::Mutex<Vec<(String, String)>> = std::sync::Mutex::new(Vec::new());
+
+    let root = env::temp_dir().join(format!("whiz-base-dir-{}", std::process::id()));
+    let config_dir = root.join("config");
+    let base_dir = root.join("worktree");
+
+    within_system(async {
+        std::fs::create_dir_all(&config_dir)?;
+        std::fs::create_dir_all(base_dir.join("app"))?;
+        std::fs::write(base_dir.join("app/.env"), "FROM_FILE=worktree")?;
+        std::fs::write(
+            config_dir.join("whiz.yaml"),
+            r#"
+            root:
+                command: pwd
+            app:
+                workdir: app
+                command: pwd
+            dotenv:
+                workdir: app
+                command: printenv FROM_FILE
+                env_file: .env
+            "#,
+        )?;
+
+        let config = ConfigBuilder::new(config_dir.join("whiz.yaml"))
+            .base_dir(Some(resolve_base_dir(&root, "worktree")?))
+            .build()?;
+
+        let console = mock_actor!(ConsoleActor, {
+            msg: Output => {
+                if msg.kind == OutputKind::Command {
+                    OUTPUTS.lock().unwrap().push((msg.panel_name.clone(), msg.message.clone()));
+                }
+                Some(())
+            },
+            _msg: RegisterPanel => Some(()),
+            _msg: StateChanged => Some(()),
+        });
+
+        let watcher = mock_actor!(WatcherActor, {
+            _msg: WatchGlob => Some(()),
+            _msg: ReloadAllOn => Some(()),
+        });
+
+        let _commands = CommandActorsBuilder::new(config, console, watcher)
+            .build()
+            .await?;
+        for _ in 0..250 {
+            if OUTPUTS.lock().unwrap().len() >= 3 {
+                break;
+            }
+            actix::clock::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        Ok(())
+    });
+
+    let mut outputs = OUTPUTS.lock().unwrap().clone();
+    outputs.sort();
+    assert_eq!(
+        outputs,
+        vec![
+            (
+                "app".to_string(),
+                base_dir.join("app").display().to_string()
+            ),
+            ("dotenv".to_string(), "worktree".to_string()),
+            ("root".to_string(), base_dir.display().to_string()),
+        ]
+    );
+    assert!(resolve_base_dir(&root, "missing").is_err());
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+/// Returns the next output line of an attached session, as printed by
+/// `whiz attach`.
+async fn next_attached_line(
+    lines: &mut tokio::io::Lines<tokio::io::BufReader<daemon::Stream>>,
+) -> Result<Option<String>> {
+    while let Some(line) = lines.next_line().await? {
+        let line: RecordedLine = serde_json::from_str(&line)?;
+        if let Some(text) = daemon::plain_line(&line.event, None) {
+            return Ok(Some(text));
+        }
+    }
+    Ok(None)
+}
+
+#[test]
+fn control_socket_answers_requests() {
+    within_system(async move {
+        let base_dir = env::temp_dir().join(format!("whiz-control-{}", std::process::id()));
+        let paths = DaemonPaths::new(&base_dir, None);
+        std::fs::create_dir_all(&paths.dir)?;
+        let console = ConsoleActor::new(vec!["api".to_string()], false)
+            .headless(true)
+            .record(Recorder::create(&paths.session, false, None)?)
+            .start();
+        console
+            .send(RegisterPanel {
+                name: "api".to_string(),
+                addr: None,
+                colors: vec![],
+                dynamic: None,
+            })
+            .await?;
+        console
+            .send(StateChanged {
+                panel_name: "api".to_string(),
+                state: TaskState::Running,
+            })
+            .await?;
+        console
+            .send(Output::now(
+                "api".to_string(),
+                "booting".to_string(),
+                OutputKind::Command,
+            ))
+            .await?;
+        ControlActor::new(DaemonPaths::new(&base_dir, None), console.clone(), None)?.start();
+
+        assert_eq!(paths.running().await, Some(std::process::id()));
+
+        let mut status = daemon::send(&paths, Request::Status).await?;
+        assert_eq!(status.next_line().await?.as_deref(), Some("api: running"));
+        assert_eq!(status.next_line().await?, None);
+
+        let mut attached = daemon::send(&paths, Request::Attach).await?;
+        let header: SessionHeader = serde_json::from_str(&attached.next_line().await?.unwrap())?;
+        assert_eq!(header.order, vec!["api"]);
+        // recorded before attaching
+        assert_eq!(
+            next_attached_line(&mut attached).await?.as_deref(),
+            Some("api | booting")
+        );
+        console
+            .send(Output::now(
+                "api".to_string(),
+                "listening".to_string(),
+                OutputKind::Command,
+            ))
+            .await?;
+        assert_eq!(
+            next_attached_line(&mut attached).await?.as_deref(),
+            Some("api | listening")
+        );
+
+        // told about the changes since it started
+        console
+            .send(RegisterPanel {
+                name: "db".to_string(),
+                addr: None,
+                colors: vec![],
+                dynamic: None,
+            })
+            .await?;
+        console
+            .send(StateChanged {
+                panel_name: "db".to_string(),
+                state: TaskState::Blocked,
+            })
+            .await?;
+        console
+            .send(UnregisterPanel {
+                name: "api".to_string(),
+            })
+            .await?;
+        let mut status = daemon::send(&paths, Request::Status).await?;
+        assert_eq!(status.next_line().await?.as_deref(), Some("db: blocked"));
+        assert_eq!(status.next_line().await?, None);
+
+        std::fs::remove_dir_all(&base_dir)?;
+        Ok(())
+    });
+}
+
+#[test]
+fn attach_to_detached_whiz() {
+    let dir = env::temp_dir().join(format!("whiz-detached-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("whiz.yaml"),
+        r#"
+        api:
+            command: echo api is up
+        db:
+            command: echo db is up
+        "#,
+    )
+    .unwrap();
+    let whiz = || {
+        let mut cmd = Command::cargo_bin("whiz").unwrap();
+        cmd.current_dir(&dir);
+        cmd
+    };
+
+    whiz().arg("--detach").assert().success();
+    // attaching after the tasks are done still shows their output
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    let attached = std::process::Command::new(assert_cmd::cargo::cargo_bin("whiz"))
+        .current_dir(&dir)
+        .args(["attach", "api"])
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    whiz().arg("stop").assert().success();
+
+    let output = attached.wait_with_output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success());
+    let lines: Vec<&str> = stdout.lines().take(2).collect();
+    assert!(
+        lines[0].starts_with("started `echo api is up`"),
+        "{lines:?}"
+    );
+    assert_eq!(lines[1], "api is up");
+    assert!(!stdout.contains("db is up"));
+    whiz().arg("status").assert().failure();
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn config_search_recursive() {
+    assert!(env::current_dir().is_ok());
+    let previous_cwd = env::current_dir().unwrap().as_path().display().to_string();
+
+    // change current working directory to {root_app}/src
+    assert!(env::set_current_dir(Path::new("src")).is_ok());
+    assert!(env::current_dir().is_ok());
+
+    // cwd as string
+    let new_cwd = env::current_dir().unwrap().as_path().display().to_string();
+    println!(" Working directory set to {}", new_cwd);
+
+    let config_name = "whiz.yaml";
+    let expected_if_exist = Path::new(&new_cwd).join(config_name).display().to_string();
+
+    let config_path = find_config_path(&env::current_dir().unwrap(), config_name).unwrap();
+    let config_path_got = config_path.display().to_string();
+
+    println!(" Config file located at {}", config_path_got);
+    println!(
+        " Path \"{}\" should be different from \"{}\"",
+        config_path_got, expected_if_exist
+    );
+    assert_ne!(config_path_got, expected_if_exist);
+
+    // reset cwd to be safe
+    assert!(env::set_current_dir(Path::new(&previous_cwd)).is_ok());
+    println!(" Working directory reset to {}", previous_cwd);
+}
+
+#[test]
+fn config_path_from_env() {
+    let dir = env::temp_dir().join(format!("whiz-config-env-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("ci")).unwrap();
+    std::fs::write(dir.join("ci").join("whiz.yaml"), "").unwrap();
+    std::fs::write(dir.join("other.yaml"), "").unwrap();
+
+    env::set_var(CONFIG_ENV, "ci/whiz.yaml");
+    let from_env = resolve_config_path(&dir, None);
+    // --file takes precedence
+    let from_file = resolve_config_path(&dir, Some("other.yaml"));
+    env::set_var(CONFIG_ENV, "missing.yaml");
+    let missing = resolve_config_path(&dir, None);
+    env::remove_var(CONFIG_ENV);
+
+    assert_eq!(from_env.unwrap(), dir.join("ci").join("whiz.yaml"));
+    assert_eq!(from_file.unwrap(), dir.join("other.yaml"));
+    assert!(missing
+        .unwrap_err()
+        .to_string()
+        .contains("set in WHIZ_CONFIG not found"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn a11y_announces_and_follows_instructions() {
+    #[derive(Clone, Default)]
+    struct Announced(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Announced {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            std::io::Result::Ok(())
+        }
+    }
+
+    let announced = Announced::default();
+    within_system({
+        let announced = announced.clone();
+        async move {
+            let console = ConsoleActor::new(vec!["api".to_string(), "web".to_string()], false)
+                .a11y(Announcer::new(Box::new(announced)))
+                .start();
+            let state = |name: &str, state: TaskState| StateChanged {
+                panel_name: name.to_string(),
+                state,
+            };
+            let output =
+                |name: &str| Output::now(name.to_string(), name.to_string(), OutputKind::Command);
+
+            for name in ["api", "web"] {
+                console
+                    .send(RegisterPanel {
+                        name: name.to_string(),
+                        addr: None,
+                        colors: vec![],
+                        dynamic: None,
+                    })
+                    .await?;
+            }
+            console.send(state("api", TaskState::Starting)).await?;
+            console.send(state("api", TaskState::Running)).await?;
+            console.send(output("api")).await?;
+            console.send(output("web")).await?;
+            console
+                .send(state("api", TaskState::Exited(ExitStatus::Exited(0))))
+                .await?;
+
+            let stdin: &[u8] = b"focus web\n\nreload nope\nreload api\n";
+            read_instructions(stdin, console.clone()).await;
+            console.send(output("api")).await?;
+            console.send(output("web")).await?;
+            let stdin: &[u8] = b"focus all\nstatus\nbogus\n";
+            read_instructions(stdin, console.clone()).await;
+            Ok(())
+        }
+    });
+
+    let announced = String::from_utf8(announced.0.lock().unwrap().clone()).unwrap();
+    let lines = announced.lines().collect::<Vec<_>>();
+    assert_eq!(lines[..3], ["task api: started", "api | api", "web | web"]);
+    assert!(
+        lines[3].starts_with("task api: exited 0 after ") && lines[3].ends_with("ms"),
+        "{announced}"
+    );
+    assert_eq!(
+        lines[4..],
+        [
+            "now showing output of task web",
+            "no task named nope",
+            "api is not a task, cannot reload it",
+            // the line of api is left out
+            "web | web",
+            "now showing output of every task",
+            "task api: exited (Exited(0))",
+            "task web: pending",
+            &format!("unknown command \"bogus\", {HELP}"),
+        ]
+    );
 }
@@ -1,8 +1,47 @@
+use globset::{Glob, GlobSet};
+use ignore::WalkBuilder;
+use path_absolutize::*;
 use std::{
     io::{Error, ErrorKind},
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
 };
 
+/// Variable setting the config file when `--file` is not given.
+pub const CONFIG_ENV: &str = "WHIZ_CONFIG";
+
+/// Config file searched when neither `--file` nor [`CONFIG_ENV`] is given.
+pub const DEFAULT_CONFIG: &str = "whiz.yaml";
+
+/// Config file searched when no [`DEFAULT_CONFIG`] is found.
+pub const DEFAULT_TOML_CONFIG: &str = "whiz.toml";
+
+/// Returns the config file, by order of precedence:
+///
+/// - `file` (i.e. `--file`) searched from `location` upward,
+/// - the path set in [`CONFIG_ENV`], relative to `location`,
+/// - [`DEFAULT_CONFIG`] searched from `location` upward, or else
+///   [`DEFAULT_TOML_CONFIG`].
+pub fn resolve_config_path(location: &Path, file: Option<&str>) -> Result<PathBuf, Error> {
+    if let Some(file) = file {
+        return find_config_path(location, file);
+    }
+    match std::env::var_os(CONFIG_ENV).filter(|path| !path.is_empty()) {
+        Some(path) => {
+            let path = location.join(path).absolutize()?.to_path_buf();
+            if !path.is_file() {
+                let message = format!(
+                    "configuration file {} set in {CONFIG_ENV} not found",
+                    path.display()
+                );
+                return Err(Error::new(ErrorKind::NotFound, message));
+            }
+            Ok(path)
+        }
+        None => find_config_path(location, DEFAULT_CONFIG)
+            .or_else(|err| find_config_path(location, DEFAULT_TOML_CONFIG).map_err(|_| err)),
+    }
+}
+
 pub fn find_config_path(location: &Path, config_name: &str) -> Result<PathBuf, std::io::Error> {
     let config_name_as_path = Path::new(config_name);
     let mut config_path = location.to_path_buf();
@@ -22,3 +61,50 @@ pub fn find_config_path(location: &Path, config_name: &str) -> Result<PathBuf, s
         Some(parent) => find_config_path(parent, config_name),
     }
 }
+
+/// Returns `dir` made absolute against `location`, making sure it is an
+/// existing directory.
+pub fn resolve_base_dir(location: &Path, dir: &str) -> Result<PathBuf, std::io::Error> {
+    resolve_dir(location, dir, "base directory")
+}
+
+/// Same as [`resolve_base_dir`] for the directory watched recursively.
+pub fn resolve_watch_root(location: &Path, dir: &str) -> Result<PathBuf, std::io::Error> {
+    resolve_dir(location, dir, "watch root")
+}
+
+fn resolve_dir(location: &Path, dir: &str, name: &str) -> Result<PathBuf, std::io::Error> {
+    let resolved = location.join(dir).absolutize()?.to_path_buf();
+    if !resolved.is_dir() {
+        let message = format!("{name} {} not found", resolved.display());
+        return Err(Error::new(ErrorKind::NotFound, message));
+    }
+    Ok(resolved)
+}
+
+/// Returns whether the absolute glob `pattern` matches an existing path
+/// not excluded by `off`. Only the deepest directory without wildcards is
+/// walked, skipping the files ignored by git as the watcher does.
+pub fn glob_matches_any(pattern: &str, off: &GlobSet) -> bool {
+    let Ok(glob) = Glob::new(pattern) else {
+        return false;
+    };
+    let matcher = glob.compile_matcher();
+
+    let root: PathBuf = Path::new(pattern)
+        .components()
+        .take_while(|c| match c {
+            Component::Normal(part) => !part.to_string_lossy().contains(['*', '?', '[', '{']),
+            _ => true,
+        })
+        .collect();
+    if !root.exists() {
+        return false;
+    }
+
+    WalkBuilder::new(root)
+        .hidden(false)
+        .build()
+        .filter_map(Result::ok)
+        .any(|entry| matcher.is_match(entry.path()) && !off.is_match(entry.path()))
+}
@@ -0,0 +1,59 @@
+use std::time::Duration;
+
+use actix::System;
+use subprocess::ExitStatus;
+use whiz::actors::command::Reload;
+use whiz::actors::state::TaskState;
+use whiz::testing::{config_from_str, ConsoleEvent, Harness};
+
+#[test]
+fn runs_tasks_in_order() {
+    System::new().block_on(async {
+        let config = config_from_str(
+            r#"
+            build:
+                command: echo built
+            serve:
+                command: echo serving
+                depends_on: build
+            "#,
+            std::env::current_dir().unwrap(),
+        )
+        .unwrap();
+        let harness = Harness::start(config).await.unwrap();
+
+        harness.wait_for_exit("serve").await.unwrap();
+        assert_eq!(harness.sink.output("build"), vec!["built"]);
+        assert_eq!(harness.sink.output("serve"), vec!["serving"]);
+        assert_eq!(
+            harness.sink.states("serve").last(),
+            Some(&TaskState::Exited(ExitStatus::Exited(0)))
+        );
+
+        // serve runs once build reloaded
+        let first_output = |events: &[ConsoleEvent], line: &str| {
+            events.iter().position(
+                |event| matches!(event, ConsoleEvent::Output { message, .. } if message == line),
+            )
+        };
+        let events = harness.sink.events();
+        assert!(first_output(&events, "built") < first_output(&events, "serving"));
+
+        harness
+            .command("build")
+            .unwrap()
+            .send(Reload::Manual)
+            .await
+            .unwrap();
+        let reloaded = harness
+            .sink
+            .wait_for(Duration::from_secs(10), |events| {
+                let serving = events.iter().filter(|event| {
+                    matches!(event, ConsoleEvent::Output { message, .. } if message == "serving")
+                });
+                serving.count() == 2
+            })
+            .await;
+        assert!(reloaded);
+    });
+}